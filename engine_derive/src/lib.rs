@@ -4,7 +4,7 @@ use proc_macro::{Span, TokenStream};
 use quote::{quote, __private::Span as OtherSpan};
 use syn::{self, Attribute, Data, DataEnum, DataStruct, DeriveInput, Fields::{self, *}, FieldsNamed, FieldsUnnamed, Ident, Lit, LitInt, Meta, Path, Type, __private::TokenStream2};
 
-#[proc_macro_derive(GameEngine, attributes(not_rendered, rendering_engine, rendering_engine_generic, not_multiplayer, do_multiplayer, extra_data, tick_stages))] 
+#[proc_macro_derive(GameEngine, attributes(not_rendered, rendering_engine, rendering_engine_generic, not_multiplayer, do_multiplayer, do_snapshots, do_deferred_events, do_lifecycle_hooks, deterministic_events, do_time_control, profile_stages, do_rng, extra_data, tick_stages))] 
 pub fn derive_engine(input: TokenStream) -> TokenStream {
     // Construct a representation of Rust code as a syntax tree
     // that we can manipulate
@@ -42,6 +42,13 @@ fn derive_engine_on_struct(ast:&DeriveInput, data:&DataStruct, fields:&FieldsNam
 
     let mut rendering_engine = None;
     let mut do_multiplayer = false;
+    let mut do_snapshots = false;
+    let mut do_deferred_events = false;
+    let mut do_lifecycle_hooks = false;
+    let mut deterministic_events = false;
+    let mut do_time_control = false;
+    let mut profile_stages = false;
+    let mut do_rng = false;
 
     let mut tick_stages: usize = 2;
 
@@ -49,6 +56,27 @@ fn derive_engine_on_struct(ast:&DeriveInput, data:&DataStruct, fields:&FieldsNam
         if attr.path.is_ident(&Ident::new("do_multiplayer", OtherSpan::call_site())) {
             do_multiplayer = true;
         }
+        else if attr.path.is_ident(&Ident::new("do_snapshots", OtherSpan::call_site())) {
+            do_snapshots = true;
+        }
+        else if attr.path.is_ident(&Ident::new("do_deferred_events", OtherSpan::call_site())) {
+            do_deferred_events = true;
+        }
+        else if attr.path.is_ident(&Ident::new("do_lifecycle_hooks", OtherSpan::call_site())) {
+            do_lifecycle_hooks = true;
+        }
+        else if attr.path.is_ident(&Ident::new("deterministic_events", OtherSpan::call_site())) {
+            deterministic_events = true;
+        }
+        else if attr.path.is_ident(&Ident::new("do_time_control", OtherSpan::call_site())) {
+            do_time_control = true;
+        }
+        else if attr.path.is_ident(&Ident::new("profile_stages", OtherSpan::call_site())) {
+            profile_stages = true;
+        }
+        else if attr.path.is_ident(&Ident::new("do_rng", OtherSpan::call_site())) {
+            do_rng = true;
+        }
         else {
             match attr.parse_meta() {
                 Ok(meta) => match meta {
@@ -167,7 +195,14 @@ fn derive_engine_on_struct(ast:&DeriveInput, data:&DataStruct, fields:&FieldsNam
             multiplayer_ents_types,
             extra_data,
             extra_data_type,
-            tick_stages
+            tick_stages,
+            do_snapshots,
+            do_deferred_events,
+            do_lifecycle_hooks,
+            deterministic_events,
+            do_time_control,
+            profile_stages,
+            do_rng
         },
         None => panic!("No world type for engine, aborting codegen")
     };
@@ -189,7 +224,14 @@ struct UserGivenData {
     multiplayer_ents_types:Vec<Type>,
     extra_data:Option<Ident>,
     extra_data_type:Option<Type>,
-    tick_stages:usize
+    tick_stages:usize,
+    do_snapshots:bool,
+    do_deferred_events:bool,
+    do_lifecycle_hooks:bool,
+    deterministic_events:bool,
+    do_time_control:bool,
+    profile_stages:bool,
+    do_rng:bool
 }
 
 fn create_engine(ast:&DeriveInput, data:&DataStruct, fields:&FieldsNamed, user_data:&UserGivenData) -> TokenStream2 {
@@ -295,7 +337,259 @@ fn create_engine(ast:&DeriveInput, data:&DataStruct, fields:&FieldsNamed, user_d
     }
     let mut total_id_ident = Ident::new(format!("{}TID", user_data.engine_ident.to_string().trim()).trim(), OtherSpan::call_site());
 
+    let deferred_ident = Ident::new(format!("{}Deferred", user_data.engine_ident.to_string().trim()).trim(), OtherSpan::call_site());
+    let deferred_component_ident = Ident::new(format!("{}GC", user_data.engine_ident.to_string().trim()).trim(), OtherSpan::call_site());
+    let (deferred_definition, deferred_struct_addon, deferred_new_param, deferred_new_addon, deferred_func, deferred_begin, deferred_rw_field, deferred_rw_get, deferred_component_variant, deferred_component_set, deferred_component_push) = if user_data.do_deferred_events {
+        let mut entity_deferred_idents = Vec::with_capacity(user_data.ent_idents.len());
+        for ent_type in &user_data.ent_types {
+            match ent_type {
+                Type::Path(cool_path) => match cool_path.path.get_ident() {
+                    Some(nice_ident) => entity_deferred_idents.push(Ident::new(format!("{}DeferredEvent",  nice_ident.to_string().trim()).trim(), OtherSpan::call_site())),
+                    None => panic!("Couldn't get ident from type")
+                },
+                _ => panic!("Can't have that type in an engine derive")
+            }
+        }
+        // clients would fire the events the server already fires and replicates
+        let deferred_authority = if user_data.multiplayer_ents.len() > 0 {
+            quote! {self.is_server}
+        }
+        else {
+            quote! {true}
+        };
+        let deferred_derive = if user_data.multiplayer_ents.len() > 0 {
+            quote! {#[derive(Clone, PartialEq, to_from_bytes_derive::ToBytes, to_from_bytes_derive::FromBytes)]}
+        }
+        else {
+            quote! {#[derive(Clone, to_from_bytes_derive::ToBytes, to_from_bytes_derive::FromBytes)]}
+        };
+        (
+            // deferred_definition
+            quote! {
+                #deferred_derive
+                pub enum #deferred_ident {
+                    #(#ent_idents(#entity_deferred_idents<#total_id_ident>)),*,
+                    #world_id(<#world_type as World<#total_id_ident>>::WE)
+                }
+            },
+            // deferred_struct_addon
+            quote! {
+                pub deferred:DeferredEvents<#deferred_ident>,
+            },
+            // deferred_new_param, converts `ScheduleDelay::Seconds`, `FixedStepRunner::with_deferred_events` keeps it at the rate the runner ticks at
+            quote! {
+                ticks_per_second:f32,
+            },
+            // deferred_new_addon
+            quote! {
+                deferred:DeferredEvents::new(256, ticks_per_second),
+            },
+            // deferred_func
+            quote! {
+                pub fn schedule_event(&self, delay:ScheduleDelay, event:#deferred_ident) -> ScheduledEventHandle {
+                    self.deferred.schedule(delay, event)
+                }
+                pub fn cancel_event(&self, handle:ScheduledEventHandle) -> bool {
+                    self.deferred.cancel(handle)
+                }
+                /// Sends the deferred events firing this tick through the tunnels, so they get applied with the events of the first tick stage
+                ///
+                /// Clients skip the events the server replicates to them
+                fn send_deferred_events(&self) {
+                    let is_authority = #deferred_authority;
+                    for event in self.deferred.advance() {
+                        match event {
+                            #(#deferred_ident::#ent_idents(event) => if is_authority || !event.is_server_synced() {
+                                event.send(&self.#ent_idents.tunnels_out)
+                            }),*,
+                            #deferred_ident::#world_id(event) => if is_authority || !<<#world_type as World<#total_id_ident>>::WE as WorldEvent<#world_type, #total_id_ident>>::should_sync(&event).is_server() {
+                                self.#world_id.tunnels_out.send_event(event)
+                            },
+                        }
+                    }
+                }
+            },
+            // deferred_begin
+            quote! {
+                self.send_deferred_events();
+            },
+            // deferred_rw_field
+            quote! {
+                deferred:DeferredEvents<#deferred_ident>,
+            },
+            // deferred_rw_get
+            quote! {
+                deferred:engine.deferred.clone(),
+            },
+            // deferred_component_variant
+            quote! {
+                deferred(DeferredEventsState<#deferred_ident>),
+            },
+            // deferred_component_set
+            quote! {
+                #deferred_component_ident::deferred(state) => self.deferred.load_state(state),
+            },
+            // deferred_component_push
+            quote! {
+                components.push((#total_id_ident::#world_id, #deferred_component_ident::deferred(self.deferred.get_state())));
+            }
+        )
+    }
+    else {
+        (quote! {}, quote! {}, quote! {}, quote! {}, quote! {}, quote! {}, quote! {}, quote! {}, quote! {}, quote! {}, quote! {})
+    };
     
+    let (time_control_struct_addon, time_control_new_addon, time_control_begin, time_control_stage_gate, time_control_rw_field, time_control_rw_get, time_control_component_variant, time_control_component_set, time_control_component_push, time_control_event_variant, time_control_event_origin, time_control_event_target, time_control_event_apply, time_control_send) = if user_data.do_time_control {
+        let total_component_ident = Ident::new(format!("{}GC", user_data.engine_ident.to_string().trim()).trim(), OtherSpan::call_site());
+        let total_event_ident = Ident::new(format!("{}GE", user_data.engine_ident.to_string().trim()).trim(), OtherSpan::call_site());
+        let total_event_variant_ident = Ident::new(format!("{}GEVariant", user_data.engine_ident.to_string().trim()).trim(), OtherSpan::call_site());
+        // only the server changes time on multiplayer engines, clients follow it through events
+        let authority = if user_data.multiplayer_ents.len() > 0 {
+            quote! {is_server}
+        }
+        else {
+            quote! {true}
+        };
+        (
+            // time_control_struct_addon
+            quote! {
+                pub time_control:TimeControl,
+            },
+            // time_control_new_addon
+            quote! {
+                time_control:TimeControl::new(#authority),
+            },
+            // time_control_begin, nothing else moves forward on paused ticks
+            quote! {
+                if !self.time_control.begin_tick() {
+                    return
+                }
+            },
+            // time_control_stage_gate
+            quote! {
+                if !self.time_control.is_tick_running() {
+                    return
+                }
+            },
+            // time_control_rw_field
+            quote! {
+                time_control:TimeControl,
+            },
+            // time_control_rw_get
+            quote! {
+                time_control:engine.time_control.clone(),
+            },
+            // time_control_component_variant
+            quote! {
+                time_control(TimeControlState),
+            },
+            // time_control_component_set
+            quote! {
+                #total_component_ident::time_control(state) => self.time_control.apply_state(state),
+            },
+            // time_control_component_push
+            quote! {
+                components.push((#total_id_ident::#world_id, #total_component_ident::time_control(self.time_control.get_state())));
+            },
+            // time_control_event_variant
+            quote! {
+                time_control(TimeControlState),
+            },
+            // time_control_event_origin
+            quote! {
+                #total_event_variant_ident::time_control(_) => None,
+            },
+            // time_control_event_target
+            quote! {
+                #total_event_variant_ident::time_control(_) => #total_id_ident::#world_id,
+            },
+            // time_control_event_apply
+            quote! {
+                #total_event_variant_ident::time_control(state) => self.time_control.apply_state(*state),
+            },
+            // time_control_send
+            quote! {
+                if let Some(state) = self.time_control.take_change() {
+                    self.general_events_sender.send(#total_event_ident {variant:#total_event_variant_ident::time_control(state), tick});
+                }
+            }
+        )
+    }
+    else {
+        (quote! {}, quote! {}, quote! {}, quote! {}, quote! {}, quote! {}, quote! {}, quote! {}, quote! {}, quote! {}, quote! {}, quote! {}, quote! {}, quote! {})
+    };
+
+    let (rng_struct_addon, rng_new_param, rng_new_addon, rng_begin, rng_rw_field, rng_rw_get, rng_component_variant, rng_component_set, rng_component_push, rng_event_variant, rng_event_origin, rng_event_target, rng_event_apply, rng_send) = if user_data.do_rng {
+        let total_component_ident = Ident::new(format!("{}GC", user_data.engine_ident.to_string().trim()).trim(), OtherSpan::call_site());
+        let total_event_ident = Ident::new(format!("{}GE", user_data.engine_ident.to_string().trim()).trim(), OtherSpan::call_site());
+        let total_event_variant_ident = Ident::new(format!("{}GEVariant", user_data.engine_ident.to_string().trim()).trim(), OtherSpan::call_site());
+        (
+            // rng_struct_addon
+            quote! {
+                pub rng:EngineRng,
+            },
+            // rng_new_param, clients get the seed of the server when joining
+            quote! {
+                rng_seed:u64,
+            },
+            // rng_new_addon
+            quote! {
+                rng:EngineRng::new(rng_seed),
+            },
+            // rng_begin
+            quote! {
+                self.rng.advance();
+            },
+            // rng_rw_field
+            quote! {
+                rng:EngineRng,
+            },
+            // rng_rw_get
+            quote! {
+                rng:engine.rng.clone(),
+            },
+            // rng_component_variant
+            quote! {
+                rng(RngState),
+            },
+            // rng_component_set
+            quote! {
+                #total_component_ident::rng(state) => self.rng.load_state(state),
+            },
+            // rng_component_push
+            quote! {
+                components.push((#total_id_ident::#world_id, #total_component_ident::rng(self.rng.get_state())));
+            },
+            // rng_event_variant
+            quote! {
+                rng(RngState),
+            },
+            // rng_event_origin
+            quote! {
+                #total_event_variant_ident::rng(_) => None,
+            },
+            // rng_event_target
+            quote! {
+                #total_event_variant_ident::rng(_) => #total_id_ident::#world_id,
+            },
+            // rng_event_apply
+            quote! {
+                #total_event_variant_ident::rng(state) => self.rng.load_state(*state),
+            },
+            // rng_send, seeds only come from the server
+            quote! {
+                if let Some(state) = self.rng.take_change() {
+                    if self.is_server {
+                        self.general_events_sender.send(#total_event_ident {variant:#total_event_variant_ident::rng(state), tick});
+                    }
+                }
+            }
+        )
+    }
+    else {
+        (quote! {}, quote! {}, quote! {}, quote! {}, quote! {}, quote! {}, quote! {}, quote! {}, quote! {}, quote! {}, quote! {}, quote! {}, quote! {}, quote! {})
+    };
+
     let (multiplayer_struct_addon, multiplayer_type_ident, multiplayer_func, multiplayer_task, multiplayer_creator, total_id_definition, total_id_struct_ident, world_multiplayer_handling, multiplayer_new_addon, entity_multiplayer_handling) = if user_data.multiplayer_ents.len() > 0 {
         
         let mut total_id_ident = Ident::new(format!("{}TID", user_data.engine_ident.to_string().trim()).trim(), OtherSpan::call_site());
@@ -343,6 +637,8 @@ fn create_engine(ast:&DeriveInput, data:&DataStruct, fields:&FieldsNamed, user_d
                         }
                         world_writer.clear();
                     }
+                    #time_control_send
+                    #rng_send
                     self.tick.fetch_add(1, Ordering::Relaxed);
                 }
             },
@@ -386,6 +682,9 @@ fn create_engine(ast:&DeriveInput, data:&DataStruct, fields:&FieldsNamed, user_d
                     current_tick_over:std::sync::Arc<AtomicUsize>,
                     world_events:std::sync::Arc<std::sync::RwLock<Vec<<#world_type as World<#total_id_ident>>::WE>>>,
                     all_world_events:std::sync::Arc<std::sync::RwLock<Vec<<#world_type as World<#total_id_ident>>::WE>>>,
+                    #deferred_rw_field
+                    #time_control_rw_field
+                    #rng_rw_field
                 }
                 pub struct #engine_reader_ident <'a> {
                     #(#ent_idents:#entity_reader_idents<'a, #total_id_ident>),*,
@@ -417,14 +716,20 @@ fn create_engine(ast:&DeriveInput, data:&DataStruct, fields:&FieldsNamed, user_d
                             tick:engine.tick.clone(),
                             current_tick_over:engine.current_tick_over.clone(),
                             world_events:engine.world_events.clone(),
-                            all_world_events:engine.all_world_events.clone()
+                            all_world_events:engine.all_world_events.clone(),
+                            #deferred_rw_get
+                            #time_control_rw_get
+                            #rng_rw_get
                         }
                     }
                 }
                 #[derive(Clone, PartialEq, to_from_bytes_derive::ToBytes, to_from_bytes_derive::FromBytes)]
                 pub enum #total_component_ident {
                     #(#ent_idents(<#ent_types as MultiplayerEntity<#total_id_ident>>::GEC)),*,
-                    #world_id(#world_type)
+                    #world_id(#world_type),
+                    #deferred_component_variant
+                    #time_control_component_variant
+                    #rng_component_variant
                 }
 
                 impl GlobalComponent for #total_component_ident {
@@ -434,7 +739,9 @@ fn create_engine(ast:&DeriveInput, data:&DataStruct, fields:&FieldsNamed, user_d
                 #[derive(Clone, PartialEq, to_from_bytes_derive::ToBytes, to_from_bytes_derive::FromBytes)]
                 pub enum #total_event_variant_ident {
                     #(#ent_idents(<#ent_types as MultiplayerEntity<#total_id_ident>>::GEV<#total_id_ident>)),*,
-                    #world_id(<#world_type as World<#total_id_ident>>::WE)
+                    #world_id(<#world_type as World<#total_id_ident>>::WE),
+                    #time_control_event_variant
+                    #rng_event_variant
                 }
                 #[derive(Clone, PartialEq, to_from_bytes_derive::ToBytes, to_from_bytes_derive::FromBytes)]
                 pub struct #total_event_ident {
@@ -454,6 +761,9 @@ fn create_engine(ast:&DeriveInput, data:&DataStruct, fields:&FieldsNamed, user_d
                             ),*,
                             #total_id_ident::#world_id => match value {
                                 #total_component_ident::#world_id(new_world) => *WorldWriteHandler::from_world_handler(&self.#world_id).world = new_world,
+                                #deferred_component_set
+                                #time_control_component_set
+                                #rng_component_set
                                 _ => panic!("Wrong ID for component to set")
                             }
                         }
@@ -475,6 +785,8 @@ fn create_engine(ast:&DeriveInput, data:&DataStruct, fields:&FieldsNamed, user_d
                         match &event.variant {
                             #(#total_event_variant_ident::#ent_idents(sub_event) => sub_event.get_source()),*,
                             #total_event_variant_ident::#world_id(world_event) => world_event.get_source(),
+                            #time_control_event_origin
+                            #rng_event_origin
                         }
                     }
                     fn get_tick(&self, event:&Self::GE) -> usize {
@@ -483,7 +795,9 @@ fn create_engine(ast:&DeriveInput, data:&DataStruct, fields:&FieldsNamed, user_d
                     fn get_target(event:&Self::GE) -> Self::ID {
                         match &event.variant {
                             #(#total_event_variant_ident::#ent_idents(sub_event) => #total_id_ident::#ent_idents(sub_event.get_id())),*,
-                            #total_event_variant_ident::#world_id(world_event) => #total_id_ident::#world_id
+                            #total_event_variant_ident::#world_id(world_event) => #total_id_ident::#world_id,
+                            #time_control_event_target
+                            #rng_event_target
                         }
                     }
                     fn get_components_to_sync_for(&self, id:&Self::ID) -> Vec<#total_component_ident> {
@@ -499,7 +813,9 @@ fn create_engine(ast:&DeriveInput, data:&DataStruct, fields:&FieldsNamed, user_d
                         
                         match &event.variant {
                             #(#total_event_variant_ident::#ent_idents(sub_event) => self.#ent_idents.apply_one_event(sub_event.clone())),*,
-                            #total_event_variant_ident::#world_id(world_event) => {let mut writer = self.get_write(); <<#world_type as World<#total_id_ident>>::WE as WorldEvent<#world_type, #total_id_ident>>::apply_event(world_event.clone(), &mut writer.#world_id.world)}
+                            #total_event_variant_ident::#world_id(world_event) => {let mut writer = self.get_write(); <<#world_type as World<#total_id_ident>>::WE as WorldEvent<#world_type, #total_id_ident>>::apply_event(world_event.clone(), &mut writer.#world_id.world)},
+                            #time_control_event_apply
+                            #rng_event_apply
                         }
                     }
                     fn get_all_components_and_world(&self) -> (Vec<(Self::ID, <Self::GE as GlobalEvent>::GC)>, <Self::GE as GlobalEvent>::WD) {
@@ -513,6 +829,9 @@ fn create_engine(ast:&DeriveInput, data:&DataStruct, fields:&FieldsNamed, user_d
                             }
                         }
                         );*;
+                        #deferred_component_push
+                        #time_control_component_push
+                        #rng_component_push
                         (
                             components,
                             read.#world_id.world.clone()
@@ -610,8 +929,12 @@ fn create_engine(ast:&DeriveInput, data:&DataStruct, fields:&FieldsNamed, user_d
         let mut engine_reader_ident = Ident::new(format!("{}Reader", user_data.engine_ident.to_string().trim()).trim(), OtherSpan::call_site());
         let mut engine_writer_ident = Ident::new(format!("{}Writer", user_data.engine_ident.to_string().trim()).trim(), OtherSpan::call_site());
         (
-            quote! {},
-            quote! {},
+            quote! {
+                tick:std::sync::Arc<AtomicUsize>,
+            },
+            quote! {
+                tick:std::sync::Arc::new(AtomicUsize::new(0)),
+            },
             quote! {},
             quote! {},
             quote! {},
@@ -661,6 +984,16 @@ fn create_engine(ast:&DeriveInput, data:&DataStruct, fields:&FieldsNamed, user_d
         )
     };
 
+    // multiplayer engines count ticks in `send_must_send`, even paused ones since events keep flowing
+    let tick_begin = if user_data.multiplayer_ents.len() > 0 {
+        quote! {}
+    }
+    else {
+        quote! {
+            self.tick.fetch_add(1, Ordering::Relaxed);
+        }
+    };
+
     let all_handlers = quote ! {
         #(&#entity_handler_names),*
     };
@@ -684,6 +1017,151 @@ fn create_engine(ast:&DeriveInput, data:&DataStruct, fields:&FieldsNamed, user_d
         )
     };
 
+    let snapshot_part = if user_data.do_snapshots {
+        let snapshot_ident = Ident::new(format!("{}Snapshot", user_data.engine_ident.to_string().trim()).trim(), OtherSpan::call_site());
+        let mut entity_snapshot_idents = Vec::with_capacity(user_data.ent_idents.len());
+        for ent_type in &user_data.ent_types {
+            match ent_type {
+                Type::Path(cool_path) => match cool_path.path.get_ident() {
+                    Some(nice_ident) => entity_snapshot_idents.push(Ident::new(format!("{}VecSnapshot",  nice_ident.to_string().trim()).trim(), OtherSpan::call_site())),
+                    None => panic!("Couldn't get ident from type")
+                },
+                _ => panic!("Can't have that type in an engine derive")
+            }
+        }
+        let (extra_snapshot_field, extra_snapshot_save, extra_snapshot_load) = match (&user_data.extra_data, &user_data.extra_data_type) {
+            (Some(extra_data), Some(extra_data_type)) => (
+                quote! {pub #extra_data:<#extra_data_type as SnapshotExtraData>::Saved,},
+                quote! {#extra_data:self.#extra_data.save_snapshot_part(),},
+                quote! {self.#extra_data.load_snapshot_part(snapshot.#extra_data);}
+            ),
+            _ => (quote! {}, quote! {}, quote! {})
+        };
+        let multiplayer_tick_load = if user_data.multiplayer_ents.len() > 0 {
+            quote! {
+                self.current_tick_over.store(header.tick, Ordering::Relaxed);
+            }
+        }
+        else {
+            quote! {}
+        };
+        let (deferred_snapshot_field, deferred_snapshot_save, deferred_snapshot_load) = if user_data.do_deferred_events {
+            (
+                quote! {pub deferred:DeferredEventsState<#deferred_ident>,},
+                quote! {deferred:self.deferred.get_state(),},
+                quote! {self.deferred.load_state(snapshot.deferred);}
+            )
+        }
+        else {
+            (quote! {}, quote! {}, quote! {})
+        };
+        let (rng_snapshot_field, rng_snapshot_save, rng_snapshot_load) = if user_data.do_rng {
+            (
+                quote! {pub rng:RngState,},
+                quote! {rng:self.rng.get_state(),},
+                quote! {self.rng.load_state(snapshot.rng);}
+            )
+        }
+        else {
+            (quote! {}, quote! {}, quote! {})
+        };
+        let (rendering_unload, rendering_load) = match &user_data.rendering_engine {
+            Some((rident, _, _)) => {
+                let renderable_fields = &user_data.renderable_fields;
+                (
+                    quote! {
+                        {
+                            let mut writer = self.#rident.get_write();
+                            #(self.#renderable_fields.remove_all_renders(&mut writer));*;
+                        }
+                    },
+                    quote! {self.update_all_renderings();}
+                )
+            },
+            None => (quote! {}, quote! {})
+        };
+        quote! {
+            #[derive(Clone, to_from_bytes_derive::ToBytes, to_from_bytes_derive::FromBytes)]
+            pub struct #snapshot_ident {
+                #(pub #ent_idents:#entity_snapshot_idents),*,
+                pub #world_id:#world_type,
+                #extra_snapshot_field
+                #deferred_snapshot_field
+                #rng_snapshot_field
+            }
+
+            impl #engine_struct_ident {
+                /// Only call between ticks
+                pub fn save_snapshot(&self, path:std::path::PathBuf) -> Result<(), SnapshotError> {
+                    let tick = self.tick.load(Ordering::Relaxed);
+                    let snapshot = #snapshot_ident {
+                        #(#ent_idents:self.#ent_idents.take_snapshot()),*,
+                        #world_id:self.#world_id.world.read().unwrap().clone(),
+                        #extra_snapshot_save
+                        #deferred_snapshot_save
+                        #rng_snapshot_save
+                    };
+                    write_snapshot(path, SnapshotHeader::new(tick), snapshot)
+                }
+                /// Only call between ticks, on an engine that had the same static types registered
+                ///
+                /// The rendering instances of the current entities are removed through their `remove_render` before restoring, then rebuilt from the restored ones
+                pub fn load_snapshot(&mut self, path:std::path::PathBuf) -> Result<SnapshotHeader, SnapshotError> {
+                    let (header, snapshot) = read_snapshot::<#snapshot_ident>(path)?;
+                    #rendering_unload
+                    #(self.#ent_idents.restore_snapshot(snapshot.#ent_idents, header.tick));*;
+                    self.tick.store(header.tick, Ordering::Relaxed);
+                    *WorldWriteHandler::from_world_handler(&self.#world_id).world = snapshot.#world_id;
+                    #extra_snapshot_load
+                    #deferred_snapshot_load
+                    #rng_snapshot_load
+                    #multiplayer_tick_load
+                    #rendering_load
+                    Ok(header)
+                }
+            }
+        }
+    }
+    else {
+        quote! {}
+    };
+
+    let lifecycle_apply = if user_data.do_lifecycle_hooks {
+        match &user_data.rendering_engine {
+            Some((rident, _, _)) => quote! {
+                {
+                    let mut writer = self.#rident.get_write();
+                    let world_handler = WorldComputeHandler::from_world_handler(&self.#world_id);
+                    #(self.#ent_idents.run_lifecycle_hooks(&mut writer, &world_handler));*;
+                }
+            },
+            None => quote! {
+                {
+                    let world_handler = WorldComputeHandler::from_world_handler(&self.#world_id);
+                    #(self.#ent_idents.run_lifecycle_hooks(&mut (), &world_handler));*;
+                }
+            }
+        }
+    }
+    else {
+        quote! {}
+    };
+
+    let (deterministic_new, deterministic_task) = if user_data.deterministic_events {
+        (
+            quote! {
+                let mut #world_id = #world_id;
+                #world_id.set_deterministic(true);
+            },
+            quote! {
+                begin_event_task();
+            }
+        )
+    }
+    else {
+        (quote! {}, quote! {})
+    };
+
     let tick_stages = user_data.tick_stages;
     let mut stage_method_idents = Vec::with_capacity(tick_stages);
     let mut stage_task_idents = Vec::with_capacity(tick_stages);
@@ -693,20 +1171,46 @@ fn create_engine(ast:&DeriveInput, data:&DataStruct, fields:&FieldsNamed, user_d
         stage_method_idents.push(stage_method_ident.clone());
         let stage_func_ident = Ident::new(format!("stage_{i}").trim(), OtherSpan::call_site());
         stage_task_idents.push(Lit::Int(LitInt::new(&format!("{}", i + 100).trim(), OtherSpan::call_site())));
+        let stage_rng = if user_data.do_rng {
+            quote! {
+                set_rng_stage(#i);
+            }
+        }
+        else {
+            quote! {}
+        };
+        let stage_turns:Vec<_> = (0..ent_idents.len()).map(|kind| if user_data.deterministic_events {
+            quote! {
+                set_event_turn(#i, #kind, ent);
+            }
+        }
+        else {
+            quote! {}
+        }).collect();
+        let stage_profile = if user_data.profile_stages {
+            let profile_name = format!("{} stage {i}", user_data.engine_ident.to_string().trim());
+            quote! {
+                let _profile = profile_scope(#profile_name, ProfileKind::Stage);
+            }
+        }
+        else {
+            quote! {}
+        };
         tick_stage_methods.push(quote! {
-            fn #stage_method_ident(&self) {
+            fn #stage_method_ident(&self, number_of_threads:usize) {
+                #time_control_stage_gate
+                #stage_profile
+                #stage_rng
                 #(
                     let #entity_handler_names = self.#ent_idents.get_read()
                 );*;
                 let #world_handler_name = WorldComputeHandler::from_world_handler(&self.#world_id);
                 #(
-                    let mut counter = self.#ent_idents.stops.iteration_counter.clone();
-                    counter.update_len(#entity_handler_names.get_expected_len());
-                    
-                    counter.initialise();
-                    for ent in counter {
-                        // println!("{}", ent);
-                        #stage_func_ident(EntityTurn::#ent_idents, ent, #all_handlers, & #world_handler_name, #extra_func_get_addon)
+                    for chunk in self.#ent_idents.stops.stage_chunk_source(#i).chunks(#entity_handler_names.get_expected_len(), number_of_threads) {
+                        for ent in chunk.filter(|ent| #entity_handler_names.alive.is_alive(*ent)) {
+                            #stage_turns
+                            #stage_func_ident(EntityTurn::#ent_idents, ent, #all_handlers, & #world_handler_name, #extra_func_get_addon)
+                        }
                     }
                 );*;
             }
@@ -727,6 +1231,8 @@ fn create_engine(ast:&DeriveInput, data:&DataStruct, fields:&FieldsNamed, user_d
 
         #total_id_definition
 
+        #deferred_definition
+
         #[derive(Clone)]
         pub struct #engine_struct_ident {
             #(pub #ent_idents:<#ent_types as Entity<#total_id_ident>>::EV<#total_id_ident>),*,
@@ -734,21 +1240,33 @@ fn create_engine(ast:&DeriveInput, data:&DataStruct, fields:&FieldsNamed, user_d
             #rendering_struct_addon
             #multiplayer_struct_addon
             #extra_struct_addon
+            #deferred_struct_addon
+            #time_control_struct_addon
+            #rng_struct_addon
         }
 
         impl #engine_struct_ident {
-            pub fn new(#(#ent_idents:<#ent_types as Entity<#total_id_ident>>::EV<#total_id_ident>),*, #world_id:WorldHandler<#world_type, #total_id_ident>, #rendering_struct_addon #multiplayer_new_addon #extra_funcs_addon) -> Self {
+            pub fn new(#(#ent_idents:<#ent_types as Entity<#total_id_ident>>::EV<#total_id_ident>),*, #world_id:WorldHandler<#world_type, #total_id_ident>, #rendering_struct_addon #multiplayer_new_addon #extra_funcs_addon #deferred_new_param #rng_new_param) -> Self {
                 #multiplayer_creator
+                #deterministic_new
                 Self {
                     #(#ent_idents),*,
                     #world_id,
                     #rendering_type_ident
                     #multiplayer_type_ident
+                    #deferred_new_addon
+                    #time_control_new_addon
+                    #rng_new_addon
                     #extra_new_addon
                 }
             }
+            /// Number of ticks done, saved in snapshots
+            pub fn get_tick(&self) -> usize {
+                self.tick.load(Ordering::Relaxed)
+            }
             #rendering_func
             #multiplayer_func
+            #deferred_func
         }
 
         impl GameEngine for #engine_ident {
@@ -780,12 +1298,14 @@ fn create_engine(ast:&DeriveInput, data:&DataStruct, fields:&FieldsNamed, user_d
             type TID = usize;
             type TD = usize;
             fn do_task(&mut self, task_id:usize, thread_number:usize, number_of_threads:usize) {
+                #deterministic_task
                 match task_id {
                     0 => self.apply_all_events(),
+                    task_id if task_id == BEGIN_TICK_TASK => self.begin_tick(),
                     #rendering_task
                     #multiplayer_task
                     #(
-                        #stage_task_idents => self.#stage_method_idents()
+                        #stage_task_idents => self.#stage_method_idents(number_of_threads)
                     ),*,
                     _ => panic!("No task ids after 4"),
                 }
@@ -802,9 +1322,16 @@ fn create_engine(ast:&DeriveInput, data:&DataStruct, fields:&FieldsNamed, user_d
             fn reset_stops(&mut self) {
                 #(self.#ent_idents.reset_stop());*;
                 self.#world_id.reset_stop();
-                #(self.#ent_idents.stops.iteration_counter.reset());*;
             }
             
+            /// Task `BEGIN_TICK_TASK`, once per tick before the tick stages
+            fn begin_tick(&mut self) {
+                #time_control_begin
+                #tick_begin
+                #(self.#ent_idents.next_tick());*;
+                #rng_begin
+                #deferred_begin
+            }
             fn apply_all_events(&mut self) {
                 #(
                     {
@@ -812,6 +1339,7 @@ fn create_engine(ast:&DeriveInput, data:&DataStruct, fields:&FieldsNamed, user_d
                     }
                 );*;
                 self.#world_id.apply_all_events(&mut WorldWriteHandler::from_world_handler(&self.#world_id), #world_multiplayer_handling);
+                #lifecycle_apply
                 self.reset_stops();
             }
             fn update_number_of_threads(&mut self, number_of_threads:usize, thread_number:usize) {
@@ -831,6 +1359,8 @@ fn create_engine(ast:&DeriveInput, data:&DataStruct, fields:&FieldsNamed, user_d
             #(#ent_idents),*,
         }
 
+        #snapshot_part
+
         
     };
 
@@ -3,12 +3,12 @@ extern crate proc_macro;
 use std::sync::{Arc, RwLock};
 
 use proc_macro::{TokenStream};
-use quote::{quote, __private::Span};
-use syn::{self, Attribute, Data, DataEnum, DataStruct, DeriveInput, Fields::{self, *}, FieldsNamed, FieldsUnnamed, Ident, Path, __private::TokenStream2};
+use quote::{quote, ToTokens, __private::Span};
+use syn::{self, Attribute, Data, DataEnum, DataStruct, DeriveInput, Fields::{self, *}, FieldsNamed, FieldsUnnamed, Ident, Lit, Meta, Path, __private::TokenStream2};
 #[cfg(test)]
 mod tests;
 
-#[proc_macro_derive(Entity, attributes(used_in_new, used_in_render, must_sync, position, static_id))]
+#[proc_macro_derive(Entity, attributes(used_in_new, used_in_render, must_sync, position, static_id, merge, hierarchy, mesh_instance, rigid_body, character, prefab, snapshot, not_saved, reflect_debug, deferred_events, lifecycle_hooks, deterministic))]
 pub fn derive_entity(input: TokenStream) -> TokenStream {
     // Construct a representation of Rust code as a syntax tree
     // that we can manipulate
@@ -24,13 +24,14 @@ pub fn derive_entity(input: TokenStream) -> TokenStream {
 
 }
 
-fn get_entity_vec(ast:&DeriveInput, data:&DataStruct, fields:&FieldsNamed) -> (TokenStream2, Ident, Ident, TokenStream2) {
+fn get_entity_vec(ast:&DeriveInput, data:&DataStruct, fields:&FieldsNamed, is_prefab:bool, is_snapshot:bool, is_reflect_debug:bool, is_deferred_events:bool, is_lifecycle_hooks:bool, is_deterministic:bool) -> (TokenStream2, Ident, Ident, TokenStream2) {
     let ent_ident = ast.ident.clone();
 
     let (impl_generics, ty_generics, where_clause) = ast.generics.split_for_impl();
 
     let mut tunnel_in_components = Vec::new();
     let mut tunnel_out_components = Vec::new();
+    let mut apply_event_fns = Vec::new();
     let mut arw_types = Vec::new();
     let mut arw_components = Vec::new();
     let mut used_new_types = Vec::new();
@@ -44,6 +45,16 @@ fn get_entity_vec(ast:&DeriveInput, data:&DataStruct, fields:&FieldsNamed) -> (T
     let mut static_type_id_type = None;
     let mut must_sync_types = Vec::new();
     let mut must_sync_components = Vec::new();
+    let mut saved_types = Vec::new();
+    let mut saved_components = Vec::new();
+    let mut not_saved_types = Vec::new();
+    let mut not_saved_components = Vec::new();
+    let mut components_metadata = Vec::new();
+    let mut merge_reducers = Vec::new();
+    let mut hierarchy_component = None;
+    let mut mesh_instance = None;
+    let mut rigid_body_component = None;
+    let mut character_component = None;
 
 
     for field in &fields.named {
@@ -51,13 +62,69 @@ fn get_entity_vec(ast:&DeriveInput, data:&DataStruct, fields:&FieldsNamed) -> (T
         arw_types.push(field.ty.clone());
         tunnel_in_components.push(Ident::new(format!("{}_in", field.ident.as_ref().unwrap().clone()).trim(), Span::call_site()));
         tunnel_out_components.push(Ident::new(format!("{}_out", field.ident.as_ref().unwrap().clone()).trim(), Span::call_site()));
+        apply_event_fns.push(Ident::new(format!("apply_{}_event", field.ident.as_ref().unwrap().clone()).trim(), Span::call_site()));
         
         let mut used_new = false;
         let mut used_render = false;
         let mut must_sync = false;
         let mut position = false;
         let mut static_type_id = false;
+        let mut not_saved = false;
+        let mut merge_reducer = None;
         for attr in &field.attrs {
+            if attr.path.is_ident(&Ident::new("merge", Span::call_site())) {
+                match attr.parse_meta() {
+                    Ok(Meta::NameValue(name_value)) => match name_value.lit {
+                        Lit::Str(value) => {
+                            merge_reducer = Some(match value.value().trim() {
+                                "last_writer_wins" => quote! {last_writer_wins},
+                                "sum" => quote! {sum_merge},
+                                "min" => quote! {min_merge},
+                                "max" => quote! {max_merge},
+                                custom => match syn::parse_str::<Path>(custom) {
+                                    Ok(path) => quote! {#path},
+                                    Err(_) => panic!("Merge policy should be last_writer_wins, sum, min, max or the path to a reducer function")
+                                }
+                            });
+                        },
+                        _ => panic!("Merge policy has to be a string")
+                    },
+                    _ => panic!("Merge policy should be given as #[merge = \"policy\"]")
+                }
+            }
+            if attr.path.is_ident(&Ident::new("hierarchy", Span::call_site())) {
+                if hierarchy_component.is_some() {
+                    panic!("Already got hierarchy !");
+                }
+                hierarchy_component = Some(field.ident.as_ref().unwrap().clone());
+            }
+            if attr.path.is_ident(&Ident::new("rigid_body", Span::call_site())) {
+                if rigid_body_component.is_some() {
+                    panic!("Already got rigid body !");
+                }
+                rigid_body_component = Some(field.ident.as_ref().unwrap().clone());
+            }
+            if attr.path.is_ident(&Ident::new("character", Span::call_site())) {
+                if character_component.is_some() {
+                    panic!("Already got character !");
+                }
+                character_component = Some(field.ident.as_ref().unwrap().clone());
+            }
+            if attr.path.is_ident(&Ident::new("mesh_instance", Span::call_site())) {
+                match attr.parse_meta() {
+                    Ok(Meta::NameValue(name_value)) => match name_value.lit {
+                        Lit::Str(value) => match value.value().trim().parse::<usize>() {
+                            Ok(for_vec) => mesh_instance = Some((field.ident.as_ref().unwrap().clone(), for_vec)),
+                            Err(_) => panic!("Mesh instance vec should be a number")
+                        },
+                        _ => panic!("Mesh instance vec has to be a string")
+                    },
+                    _ => panic!("Mesh instance should be given as #[mesh_instance = \"vec\"]")
+                }
+            }
+            if attr.path.is_ident(&Ident::new("not_saved", Span::call_site())) {
+                not_saved = true;
+            }
             if attr.path.is_ident(&Ident::new("used_in_new", Span::call_site())) {
                 used_new = true;
             }
@@ -75,6 +142,20 @@ fn get_entity_vec(ast:&DeriveInput, data:&DataStruct, fields:&FieldsNamed) -> (T
                 used_render = true;
             }
         }
+        merge_reducers.push(merge_reducer);
+        let component_name = field.ident.as_ref().unwrap().to_string();
+        let type_name = field.ty.to_token_stream().to_string();
+        components_metadata.push(quote! {
+            ComponentMetadata {
+                name:#component_name,
+                type_name:#type_name,
+                used_in_new:#used_new,
+                used_in_render:#used_render,
+                must_sync:#must_sync,
+                position:#position,
+                static_id:#static_type_id,
+            }
+        });
         if position {
             match &position_type {
                 Some(ident) => panic!("Already got position !"),
@@ -106,6 +187,17 @@ fn get_entity_vec(ast:&DeriveInput, data:&DataStruct, fields:&FieldsNamed) -> (T
             must_sync_components.push(field.ident.as_ref().unwrap().clone());
             must_sync_types.push(field.ty.clone());
         }
+        if not_saved {
+            if static_type_id {
+                panic!("The static type id component has to be saved in snapshots")
+            }
+            not_saved_components.push(field.ident.as_ref().unwrap().clone());
+            not_saved_types.push(field.ty.clone());
+        }
+        else {
+            saved_components.push(field.ident.as_ref().unwrap().clone());
+            saved_types.push(field.ty.clone());
+        }
     }
 
     let gen_vec_type = Ident::new(format!("{}Vec", ent_ident.to_string()).trim(), Span::call_site());
@@ -123,6 +215,320 @@ fn get_entity_vec(ast:&DeriveInput, data:&DataStruct, fields:&FieldsNamed) -> (T
     let static_type_id_component = static_type_id_component.unwrap();
     let position_ident = position_component.unwrap();
     let position_type = position_type.unwrap();
+
+    let gen_defaults_type = Ident::new(format!("{}Defaults", ent_ident.to_string()).trim(), Span::call_site());
+    let gen_changes_type = Ident::new(format!("{}Changes", ent_ident.to_string()).trim(), Span::call_site());
+    let (new_ent_extra_params, new_ent_extra_args, new_ent_extra_clones, new_ent_fn_generics, new_ent_generics) = if must_sync_types.len() > 0 {
+        (
+            quote! {must_be_synced:MustSync, created_by:Option<ID>},
+            quote! {must_be_synced, created_by},
+            quote! {must_be_synced.clone(), created_by.clone()},
+            quote! {<ID:Identify>},
+            quote! {<ID>}
+        )
+    }
+    else {
+        (quote! {}, quote! {}, quote! {}, quote! {}, quote! {})
+    };
+    let gen_lifecycle_type = Ident::new(format!("{}Lifecycle", ent_ident.to_string()).trim(), Span::call_site());
+    let despawn_event_type = if must_sync_types.len() > 0 {
+        let event_type_id = Ident::new(format!("{}Event", ent_ident.to_string()).trim(), Span::call_site());
+        quote! {#event_type_id<DespawnEvent<ID>>}
+    }
+    else {
+        quote! {DespawnEvent<ID>}
+    };
+    let is_multiplayer = must_sync_types.len() > 0;
+    let (event_access, new_ent_ids, despawn_ids) = if is_multiplayer {
+        (
+            quote! {&event.event},
+            quote! {(0, ent.created_by.clone())},
+            quote! {(event.event.id, event.event.made_by.clone())}
+        )
+    }
+    else {
+        (
+            quote! {event},
+            quote! {(0, None::<ID>)},
+            quote! {(event.id, event.made_by.clone())}
+        )
+    };
+    let mut component_receivers = Vec::with_capacity(arw_components.len());
+    for i in 0..arw_components.len() {
+        let tunnel_in = &tunnel_in_components[i];
+        let arw_type = &arw_types[i];
+        let receiver = if is_deterministic {
+            quote! {
+                {
+                    let mut events:Vec<_> = std::iter::from_fn(|| self.tunnels_in.#tunnel_in.recv_timeout(std::time::Duration::from_nanos(10)).ok()).collect();
+                    sort_events(&mut events, |event| (
+                        <<#arw_type as Component<ID>>::CE as ComponentEvent<#arw_type, ID>>::get_id(#event_access),
+                        <<#arw_type as Component<ID>>::CE as ComponentEvent<#arw_type, ID>>::get_source(#event_access)
+                    ));
+                    events.into_iter().map(|event| event.event)
+                }
+            }
+        }
+        else {
+            quote! {std::iter::from_fn(|| self.tunnels_in.#tunnel_in.recv_timeout(std::time::Duration::from_nanos(10)).ok())}
+        };
+        component_receivers.push(match &merge_reducers[i] {
+            Some(reducer) => {
+                let payload_reducer = if is_multiplayer {
+                    let event_type_id = Ident::new(format!("{}Event", ent_ident.to_string()).trim(), Span::call_site());
+                    quote! {|first:#event_type_id<_>, second:#event_type_id<_>| #event_type_id::new(first.must_be_synced.union(&second.must_be_synced), #reducer(first.event, second.event))}
+                }
+                else {
+                    quote! {#reducer}
+                };
+                quote! {
+                    merge_events(#receiver.collect(), |event| (<<#arw_type as Component<ID>>::CE as ComponentEvent<#arw_type, ID>>::get_id(#event_access), <<#arw_type as Component<ID>>::CE as ComponentEvent<#arw_type, ID>>::merge_kind(#event_access)), #payload_reducer)
+                }
+            },
+            None => receiver
+        });
+    }
+    let (new_ents_receiver, despawns_receiver) = if is_deterministic {
+        (
+            quote! {
+                {
+                    let mut events:Vec<_> = std::iter::from_fn(|| self.tunnels_in.new_ents.recv_timeout(std::time::Duration::from_nanos(10)).ok()).collect();
+                    sort_events(&mut events, |ent| #new_ent_ids);
+                    events.into_iter().map(|event| event.event)
+                }
+            },
+            quote! {
+                {
+                    let mut events:Vec<_> = std::iter::from_fn(|| self.tunnels_in.despawns.recv_timeout(std::time::Duration::from_nanos(10)).ok()).collect();
+                    sort_events(&mut events, |event| #despawn_ids);
+                    events.into_iter().map(|event| event.event)
+                }
+            }
+        )
+    }
+    else {
+        (
+            quote! {std::iter::from_fn(|| self.tunnels_in.new_ents.recv_timeout(std::time::Duration::from_nanos(10)).ok())},
+            quote! {std::iter::from_fn(|| self.tunnels_in.despawns.recv_timeout(std::time::Duration::from_nanos(10)).ok())}
+        )
+    };
+    let (lifecycle_vec_field, lifecycle_vec_init, record_spawn, record_despawn) = if is_lifecycle_hooks {
+        (
+            quote! {
+                pub spawned:std::sync::Arc<std::sync::RwLock<Vec<EntityID>>>,
+                pub despawned:std::sync::Arc<std::sync::RwLock<Vec<EntityID>>>,
+            },
+            quote! {
+                spawned:std::sync::Arc::new(std::sync::RwLock::new(Vec::new())),
+                despawned:std::sync::Arc::new(std::sync::RwLock::new(Vec::new())),
+            },
+            quote! {self.spawned.write().unwrap().push(new_id);},
+            quote! {self.despawned.write().unwrap().push(despawn_id);}
+        )
+    }
+    else {
+        (quote! {}, quote! {}, quote! {}, quote! {})
+    };
+    let record_batch_spawn = if is_lifecycle_hooks {
+        quote! {self.spawned.write().unwrap().extend(range.clone());}
+    }
+    else {
+        quote! {}
+    };
+    let (hierarchy_part, resolve_hierarchy) = match &hierarchy_component {
+        Some(hierarchy_ident) => (
+            quote! {
+                impl<'a, ID:Identify> #gen_vec_write_type <'a, ID> {
+                    /// Applies the `OrphanPolicy` of the children of despawned entities, then resolves the world transform of every child, see `Hierarchy`
+                    ///
+                    /// Returns the children that were despawned along with their parent
+                    pub fn resolve_hierarchy(&mut self) -> Vec<EntityID> {
+                        let len = self.#hierarchy_ident.len();
+                        let mut dead_parents:Vec<EntityID> = self.#hierarchy_ident.iter().enumerate()
+                            .filter(|(id, _)| self.alive.is_alive(*id))
+                            .filter_map(|(_, node)| node.parent)
+                            .filter(|parent| *parent >= len || !self.alive.is_alive(*parent))
+                            .collect();
+                        dead_parents.sort_unstable();
+                        dead_parents.dedup();
+                        let mut despawned = Vec::new();
+                        for parent in dead_parents {
+                            let alive = &self.alive;
+                            let orphans = orphans_of(parent, self.#hierarchy_ident.iter().enumerate().filter(|(id, _)| alive.is_alive(*id)));
+                            for child in orphans.detached {
+                                let world = self.#hierarchy_ident[child].world.take();
+                                self.#hierarchy_ident[child].parent = None;
+                                if let Some(world) = world {
+                                    if let Some(event) = <#position_type as EntityPosition<ID>>::move_event(child, None, world.pos, world.orient) {
+                                        <<#position_type as Component<ID>>::CE as ComponentEvent<#position_type, ID>>::apply_to_component(event, &mut self.#position_ident);
+                                    }
+                                }
+                                self.changes.mark_all(child);
+                            }
+                            for child in orphans.despawned {
+                                if self.despawn(child) {
+                                    despawned.push(child);
+                                }
+                            }
+                        }
+                        let alive = &self.alive;
+                        let positions = &self.#position_ident;
+                        let transforms = resolve_world_transforms(self.#hierarchy_ident.iter().enumerate().filter(|(id, _)| alive.is_alive(*id)), |root| {
+                            if *root < positions.len() && alive.is_alive(*root) {
+                                Some(WorldTransform::new(<#position_type as EntityPosition<ID>>::get_pos(&positions[*root]), <#position_type as EntityPosition<ID>>::get_orientation(&positions[*root])))
+                            }
+                            else {
+                                None
+                            }
+                        });
+                        for node in self.#hierarchy_ident.iter_mut() {
+                            node.world = None;
+                        }
+                        for (id, transform) in transforms {
+                            self.#hierarchy_ident[id].world = Some(transform);
+                        }
+                        despawned
+                    }
+                }
+            },
+            quote! {
+                for despawn_id in write_handler.resolve_hierarchy() {
+                    #record_despawn
+                }
+            }
+        ),
+        None => (quote! {}, quote! {})
+    };
+    let (push_world_transforms, render_extra_bound, push_after_render) = match (&hierarchy_component, &mesh_instance) {
+        (Some(hierarchy_ident), Some((instance_ident, for_vec))) => (
+            quote! {
+                impl<ID:Identify> #gen_vec_type<ID> {
+                    /// Moves the mesh instance of every child to the world transform its hierarchy resolved to
+                    pub fn push_world_transforms<RB:InstanceTransforms>(&self, rendering_data:&mut RB) {
+                        let hierarchies = self.#hierarchy_ident.read().unwrap();
+                        let instances = self.#instance_ident.read().unwrap();
+                        let alive = self.alive.read().unwrap();
+                        for (id, node) in hierarchies.iter().enumerate().filter(|(id, _)| alive.is_alive(*id)) {
+                            if let (Some(world), Some(instance)) = (&node.world, instances[id]) {
+                                rendering_data.set_instance_transform(instance, #for_vec, world);
+                            }
+                        }
+                    }
+                }
+            },
+            quote! {, RB:InstanceTransforms},
+            quote! {self.push_world_transforms(rendering_data);}
+        ),
+        _ => (quote! {}, quote! {}, quote! {})
+    };
+    // Physics and character stages both send a move event then an event of their own component
+    let position_tunnel = Ident::new(format!("{}_out", position_ident.to_string()).trim(), Span::call_site());
+    let (stage_sync_param, stage_move_event, stage_component_event) = if must_sync_types.len() > 0 {
+        let event_type_id = Ident::new(format!("{}Event", ent_ident.to_string()).trim(), Span::call_site());
+        (quote! {, must_be_synced:MustSync}, quote! {#event_type_id::new(must_be_synced.clone(), event)}, quote! {#event_type_id::new(must_be_synced, event)})
+    }
+    else {
+        (quote! {}, quote! {event}, quote! {event})
+    };
+    let (sent_move_event, sent_component_event) = if is_deterministic {
+        (quote! {SequencedEvent::new(#stage_move_event)}, quote! {SequencedEvent::new(#stage_component_event)})
+    }
+    else {
+        (stage_move_event, stage_component_event)
+    };
+    let (physics_part, update_broad_phase, broad_phase_vec_field, broad_phase_vec_init, broad_phase_read_field, broad_phase_read_init, broad_phase_write_field, broad_phase_write_init) = match &rigid_body_component {
+        Some(body_ident) => {
+            let body_tunnel = Ident::new(format!("{}_out", body_ident.to_string()).trim(), Span::call_site());
+            (
+                quote! {
+                    impl<'a, ID:Identify> #gen_vec_write_type <'a, ID> {
+                        /// Recomputes the bodies that may touch each other, see `PhysicsBroadPhase`
+                        pub fn update_broad_phase(&mut self) {
+                            self.broad_phase.update::<ID, #position_type>(&self.#body_ident, &self.#position_ident, &self.alive);
+                        }
+                    }
+
+                    impl<'a, ID:Identify> #gen_vec_read_type <'a, ID> {
+                        /// Physics tick stage of entity `id`, to call from one of the engine's `stage_N` functions
+                        ///
+                        /// Steps its rigid body with `step_rigid_body` against the bodies the broad phase found around it, then sends the body and move events through the tunnels
+                        pub fn physics_stage(&self, id:EntityID, statics:&PhysicsStatics, settings:&PhysicsSettings #stage_sync_param) {
+                            if !self.alive.is_alive(id) {
+                                return
+                            }
+                            if let Some(step) = step_rigid_body::<ID, #position_type>(id, &self.#body_ident, &self.#position_ident, self.broad_phase.candidates(id), statics, settings) {
+                                if let Some(event) = step.move_event::<ID, #position_type>(id, None) {
+                                    let _ = self.tunnels.#position_tunnel.send(#sent_move_event);
+                                }
+                                let event = step.body_event(id, None);
+                                let _ = self.tunnels.#body_tunnel.send(#sent_component_event);
+                            }
+                        }
+                    }
+                },
+                quote! {write_handler.update_broad_phase();},
+                quote! {pub broad_phase:std::sync::Arc<std::sync::RwLock<PhysicsBroadPhase>>,},
+                quote! {broad_phase:std::sync::Arc::new(std::sync::RwLock::new(PhysicsBroadPhase::new())),},
+                quote! {pub broad_phase:std::sync::RwLockReadGuard<'a, PhysicsBroadPhase>,},
+                quote! {broad_phase:self.broad_phase.read().unwrap(),},
+                quote! {pub broad_phase:std::sync::RwLockWriteGuard<'a, PhysicsBroadPhase>,},
+                quote! {broad_phase:self.broad_phase.write().unwrap(),}
+            )
+        },
+        None => (quote! {}, quote! {}, quote! {}, quote! {}, quote! {}, quote! {}, quote! {}, quote! {})
+    };
+    let character_part = match &character_component {
+        Some(character_ident) => {
+            let character_tunnel = Ident::new(format!("{}_out", character_ident.to_string()).trim(), Span::call_site());
+            quote! {
+                impl<'a, ID:Identify> #gen_vec_read_type <'a, ID> {
+                    /// Character controller tick stage of entity `id`, to call from one of the engine's `stage_N` functions with the input of that character
+                    ///
+                    /// Moves it with `step_character`, then sends the move and controller events through the tunnels
+                    pub fn character_stage(&self, id:EntityID, input:&CharacterInput, statics:&PhysicsStatics, tick_duration:f32 #stage_sync_param) {
+                        if !self.alive.is_alive(id) {
+                            return
+                        }
+                        let step = step_character::<ID, #position_type>(id, &self.#character_ident, &self.#position_ident, input, statics, tick_duration);
+                        if let Some(event) = step.move_event::<ID, #position_type>(id, None) {
+                            let _ = self.tunnels.#position_tunnel.send(#sent_move_event);
+                        }
+                        let event = step.controller_event(id, None);
+                        let _ = self.tunnels.#character_tunnel.send(#sent_component_event);
+                    }
+                }
+            }
+        },
+        None => quote! {}
+    };
+    let first_used_new_component = used_new_components[0].clone();
+    let (despawned_render_vec_field, despawned_render_vec_init, despawned_render_handler_field, despawned_render_handler_init, record_despawned_render) = if used_render_components.len() > 0 {
+        (
+            quote! {pub despawned_renders:std::sync::Arc<std::sync::RwLock<Vec<(#(#used_render_types,)* usize)>>>,},
+            quote! {despawned_renders:std::sync::Arc::new(std::sync::RwLock::new(Vec::new())),},
+            quote! {pub despawned_renders:std::sync::RwLockWriteGuard<'a, Vec<(#(#used_render_types,)* usize)>>,},
+            quote! {despawned_renders:self.despawned_renders.write().unwrap(),},
+            quote! {
+                let static_type = <#static_type_id_type as HasStaticTypeID>::get_id(&self.#static_type_id_component[id]);
+                self.despawned_renders.push((#(self.#used_render_components[id].clone(),)* static_type));
+            }
+        )
+    }
+    else {
+        (quote! {}, quote! {}, quote! {}, quote! {}, quote! {})
+    };
+    let (prefab_vec_field, prefab_vec_init, prefab_handler_field, prefab_handler_init) = if is_prefab {
+        (
+            quote! {pub prefabs:PrefabRegistry<#gen_defaults_type>,},
+            quote! {prefabs:PrefabRegistry::new(),},
+            quote! {pub prefabs:PrefabRegistry<#gen_defaults_type>,},
+            quote! {prefabs:self.prefabs.clone(),}
+        )
+    }
+    else {
+        (quote! {}, quote! {}, quote! {}, quote! {})
+    };
+
     let (vec_type, new_and_apply_events, new_ent_type_generics) = if must_sync_types.len() > 0 {
         let sync_event_type_id = Ident::new(format!("{}SyncEvent", ent_ident.to_string()).trim(), Span::call_site());
         let sync_event_enum_id = Ident::new(format!("{}SyncEventVariant", ent_ident.to_string()).trim(), Span::call_site());
@@ -169,20 +575,23 @@ fn get_entity_vec(ast:&DeriveInput, data:&DataStruct, fields:&FieldsNamed) -> (T
                     //CoolComponent(usize),
                     #(#arw_components (<#arw_types as Component<ID>>::CE)),*,
 
-                    NewEnt { ent:#gen_new_ent_type<ID>, new_id:usize, made_by:Option<ID> }
+                    NewEnt { ent:#gen_new_ent_type<ID>, new_id:usize, made_by:Option<ID> },
+                    Despawn(DespawnEvent<ID>),
                 }
 
                 impl<ID:Identify> #sync_event_enum_id<ID> {
                     pub fn get_source(&self) -> Option<ID> {
                         match &self {
                             #(#sync_event_enum_id::#arw_components(evt) => evt.get_source()),*,
-                            #sync_event_enum_id::NewEnt {made_by, ..} => made_by.clone()
+                            #sync_event_enum_id::NewEnt {made_by, ..} => made_by.clone(),
+                            #sync_event_enum_id::Despawn(event) => event.made_by.clone(),
                         }
                     }
                     pub fn get_id(&self) -> EntityID {
                         match &self {
                             #(#sync_event_enum_id::#arw_components(evt) => <<#arw_types as Component<ID>>::CE as ComponentEvent<#arw_types, ID>>::get_id(evt)),*,
-                            #sync_event_enum_id::NewEnt {new_id, ..} => new_id.clone()
+                            #sync_event_enum_id::NewEnt {new_id, ..} => new_id.clone(),
+                            #sync_event_enum_id::Despawn(event) => event.id,
                         }
                     }
                 }
@@ -205,9 +614,15 @@ fn get_entity_vec(ast:&DeriveInput, data:&DataStruct, fields:&FieldsNamed) -> (T
                     pub tunnels_in:#gen_vec_tunnels_in<ID>,
                     pub tunnels_out:#gen_vec_tunnels_out<ID>,
                     pub available_entities:std::sync::Arc<std::sync::RwLock<std::collections::VecDeque<EntityID>>>,
+                    pub alive:std::sync::Arc<std::sync::RwLock<AliveMask>>,
+                    #despawned_render_vec_field
                     pub stops:EVecStopsIn,
                     pub to_sync:std::sync::Arc<std::sync::RwLock<Vec<#sync_event_enum_id<ID>>>>,
-                    pub all_events:std::sync::Arc<std::sync::RwLock<Vec<#sync_event_enum_id<ID>>>>
+                    pub all_events:std::sync::Arc<std::sync::RwLock<Vec<#sync_event_enum_id<ID>>>>,
+                    pub changes:std::sync::Arc<std::sync::RwLock<#gen_changes_type>>,
+                    #prefab_vec_field
+                    #broad_phase_vec_field
+                    #lifecycle_vec_field
                 }
             },
             quote! {
@@ -224,9 +639,15 @@ fn get_entity_vec(ast:&DeriveInput, data:&DataStruct, fields:&FieldsNamed) -> (T
                         tunnels_in,
                         tunnels_out,
                         available_entities:available_entities.clone(),
+                        alive:std::sync::Arc::new(std::sync::RwLock::new(AliveMask::with_capacity(capacity))),
+                        #despawned_render_vec_init
                         stops:stops_in,
                         to_sync,
                         all_events:std::sync::Arc::new(std::sync::RwLock::new(Vec::with_capacity(2048))),
+                        changes:std::sync::Arc::new(std::sync::RwLock::new(#gen_changes_type::new(capacity))),
+                        #prefab_vec_init
+                        #broad_phase_vec_init
+                        #lifecycle_vec_init
                     }
                 }
                 pub fn apply_all_events<'a>(&'a self, is_server:bool) {
@@ -236,18 +657,19 @@ fn get_entity_vec(ast:&DeriveInput, data:&DataStruct, fields:&FieldsNamed) -> (T
                         let mut all_events_write = self.all_events.write().unwrap();
                         #(
                             {
-                                while let Ok(event) = self.tunnels_in.#tunnel_in_components.recv_timeout(std::time::Duration::from_nanos(10)) {
+                                for event in #component_receivers {
                                     if event.must_be_synced.is_server() {
                                         to_sync_write.push(#sync_event_enum_id::#arw_components(event.event.clone()));
                                     }
                                     all_events_write.push(#sync_event_enum_id::#arw_components(event.event.clone()));
-                                    <<#arw_types as Component<ID>>::CE as ComponentEvent<#arw_types, ID>>::apply_to_component(event.event.clone(), &mut write_handler.#arw_components);
+                                    write_handler.#apply_event_fns(event.event);
                                 }
                             }
                         );* ;
                         {
-                            while let Ok(ent) = self.tunnels_in.new_ents.recv_timeout(std::time::Duration::from_nanos(10)) {
+                            for ent in #new_ents_receiver {
                                 let new_id = write_handler.new_ent(ent.clone());
+                                #record_spawn
                                 all_events_write.push(#sync_event_enum_id::NewEnt{made_by: ent.created_by.clone(), ent:ent.clone(), new_id});
                                 if ent.must_be_synced.is_server() {
                                     
@@ -255,36 +677,66 @@ fn get_entity_vec(ast:&DeriveInput, data:&DataStruct, fields:&FieldsNamed) -> (T
                                 }
                             }
                         }
+                        {
+                            for event in #despawns_receiver {
+                                let despawn_id = event.event.id;
+                                if write_handler.despawn(despawn_id) {
+                                    #record_despawn
+                                    all_events_write.push(#sync_event_enum_id::Despawn(event.event.clone()));
+                                    if event.must_be_synced.is_server() {
+                                        to_sync_write.push(#sync_event_enum_id::Despawn(event.event));
+                                    }
+                                }
+                            }
+                        }
                     }
                     else {
                         #(
                             {
-                                while let Ok(event) = self.tunnels_in.#tunnel_in_components.recv_timeout(std::time::Duration::from_nanos(10)) {
+                                for event in #component_receivers {
                                     if event.must_be_synced.is_client() {
                                         to_sync_write.push(#sync_event_enum_id::#arw_components(event.event.clone()));
                                     }
-                                    <<#arw_types as Component<ID>>::CE as ComponentEvent<#arw_types, ID>>::apply_to_component(event.event.clone(), &mut write_handler.#arw_components);
+                                    write_handler.#apply_event_fns(event.event);
                                 }
                             }
                         );* ;
                         {
-                            while let Ok(ent) = self.tunnels_in.new_ents.recv_timeout(std::time::Duration::from_nanos(10)) {
+                            for ent in #new_ents_receiver {
                                 if ent.must_be_synced.is_client() {
                                     let new_id = write_handler.new_ent(ent.clone());
+                                    #record_spawn
                                     to_sync_write.push(#sync_event_enum_id::NewEnt{made_by: ent.created_by.clone(), ent, new_id});
                                 }
                                 else {
-                                    write_handler.new_ent(ent);
+                                    let new_id = write_handler.new_ent(ent);
+                                    #record_spawn
+                                }
+                            }
+                        }
+                        {
+                            for event in #despawns_receiver {
+                                let despawn_id = event.event.id;
+                                if write_handler.despawn(despawn_id) {
+                                    #record_despawn
+                                    if event.must_be_synced.is_client() {
+                                        to_sync_write.push(#sync_event_enum_id::Despawn(event.event));
+                                    }
                                 }
                             }
                         }
                     }
-                    
+                    #resolve_hierarchy
+                    #update_broad_phase
                 }
                 pub fn change_component<'a>(&'a self, component:#sync_component_enum_id, id:usize) {
                     let mut write_handler = self.get_write();
                     match component {
-                        #(#sync_component_enum_id::#arw_components (data) => {write_handler.#arw_components[id] = data}),*,
+                        #(#sync_component_enum_id::#arw_components (data) => {
+                            let tick = write_handler.changes.tick();
+                            write_handler.changes.#arw_components.mark(id, tick);
+                            write_handler.#arw_components[id] = data
+                        }),*,
                     }
                 }
                 pub fn is_that_component_correct(&self, component:#sync_component_enum_id, id:usize) -> bool {
@@ -296,8 +748,17 @@ fn get_entity_vec(ast:&DeriveInput, data:&DataStruct, fields:&FieldsNamed) -> (T
                 pub fn apply_one_event<'a>(&'a self, event:#sync_event_enum_id<ID>) {
                     let mut write_handler = self.get_write();
                     match event {
-                        #(#sync_event_enum_id::#arw_components (sub_event) => <<#arw_types as Component<ID>>::CE as ComponentEvent<#arw_types, ID>>::apply_to_component(sub_event, &mut write_handler.#arw_components)),*,
-                        #sync_event_enum_id::NewEnt{ent, new_id, made_by} => {write_handler.new_ent(ent);}, 
+                        #(#sync_event_enum_id::#arw_components (sub_event) => write_handler.#apply_event_fns(sub_event)),*,
+                        #sync_event_enum_id::NewEnt{ent, new_id, made_by} => {
+                            let new_id = write_handler.new_ent(ent);
+                            #record_spawn
+                        },
+                        #sync_event_enum_id::Despawn(event) => {
+                            let despawn_id = event.id;
+                            if write_handler.despawn(despawn_id) {
+                                #record_despawn
+                            }
+                        },
                     }
                 }
                 pub fn get_need_sync<'a>(&'a self) -> std::sync::RwLockWriteGuard<Vec<#sync_event_enum_id<ID>>> {
@@ -319,9 +780,15 @@ fn get_entity_vec(ast:&DeriveInput, data:&DataStruct, fields:&FieldsNamed) -> (T
                 <#field_type as Component<ID>>::CE
             });
         }
+        let new_ent_derive = if is_deferred_events {
+            quote! {#[derive(Clone, to_from_bytes_derive::ToBytes, to_from_bytes_derive::FromBytes)]}
+        }
+        else {
+            quote! {#[derive(Clone)]}
+        };
         (
             quote! {
-                #[derive(Clone)]
+                #new_ent_derive
                 pub struct #gen_new_ent_type {
                     #(#used_new_components:#used_new_types),* ,
                 }
@@ -344,7 +811,13 @@ fn get_entity_vec(ast:&DeriveInput, data:&DataStruct, fields:&FieldsNamed) -> (T
                     pub tunnels_in:#gen_vec_tunnels_in<ID>,
                     pub tunnels_out:#gen_vec_tunnels_out<ID>,
                     pub available_entities:std::sync::Arc<std::sync::RwLock<std::collections::VecDeque<EntityID>>>,
-                    pub stops:EVecStopsIn
+                    pub alive:std::sync::Arc<std::sync::RwLock<AliveMask>>,
+                    #despawned_render_vec_field
+                    pub stops:EVecStopsIn,
+                    pub changes:std::sync::Arc<std::sync::RwLock<#gen_changes_type>>,
+                    #prefab_vec_field
+                    #broad_phase_vec_field
+                    #lifecycle_vec_field
                 }
             },
             quote! {
@@ -360,23 +833,40 @@ fn get_entity_vec(ast:&DeriveInput, data:&DataStruct, fields:&FieldsNamed) -> (T
                         tunnels_in,
                         tunnels_out,
                         available_entities:available_entities.clone(),
-                        stops:stops_in
+                        alive:std::sync::Arc::new(std::sync::RwLock::new(AliveMask::with_capacity(capacity))),
+                        #despawned_render_vec_init
+                        stops:stops_in,
+                        changes:std::sync::Arc::new(std::sync::RwLock::new(#gen_changes_type::new(capacity))),
+                        #prefab_vec_init
+                        #broad_phase_vec_init
+                        #lifecycle_vec_init
                     }
                 }
                 pub fn apply_all_events<'a>(&'a self, useless_bool:bool) { // a bit ugly but cleaner than the alternative I think
                     let mut write_handler = self.get_write();
                     #(
                         {
-                            while let Ok(event) = self.tunnels_in.#tunnel_in_components.recv_timeout(std::time::Duration::from_nanos(10)) {
-                                <<#arw_types as Component<ID>>::CE as ComponentEvent<#arw_types, ID>>::apply_to_component(event, &mut write_handler.#arw_components);
+                            for event in #component_receivers {
+                                write_handler.#apply_event_fns(event);
                             }
                         }
                     );* ;
                     {
-                        while let Ok(ent) = self.tunnels_in.new_ents.recv_timeout(std::time::Duration::from_nanos(10)) {
-                            write_handler.new_ent(ent);
+                        for ent in #new_ents_receiver {
+                            let new_id = write_handler.new_ent(ent);
+                            #record_spawn
+                        }
+                    }
+                    {
+                        for event in #despawns_receiver {
+                            let despawn_id = event.id;
+                            if write_handler.despawn(despawn_id) {
+                                #record_despawn
+                            }
                         }
                     }
+                    #resolve_hierarchy
+                    #update_broad_phase
                 }
             },
             quote! {
@@ -392,24 +882,400 @@ fn get_entity_vec(ast:&DeriveInput, data:&DataStruct, fields:&FieldsNamed) -> (T
         quote! {
             pub trait #gen_render_ent_type<RB, ID:Identify> {
                 fn do_render_changes(rendering_data:&mut RB, #(#used_render_components:&mut #used_render_types),*, static_type:&#static_type_ident<ID>);
+                /// Called when the rendering instances of the entity should go away, like before a snapshot replaces it, does nothing by default
+                fn remove_render(rendering_data:&mut RB, #(#used_render_components:&mut #used_render_types),*, static_type:&#static_type_ident<ID>) {
+
+                }
+                /// Called on entities spawned together, can be overriden to register all their rendering instances at once
+                fn do_batch_render_changes(rendering_data:&mut RB, #(#used_render_components:&mut [#used_render_types]),*, static_types:&[#static_type_ident<ID>]) {
+                    for i in 0..#first_component.len() {
+                        let static_type = <#static_type_id_type as HasStaticTypeID>::get_id(&#static_type_id_component[i]);
+                        Self::do_render_changes(rendering_data, #(&mut #used_render_components[i]),*, &static_types[static_type]);
+                    }
+                }
             }
 
             impl<ID:Identify> #gen_vec_type<ID> {
-                pub fn do_all_renders<RB>(&mut self, rendering_data:&mut RB) where #ent_ident:#gen_render_ent_type<RB, ID> {
+                /// Calls `remove_render` on the entities despawned since the last render, with their `used_in_render` components as they were when despawned
+                pub fn remove_despawned_renders<RB>(&mut self, rendering_data:&mut RB) where #ent_ident:#gen_render_ent_type<RB, ID> {
+                    let despawned = std::mem::take(&mut *self.despawned_renders.write().unwrap());
+                    let static_types = self.static_types.read().unwrap();
+                    for (#(mut #used_render_components,)* static_type) in despawned {
+                        <#ent_ident as #gen_render_ent_type<RB, ID>>::remove_render(rendering_data, #(&mut #used_render_components),*, &static_types[static_type]);
+                    }
+                }
+                pub fn do_all_renders<RB>(&mut self, rendering_data:&mut RB) where #ent_ident:#gen_render_ent_type<RB, ID> #render_extra_bound {
+                    self.remove_despawned_renders(rendering_data);
+                    {
+                        #(let mut #used_render_components = self.#used_render_components.write().unwrap());*;
+                        let static_types = self.static_types.read().unwrap();
+                        let alive = self.alive.read().unwrap();
+                        let len = #first_component.len();
+                        for i in (0..len).filter(|i| alive.is_alive(*i)) {
+                            let static_type = <#static_type_id_type as HasStaticTypeID>::get_id(&#static_type_id_component[i]);
+                            <#ent_ident as #gen_render_ent_type<RB, ID>>::do_render_changes(rendering_data, #(&mut #used_render_components[i]),*, &static_types[static_type]);
+                        }
+                    }
+                    #push_after_render
+                }
+                /// Calls `remove_render` on every living entity and on the ones despawned since the last render
+                pub fn remove_all_renders<RB>(&mut self, rendering_data:&mut RB) where #ent_ident:#gen_render_ent_type<RB, ID> {
+                    self.remove_despawned_renders(rendering_data);
                     #(let mut #used_render_components = self.#used_render_components.write().unwrap());*;
                     let static_types = self.static_types.read().unwrap();
+                    let alive = self.alive.read().unwrap();
                     let len = #first_component.len();
-                    for i in 0..len {
+                    for i in (0..len).filter(|i| alive.is_alive(*i)) {
                         let static_type = <#static_type_id_type as HasStaticTypeID>::get_id(&#static_type_id_component[i]);
-                        <#ent_ident as #gen_render_ent_type<RB, ID>>::do_render_changes(rendering_data, #(&mut #used_render_components[i]),*, &static_types[static_type]);
+                        <#ent_ident as #gen_render_ent_type<RB, ID>>::remove_render(rendering_data, #(&mut #used_render_components[i]),*, &static_types[static_type]);
+                    }
+                }
+                /// Renders the entities of `ids` as one batch, for example the ones returned by `spawn_batch`
+                pub fn do_range_renders<RB>(&mut self, rendering_data:&mut RB, ids:std::ops::Range<EntityID>) where #ent_ident:#gen_render_ent_type<RB, ID> {
+                    #(let mut #used_render_components = self.#used_render_components.write().unwrap());*;
+                    let static_types = self.static_types.read().unwrap();
+                    <#ent_ident as #gen_render_ent_type<RB, ID>>::do_batch_render_changes(rendering_data, #(&mut #used_render_components[ids.clone()]),*, &static_types);
+                }
+                /// Only renders living entities that had one of their `used_in_render` components changed strictly after `tick`
+                ///
+                /// Children of a hierarchy still get their mesh instance moved, as their parent may have moved
+                pub fn do_changed_renders<RB>(&mut self, rendering_data:&mut RB, tick:usize) where #ent_ident:#gen_render_ent_type<RB, ID> #render_extra_bound {
+                    self.remove_despawned_renders(rendering_data);
+                    {
+                        #(let mut #used_render_components = self.#used_render_components.write().unwrap());*;
+                        let static_types = self.static_types.read().unwrap();
+                        let alive = self.alive.read().unwrap();
+                        let changes = self.changes.read().unwrap();
+                        let len = #first_component.len();
+                        let mut changed:Vec<EntityID> = std::iter::empty() #(.chain(changes.#used_render_components.changed_since(tick)))*.collect();
+                        changed.sort_unstable();
+                        changed.dedup();
+                        for i in changed.into_iter().take_while(|i| *i < len).filter(|i| alive.is_alive(*i)) {
+                            let static_type = <#static_type_id_type as HasStaticTypeID>::get_id(&#static_type_id_component[i]);
+                            <#ent_ident as #gen_render_ent_type<RB, ID>>::do_render_changes(rendering_data, #(&mut #used_render_components[i]),*, &static_types[static_type]);
+                        }
+                    }
+                    #push_after_render
+                }
+            }
+        }
+    }
+    else {
+        quote! {}
+    };
+    let prefab_part = if is_prefab {
+        let static_field_names:Vec<String> = arw_components.iter().map(|component| component.to_string()).collect();
+        let static_field_lines:Vec<String> = arw_components.iter().map(|component| format!("static.{} = ", component)).collect();
+        let default_field_names:Vec<String> = used_new_components.iter().map(|component| component.to_string()).collect();
+        let default_field_lines:Vec<String> = used_new_components.iter().map(|component| format!("default.{} = ", component)).collect();
+        quote! {
+            #[derive(Clone, to_from_bytes_derive::ToBytes, to_from_bytes_derive::FromBytes)]
+            pub struct #gen_defaults_type {
+                #(pub #used_new_components:#used_new_types),*
+            }
+
+            impl #gen_defaults_type {
+                pub fn into_new_ent #new_ent_fn_generics (self, static_type:usize, #new_ent_extra_params) -> #gen_new_ent_type #new_ent_generics {
+                    let mut new_ent = #gen_new_ent_type::new(#(self.#used_new_components),*, #new_ent_extra_args);
+                    <#static_type_id_type as SetStaticTypeID>::set_id(&mut new_ent.#static_type_id_component, static_type);
+                    new_ent
+                }
+            }
+
+            impl<ID:Identify> PrefabEntity<ID> for #ent_ident {
+                type Defaults = #gen_defaults_type;
+                fn static_from_text(fields:&PrefabTextFields) -> Result<#static_type_ident<ID>, PrefabError> {
+                    Ok(#static_type_ident {
+                        #(#arw_components:fields.get_static::<<#arw_types as Component<ID>>::SC>(#static_field_names)?),*
+                    })
+                }
+                fn defaults_from_text(fields:&PrefabTextFields) -> Result<#gen_defaults_type, PrefabError> {
+                    Ok(#gen_defaults_type {
+                        #(#used_new_components:fields.get_default::<#used_new_types>(#default_field_names)?),*
+                    })
+                }
+                fn write_static_text(static_type:&#static_type_ident<ID>, text:&mut String) {
+                    #(
+                        text.push_str(#static_field_lines);
+                        <<#arw_types as Component<ID>>::SC as PrefabText>::write_text(&static_type.#arw_components, text);
+                        text.push('\n');
+                    )*
+                }
+                fn write_defaults_text(defaults:&#gen_defaults_type, text:&mut String) {
+                    #(
+                        text.push_str(#default_field_lines);
+                        <#used_new_types as PrefabText>::write_text(&defaults.#used_new_components, text);
+                        text.push('\n');
+                    )*
+                }
+            }
+
+            impl<'a, ID:Identify> #gen_vec_write_type <'a, ID> {
+                /// Returns the static type ID the prefab got, or `PrefabError::DuplicateName` without adding anything if its name is taken
+                pub fn register_prefab(&mut self, prefab:EntityPrefab<#static_type_ident<ID>, #gen_defaults_type>) -> Result<usize, PrefabError> {
+                    if self.prefabs.contains(&prefab.name) {
+                        return Err(PrefabError::DuplicateName(prefab.name))
+                    }
+                    let static_type = self.static_types.len();
+                    self.static_types.push(prefab.static_type);
+                    self.prefabs.register(prefab.name, RegisteredPrefab { static_type, meshes:prefab.meshes, defaults:prefab.defaults })?;
+                    Ok(static_type)
+                }
+                /// Stops at the first prefab whose name is taken, the ones before it stay registered
+                pub fn register_prefabs(&mut self, prefabs:Vec<EntityPrefab<#static_type_ident<ID>, #gen_defaults_type>>) -> Result<(), PrefabError> {
+                    for prefab in prefabs {
+                        self.register_prefab(prefab)?;
+                    }
+                    Ok(())
+                }
+                pub fn new_ent_from_prefab(&mut self, name:&str, #new_ent_extra_params) -> Option<usize> {
+                    let prefab = self.prefabs.get(name)?;
+                    Some(self.new_ent(prefab.defaults.into_new_ent(prefab.static_type, #new_ent_extra_args)))
+                }
+            }
+
+            impl<'a, ID:Identify> #gen_vec_read_type <'a, ID> {
+                /// Builds the new entity to send through `tunnels.new_ents` during a tick
+                pub fn new_ent_from_prefab(&self, name:&str, #new_ent_extra_params) -> Option<#gen_new_ent_type #new_ent_generics> {
+                    let prefab = self.prefabs.get(name)?;
+                    Some(prefab.defaults.into_new_ent(prefab.static_type, #new_ent_extra_args))
+                }
+                /// Instances of the meshes of the prefab this entity was spawned from, at its position, see `MeshesRead::named_instances`
+                pub fn prefab_mesh_instances(&self, id:EntityID, meshes:&MeshesRead) -> Option<Vec<MeshInstance>> {
+                    let static_type = <#static_type_id_type as HasStaticTypeID>::get_id(&self.#static_type_id_component[id]);
+                    let position = &self.#position_ident[id];
+                    let names = self.prefabs.get_meshes_of_static_type(static_type)?;
+                    meshes.named_instances(&names, <#position_type as EntityPosition<ID>>::get_pos(position), <#position_type as EntityPosition<ID>>::get_orientation(position))
+                }
+            }
+        }
+    }
+    else {
+        quote! {}
+    };
+
+    let snapshot_part = if is_snapshot {
+        let gen_snapshot_type = Ident::new(format!("{}VecSnapshot", ent_ident.to_string()).trim(), Span::call_site());
+        let first_saved_component = saved_components[0].clone();
+        quote! {
+            #[derive(Clone, to_from_bytes_derive::ToBytes, to_from_bytes_derive::FromBytes)]
+            pub struct #gen_snapshot_type {
+                #(pub #saved_components:Vec<#saved_types>),*,
+                pub available_entities:Vec<EntityID>,
+            }
+
+            impl<ID:Identify> #gen_vec_type<ID> {
+                pub fn take_snapshot(&self) -> #gen_snapshot_type {
+                    let reader = self.get_read();
+                    #gen_snapshot_type {
+                        #(#saved_components:reader.#saved_components.clone()),*,
+                        available_entities:self.available_entities.read().unwrap().iter().copied().collect(),
+                    }
+                }
+                /// Static types aren't part of snapshots, they have to be registered the same way before restoring
+                ///
+                /// `#[not_saved]` components are rebuilt from the static type of each entity, and every entity is marked as changed at `tick`
+                pub fn restore_snapshot(&self, snapshot:#gen_snapshot_type, tick:usize) {
+                    let mut writer = self.get_write();
+                    #(*writer.#saved_components = snapshot.#saved_components);*;
+                    let len = writer.#first_saved_component.len();
+                    writer.alive.rebuild(len, snapshot.available_entities.iter().copied());
+                    *writer.available_entities = snapshot.available_entities.into_iter().collect();
+                    #(
+                        let rebuilt:Vec<#not_saved_types> = (0..len).map(|i| {
+                            let static_type = <#static_type_id_type as HasStaticTypeID>::get_id(&writer.#static_type_id_component[i]);
+                            <#not_saved_types as Component<ID>>::from_static(&writer.static_types[static_type].#not_saved_components)
+                        }).collect();
+                        *writer.#not_saved_components = rebuilt;
+                    )*
+                    writer.changes.set_tick(tick.max(1));
+                    for i in 0..len {
+                        writer.changes.mark_all(i);
+                    }
+                }
+            }
+        }
+    }
+    else {
+        quote! {}
+    };
+
+    let deferred_events_part = if is_deferred_events {
+        let gen_deferred_type = Ident::new(format!("{}DeferredEvent", ent_ident.to_string()).trim(), Span::call_site());
+        let wrap_sent = |payload:TokenStream2| if is_deterministic {
+            quote! {SequencedEvent::new(#payload)}
+        }
+        else {
+            payload
+        };
+        if must_sync_types.len() > 0 {
+            let event_type_id = Ident::new(format!("{}Event", ent_ident.to_string()).trim(), Span::call_site());
+            let (sent_event, sent_new_ent, sent_despawn) = (
+                wrap_sent(quote! {#event_type_id::new(must_be_synced, event)}),
+                wrap_sent(quote! {new_ent}),
+                wrap_sent(quote! {#event_type_id::new(must_be_synced, event)})
+            );
+            quote! {
+                /// An event of this entity waiting in a `TimerWheel`, sent through the tunnels once it fires
+                #[derive(Clone, PartialEq, to_from_bytes_derive::ToBytes, to_from_bytes_derive::FromBytes)]
+                pub enum #gen_deferred_type<ID:Identify> {
+                    #(#arw_components(MustSync, <#arw_types as Component<ID>>::CE)),*,
+                    NewEnt(#gen_new_ent_type<ID>),
+                    Despawn(MustSync, DespawnEvent<ID>),
+                }
+
+                impl<ID:Identify> #gen_deferred_type<ID> {
+                    /// Events the server sends to clients once applied, clients leave them to the server instead of firing them too
+                    pub fn is_server_synced(&self) -> bool {
+                        match self {
+                            #(#gen_deferred_type::#arw_components(must_be_synced, _) => must_be_synced.is_server()),*,
+                            #gen_deferred_type::NewEnt(new_ent) => new_ent.must_be_synced.is_server(),
+                            #gen_deferred_type::Despawn(must_be_synced, _) => must_be_synced.is_server(),
+                        }
+                    }
+                    pub fn send(self, tunnels:&#gen_vec_tunnels_out<ID>) {
+                        match self {
+                            #(#gen_deferred_type::#arw_components(must_be_synced, event) => {let _ = tunnels.#tunnel_out_components.send(#sent_event);}),*,
+                            #gen_deferred_type::NewEnt(new_ent) => {let _ = tunnels.new_ents.send(#sent_new_ent);},
+                            #gen_deferred_type::Despawn(must_be_synced, event) => {let _ = tunnels.despawns.send(#sent_despawn);}
+                        }
+                    }
+                }
+            }
+        }
+        else {
+            let (sent_event, sent_new_ent) = (wrap_sent(quote! {event}), wrap_sent(quote! {new_ent}));
+            quote! {
+                /// An event of this entity waiting in a `TimerWheel`, sent through the tunnels once it fires
+                #[derive(Clone, to_from_bytes_derive::ToBytes, to_from_bytes_derive::FromBytes)]
+                pub enum #gen_deferred_type<ID:Identify> {
+                    #(#arw_components(<#arw_types as Component<ID>>::CE)),*,
+                    NewEnt(#gen_new_ent_type),
+                    Despawn(DespawnEvent<ID>),
+                }
+
+                impl<ID:Identify> #gen_deferred_type<ID> {
+                    /// Always false, entities without synced components are never sent by the server
+                    pub fn is_server_synced(&self) -> bool {
+                        false
+                    }
+                    pub fn send(self, tunnels:&#gen_vec_tunnels_out<ID>) {
+                        match self {
+                            #(#gen_deferred_type::#arw_components(event) => {let _ = tunnels.#tunnel_out_components.send(#sent_event);}),*,
+                            #gen_deferred_type::NewEnt(new_ent) => {let _ = tunnels.new_ents.send(#sent_new_ent);},
+                            #gen_deferred_type::Despawn(event) => {let _ = tunnels.despawns.send(#sent_event);}
+                        }
+                    }
+                }
+            }
+        }
+    }
+    else {
+        quote! {}
+    };
+
+    let lifecycle_part = if is_lifecycle_hooks {
+        quote! {
+            /// Both hooks do nothing by default, `RB` is `()` in engines without a rendering engine
+            pub trait #gen_lifecycle_type<RB, W:World<ID>, ID:Identify> {
+                fn on_spawn(id:EntityID, ents:&mut #gen_vec_write_type<'_, ID>, rendering_data:&mut RB, world:&WorldComputeHandler<'_, W, ID>) {
+
+                }
+                /// The components of the entity are still there, its ID is only reused starting with the next tick
+                fn on_despawn(id:EntityID, ents:&mut #gen_vec_write_type<'_, ID>, rendering_data:&mut RB, world:&WorldComputeHandler<'_, W, ID>) {
+
+                }
+            }
+
+            impl<ID:Identify> #gen_vec_type<ID> {
+                /// Calls the hooks of every entity spawned or despawned since the last call, in the order events were applied
+                pub fn run_lifecycle_hooks<RB, W:World<ID>>(&self, rendering_data:&mut RB, world:&WorldComputeHandler<'_, W, ID>) where #ent_ident:#gen_lifecycle_type<RB, W, ID> {
+                    let spawned = std::mem::take(&mut *self.spawned.write().unwrap());
+                    let despawned = std::mem::take(&mut *self.despawned.write().unwrap());
+                    let mut write_handler = self.get_write();
+                    for id in spawned {
+                        <#ent_ident as #gen_lifecycle_type<RB, W, ID>>::on_spawn(id, &mut write_handler, rendering_data, world);
+                    }
+                    for id in despawned {
+                        <#ent_ident as #gen_lifecycle_type<RB, W, ID>>::on_despawn(id, &mut write_handler, rendering_data, world);
+                    }
+                }
+            }
+        }
+    }
+    else {
+        quote! {}
+    };
+
+    let ent_name = ent_ident.to_string();
+    let reflect_debug_part = if is_reflect_debug {
+        let component_indices:Vec<usize> = (0..arw_components.len()).collect();
+        let edit_event = if must_sync_types.len() > 0 {
+            let event_type_id = Ident::new(format!("{}Event", ent_ident.to_string()).trim(), Span::call_site());
+            quote! {#event_type_id::new(MustSync::Both, event)}
+        }
+        else {
+            quote! {event}
+        };
+        let sent_edit_event = if is_deterministic {
+            quote! {SequencedEvent::new(#edit_event)}
+        }
+        else {
+            edit_event
+        };
+        quote! {
+            /// Edits are sent with `MustSync::Both` on multiplayer entities, so they reach every peer
+            impl<'a, ID:Identify> ComponentEditor for #gen_vec_read_type <'a, ID> {
+                fn send_edit(&self, id:EntityID, component:usize, text:&str) -> bool {
+                    if !self.alive.is_alive(id) {
+                        return false
+                    }
+                    match component {
+                        #(#component_indices => match <#arw_types as PrefabText>::read_text(text).and_then(|value| <#arw_types as EditableComponent<ID>>::set_event(id, value)) {
+                            Some(event) => self.tunnels.#tunnel_out_components.send(#sent_edit_event).is_ok(),
+                            None => false
+                        },)*
+                        _ => false
                     }
                 }
             }
+
+            impl<'a, ID:Identify> EntityValuesText for #gen_vec_read_type <'a, ID> {
+                fn metadata(&self) -> &'static EntityMetadata {
+                    &<#ent_ident as EntityReflection>::METADATA
+                }
+                fn entity_count(&self) -> usize {
+                    self.get_expected_len()
+                }
+                fn is_alive(&self, id:EntityID) -> bool {
+                    self.alive.is_alive(id)
+                }
+                fn component_text(&self, id:EntityID, component:usize) -> Option<String> {
+                    match component {
+                        #(#component_indices => self.#arw_components.get(id).map(|value| format!("{:?}", value)),)*
+                        _ => None
+                    }
+                }
+                fn position_of(&self, id:EntityID) -> Option<Vec3Df> {
+                    self.#position_ident.get(id).map(|position| <#position_type as EntityPosition<ID>>::get_pos(position))
+                }
+            }
         }
     }
     else {
         quote! {}
     };
+
+    let (tunnel_event_types, tunnel_new_ent_type, tunnel_despawn_type) = if is_deterministic {
+        (
+            event_types.iter().map(|event_type| quote! {SequencedEvent<#event_type>}).collect(),
+            quote! {SequencedEvent<#gen_new_ent_type #new_ent_type_generics>},
+            quote! {SequencedEvent<#despawn_event_type>}
+        )
+    }
+    else {
+        (event_types.clone(), quote! {#gen_new_ent_type #new_ent_type_generics}, despawn_event_type.clone())
+    };
     let first_component = &arw_components[0].clone();
     let mut gen_vec = quote! {
         #render_part
@@ -421,8 +1287,9 @@ fn get_entity_vec(ast:&DeriveInput, data:&DataStruct, fields:&FieldsNamed) -> (T
         
         #[derive(Clone)]
         pub struct #gen_vec_tunnels_in<ID:Identify> {
-            #(pub #tunnel_in_components:std::sync::mpmc::Receiver<#event_types>),* ,
-            pub new_ents:std::sync::mpmc::Receiver<#gen_new_ent_type #new_ent_type_generics>,
+            #(pub #tunnel_in_components:std::sync::mpmc::Receiver<#tunnel_event_types>),* ,
+            pub new_ents:std::sync::mpmc::Receiver<#tunnel_new_ent_type>,
+            pub despawns:std::sync::mpmc::Receiver<#tunnel_despawn_type>,
         }
 
         impl<ID:Identify> #gen_vec_tunnels_in<ID> {
@@ -431,14 +1298,17 @@ fn get_entity_vec(ast:&DeriveInput, data:&DataStruct, fields:&FieldsNamed) -> (T
                 let (#tunnel_out_components, #tunnel_in_components) = std::sync::mpmc::channel()
                 );* ;
                 let (new_ents_out, new_ents_in) = std::sync::mpmc::channel();
+                let (despawns_out, despawns_in) = std::sync::mpmc::channel();
                 (
                     #gen_vec_tunnels_in {
                         #(#tunnel_in_components),* ,
                         new_ents:new_ents_in,
+                        despawns:despawns_in,
                     },
                     #gen_vec_tunnels_out {
                         #(#tunnel_out_components),* ,
-                        new_ents:new_ents_out
+                        new_ents:new_ents_out,
+                        despawns:despawns_out,
                     }
                 )
             }
@@ -446,22 +1316,39 @@ fn get_entity_vec(ast:&DeriveInput, data:&DataStruct, fields:&FieldsNamed) -> (T
 
         #[derive(Clone)]
         pub struct #gen_vec_tunnels_out<ID:Identify> {
-            #(pub #tunnel_out_components:std::sync::mpmc::Sender<#event_types>),* ,
-            pub new_ents:std::sync::mpmc::Sender<#gen_new_ent_type #new_ent_type_generics>,
+            #(pub #tunnel_out_components:std::sync::mpmc::Sender<#tunnel_event_types>),* ,
+            pub new_ents:std::sync::mpmc::Sender<#tunnel_new_ent_type>,
+            pub despawns:std::sync::mpmc::Sender<#tunnel_despawn_type>,
         }
         
         #vec_type
 
         impl<ID:Identify> #gen_vec_type<ID> {
             #new_and_apply_events
+            /// Spawns all the entities right away while taking the locks only once, see `new_ents` on the write handler
+            pub fn spawn_batch<I:IntoIterator<Item = #gen_new_ent_type #new_ent_type_generics>>(&self, new_ents:I) -> std::ops::Range<EntityID> {
+                let range = self.get_write().new_ents(new_ents);
+                #record_batch_spawn
+                range
+            }
             pub fn get_write<'a>(&'a self) -> #gen_vec_write_type <'a, ID> {
                 #gen_vec_write_type {
                     #(#arw_components:self.#arw_components.write().unwrap()),* ,
                     static_types:self.static_types.write().unwrap(),
                     available_entities:self.available_entities.write().unwrap(),
+                    changes:self.changes.write().unwrap(),
+                    alive:self.alive.write().unwrap(),
+                    #despawned_render_handler_init
+                    #prefab_handler_init
+                    #broad_phase_write_init
                 }
             }
 
+            /// Called once per tick by the engine's `BEGIN_TICK_TASK`
+            pub fn next_tick(&self) {
+                self.changes.write().unwrap().next_tick();
+            }
+
             pub fn reset_stop(&mut self) {
                 self.stops.reset_stop(None)
             }
@@ -478,7 +1365,11 @@ fn get_entity_vec(ast:&DeriveInput, data:&DataStruct, fields:&FieldsNamed) -> (T
                 #gen_vec_read_type {
                     #(#arw_components:self.#arw_components.read().unwrap()),* ,
                     static_types:self.static_types.read().unwrap(),
-                    tunnels:self.tunnels_out.clone()
+                    tunnels:self.tunnels_out.clone(),
+                    changes:self.changes.read().unwrap(),
+                    alive:self.alive.read().unwrap(),
+                    #prefab_handler_init
+                    #broad_phase_read_init
                 }
             }
         }
@@ -486,6 +1377,10 @@ fn get_entity_vec(ast:&DeriveInput, data:&DataStruct, fields:&FieldsNamed) -> (T
             #(pub #arw_components:std::sync::RwLockReadGuard<'a, Vec<#arw_types>>),* ,
             pub static_types:std::sync::RwLockReadGuard<'a, Vec<#static_type_ident<ID>>>,
             pub tunnels:#gen_vec_tunnels_out<ID>,
+            pub changes:std::sync::RwLockReadGuard<'a, #gen_changes_type>,
+            pub alive:std::sync::RwLockReadGuard<'a, AliveMask>,
+            #prefab_handler_field
+            #broad_phase_read_field
         }
 
         impl<'a, ID:Identify> #gen_vec_read_type <'a, ID> {
@@ -502,14 +1397,27 @@ fn get_entity_vec(ast:&DeriveInput, data:&DataStruct, fields:&FieldsNamed) -> (T
         pub struct #gen_vec_write_type <'a, ID:Identify> {
             #(pub #arw_components:std::sync::RwLockWriteGuard<'a, Vec<#arw_types>>),* ,
             pub available_entities:std::sync::RwLockWriteGuard<'a, std::collections::VecDeque<usize>>,
-            pub static_types:std::sync::RwLockWriteGuard<'a, Vec<#static_type_ident<ID>>>
+            pub static_types:std::sync::RwLockWriteGuard<'a, Vec<#static_type_ident<ID>>>,
+            pub changes:std::sync::RwLockWriteGuard<'a, #gen_changes_type>,
+            pub alive:std::sync::RwLockWriteGuard<'a, AliveMask>,
+            #despawned_render_handler_field
+            #prefab_handler_field
+            #broad_phase_write_field
         }
 
         impl<'a, ID:Identify> #gen_vec_write_type <'a, ID> {
+            #(
+                /// Marks the entity the event is for as changed this tick, then applies the event
+                pub fn #apply_event_fns(&mut self, event:<#arw_types as Component<ID>>::CE) {
+                    let tick = self.changes.tick();
+                    self.changes.#arw_components.mark(<<#arw_types as Component<ID>>::CE as ComponentEvent<#arw_types, ID>>::get_id(&event), tick);
+                    <<#arw_types as Component<ID>>::CE as ComponentEvent<#arw_types, ID>>::apply_to_component(event, &mut self.#arw_components);
+                }
+            )*
             pub fn new_ent(&mut self, new_ent:#gen_new_ent_type #new_ent_type_generics) -> usize {
                 let static_type = new_ent.get_static_type_id();
                 let ent = <#gen_new_ent_type #new_ent_type_generics as NewEntity<#ent_ident #ty_generics, ID>>::get_ent(new_ent, &self.static_types[static_type]);
-                match self.available_entities.pop_back() {
+                let id = match self.available_entities.pop_back() {
                     Some(id) => {
                         #(self.#arw_components[id] = ent.#arw_components);*;
                         id
@@ -519,6 +1427,47 @@ fn get_entity_vec(ast:&DeriveInput, data:&DataStruct, fields:&FieldsNamed) -> (T
                         #(self.#arw_components.push(ent.#arw_components));*;
                         id
                     }
+                };
+                self.alive.set_alive(id);
+                self.changes.mark_all(id);
+                id
+            }
+            /// Spawns all the entities after the last one, without reusing free IDs, so the returned range is contiguous
+            pub fn new_ents<I:IntoIterator<Item = #gen_new_ent_type #new_ent_type_generics>>(&mut self, new_ents:I) -> std::ops::Range<EntityID> {
+                let new_ents = new_ents.into_iter();
+                let start = self.#first_component.len();
+                let reserved = new_ents.size_hint().0;
+                #(self.#arw_components.reserve(reserved));*;
+                for new_ent in new_ents {
+                    let static_type = new_ent.get_static_type_id();
+                    let ent = <#gen_new_ent_type #new_ent_type_generics as NewEntity<#ent_ident #ty_generics, ID>>::get_ent(new_ent, &self.static_types[static_type]);
+                    #(self.#arw_components.push(ent.#arw_components));*;
+                }
+                let end = self.#first_component.len();
+                for id in start..end {
+                    self.alive.set_alive(id);
+                    self.changes.mark_all(id);
+                }
+                start..end
+            }
+            /// Same as `new_ents`, with one slice per `used_in_new` component, all of the same length
+            pub fn new_ents_from_slices(&mut self, #(#used_new_components:&[#used_new_types]),*, #new_ent_extra_params) -> std::ops::Range<EntityID> {
+                let len = #first_used_new_component.len();
+                #(assert_eq!(#used_new_components.len(), len, "All component slices should have the same length"));*;
+                self.new_ents((0..len).map(|i| #gen_new_ent_type::new(#(#used_new_components[i].clone()),*, #new_ent_extra_clones)))
+            }
+            /// Frees the ID for new entities, the components are left as they are until a new entity takes their place but stages and renders skip it
+            ///
+            /// Returns false if the ID doesn't exist or was already free
+            pub fn despawn(&mut self, id:EntityID) -> bool {
+                if id >= self.#first_component.len() || !self.alive.kill(id) {
+                    false
+                }
+                else {
+                    #record_despawned_render
+                    self.available_entities.push_back(id);
+                    self.changes.mark_all(id);
+                    true
                 }
             }
             pub fn new_sct(&mut self, sct:#static_type_ident<ID>) {
@@ -531,7 +1480,11 @@ fn get_entity_vec(ast:&DeriveInput, data:&DataStruct, fields:&FieldsNamed) -> (T
             pub static_types:std::sync::Arc<std::sync::RwLock<Vec<#static_type_ident<ID>>>>,
             pub tunnels_out:#gen_vec_tunnels_out<ID>,
             pub available_entities:std::sync::Arc<std::sync::RwLock<std::collections::VecDeque<EntityID>>>,
-            pub stops:EVecStopsOut
+            pub alive:std::sync::Arc<std::sync::RwLock<AliveMask>>,
+            pub stops:EVecStopsOut,
+            pub changes:std::sync::Arc<std::sync::RwLock<#gen_changes_type>>,
+            #prefab_vec_field
+            #broad_phase_vec_field
         }
 
         impl<ID:Identify> #gen_vec_out_type<ID> {
@@ -542,7 +1495,11 @@ fn get_entity_vec(ast:&DeriveInput, data:&DataStruct, fields:&FieldsNamed) -> (T
                 #gen_vec_read_type {
                     #(#arw_components:self.#arw_components.read().unwrap()),* ,
                     static_types:self.static_types.read().unwrap(),
-                    tunnels:self.tunnels_out.clone()
+                    tunnels:self.tunnels_out.clone(),
+                    changes:self.changes.read().unwrap(),
+                    alive:self.alive.read().unwrap(),
+                    #prefab_handler_init
+                    #broad_phase_read_init
                 }
             }
         }
@@ -551,12 +1508,70 @@ fn get_entity_vec(ast:&DeriveInput, data:&DataStruct, fields:&FieldsNamed) -> (T
         impl<ID:Identify> EntityVec<ID> for #gen_vec_type<ID> {
             type OutVec = #gen_vec_out_type<ID>;
         }
+
+        #[derive(Clone)]
+        pub struct #gen_changes_type {
+            tick:usize,
+            #(pub #arw_components:ComponentChanges),*
+        }
+
+        impl #gen_changes_type {
+            pub fn new(capacity:usize) -> Self {
+                Self {
+                    tick:1,
+                    #(#arw_components:ComponentChanges::with_capacity(capacity)),*
+                }
+            }
+            pub fn tick(&self) -> usize {
+                self.tick
+            }
+            pub fn next_tick(&mut self) {
+                self.tick += 1;
+                #(self.#arw_components.clear_tick());*;
+            }
+            /// Forgets every change made before, `tick` should be at least 1
+            pub fn set_tick(&mut self, tick:usize) {
+                self.tick = tick;
+                #(self.#arw_components.reset());*;
+            }
+            pub fn mark_all(&mut self, id:EntityID) {
+                #(self.#arw_components.mark(id, self.tick));*;
+            }
+            /// Entities that had any of their components changed strictly after `tick`
+            pub fn changed_since(&self, tick:usize) -> impl Iterator<Item = EntityID> {
+                let mut changed:Vec<EntityID> = std::iter::empty() #(.chain(self.#arw_components.changed_since(tick)))*.collect();
+                changed.sort_unstable();
+                changed.dedup();
+                changed.into_iter()
+            }
+        }
+
+        #prefab_part
+        #hierarchy_part
+        #push_world_transforms
+        #physics_part
+        #character_part
+
+        #snapshot_part
+
+        impl #impl_generics EntityReflection for #ent_ident #ty_generics {
+            const METADATA:EntityMetadata = EntityMetadata {
+                name:#ent_name,
+                components:&[#(#components_metadata),*],
+            };
+        }
+
+        #reflect_debug_part
+
+        #deferred_events_part
+
+        #lifecycle_part
     };
     
     (gen_vec, gen_vec_type, gen_new_ent_type, new_ent_type_generics)
 }
 
-fn get_static_entity(ast:&DeriveInput, data:&DataStruct, fields:&FieldsNamed) -> (TokenStream2, Ident) {
+fn get_static_entity(ast:&DeriveInput, data:&DataStruct, fields:&FieldsNamed, is_prefab:bool) -> (TokenStream2, Ident) {
     let ent_ident = ast.ident.clone();
 
     let mut arw_types = Vec::new();
@@ -570,7 +1585,15 @@ fn get_static_entity(ast:&DeriveInput, data:&DataStruct, fields:&FieldsNamed) ->
 
     let static_type_ident = Ident::new(format!("Static{}", ent_ident.to_string()).trim(), Span::call_site());
 
+    let prefab_derive = if is_prefab {
+        quote! {#[derive(Clone, to_from_bytes_derive::ToBytes, to_from_bytes_derive::FromBytes)]}
+    }
+    else {
+        quote! {}
+    };
+
     let mut gen = quote! {
+        #prefab_derive
         pub struct #static_type_ident<ID:Identify> {
             #(pub #arw_components:<#arw_types as Component<ID>>::SC),*
         }
@@ -587,10 +1610,37 @@ fn derive_entity_on_struct(ast:&DeriveInput, data:&DataStruct, fields:&FieldsNam
     let name = &ast.ident.clone();
 
     let (impl_generics, ty_generics, where_clause) = ast.generics.split_for_impl();
+
+    let mut is_prefab = false;
+    let mut is_snapshot = false;
+    let mut is_reflect_debug = false;
+    let mut is_deferred_events = false;
+    let mut is_lifecycle_hooks = false;
+    let mut is_deterministic = false;
+    for attr in &ast.attrs {
+        if attr.path.is_ident(&Ident::new("prefab", Span::call_site())) {
+            is_prefab = true;
+        }
+        if attr.path.is_ident(&Ident::new("snapshot", Span::call_site())) {
+            is_snapshot = true;
+        }
+        if attr.path.is_ident(&Ident::new("reflect_debug", Span::call_site())) {
+            is_reflect_debug = true;
+        }
+        if attr.path.is_ident(&Ident::new("deferred_events", Span::call_site())) {
+            is_deferred_events = true;
+        }
+        if attr.path.is_ident(&Ident::new("lifecycle_hooks", Span::call_site())) {
+            is_lifecycle_hooks = true;
+        }
+        if attr.path.is_ident(&Ident::new("deterministic", Span::call_site())) {
+            is_deterministic = true;
+        }
+    }
     
-    let (ent_vec_create, ent_vec_type, new_ent_type, new_ent_type_generics) = get_entity_vec(ast, data, fields);
+    let (ent_vec_create, ent_vec_type, new_ent_type, new_ent_type_generics) = get_entity_vec(ast, data, fields, is_prefab, is_snapshot, is_reflect_debug, is_deferred_events, is_lifecycle_hooks, is_deterministic);
 
-    let (static_ent_create, static_ent_type) = get_static_entity(ast, data, fields);
+    let (static_ent_create, static_ent_type) = get_static_entity(ast, data, fields, is_prefab);
 
     let mut gen = quote! {
         #static_ent_create
@@ -1,2 +1,3 @@
 pub mod vectorinator;
-pub mod vectorinator_binned;
\ No newline at end of file
+pub mod vectorinator_binned;
+pub mod null_rendering;
\ No newline at end of file
@@ -0,0 +1,35 @@
+use crate::horde::{game_engine::hierarchy::{InstanceTransforms, WorldTransform}, rendering::RenderingBackend};
+
+/// Rendering engine that draws nothing, for engines running without a window like in headless tests
+///
+/// Worlds need an empty `Renderable<NullRenderingWrite>` implementation and entities an empty one for their render trait
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct NullRendering {
+
+}
+
+impl NullRendering {
+    pub fn new() -> Self {
+        Self {  }
+    }
+    pub fn get_write<'a>(&'a self) -> NullRenderingWrite<'a> {
+        NullRenderingWrite { rendering: self }
+    }
+}
+
+impl RenderingBackend for NullRendering {
+    type EntityRenderingData = ();
+    type PreTickData = ();
+    type RenderingStatus = ();
+    type RenderingStatusUpdate = ();
+}
+
+pub struct NullRenderingWrite<'a> {
+    pub rendering:&'a NullRendering,
+}
+
+impl<'a> InstanceTransforms for NullRenderingWrite<'a> {
+    fn set_instance_transform(&mut self, _instance:usize, _for_vec:usize, _transform:&WorldTransform) {
+        
+    }
+}
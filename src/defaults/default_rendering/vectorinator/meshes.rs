@@ -75,9 +75,28 @@ impl<'a> MeshesRead<'a> {
             MeshID::Named(name) => *self.reference_table.get(name).expect("Named mesh hasn't been loaded yet !!!!!!!")
         }
     }
+    /// None if the mesh isn't loaded yet
+    pub fn try_get_index_id(&self, id:&MeshID) -> Option<usize> {
+        match id {
+            MeshID::Referenced(index) => if *index < self.all_meshes.data.len() {Some(*index)} else {None},
+            MeshID::Named(name) => self.reference_table.get(name).copied()
+        }
+    }
+    /// One visible instance per named mesh, ready for `MeshesWrite::add_instances`, like the meshes of a prefab
+    ///
+    /// None if one of the meshes isn't loaded yet
+    pub fn named_instances(&self, names:&[String], pos:Vec3Df, orient:Orientation) -> Option<Vec<MeshInstance>> {
+        names.iter().map(|name| {
+            let index = self.try_get_index_id(&MeshID::Named(name.clone()))?;
+            Some(MeshInstance::new(pos, orient, MeshID::Referenced(index), true, false, false))
+        }).collect()
+    }
     pub fn get_instances(&self) -> &Vec<MeshInstances> {
         &self.instances
     }
+    pub fn get_mesh(&self, id:&MeshID) -> &Mesh {
+        &self.all_meshes.data[self.get_index_id(id)]
+    }
 }
 
 pub struct MeshesWrite<'a> {
@@ -112,6 +131,21 @@ impl<'a> MeshesWrite<'a> {
         instance.mesh_id = final_id;
         self.push_instance(instance, for_vec)
     }
+    /// Adds all the instances at the end of the instance vec, without reusing free indices, so the returned range is contiguous
+    pub fn add_instances(&mut self, instances:Vec<MeshInstance>, for_vec:usize) -> Range<usize> {
+        if for_vec >= self.instances.len() {
+            for i in self.instances.len()..(for_vec + 1) {
+                self.instances.push(MeshInstances::with_capacity(256, 64));
+            }
+        }
+        let mut instances = instances;
+        for instance in instances.iter_mut() {
+            instance.mesh_id = MeshID::Referenced(self.get_index_id(&instance.mesh_id));
+        }
+        let start = self.instances[for_vec].instances.len();
+        self.instances[for_vec].instances.extend(instances);
+        start..self.instances[for_vec].instances.len()
+    }
     fn push_instance(&mut self, instance:MeshInstance, for_vec:usize) -> usize {
         if for_vec >= self.instances.len() {
             for i in self.instances.len()..(for_vec + 1) {
@@ -289,6 +323,9 @@ impl MeshLODS {
     pub fn new_lod(&mut self, new_lod:MeshLODType) {
         self.lods.push(new_lod)
     }
+    pub fn len(&self) -> usize {
+        self.lods.len()
+    }
 }
 
 
@@ -303,6 +340,12 @@ impl Mesh {
     pub fn new(lods:MeshLODS, name:MeshName, size:HordeFloat) -> Self {
         Self { lods, name, size }
     }
+    pub fn get_lods(&self) -> &MeshLODS {
+        &self.lods
+    }
+    pub fn get_size(&self) -> HordeFloat {
+        self.size
+    }
     pub fn might_be_renderable(&self, poscam:&Vec3Df, rotat: &Rotation, at:&Vec3Df, viewport_data:&ViewportData) -> Option<Vec3DfCam> {
         let cam = Vec3DfCam::from_realspace(*at, poscam, rotat);
         let dist = viewport_data.camera_plane.signed_distance(&cam.0);
@@ -539,6 +582,15 @@ impl MeshInstance {
     pub fn get_pos(&self) -> &Vec3Df {
         &self.pos
     }
+    pub fn get_orient(&self) -> &Orientation {
+        &self.orient
+    }
+    pub fn get_mesh_id(&self) -> &MeshID {
+        &self.mesh_id
+    }
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
 
     pub fn new(pos:Vec3Df, orient:Orientation, mesh_id:MeshID, visible:bool, worldpos_mesh:bool, viewmodel:bool) -> Self {
         Self { pos, orient, mesh_id, visible, worldpos_mesh, viewmodel }
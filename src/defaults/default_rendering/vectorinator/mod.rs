@@ -6,7 +6,7 @@ use rendering_spaces::ViewportData;
 use textures::{rgb_to_argb, Textures};
 use triangles::TransformedMesh;
 
-use crate::horde::{frontend::SyncUnsafeHordeFramebuffer, geometry::{plane::EquationPlane, rotation::{Orientation, Rotation}, vec3d::{Vec3D, Vec3Df}}, rendering::{camera::Camera, RenderingBackend}, scheduler::IndividualTask};
+use crate::horde::{frontend::SyncUnsafeHordeFramebuffer, game_engine::hierarchy::{InstanceTransforms, WorldTransform}, geometry::{plane::EquationPlane, rotation::{Orientation, Rotation}, vec3d::{Vec3D, Vec3Df}}, rendering::{camera::Camera, RenderingBackend}, scheduler::IndividualTask};
 
 pub mod meshes;
 pub mod rendering_spaces;
@@ -16,6 +16,7 @@ pub mod consts;
 pub mod rasterisation;
 pub mod textures;
 pub mod shapes_to_tris;
+pub mod picking;
 
 #[derive(Clone)]
 pub struct Vectorinator {
@@ -134,6 +135,16 @@ pub struct VectorinatorWrite<'a> {
     pub camera:RwLockWriteGuard<'a, Camera>
 }
 
+impl<'a> InstanceTransforms for VectorinatorWrite<'a> {
+    fn set_instance_transform(&mut self, instance:usize, for_vec:usize, transform:&WorldTransform) {
+        if for_vec < self.meshes.instances.len() && instance < self.meshes.instances[for_vec].instances_len() {
+            let mesh_instance = self.meshes.instances[for_vec].get_instance_mut(instance);
+            mesh_instance.change_pos(transform.pos);
+            mesh_instance.change_orient(transform.orient);
+        }
+    }
+}
+
 impl IndividualTask for Vectorinator {
     type TD = usize;
     type TID = usize;
@@ -0,0 +1,175 @@
+use std::sync::Arc;
+
+use crate::horde::{frontend::MouseState, game_engine::entity::EntityID, geometry::{line::Line3D, rotation::Rotation, vec3d::Vec3Df}, rendering::camera::Camera};
+
+use super::{meshes::{Mesh, MeshLOD, MeshLODType, MeshesRead}, rendering_spaces::ViewportData};
+
+/// Closest triangle of a mesh instance hit by a ray
+#[derive(Clone, Debug, PartialEq)]
+pub struct RayHit {
+    /// Instance vec of the instance, as passed to `MeshesWrite::add_instance`
+    pub instance_vec:usize,
+    pub instance:usize,
+    /// Index of the triangle in the `MeshTriangles` of the most detailed LOD of the mesh
+    pub triangle:usize,
+    /// Distance along the ray, in lengths of its director
+    pub distance:f32,
+    pub point:Vec3Df,
+    /// Normal of the triangle, facing the ray origin
+    pub normal:Vec3Df,
+    pub u:f32,
+    pub v:f32,
+    pub texture:u32,
+}
+
+impl RayHit {
+    /// Entity whose instance was hit, `instance_ids` being the instance id component of the entities of `instance_vec`
+    pub fn find_entity(&self, instance_vec:usize, instance_ids:&[Option<usize>]) -> Option<EntityID> {
+        if self.instance_vec != instance_vec {
+            return None
+        }
+        instance_ids.iter().position(|id| *id == Some(self.instance))
+    }
+}
+
+/// Möller–Trumbore, hits both faces, returns the distance along the ray and the barycentric coordinates of `p2` and `p3`
+fn ray_triangle(origin:&Vec3Df, director:&Vec3Df, p1:&Vec3Df, p2:&Vec3Df, p3:&Vec3Df) -> Option<(f32, f32, f32)> {
+    let edge1 = p2 - p1;
+    let edge2 = p3 - p1;
+    let pvec = director.cross(&edge2);
+    let det = edge1.dot(&pvec);
+    if det.abs() <= f32::MIN_POSITIVE {
+        return None
+    }
+    let inv_det = 1.0 / det;
+    let tvec = origin - p1;
+    let b1 = tvec.dot(&pvec) * inv_det;
+    if !(0.0..=1.0).contains(&b1) {
+        return None
+    }
+    let qvec = tvec.cross(&edge1);
+    let b2 = director.dot(&qvec) * inv_det;
+    if b2 < 0.0 || b1 + b2 > 1.0 {
+        return None
+    }
+    Some((edge2.dot(&qvec) * inv_det, b1, b2))
+}
+
+/// Whether the ray comes within `radius` of `center` before `max_distance`
+fn ray_reaches_sphere(ray:&Line3D, center:&Vec3Df, radius:f32, max_distance:f32) -> bool {
+    let length_squared = ray.get_director().norme_square();
+    if length_squared == 0.0 {
+        return false
+    }
+    let closest = ((center - ray.get_origin()).dot(ray.get_director()) / length_squared).clamp(0.0, max_distance);
+    (ray.get_at(closest) - center).norme_square() <= radius * radius
+}
+
+/// The renderer switches to higher LODs as meshes get closer, so the last mesh LOD is the most detailed one
+fn most_detailed_lod(mesh:&Mesh) -> Option<Arc<MeshLOD>> {
+    (0..mesh.get_lods().len()).rev().find_map(|lod| match mesh.get_lods().get_lod(lod) {
+        MeshLODType::Mesh(lod) => Some(lod),
+        MeshLODType::Image { .. } => None
+    })
+}
+
+impl<'a> MeshesRead<'a> {
+    /// Closest hit of `ray` on one instance, at most `max_distance` director lengths away
+    ///
+    /// `Mesh::size` around the instance position is used as a bounding sphere to skip the triangles.
+    /// Invisible and viewmodel instances, and meshes with only image LODs, are never hit
+    pub fn ray_cast_instance(&self, ray:&Line3D, max_distance:f32, instance_vec:usize, instance:usize) -> Option<RayHit> {
+        let instances = self.get_instances().get(instance_vec)?;
+        if instance >= instances.instances_len() {
+            return None
+        }
+        let data = instances.get_instance(instance);
+        if !data.is_visible() || data.is_viewmodel() {
+            return None
+        }
+        let mesh = self.get_mesh(data.get_mesh_id());
+        if !ray_reaches_sphere(ray, data.get_pos(), mesh.get_size(), max_distance) {
+            return None
+        }
+        let lod = most_detailed_lod(mesh)?;
+        // The ray is moved into the space of the mesh instead of moving every point of the mesh
+        let (origin, director, rotation) = if data.is_worldpos() {
+            (*ray.get_origin(), *ray.get_director(), None)
+        }
+        else {
+            let inverse = Rotation::new_from_inverted_orient(*data.get_orient());
+            (inverse.rotate(ray.get_origin() - data.get_pos()), inverse.rotate(*ray.get_director()), Some(Rotation::from_orientation(*data.get_orient())))
+        };
+        let mut closest:Option<(usize, f32, f32, f32)> = None;
+        for triangle in 0..lod.triangles.len() {
+            let points = lod.triangles.get_triangle(&lod.x, &lod.y, &lod.z, triangle);
+            if let Some((distance, b1, b2)) = ray_triangle(&origin, &director, &points.p1.pos, &points.p2.pos, &points.p3.pos) {
+                if distance >= 0.0 && distance <= max_distance && closest.map_or(true, |(_, closest_distance, _, _)| distance < closest_distance) {
+                    closest = Some((triangle, distance, b1, b2));
+                }
+            }
+        }
+        let (triangle, distance, b1, b2) = closest?;
+        let points = lod.triangles.get_triangle(&lod.x, &lod.y, &lod.z, triangle);
+        let mut normal = (points.p2.pos.0 - points.p1.pos.0).cross(&(points.p3.pos.0 - points.p1.pos.0)).normalise();
+        if normal.dot(&director) > 0.0 {
+            normal = -normal;
+        }
+        if let Some(rotation) = rotation {
+            normal = rotation.rotate(normal);
+        }
+        let b0 = 1.0 - b1 - b2;
+        Some(RayHit {
+            instance_vec,
+            instance,
+            triangle,
+            distance,
+            point: ray.get_at(distance),
+            normal,
+            u: points.p1.u * b0 + points.p2.u * b1 + points.p3.u * b2,
+            v: points.p1.v * b0 + points.p2.v * b1 + points.p3.v * b2,
+            texture: points.texture_flags.0,
+        })
+    }
+    /// Closest hit of `ray` among the instances of `instance_vec`
+    pub fn ray_cast_vec(&self, ray:&Line3D, max_distance:f32, instance_vec:usize) -> Option<RayHit> {
+        let len = self.get_instances().get(instance_vec)?.instances_len();
+        let mut closest:Option<RayHit> = None;
+        for instance in 0..len {
+            let max_distance = closest.as_ref().map_or(max_distance, |hit| hit.distance);
+            if let Some(hit) = self.ray_cast_instance(ray, max_distance, instance_vec, instance) {
+                closest = Some(hit);
+            }
+        }
+        closest
+    }
+    /// Closest hit of `ray` among all instances
+    pub fn ray_cast(&self, ray:&Line3D, max_distance:f32) -> Option<RayHit> {
+        let mut closest:Option<RayHit> = None;
+        for instance_vec in 0..self.get_instances().len() {
+            let max_distance = closest.as_ref().map_or(max_distance, |hit| hit.distance);
+            if let Some(hit) = self.ray_cast_vec(ray, max_distance, instance_vec) {
+                closest = Some(hit);
+            }
+        }
+        closest
+    }
+}
+
+impl ViewportData {
+    /// Ray from `camera` through the pixel at `x`, `y`, undoing `Vec3DfRaster::from_cameraspace`, with a normalised director so distances are in world units
+    pub fn screen_ray(&self, camera:&Camera, x:f32, y:f32) -> Line3D {
+        let camera_space = Vec3Df::new(
+            (x / self.half_image_width - 1.0) / self.near_clipping_plane,
+            (1.0 - y / (self.half_image_height * self.aspect_ratio)) / self.near_clipping_plane,
+            1.0
+        );
+        Line3D::new(camera.pos, Rotation::from_orientation(camera.orient).rotate(camera_space).normalise())
+    }
+}
+
+/// Ray from `camera` through the mouse cursor, the mouse position being in pixels of the framebuffer
+pub fn mouse_ray(camera:&Camera, mouse:&MouseState, viewport_data:&ViewportData) -> Line3D {
+    let state = mouse.get_current_state();
+    viewport_data.screen_ray(camera, state.x as f32, state.y as f32)
+}
@@ -112,6 +112,21 @@ impl<'a> MeshesWrite<'a> {
         instance.mesh_id = final_id;
         self.push_instance(instance, for_vec)
     }
+    /// Adds all the instances at the end of the instance vec, without reusing free indices, so the returned range is contiguous
+    pub fn add_instances(&mut self, instances:Vec<MeshInstance>, for_vec:usize) -> Range<usize> {
+        if for_vec >= self.instances.len() {
+            for i in self.instances.len()..(for_vec + 1) {
+                self.instances.push(MeshInstances::with_capacity(256, 64));
+            }
+        }
+        let mut instances = instances;
+        for instance in instances.iter_mut() {
+            instance.mesh_id = MeshID::Referenced(self.get_index_id(&instance.mesh_id));
+        }
+        let start = self.instances[for_vec].instances.len();
+        self.instances[for_vec].instances.extend(instances);
+        start..self.instances[for_vec].instances.len()
+    }
     fn push_instance(&mut self, instance:MeshInstance, for_vec:usize) -> usize {
         if for_vec >= self.instances.len() {
             for i in self.instances.len()..(for_vec + 1) {
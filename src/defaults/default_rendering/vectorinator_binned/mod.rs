@@ -233,6 +233,9 @@ impl<'a> VectorinatorWrite<'a> {
             RenderingUpdate::AddInstance { instance, for_vec } => {
                 Ok(RenderingResponse::InstanceID(self.meshes.add_instance(instance, for_vec)))
             },
+            RenderingUpdate::AddInstances { instances, for_vec } => {
+                Ok(RenderingResponse::InstanceRange(self.meshes.add_instances(instances, for_vec)))
+            },
             RenderingUpdate::SetMesh(mesh_id, mesh) => {
                 self.meshes.set_mesh(&mesh_id, mesh);
                 Ok(RenderingResponse::MeshID(mesh_id))
@@ -299,10 +302,20 @@ pub struct RenderingEvent {
     update:RenderingUpdate,
 }
 
+impl RenderingEvent {
+    /// The response arrives on the receiver once the rendering events are applied
+    pub fn new(update:RenderingUpdate) -> (Self, Receiver<Result<RenderingResponse, ()>>) {
+        let (response_sender, response_receiver) = mpmc::channel();
+        (Self { response_sender, update }, response_receiver)
+    }
+}
+
 pub enum RenderingUpdate {
     AddMesh(Mesh),
     SetMesh(MeshID, Mesh),
     AddInstance {instance:MeshInstance, for_vec:usize},
+    /// Gets a single `InstanceRange` response, all instances are contiguous
+    AddInstances {instances:Vec<MeshInstance>, for_vec:usize},
     UpdateInstance {for_vec:usize, index:usize, update:MeshInstanceUpdate}
 }
 
@@ -313,5 +326,6 @@ pub enum MeshInstanceUpdate {
 
 pub enum RenderingResponse {
     MeshID(MeshID),
-    InstanceID(usize)
+    InstanceID(usize),
+    InstanceRange(std::ops::Range<usize>),
 }
\ No newline at end of file
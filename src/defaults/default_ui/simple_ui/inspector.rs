@@ -0,0 +1,316 @@
+use std::sync::{Arc, RwLock};
+
+use cosmic_text::{Color, Metrics};
+
+use crate::{defaults::default_rendering::vectorinator::{meshes::{MeshID, MeshInstance, MeshesRead, MeshesWrite}, picking::mouse_ray, rendering_spaces::ViewportData, textures::rgb_to_argb}, horde::{frontend::MouseState, game_engine::{entity::EntityID, reflection::{EntityMetadata, EntityValuesText}, time_control::TimeControl}, geometry::rotation::Orientation, rendering::camera::Camera}};
+
+use super::{SimpleUI, TextCentering, UIDimensions, UIElement, UIElementBackground, UIElementContent, UIElementID, UIEvent, UIUnit, UIUserAction, UIVector, UserEvent};
+
+const INSPECTOR_ROOT:&str = "inspector";
+const INSPECTOR_VALUES:&str = "inspector_values";
+const INSPECTOR_PREVIOUS:&str = "inspector_previous";
+const INSPECTOR_NEXT:&str = "inspector_next";
+const INSPECTOR_PAUSE:&str = "inspector_pause";
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum InspectorEvent {
+    Toggle,
+    SelectType(usize),
+    PreviousEntity,
+    NextEntity,
+    TogglePause,
+}
+
+impl UserEvent for InspectorEvent {
+
+}
+
+/// A change asked by the user, to be turned into a component event by a `ComponentEditor`
+#[derive(Clone, Debug)]
+pub struct InspectorEdit {
+    pub entity_type:usize,
+    pub id:EntityID,
+    pub component:usize,
+    pub text:String,
+}
+
+/// Implemented by `#[derive(Entity)]` on the `VecRead` of `#[reflect_debug]` entities, to send the component event matching an edit through its tunnels
+///
+/// Each component turns the text into a value with `PrefabText` and into an event with `EditableComponent::set_event`
+pub trait ComponentEditor {
+    /// Returns false if `text` isn't a valid value for that component
+    fn send_edit(&self, id:EntityID, component:usize, text:&str) -> bool;
+}
+
+/// Where the mesh instances of one entity type are, so that clicking them selects the entity
+pub struct PickableInstances<'a> {
+    pub entity_type:usize,
+    pub instance_vec:usize,
+    /// Instance component of every entity of that type
+    pub instance_ids:&'a [Option<usize>],
+}
+
+struct InspectorState {
+    visible:bool,
+    selected:Option<(usize, EntityID)>,
+    counts:Vec<usize>,
+    shown_texts:Vec<String>,
+    edits:Vec<InspectorEdit>,
+    marker:Option<usize>,
+}
+
+/// Debug overlay listing entity types and the component values of one selected entity
+///
+/// Every entity type shown must be marked `#[reflect_debug]`, and they must always be given in the same order to `refresh`, `update_marker` and `send_edits`
+///
+/// Events from the overlay come back through the `SimpleUI` user events receiver, and must be given to `handle_event`
+///
+/// The pause button goes through the `TimeControl` of the engine, so it only works on handles with authority
+#[derive(Clone)]
+pub struct EntityInspector {
+    metadata:Vec<&'static EntityMetadata>,
+    state:Arc<RwLock<InspectorState>>,
+    time_control:TimeControl,
+}
+
+impl EntityInspector {
+    pub fn new(metadata:Vec<&'static EntityMetadata>, time_control:TimeControl) -> Self {
+        let types = metadata.len();
+        Self {
+            metadata,
+            state:Arc::new(RwLock::new(InspectorState { visible: false, selected: None, counts: vec![0 ; types], shown_texts: vec![String::new() ; types + 1], edits: Vec::new(), marker: None })),
+            time_control,
+        }
+    }
+    fn text_content(text:String) -> UIElementContent {
+        UIElementContent::Text { text, font: "rien".to_string(), metrics: Metrics::new(16.0, 20.0), color: Color::rgb(255, 255, 255), centering: TextCentering::Neither }
+    }
+    fn type_element_name(entity_type:usize) -> String {
+        format!("inspector_type_{}", entity_type)
+    }
+    fn all_element_names(&self) -> Vec<String> {
+        let mut names = vec![INSPECTOR_ROOT.to_string()];
+        names.extend((0..self.metadata.len()).map(|i| Self::type_element_name(i)));
+        names.extend([INSPECTOR_PREVIOUS, INSPECTOR_NEXT, INSPECTOR_PAUSE, INSPECTOR_VALUES].iter().map(|name| name.to_string()));
+        names
+    }
+    fn button<UE:UserEvent + From<InspectorEvent>>(name:&str, text:&str, x:f32, y:f32, event:InspectorEvent) -> UIElement<UE> {
+        UIElement::new(
+            UIVector::new(UIUnit::ParentWidthProportion(x), UIUnit::ParentHeightProportion(y)),
+            UIDimensions::Decided(UIVector::new(UIUnit::ParentWidthProportion(0.3), UIUnit::ParentHeightProportion(0.04))),
+            UIVector::new(UIUnit::RelativeToParentOrigin(4), UIUnit::RelativeToParentOrigin(2)),
+            Some(UIElementID::Name(INSPECTOR_ROOT.to_string())),
+            name.to_string()
+        )
+        .with_background(UIElementBackground::Color(rgb_to_argb((70, 70, 90))))
+        .with_content(Self::text_content(text.to_string()))
+        .with_reaction((UIUserAction::Clicking, UIEvent::User(UE::from(event))))
+        .change_visibility(false)
+    }
+    /// Elements to add to a `SimpleUI` with `add_many_connected_elements`, hidden until toggled
+    pub fn elements<UE:UserEvent + From<InspectorEvent>>(&self) -> Vec<UIElement<UE>> {
+        let row_height = 0.04;
+        let mut root = UIElement::new(
+            UIVector::new(UIUnit::ParentWidthProportion(0.65), UIUnit::ParentHeightProportion(0.05)),
+            UIDimensions::Decided(UIVector::new(UIUnit::ParentWidthProportion(0.33), UIUnit::ParentHeightProportion(0.9))),
+            UIVector::new(UIUnit::RelativeToParentOrigin(8), UIUnit::RelativeToParentOrigin(8)),
+            None,
+            INSPECTOR_ROOT.to_string()
+        )
+        .with_background(UIElementBackground::Color(rgb_to_argb((30, 30, 40))))
+        .change_visibility(false);
+        let mut children = Vec::with_capacity(self.metadata.len() + 4);
+        for (i, metadata) in self.metadata.iter().enumerate() {
+            root = root.with_child(UIElementID::Name(Self::type_element_name(i)));
+            children.push(
+                UIElement::new(
+                    UIVector::new(UIUnit::ParentWidthProportion(0.0), UIUnit::ParentHeightProportion(row_height * i as f32)),
+                    UIDimensions::Decided(UIVector::new(UIUnit::ParentWidthProportion(1.0), UIUnit::ParentHeightProportion(row_height))),
+                    UIVector::new(UIUnit::RelativeToParentOrigin(4), UIUnit::RelativeToParentOrigin(2)),
+                    Some(UIElementID::Name(INSPECTOR_ROOT.to_string())),
+                    Self::type_element_name(i)
+                )
+                .with_content(Self::text_content(metadata.name.to_string()))
+                .with_reaction((UIUserAction::Clicking, UIEvent::User(UE::from(InspectorEvent::SelectType(i)))))
+                .change_visibility(false)
+            );
+        }
+        let buttons_y = row_height * self.metadata.len() as f32 + 0.01;
+        for (name, text, x, event) in [(INSPECTOR_PREVIOUS, "<", 0.0, InspectorEvent::PreviousEntity), (INSPECTOR_NEXT, ">", 0.33, InspectorEvent::NextEntity), (INSPECTOR_PAUSE, "pause", 0.66, InspectorEvent::TogglePause)] {
+            root = root.with_child(UIElementID::Name(name.to_string()));
+            children.push(Self::button(name, text, x, buttons_y, event));
+        }
+        root = root.with_child(UIElementID::Name(INSPECTOR_VALUES.to_string()));
+        children.push(
+            UIElement::new(
+                UIVector::new(UIUnit::ParentWidthProportion(0.0), UIUnit::ParentHeightProportion(buttons_y + row_height + 0.01)),
+                UIDimensions::Decided(UIVector::new(UIUnit::ParentWidthProportion(1.0), UIUnit::ParentHeightProportion(1.0 - buttons_y - row_height - 0.01))),
+                UIVector::new(UIUnit::RelativeToParentOrigin(4), UIUnit::RelativeToParentOrigin(4)),
+                Some(UIElementID::Name(INSPECTOR_ROOT.to_string())),
+                INSPECTOR_VALUES.to_string()
+            )
+            .with_content(Self::text_content(String::new()))
+            .change_visibility(false)
+        );
+        let mut elements = vec![root];
+        elements.append(&mut children);
+        elements
+    }
+    /// Previous and next entity skip despawned ones, and stay where they are when there is none left in that direction
+    pub fn handle_event<UE:UserEvent>(&self, event:InspectorEvent, ui:&SimpleUI<UE>, entities:&[&dyn EntityValuesText]) {
+        match event {
+            InspectorEvent::Toggle => self.toggle(ui),
+            InspectorEvent::SelectType(entity_type) => {
+                let first = entities.get(entity_type).and_then(|entity| entity.first_alive()).unwrap_or(0);
+                self.select(entity_type, first);
+            },
+            InspectorEvent::PreviousEntity => {
+                let mut state = self.state.write().unwrap();
+                if let Some((entity_type, id)) = state.selected {
+                    if let Some(previous) = entities.get(entity_type).and_then(|entity| entity.previous_alive(id)) {
+                        state.selected = Some((entity_type, previous));
+                    }
+                }
+            },
+            InspectorEvent::NextEntity => {
+                let mut state = self.state.write().unwrap();
+                if let Some((entity_type, id)) = state.selected {
+                    if let Some(next) = entities.get(entity_type).and_then(|entity| entity.next_alive(id)) {
+                        state.selected = Some((entity_type, next));
+                    }
+                }
+            },
+            InspectorEvent::TogglePause => {
+                let _ = self.time_control.toggle_pause();
+            },
+        }
+    }
+    pub fn toggle<UE:UserEvent>(&self, ui:&SimpleUI<UE>) {
+        let visible = {
+            let mut state = self.state.write().unwrap();
+            state.visible = !state.visible;
+            state.visible
+        };
+        for name in self.all_element_names() {
+            ui.change_visibility_of(UIElementID::Name(name), visible);
+        }
+    }
+    pub fn is_visible(&self) -> bool {
+        self.state.read().unwrap().visible
+    }
+    pub fn is_paused(&self) -> bool {
+        self.time_control.is_paused()
+    }
+    /// Err if the time control doesn't have authority
+    pub fn set_paused(&self, paused:bool) -> Result<(), ()> {
+        if paused {
+            self.time_control.pause()
+        }
+        else {
+            self.time_control.resume()
+        }
+    }
+    pub fn select(&self, entity_type:usize, id:EntityID) {
+        if entity_type < self.metadata.len() {
+            self.state.write().unwrap().selected = Some((entity_type, id));
+        }
+    }
+    pub fn selected(&self) -> Option<(usize, EntityID)> {
+        self.state.read().unwrap().selected
+    }
+    /// Selects the living entity whose mesh instance is under the mouse, to be called when the 3D view is clicked while the inspector is visible
+    ///
+    /// The closest instance hit counts even if it isn't in `pickable`, so entities behind the terrain can't be clicked through it
+    pub fn click_select(&self, meshes:&MeshesRead, camera:&Camera, mouse:&MouseState, viewport_data:&ViewportData, pickable:&[PickableInstances], entities:&[&dyn EntityValuesText]) -> Option<(usize, EntityID)> {
+        if !self.is_visible() {
+            return None
+        }
+        let hit = meshes.ray_cast(&mouse_ray(camera, mouse, viewport_data), f32::MAX)?;
+        let (entity_type, id) = pickable.iter().find_map(|instances| {
+            hit.find_entity(instances.instance_vec, instances.instance_ids).map(|id| (instances.entity_type, id))
+        })?;
+        if !entities.get(entity_type).is_some_and(|entity| entity.is_alive(id)) {
+            return None
+        }
+        self.select(entity_type, id);
+        Some((entity_type, id))
+    }
+    /// Updates the counts and the values of the selected entity, only touching UI elements whose text changed
+    pub fn refresh<UE:UserEvent>(&self, ui:&SimpleUI<UE>, entities:&[&dyn EntityValuesText]) {
+        let mut state = self.state.write().unwrap();
+        if !state.visible {
+            return;
+        }
+        for (i, entity) in entities.iter().enumerate().take(self.metadata.len()) {
+            let count = entity.entity_count();
+            state.counts[i] = count;
+            let marker = match state.selected {
+                Some((entity_type, _)) if entity_type == i => "> ",
+                _ => ""
+            };
+            let text = format!("{}{} : {}", marker, self.metadata[i].name, count);
+            if state.shown_texts[i] != text {
+                ui.change_content_of(UIElementID::Name(Self::type_element_name(i)), 0, Self::text_content(text.clone()));
+                state.shown_texts[i] = text;
+            }
+        }
+        let values = match state.selected {
+            Some((entity_type, id)) if entity_type < entities.len() && entities[entity_type].is_alive(id) => {
+                let mut text = format!("{} #{}\n", self.metadata[entity_type].name, id);
+                for (name, value) in entities[entity_type].all_components_text(id) {
+                    text.push_str(&format!("{} = {}\n", name, value));
+                }
+                text
+            },
+            Some((entity_type, id)) => format!("{} #{} doesn't exist", self.metadata[entity_type].name, id),
+            None => String::from("Click an entity type")
+        };
+        let last = self.metadata.len();
+        if state.shown_texts[last] != values {
+            ui.change_content_of(UIElementID::Name(INSPECTOR_VALUES.to_string()), 0, Self::text_content(values.clone()));
+            state.shown_texts[last] = values;
+        }
+    }
+    /// Moves the 3D marker to the selected entity, `marker_mesh` being any mesh already loaded in the rendering engine
+    pub fn update_marker<'a>(&self, meshes:&mut MeshesWrite<'a>, marker_mesh:MeshID, for_vec:usize, entities:&[&dyn EntityValuesText]) {
+        let mut state = self.state.write().unwrap();
+        let position = match state.selected {
+            Some((entity_type, id)) if state.visible && entity_type < entities.len() && entities[entity_type].is_alive(id) => entities[entity_type].position_of(id),
+            _ => None
+        };
+        match (state.marker, position) {
+            (Some(instance), Some(pos)) => {
+                let marker = meshes.instances[for_vec].get_instance_mut(instance);
+                marker.change_pos(pos);
+                marker.change_visibility(true);
+            },
+            (Some(instance), None) => meshes.instances[for_vec].get_instance_mut(instance).change_visibility(false),
+            (None, Some(pos)) => state.marker = Some(meshes.add_instance(MeshInstance::new(pos, Orientation::zero(), marker_mesh, true, false, false), for_vec)),
+            (None, None) => ()
+        }
+    }
+    /// Queues a change to the named component of the selected entity, returns false if nothing is selected or the component doesn't exist
+    pub fn queue_edit(&self, component:&str, text:String) -> bool {
+        let mut state = self.state.write().unwrap();
+        match state.selected {
+            Some((entity_type, id)) => match self.metadata[entity_type].component_index(component) {
+                Some(component) => {
+                    state.edits.push(InspectorEdit { entity_type, id, component, text });
+                    true
+                },
+                None => false
+            },
+            None => false
+        }
+    }
+    /// Sends every queued edit as component events, returns the edits that couldn't be applied
+    pub fn send_edits(&self, editors:&[&dyn ComponentEditor]) -> Vec<InspectorEdit> {
+        let edits = std::mem::take(&mut self.state.write().unwrap().edits);
+        edits.into_iter().filter(|edit| {
+            match editors.get(edit.entity_type) {
+                Some(editor) => !editor.send_edit(edit.id, edit.component, &edit.text),
+                None => true
+            }
+        }).collect()
+    }
+}
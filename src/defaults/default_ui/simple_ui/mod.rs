@@ -1,3 +1,5 @@
+pub mod inspector;
+
 use std::{collections::{HashMap, HashSet}, hash::{Hash, RandomState}, path::PathBuf, sync::{atomic::{AtomicBool, AtomicI32, AtomicIsize, AtomicU8, Ordering}, mpmc::{channel, Receiver, Sender}, Arc, RwLock, RwLockReadGuard, RwLockWriteGuard}};
 
 use cosmic_text::{fontdb::ID, Align, Attrs, Buffer, CacheKeyFlags, Color, Family, FontSystem, Metrics, SwashCache};
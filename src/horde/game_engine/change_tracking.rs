@@ -0,0 +1,70 @@
+use super::entity::EntityID;
+
+/// Tracks which entities had one of their components changed, for a single component of an entity vec
+///
+/// `#[derive(Entity)]` generates one of these per component, marked every time a component event is applied or an entity is created
+///
+/// Ticks here are counted by the entity vec itself, starting at 1 and moved forward once per tick by the engine's `BEGIN_TICK_TASK`, a tick of 0 means "never changed"
+#[derive(Clone)]
+pub struct ComponentChanges {
+    this_tick:Vec<u64>,
+    last_changed:Vec<usize>,
+    /// Entities in the order their `last_changed` tick was set, entries overwritten by a later mark are pruned once there are too many of them
+    log:Vec<(usize, EntityID)>,
+}
+
+impl ComponentChanges {
+    pub fn with_capacity(capacity:usize) -> Self {
+        Self { this_tick: Vec::with_capacity(capacity / 64 + 1), last_changed: Vec::with_capacity(capacity), log: Vec::with_capacity(capacity) }
+    }
+    pub fn mark(&mut self, id:EntityID, tick:usize) {
+        if id >= self.last_changed.len() {
+            self.last_changed.resize(id + 1, 0);
+            self.this_tick.resize(id / 64 + 1, 0);
+        }
+        self.this_tick[id / 64] |= 1 << (id % 64);
+        if self.last_changed[id] != tick {
+            self.last_changed[id] = tick;
+            self.log.push((tick, id));
+            if self.log.len() > self.last_changed.len() * 2 + 64 {
+                let last_changed = &self.last_changed;
+                self.log.retain(|(changed, id)| last_changed[*id] == *changed);
+            }
+        }
+    }
+    pub fn is_changed_this_tick(&self, id:EntityID) -> bool {
+        match self.this_tick.get(id / 64) {
+            Some(word) => word & (1 << (id % 64)) != 0,
+            None => false
+        }
+    }
+    /// Tick at which this component was last changed for that entity, if ever
+    pub fn last_changed(&self, id:EntityID) -> Option<usize> {
+        match self.last_changed.get(id) {
+            Some(0) | None => None,
+            Some(tick) => Some(*tick)
+        }
+    }
+    pub fn changed_this_tick<'a>(&'a self) -> impl Iterator<Item = EntityID> + 'a {
+        self.this_tick.iter().enumerate().flat_map(|(i, word)| {
+            let word = *word;
+            (0..64).filter(move |bit| word & (1 << bit) != 0).map(move |bit| i * 64 + bit)
+        })
+    }
+    /// Entities changed strictly after `tick`, most recently changed first, only goes through the entities that changed
+    pub fn changed_since<'a>(&'a self, tick:usize) -> impl Iterator<Item = EntityID> + 'a {
+        self.log.iter().rev().take_while(move |(changed, _)| *changed > tick).filter(|(changed, id)| self.last_changed[*id] == *changed).map(|(_, id)| *id)
+    }
+    pub fn clear_tick(&mut self) {
+        self.this_tick.fill(0);
+    }
+    /// Forgets every change, for when the tick count goes back, like when restoring a snapshot
+    pub fn reset(&mut self) {
+        self.this_tick.fill(0);
+        self.last_changed.fill(0);
+        self.log.clear();
+    }
+    pub fn len(&self) -> usize {
+        self.last_changed.len()
+    }
+}
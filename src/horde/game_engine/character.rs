@@ -0,0 +1,342 @@
+use to_from_bytes_derive::{FromBytes, ToBytes};
+
+use crate::horde::geometry::{collision::{narrow_phase::Collider, shapes::Capsule, Contact}, rotation::Orientation, shapes_3d::Sphere, vec3d::Vec3Df};
+
+use super::{entity::{Component, EntityID, SimpleComponentEvent, SimpleComponentUpdate, StaticComponent}, multiplayer::Identify, physics::PhysicsStatics, position::EntityPosition};
+
+/// Tunables of a character controller, the character being an upright capsule centered on its position
+#[derive(Clone, Debug, PartialEq, ToBytes, FromBytes)]
+pub struct CharacterTunables {
+    pub radius:f32,
+    /// Half of the segment of the capsule, the character is `2.0 * (half_height + radius)` tall
+    pub half_height:f32,
+    /// Highest ledge the character walks onto without jumping, also how far down it follows the ground when walking down slopes and stairs
+    pub step_height:f32,
+    /// Steepest walkable slope, in radians from the horizontal
+    pub max_slope:f32,
+    /// Gap kept between the capsule and the world, so moves never start touching a surface
+    pub skin_width:f32,
+    /// Also defines up for the character
+    pub gravity:Vec3Df,
+    pub jump_speed:f32,
+    /// How many times one move can be deflected along surfaces
+    pub max_slides:u32,
+}
+
+impl CharacterTunables {
+    /// Walks up to 45° slopes and 0.3 high steps, with Earth gravity along -Z
+    pub fn new(radius:f32, half_height:f32) -> Self {
+        Self { radius, half_height, step_height: 0.3, max_slope: std::f32::consts::FRAC_PI_4, skin_width: 0.02, gravity: Vec3Df::new(0.0, 0.0, -9.81), jump_speed: 5.0, max_slides: 4 }
+    }
+    pub fn with_step_height(mut self, step_height:f32) -> Self {
+        self.step_height = step_height;
+        self
+    }
+    pub fn with_max_slope(mut self, max_slope:f32) -> Self {
+        self.max_slope = max_slope;
+        self
+    }
+    pub fn with_skin_width(mut self, skin_width:f32) -> Self {
+        self.skin_width = skin_width;
+        self
+    }
+    pub fn with_gravity(mut self, gravity:Vec3Df) -> Self {
+        self.gravity = gravity;
+        self
+    }
+    pub fn with_jump_speed(mut self, jump_speed:f32) -> Self {
+        self.jump_speed = jump_speed;
+        self
+    }
+}
+
+/// Kinematic character controller component, moving the `EntityPosition` component of its entity through the static world geometry
+///
+/// Horizontal movement comes from the `CharacterInput` of each tick, only the speed along gravity is kept between ticks
+#[derive(Clone, Debug, PartialEq, ToBytes, FromBytes)]
+pub struct CharacterController {
+    pub tunables:CharacterTunables,
+    /// Speed along up, negative when falling
+    pub vertical_speed:f32,
+    pub grounded:bool,
+    /// Normal of the ground under the character, up when in the air
+    pub ground_normal:Vec3Df,
+}
+
+impl CharacterController {
+    pub fn new(tunables:CharacterTunables) -> Self {
+        let ground_normal = up_of(&tunables.gravity);
+        Self { tunables, vertical_speed: 0.0, grounded: false, ground_normal }
+    }
+}
+
+impl StaticComponent for CharacterController {
+
+}
+
+impl<ID:Identify> Component<ID> for CharacterController {
+    type SC = Self;
+    type CE = SimpleComponentEvent<ID, CharacterUpdate>;
+    fn from_static(static_comp:&Self::SC) -> Self {
+        static_comp.clone()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, ToBytes, FromBytes)]
+pub enum CharacterUpdate {
+    /// Sent by `step_character`
+    Step{vertical_speed:f32, grounded:bool, ground_normal:Vec3Df},
+    /// Launches the character along up, like jump pads do
+    SetVerticalSpeed(f32),
+    SetTunables(CharacterTunables),
+}
+
+impl<ID:Identify> SimpleComponentUpdate<CharacterController, ID> for CharacterUpdate {
+    fn apply_to_comp(self, component:&mut CharacterController) {
+        match self {
+            CharacterUpdate::Step { vertical_speed, grounded, ground_normal } => {
+                component.vertical_speed = vertical_speed;
+                component.grounded = grounded;
+                component.ground_normal = ground_normal;
+            },
+            CharacterUpdate::SetVerticalSpeed(vertical_speed) => {
+                component.vertical_speed = vertical_speed;
+                component.grounded = false;
+            },
+            CharacterUpdate::SetTunables(tunables) => component.tunables = tunables,
+        }
+    }
+}
+
+/// What the character wants to do this tick, usually built from player input
+#[derive(Clone, Debug, PartialEq)]
+pub struct CharacterInput {
+    /// Only the part orthogonal to gravity is used
+    pub wish_velocity:Vec3Df,
+    /// Only jumps when grounded
+    pub jump:bool,
+}
+
+/// What `step_character` computed for one character, turn it into events with `controller_event` and `move_event`
+#[derive(Clone, Debug, PartialEq)]
+pub struct CharacterStep {
+    pub update:CharacterUpdate,
+    pub pos:Vec3Df,
+    pub orient:Orientation,
+}
+
+impl CharacterStep {
+    pub fn controller_event<ID:Identify>(&self, id:EntityID, source:Option<ID>) -> SimpleComponentEvent<ID, CharacterUpdate> {
+        SimpleComponentEvent::new(id, source, self.update.clone())
+    }
+    /// None if the position component doesn't implement `EntityPosition::move_event`
+    pub fn move_event<ID:Identify, P:EntityPosition<ID>>(&self, id:EntityID, source:Option<ID>) -> Option<P::CE> {
+        P::move_event(id, source, self.pos, self.orient)
+    }
+}
+
+fn up_of(gravity:&Vec3Df) -> Vec3Df {
+    let strength = gravity.norme();
+    if strength > f32::EPSILON {
+        -gravity / strength
+    }
+    else {
+        Vec3Df::new(0.0, 0.0, 1.0)
+    }
+}
+
+/// Swept capsule queries of one character against the statics
+struct Mover<'a> {
+    tunables:&'a CharacterTunables,
+    statics:&'a PhysicsStatics,
+    up:Vec3Df,
+    min_ground_dot:f32,
+}
+
+impl<'a> Mover<'a> {
+    fn capsule(&self, pos:Vec3Df) -> Collider {
+        let axis = self.up * self.tunables.half_height;
+        Collider::Capsule(Capsule::new(pos - axis, pos + axis, self.tunables.radius))
+    }
+    fn is_walkable(&self, normal:&Vec3Df) -> bool {
+        normal.dot(&self.up) >= self.min_ground_dot
+    }
+    fn horizontal(&self, vector:Vec3Df) -> Vec3Df {
+        vector - self.up * vector.dot(&self.up)
+    }
+    /// Pushes the capsule out of the statics it overlaps, deepest first
+    fn depenetrate(&self, mut pos:Vec3Df) -> Vec3Df {
+        for _ in 0..self.tunables.max_slides {
+            let contacts = self.statics.contacts(&self.capsule(pos));
+            match contacts.iter().filter(|contact| contact.depth > 0.0).max_by(|c1, c2| c1.depth.total_cmp(&c2.depth)) {
+                Some(contact) => pos -= contact.normal * (contact.depth + self.tunables.skin_width),
+                None => break
+            }
+        }
+        pos
+    }
+    /// Moves along `motion` until `skin_width` before the first hit, with the contact of the hit, its normal facing the character
+    fn sweep_move(&self, pos:Vec3Df, motion:Vec3Df) -> (Vec3Df, Option<Contact>) {
+        let length = motion.norme();
+        if length <= f32::EPSILON {
+            return (pos, None)
+        }
+        match self.statics.sweep(&self.capsule(pos), &motion) {
+            Some(hit) => {
+                let travel = (length * hit.time - self.tunables.skin_width).max(0.0);
+                (pos + motion * (travel / length), Some(hit.contact.flipped()))
+            },
+            None => (pos + motion, None)
+        }
+    }
+    /// Normal of the ground if the character at `pos` can stand on what it touches at `contact`
+    ///
+    /// The rounded bottom of the capsule gives steep normals on the edges of ledges and steps,
+    /// so for those the surface just past the edge is checked with a small probe
+    fn ground_normal(&self, pos:Vec3Df, contact:&Contact) -> Option<Vec3Df> {
+        if self.is_walkable(&contact.normal) {
+            return Some(contact.normal)
+        }
+        let outward = self.horizontal(contact.point - pos);
+        if contact.normal.dot(&self.up) <= 0.0 || outward.norme() <= f32::EPSILON {
+            return None
+        }
+        let probe_radius = self.tunables.skin_width;
+        let probe = Collider::Sphere(Sphere::new(contact.point + (outward.normalise() + self.up) * (2.0 * probe_radius), probe_radius));
+        let hit = self.statics.sweep(&probe, &(-self.up * (4.0 * probe_radius)))?;
+        let normal = -hit.contact.normal;
+        self.is_walkable(&normal).then_some(normal)
+    }
+    /// Moves along `motion`, sliding along the surfaces hit, with their normals
+    ///
+    /// With `steep_as_walls`, slopes too steep to walk on block like vertical walls instead of being climbed
+    fn slide(&self, mut pos:Vec3Df, motion:Vec3Df, steep_as_walls:bool) -> (Vec3Df, Vec<Vec3Df>) {
+        let mut remaining = motion;
+        let mut normals:Vec<Vec3Df> = Vec::new();
+        for _ in 0..self.tunables.max_slides {
+            if remaining.norme() <= f32::EPSILON {
+                break
+            }
+            let (new_pos, hit) = self.sweep_move(pos, remaining);
+            remaining -= new_pos - pos;
+            pos = new_pos;
+            let mut normal = match hit {
+                Some(contact) => contact.normal,
+                None => break
+            };
+            if steep_as_walls && !self.is_walkable(&normal) && normal.dot(&self.up) > 0.0 {
+                let wall = self.horizontal(normal);
+                if wall.norme() > f32::EPSILON {
+                    normal = wall.normalise();
+                }
+            }
+            let into = remaining.dot(&normal);
+            if into < 0.0 {
+                remaining -= normal * into;
+            }
+            // In a crease between two surfaces, only moving along the crease doesn't go into either
+            for previous in normals.iter() {
+                if remaining.dot(previous) < 0.0 {
+                    let crease = previous.cross(&normal);
+                    remaining = if crease.norme() > f32::EPSILON {
+                        let crease = crease.normalise();
+                        crease * remaining.dot(&crease)
+                    }
+                    else {
+                        Vec3Df::zero()
+                    };
+                }
+            }
+            normals.push(normal);
+        }
+        (pos, normals)
+    }
+    /// Ground at most `distance` under the character, with the position on it and its normal
+    fn probe_ground(&self, pos:Vec3Df, distance:f32) -> Option<(Vec3Df, Vec3Df)> {
+        match self.sweep_move(pos, -self.up * (distance + self.tunables.skin_width)) {
+            (ground_pos, Some(contact)) => self.ground_normal(ground_pos, &contact).map(|normal| (ground_pos, normal)),
+            (_, None) => None
+        }
+    }
+    /// Goes up by `step_height`, moves, then goes back down, only succeeds when landing on walkable ground
+    fn step_up(&self, pos:Vec3Df, motion:Vec3Df) -> Option<Vec3Df> {
+        let (raised, _) = self.slide(pos, self.up * self.tunables.step_height, false);
+        let (moved, _) = self.slide(raised, motion, true);
+        self.probe_ground(moved, (raised - pos).dot(&self.up)).map(|(landed, _)| landed)
+    }
+}
+
+/// Moves character `id` for one tick of `tick_duration` seconds according to `input`, against `statics`
+///
+/// Called for every entity by the `character_stage` that `#[derive(Entity)]` generates for `#[character]` components, which sends the events of the returned step,
+/// and only depends on the previous tick so characters can be stepped in any order.
+/// Characters don't collide with each other or with rigid bodies
+pub fn step_character<ID:Identify, P:EntityPosition<ID>>(id:EntityID, controllers:&[CharacterController], positions:&[P], input:&CharacterInput, statics:&PhysicsStatics, tick_duration:f32) -> CharacterStep {
+    let controller = &controllers[id];
+    let tunables = &controller.tunables;
+    let mover = Mover { tunables, statics, up: up_of(&tunables.gravity), min_ground_dot: tunables.max_slope.cos() };
+    let up = mover.up;
+    let mut pos = mover.depenetrate(positions[id].get_pos());
+
+    let jumping = controller.grounded && input.jump;
+    let mut vertical_speed = if jumping {
+        tunables.jump_speed
+    }
+    else if controller.grounded {
+        0.0
+    }
+    else {
+        controller.vertical_speed - tunables.gravity.norme() * tick_duration
+    };
+
+    let motion = mover.horizontal(input.wish_velocity) * tick_duration;
+    let start = pos;
+    let (slid, normals) = mover.slide(pos, motion, true);
+    pos = slid;
+    if controller.grounded && !jumping && tunables.step_height > 0.0 && normals.iter().any(|normal| !mover.is_walkable(normal)) {
+        if let Some(stepped) = mover.step_up(start, motion) {
+            if mover.horizontal(stepped - start).norme() > mover.horizontal(slid - start).norme() + f32::EPSILON {
+                pos = stepped;
+            }
+        }
+    }
+
+    let mut grounded = false;
+    let mut ground_normal = up;
+    let motion = up * (vertical_speed * tick_duration);
+    let (moved, hit) = mover.sweep_move(pos, motion);
+    let remaining = motion - (moved - pos);
+    pos = moved;
+    if let Some(contact) = hit {
+        let normal = contact.normal;
+        if let Some(ground) = mover.ground_normal(pos, &contact).filter(|_| vertical_speed < 0.0) {
+            grounded = true;
+            ground_normal = ground;
+            vertical_speed = 0.0;
+        }
+        else {
+            if vertical_speed > 0.0 && normal.dot(&up) < 0.0 {
+                vertical_speed = 0.0;
+            }
+            // Slides down slopes too steep to stand on
+            let into = remaining.dot(&normal);
+            pos = mover.slide(pos, if into < 0.0 {remaining - normal * into} else {remaining}, false).0;
+        }
+    }
+    if !grounded && vertical_speed <= 0.0 {
+        // Following the ground when walking down slopes and stairs
+        let snap = if controller.grounded {tunables.step_height} else {0.0};
+        if let Some((ground_pos, normal)) = mover.probe_ground(pos, snap) {
+            pos = ground_pos;
+            grounded = true;
+            ground_normal = normal;
+            vertical_speed = 0.0;
+        }
+    }
+
+    CharacterStep {
+        update: CharacterUpdate::Step { vertical_speed, grounded, ground_normal },
+        pos,
+        orient: positions[id].get_orientation(),
+    }
+}
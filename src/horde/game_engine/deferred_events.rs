@@ -0,0 +1,174 @@
+use std::{collections::HashMap, sync::{Arc, RwLock}};
+
+use to_from_bytes::{FromBytes, ToBytes};
+use to_from_bytes_derive::{FromBytes, ToBytes};
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, ToBytes, FromBytes)]
+pub struct ScheduledEventHandle {
+    id:u64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ScheduleDelay {
+    Ticks(usize),
+    /// Converted to ticks with the tick rate of the wheel, rounded up
+    Seconds(f32),
+}
+
+#[derive(Clone, PartialEq, ToBytes, FromBytes)]
+pub struct PendingEvent<E:Clone + ToBytes + FromBytes> {
+    pub handle:ScheduledEventHandle,
+    pub fire_tick:usize,
+    pub event:E,
+}
+
+/// Everything needed to rebuild a wheel, used by snapshots and multiplayer syncs
+#[derive(Clone, PartialEq, ToBytes, FromBytes)]
+pub struct DeferredEventsState<E:Clone + ToBytes + FromBytes> {
+    pub current_tick:usize,
+    pub next_handle:u64,
+    pub pending:Vec<PendingEvent<E>>,
+}
+
+/// Timer wheel holding events until the tick they should fire at
+///
+/// Events further in the future than the wheel is long wait in an overflow list, and are moved into the wheel once they get close enough
+pub struct TimerWheel<E:Clone + ToBytes + FromBytes> {
+    current_tick:usize,
+    slots:Vec<Vec<PendingEvent<E>>>,
+    overflow:Vec<PendingEvent<E>>,
+    fire_ticks:HashMap<ScheduledEventHandle, usize>,
+    next_handle:u64,
+    ticks_per_second:f32,
+}
+
+impl<E:Clone + ToBytes + FromBytes> TimerWheel<E> {
+    pub fn new(slots:usize, ticks_per_second:f32) -> Self {
+        Self { current_tick: 0, slots: vec![Vec::new() ; slots.max(1)], overflow: Vec::new(), fire_ticks: HashMap::new(), next_handle: 0, ticks_per_second }
+    }
+    pub fn current_tick(&self) -> usize {
+        self.current_tick
+    }
+    pub fn set_ticks_per_second(&mut self, ticks_per_second:f32) {
+        self.ticks_per_second = ticks_per_second;
+    }
+    pub fn delay_in_ticks(&self, delay:ScheduleDelay) -> usize {
+        match delay {
+            ScheduleDelay::Ticks(ticks) => ticks,
+            ScheduleDelay::Seconds(seconds) => (seconds * self.ticks_per_second).ceil().max(0.0) as usize
+        }
+    }
+    /// A delay of 0 ticks fires on the next `advance`
+    pub fn schedule(&mut self, delay:ScheduleDelay, event:E) -> ScheduledEventHandle {
+        let fire_tick = self.current_tick + self.delay_in_ticks(delay).max(1);
+        self.schedule_at_tick(fire_tick, event)
+    }
+    pub fn schedule_at_tick(&mut self, fire_tick:usize, event:E) -> ScheduledEventHandle {
+        let handle = ScheduledEventHandle { id: self.next_handle };
+        self.next_handle += 1;
+        self.insert(PendingEvent { handle, fire_tick:fire_tick.max(self.current_tick + 1), event });
+        handle
+    }
+    fn insert(&mut self, pending:PendingEvent<E>) {
+        self.fire_ticks.insert(pending.handle, pending.fire_tick);
+        if pending.fire_tick.saturating_sub(self.current_tick) <= self.slots.len() {
+            let slot = pending.fire_tick % self.slots.len();
+            self.slots[slot].push(pending);
+        }
+        else {
+            self.overflow.push(pending);
+        }
+    }
+    /// Returns false if the event already fired or was already cancelled
+    pub fn cancel(&mut self, handle:ScheduledEventHandle) -> bool {
+        match self.fire_ticks.remove(&handle) {
+            Some(fire_tick) => {
+                let slot = fire_tick % self.slots.len();
+                self.slots[slot].retain(|pending| pending.handle != handle);
+                self.overflow.retain(|pending| pending.handle != handle);
+                true
+            },
+            None => false
+        }
+    }
+    pub fn is_pending(&self, handle:ScheduledEventHandle) -> bool {
+        self.fire_ticks.contains_key(&handle)
+    }
+    /// Moves to the next tick and returns the events firing at it, in the order they were scheduled
+    pub fn advance(&mut self) -> Vec<E> {
+        self.current_tick += 1;
+        let len = self.slots.len();
+        if !self.overflow.is_empty() {
+            let current_tick = self.current_tick;
+            let (now_close, still_far):(Vec<PendingEvent<E>>, Vec<PendingEvent<E>>) = std::mem::take(&mut self.overflow).into_iter().partition(|pending| pending.fire_tick.saturating_sub(current_tick) < len);
+            self.overflow = still_far;
+            for pending in now_close {
+                self.slots[pending.fire_tick % len].push(pending);
+            }
+        }
+        let slot = self.current_tick % len;
+        let (mut firing, staying):(Vec<PendingEvent<E>>, Vec<PendingEvent<E>>) = std::mem::take(&mut self.slots[slot]).into_iter().partition(|pending| pending.fire_tick <= self.current_tick);
+        self.slots[slot] = staying;
+        firing.sort_by_key(|pending| pending.handle.id);
+        firing.into_iter().map(|pending| {
+            self.fire_ticks.remove(&pending.handle);
+            pending.event
+        }).collect()
+    }
+    pub fn get_state(&self) -> DeferredEventsState<E> {
+        let mut pending:Vec<PendingEvent<E>> = self.slots.iter().flatten().chain(self.overflow.iter()).cloned().collect();
+        pending.sort_by_key(|pending| pending.handle.id);
+        DeferredEventsState { current_tick: self.current_tick, next_handle: self.next_handle, pending }
+    }
+    pub fn load_state(&mut self, state:DeferredEventsState<E>) {
+        for slot in self.slots.iter_mut() {
+            slot.clear();
+        }
+        self.overflow.clear();
+        self.fire_ticks.clear();
+        self.current_tick = state.current_tick;
+        self.next_handle = state.next_handle;
+        for pending in state.pending {
+            self.insert(pending);
+        }
+    }
+}
+
+/// Shared handle to the `TimerWheel` of an engine, it can be cloned into extra data to schedule events from tick stages
+#[derive(Clone)]
+pub struct DeferredEvents<E:Clone + ToBytes + FromBytes> {
+    wheel:Arc<RwLock<TimerWheel<E>>>,
+}
+
+impl<E:Clone + ToBytes + FromBytes> DeferredEvents<E> {
+    pub fn new(slots:usize, ticks_per_second:f32) -> Self {
+        Self { wheel: Arc::new(RwLock::new(TimerWheel::new(slots, ticks_per_second))) }
+    }
+    pub fn schedule(&self, delay:ScheduleDelay, event:E) -> ScheduledEventHandle {
+        self.wheel.write().unwrap().schedule(delay, event)
+    }
+    pub fn schedule_at_tick(&self, fire_tick:usize, event:E) -> ScheduledEventHandle {
+        self.wheel.write().unwrap().schedule_at_tick(fire_tick, event)
+    }
+    pub fn cancel(&self, handle:ScheduledEventHandle) -> bool {
+        self.wheel.write().unwrap().cancel(handle)
+    }
+    pub fn is_pending(&self, handle:ScheduledEventHandle) -> bool {
+        self.wheel.read().unwrap().is_pending(handle)
+    }
+    pub fn current_tick(&self) -> usize {
+        self.wheel.read().unwrap().current_tick()
+    }
+    pub fn set_ticks_per_second(&self, ticks_per_second:f32) {
+        self.wheel.write().unwrap().set_ticks_per_second(ticks_per_second);
+    }
+    pub fn advance(&self) -> Vec<E> {
+        self.wheel.write().unwrap().advance()
+    }
+    pub fn get_state(&self) -> DeferredEventsState<E> {
+        self.wheel.read().unwrap().get_state()
+    }
+    pub fn load_state(&self, state:DeferredEventsState<E>) {
+        self.wheel.write().unwrap().load_state(state);
+    }
+}
@@ -2,6 +2,9 @@ use std::hash::Hash;
 
 use crate::horde::geometry::vec3d::Vec3Df;
 
+/// Task ID every generated engine runs once at the start of each simulation tick, it should come before the tick stages in the queue
+pub const BEGIN_TICK_TASK:usize = 40;
+
 pub trait GameEngine:Sync + Send + Sized {
     type GEC:Sync + Send + Sized;
     type MOID: MovingObjectID<Self>;
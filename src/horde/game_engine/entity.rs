@@ -2,9 +2,11 @@ use crossbeam::channel::{unbounded, Receiver, Sender};
 use to_from_bytes::{FromBytes, ToBytes};
 use to_from_bytes_derive::{FromBytes, ToBytes};
 
-use crate::horde::utils::parallel_counter::ParallelCounter;
+use std::sync::{Arc, Mutex};
 
-use super::multiplayer::Identify;
+use crate::horde::{scheduler::work_stealing::ChunkSource, utils::parallel_counter::ParallelCounter};
+
+use super::{merge::{variant_kind, MergeableEvent}, multiplayer::Identify};
 
 pub trait Entity<ID:Identify>:Sized + Sync + Send {
     type EV<O>:EntityVec<ID>;
@@ -24,6 +26,67 @@ pub trait StaticEntity<ID:Identify> {
 
 pub type EntityID = usize;
 
+/// Sent through the `despawns` tunnel of an entity vec, the ID becomes free for new entities once the event is applied
+#[derive(Clone, PartialEq, Debug, ToBytes, FromBytes)]
+pub struct DespawnEvent<ID:Identify> {
+    pub id:EntityID,
+    pub made_by:Option<ID>,
+}
+
+impl<ID:Identify> DespawnEvent<ID> {
+    pub fn new(id:EntityID, made_by:Option<ID>) -> Self {
+        Self { id, made_by }
+    }
+}
+
+/// One bit per entity ID of an entity vec, set while an entity lives at that ID
+///
+/// Tick stages and renders skip the IDs that aren't alive
+#[derive(Clone, Debug, PartialEq)]
+pub struct AliveMask {
+    words:Vec<u64>,
+}
+
+impl AliveMask {
+    pub fn with_capacity(capacity:usize) -> Self {
+        Self { words: Vec::with_capacity(capacity / 64 + 1) }
+    }
+    pub fn is_alive(&self, id:EntityID) -> bool {
+        match self.words.get(id / 64) {
+            Some(word) => word & (1 << (id % 64)) != 0,
+            None => false
+        }
+    }
+    pub fn set_alive(&mut self, id:EntityID) {
+        if id / 64 >= self.words.len() {
+            self.words.resize(id / 64 + 1, 0);
+        }
+        self.words[id / 64] |= 1 << (id % 64);
+    }
+    /// Returns false if the ID wasn't alive
+    pub fn kill(&mut self, id:EntityID) -> bool {
+        let alive = self.is_alive(id);
+        if alive {
+            self.words[id / 64] &= !(1 << (id % 64));
+        }
+        alive
+    }
+    /// Every ID below `len` is alive except the ones of `dead`
+    pub fn rebuild<I:IntoIterator<Item = EntityID>>(&mut self, len:usize, dead:I) {
+        self.words.clear();
+        self.words.resize(len / 64 + 1, 0);
+        for id in 0..len {
+            self.words[id / 64] |= 1 << (id % 64);
+        }
+        for id in dead {
+            self.kill(id);
+        }
+    }
+    pub fn alive_count(&self) -> usize {
+        self.words.iter().map(|word| word.count_ones() as usize).sum()
+    }
+}
+
 pub trait EntityVec<ID:Identify>:Send + Sync + Sized + Clone {
     type OutVec;
 }
@@ -47,6 +110,10 @@ pub trait ComponentEvent<C:Component<ID>, ID:Identify>: Send + Sync + Clone {
     fn get_id(&self) -> EntityID;
     fn apply_to_component(self, components:&mut Vec<C>);
     fn get_source(&self) -> Option<ID>;
+    /// `#[merge]` only reduces events of the same kind targeting the same entity, by default the variant of the event when it is an enum
+    fn merge_kind(&self) -> u64 {
+        variant_kind(self)
+    }
 }
 
 pub trait SimpleComponentUpdate<C:Component<ID>, ID:Identify>: Send + Sync + Clone + ToBytes + FromBytes + PartialEq {
@@ -76,6 +143,19 @@ impl<ID:Identify, C:Component<ID>, SCU:SimpleComponentUpdate<C, ID>> ComponentEv
     fn apply_to_component(self, components:&mut Vec<C>) {
         self.update.apply_to_comp(&mut components[self.id]);
     }
+    fn merge_kind(&self) -> u64 {
+        variant_kind(&self.update)
+    }
+}
+
+impl<ID:Identify, SCU: Clone + ToBytes + FromBytes + PartialEq> MergeableEvent for SimpleComponentEvent<ID, SCU> {
+    type Value = SCU;
+    fn value(&self) -> &SCU {
+        &self.update
+    }
+    fn with_value(self, value:SCU) -> Self {
+        Self { update:value, ..self }
+    }
 }
 
 
@@ -91,8 +171,12 @@ pub struct EVecStopsIn {
     target_count:usize,
     thread_number:usize,
     pub iteration_counter:ParallelCounter,
+    stage_chunks:Arc<Mutex<Vec<ChunkSource>>>,
 }
 
+/// Smallest range of entities a thread takes at once in a tick stage
+pub const STAGE_MIN_CHUNK:usize = 64;
+
 impl EVecStopsIn {
     pub fn new() -> (Self, EVecStopsOut) {
         let (stops_send, stops_recv) = unbounded();
@@ -103,7 +187,8 @@ impl EVecStopsIn {
                 stops_counter:0,
                 target_count:4,
                 thread_number:0,
-                iteration_counter:counter.clone()
+                iteration_counter:counter.clone(),
+                stage_chunks:Arc::new(Mutex::new(Vec::new()))
             },
             EVecStopsOut {
                 stops_send,
@@ -113,6 +198,14 @@ impl EVecStopsIn {
             }
         )
     }
+    /// Source of the entity ranges taken by the threads running tick stage `stage` on this entity vec, made the first time it's asked for
+    pub fn stage_chunk_source(&self, stage:usize) -> ChunkSource {
+        let mut stage_chunks = self.stage_chunks.lock().unwrap();
+        while stage_chunks.len() <= stage {
+            stage_chunks.push(ChunkSource::new(STAGE_MIN_CHUNK));
+        }
+        stage_chunks[stage].clone()
+    }
     pub fn calc_fini(&self) -> bool {
         self.stops_counter >= self.target_count
     }
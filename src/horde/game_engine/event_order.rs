@@ -0,0 +1,69 @@
+use std::cell::Cell;
+
+use to_from_bytes::ToBytes;
+
+use super::entity::EntityID;
+
+/// The entity whose stage was running when an event was sent, `kind` is the index of its entity vec in the engine
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct EventTurn {
+    pub stage:usize,
+    pub kind:usize,
+    pub entity:EntityID,
+}
+
+thread_local! {
+    static EVENT_TURN:Cell<Option<EventTurn>> = Cell::new(None);
+    static EVENT_SEQUENCE:Cell<u64> = Cell::new(0);
+}
+
+/// Called by engines with `#[deterministic_events]` at the start of every task, events sent outside of an entity turn have no turn and count from 0 again
+pub fn begin_event_task() {
+    EVENT_TURN.with(|turn| turn.set(None));
+    EVENT_SEQUENCE.with(|sequence| sequence.set(0));
+}
+
+/// Called by engines with `#[deterministic_events]` before each entity of a stage, the sequence counts from 0 again for every turn
+///
+/// This way the stamp of an event only depends on which entity sent it and not on which thread ran that entity
+pub fn set_event_turn(stage:usize, kind:usize, entity:EntityID) {
+    EVENT_TURN.with(|turn| turn.set(Some(EventTurn { stage, kind, entity })));
+    EVENT_SEQUENCE.with(|sequence| sequence.set(0));
+}
+
+/// Events sent through the tunnels of `#[deterministic]` entities (and of every world) are wrapped in this, stamped with the turn that sent them and a sequence number local to that turn
+#[derive(Clone, Debug)]
+pub struct SequencedEvent<E> {
+    pub turn:Option<EventTurn>,
+    pub sequence:u64,
+    pub event:E,
+}
+
+impl<E> SequencedEvent<E> {
+    pub fn new(event:E) -> Self {
+        let turn = EVENT_TURN.with(|turn| turn.get());
+        let sequence = EVENT_SEQUENCE.with(|sequence| {
+            let value = sequence.get();
+            sequence.set(value + 1);
+            value
+        });
+        Self { turn, sequence, event }
+    }
+}
+
+/// Sort key of an event : target ID, then source ID, then the turn and sequence number of the send
+///
+/// Source IDs are compared through their bytes since `Identify` types aren't ordered, events without a source come first, as do events sent outside of entity turns
+pub type EventOrderKey = (EntityID, Option<Vec<u8>>, Option<EventTurn>, u64);
+
+pub fn event_order_key<E, ID:ToBytes>(target:EntityID, source:Option<ID>, event:&SequencedEvent<E>) -> EventOrderKey {
+    (target, source.map(|source| source.get_bytes_vec()), event.turn, event.sequence)
+}
+
+/// Sorts the events of one tick, `ids` gives the target and source of an event
+pub fn sort_events<E, ID:ToBytes, F:Fn(&E) -> (EntityID, Option<ID>)>(events:&mut Vec<SequencedEvent<E>>, ids:F) {
+    events.sort_by_cached_key(|event| {
+        let (target, source) = ids(&event.event);
+        event_order_key(target, source, event)
+    });
+}
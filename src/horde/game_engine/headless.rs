@@ -0,0 +1,96 @@
+use std::fmt::Debug;
+
+use crate::horde::scheduler::IndividualTask;
+
+use super::engine::BEGIN_TICK_TASK;
+
+/// Runs a `#[derive(GameEngine)]` engine tick by tick on the calling thread, without a scheduler, window, sound device or network
+///
+/// Build the engine with `NullRendering` as its rendering engine, `WavesHandler::null()` for sound, and no multiplayer
+pub struct HeadlessHarness<E:IndividualTask<TID = usize>> {
+    engine:E,
+    tick_tasks:Vec<usize>,
+    ticks_done:usize,
+}
+
+impl<E:IndividualTask<TID = usize>> HeadlessHarness<E> {
+    /// Each tick begins with `BEGIN_TICK_TASK`, then runs every tick stage in order, applying events after each of them
+    pub fn new(engine:E, tick_stages:usize) -> Self {
+        let mut tick_tasks = Vec::with_capacity(tick_stages * 2 + 1);
+        tick_tasks.push(BEGIN_TICK_TASK);
+        for stage in 0..tick_stages {
+            tick_tasks.push(100 + stage);
+            tick_tasks.push(0);
+        }
+        Self::with_tick_tasks(engine, tick_tasks)
+    }
+    /// `tick_tasks` are engine task IDs run in order once per tick, for example to add the multiplayer tasks
+    pub fn with_tick_tasks(engine:E, tick_tasks:Vec<usize>) -> Self {
+        Self { engine, tick_tasks, ticks_done: 0 }
+    }
+    pub fn engine(&self) -> &E {
+        &self.engine
+    }
+    /// To send events or spawn entities between ticks
+    pub fn engine_mut(&mut self) -> &mut E {
+        &mut self.engine
+    }
+    pub fn into_engine(self) -> E {
+        self.engine
+    }
+    pub fn ticks_done(&self) -> usize {
+        self.ticks_done
+    }
+    pub fn tick(&mut self) {
+        for task in self.tick_tasks.iter() {
+            self.engine.do_task(*task, 0, 1);
+        }
+        self.ticks_done += 1;
+    }
+    pub fn run_ticks(&mut self, ticks:usize) {
+        for _ in 0..ticks {
+            self.tick();
+        }
+    }
+    /// Ticks until `condition` holds, returns the number of ticks it took or Err if it still didn't after `max_ticks`
+    pub fn run_until<F:FnMut(&E) -> bool>(&mut self, max_ticks:usize, mut condition:F) -> Result<usize, ()> {
+        for i in 0..max_ticks {
+            if condition(&self.engine) {
+                return Ok(i)
+            }
+            self.tick();
+        }
+        if condition(&self.engine) {
+            Ok(max_ticks)
+        }
+        else {
+            Err(())
+        }
+    }
+    /// Reads anything out of the engine, like components through `engine.ent.get_read()` or the world through `engine.world.world`
+    pub fn query<T, F:FnOnce(&E) -> T>(&self, query:F) -> T {
+        query(&self.engine)
+    }
+    /// Panics with `what` and the current tick if `check` is false
+    pub fn assert_that<F:FnOnce(&E) -> bool>(&self, what:&str, check:F) {
+        if !check(&self.engine) {
+            panic!("Headless assertion failed after {} ticks : {}", self.ticks_done, what);
+        }
+    }
+    /// Panics with both values and the current tick if the queried value isn't `expected`
+    pub fn assert_query_eq<T:PartialEq + Debug, F:FnOnce(&E) -> T>(&self, expected:T, query:F) {
+        let found = query(&self.engine);
+        if found != expected {
+            panic!("Headless assertion failed after {} ticks : expected {:?}, found {:?}", self.ticks_done, expected, found);
+        }
+    }
+    /// Checks `check` after each of the next `ticks` ticks, panicking on the first one where it is false
+    pub fn assert_for_ticks<F:FnMut(&E) -> bool>(&mut self, ticks:usize, what:&str, mut check:F) {
+        for _ in 0..ticks {
+            self.tick();
+            if !check(&self.engine) {
+                panic!("Headless assertion failed after {} ticks : {}", self.ticks_done, what);
+            }
+        }
+    }
+}
@@ -0,0 +1,202 @@
+use std::{collections::{HashMap, HashSet, VecDeque}, hash::Hash};
+
+use to_from_bytes::{FromBytes, ToBytes};
+use to_from_bytes_derive::{FromBytes, ToBytes};
+
+use crate::horde::geometry::{rotation::{Orientation, Quaternion, Rotation}, vec3d::Vec3Df};
+
+use super::{entity::{Component, SimpleComponentEvent, SimpleComponentUpdate, StaticComponent}, multiplayer::Identify};
+
+/// What happens to the children of an entity when that entity is removed
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ToBytes, FromBytes)]
+pub enum OrphanPolicy {
+    /// the child stays where it was and becomes a root
+    Detach,
+    /// the child is removed too, and its own children follow their own policy
+    Despawn,
+}
+
+/// Position and orientation of a child, relative to its parent
+#[derive(Clone, Debug, PartialEq, ToBytes, FromBytes)]
+pub struct LocalTransform {
+    pub offset:Vec3Df,
+    pub orient:Orientation,
+}
+
+impl LocalTransform {
+    pub fn new(offset:Vec3Df, orient:Orientation) -> Self {
+        Self { offset, orient }
+    }
+    pub fn zero() -> Self {
+        Self { offset: Vec3Df::zero(), orient: Orientation::zero() }
+    }
+    pub fn to_world(&self, parent:&WorldTransform) -> WorldTransform {
+        let parent_quat = Quaternion::new_from_euler(parent.orient.yaw, parent.orient.pitch, parent.orient.roll);
+        let local_quat = Quaternion::new_from_euler(self.orient.yaw, self.orient.pitch, self.orient.roll);
+        WorldTransform {
+            pos: parent.pos + Rotation::from_orientation(parent.orient).rotate(self.offset),
+            orient: parent_quat.compose(&local_quat).to_orientation()
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, ToBytes, FromBytes)]
+pub struct WorldTransform {
+    pub pos:Vec3Df,
+    pub orient:Orientation,
+}
+
+impl WorldTransform {
+    pub fn new(pos:Vec3Df, orient:Orientation) -> Self {
+        Self { pos, orient }
+    }
+}
+
+/// Rendering data that the mesh instances of children can be moved in
+///
+/// `#[derive(Entity)]` needs it on the rendering data of entities with both a `#[hierarchy]` and a `#[mesh_instance = "vec"]` component,
+/// the default renderers implement it next to their write handles
+pub trait InstanceTransforms {
+    fn set_instance_transform(&mut self, instance:usize, for_vec:usize, transform:&WorldTransform);
+}
+
+/// Component attaching an entity to a parent, identified by `K`
+///
+/// `K` is whatever the game uses to find any entity, usually the `MovingObjectID` of the engine, so that a turret entity can be attached to a tank entity of another type
+///
+/// Marked `#[hierarchy]` with `K = EntityID`, `#[derive(Entity)]` resolves it after every event application :
+/// - children of despawned parents are detached or despawned according to their `on_parent_removed`, detached ones are moved where they were through `EntityPosition::move_event`
+/// - `world` is set for every child whose chain of parents ends on a living root, the position component of that root giving its transform
+/// - a `#[mesh_instance = "vec"]` component (`Option<usize>`, instance index in instance vec `vec`) gets moved to `world` on each render
+#[derive(Clone, PartialEq, ToBytes, FromBytes)]
+pub struct Hierarchy<K:Clone + Send + Sync + Hash + Eq + ToBytes + FromBytes> {
+    pub parent:Option<K>,
+    pub local:LocalTransform,
+    pub on_parent_removed:OrphanPolicy,
+    /// Last resolved world transform, None for roots
+    pub world:Option<WorldTransform>,
+}
+
+impl<K:Clone + Send + Sync + Hash + Eq + ToBytes + FromBytes> Hierarchy<K> {
+    pub fn root() -> Self {
+        Self { parent: None, local: LocalTransform::zero(), on_parent_removed: OrphanPolicy::Detach, world: None }
+    }
+    pub fn child_of(parent:K, local:LocalTransform, on_parent_removed:OrphanPolicy) -> Self {
+        Self { parent: Some(parent), local, on_parent_removed, world: None }
+    }
+}
+
+impl<K:Clone + Send + Sync + Hash + Eq + ToBytes + FromBytes> StaticComponent for Hierarchy<K> {
+
+}
+
+impl<ID:Identify, K:Clone + Send + Sync + Hash + Eq + ToBytes + FromBytes + PartialEq> Component<ID> for Hierarchy<K> {
+    type SC = Self;
+    type CE = SimpleComponentEvent<ID, HierarchyUpdate<K>>;
+    fn from_static(static_comp:&Self::SC) -> Self {
+        static_comp.clone()
+    }
+}
+
+#[derive(Clone, PartialEq, ToBytes, FromBytes)]
+pub enum HierarchyUpdate<K:Clone + Send + Sync + Hash + Eq + ToBytes + FromBytes> {
+    SetParent(Option<K>),
+    SetLocal(LocalTransform),
+    SetPolicy(OrphanPolicy),
+}
+
+impl<ID:Identify, K:Clone + Send + Sync + Hash + Eq + ToBytes + FromBytes + PartialEq> SimpleComponentUpdate<Hierarchy<K>, ID> for HierarchyUpdate<K> {
+    fn apply_to_comp(self, component:&mut Hierarchy<K>) {
+        match self {
+            HierarchyUpdate::SetParent(parent) => component.parent = parent,
+            HierarchyUpdate::SetLocal(local) => component.local = local,
+            HierarchyUpdate::SetPolicy(policy) => component.on_parent_removed = policy,
+        }
+    }
+}
+
+/// Computes the world transform of every entity that has a parent, parents always coming before their children in the returned vec
+///
+/// `root_transform` gives the world transform of entities that aren't children of anything, usually from their `EntityPosition` component
+///
+/// Children whose chain of parents loops back on itself, or ends on an entity `root_transform` doesn't know about, are left out
+pub fn resolve_world_transforms<'a, K:Clone + Send + Sync + Hash + Eq + ToBytes + FromBytes + 'a, I:Iterator<Item = (K, &'a Hierarchy<K>)>, F:FnMut(&K) -> Option<WorldTransform>>(nodes:I, mut root_transform:F) -> Vec<(K, WorldTransform)> {
+    let links:HashMap<K, &Hierarchy<K>> = nodes.collect();
+    let mut resolved:HashMap<K, WorldTransform> = HashMap::with_capacity(links.len());
+    let mut unresolvable:HashSet<K> = HashSet::new();
+    let mut order = Vec::with_capacity(links.len());
+
+    for start in links.keys() {
+        if resolved.contains_key(start) || unresolvable.contains(start) {
+            continue;
+        }
+        // walk up until reaching something whose transform is known
+        let mut chain = Vec::new();
+        let mut in_chain = HashSet::new();
+        let mut current = start.clone();
+        let base = loop {
+            if let Some(transform) = resolved.get(&current) {
+                break Some(transform.clone());
+            }
+            if unresolvable.contains(&current) || in_chain.contains(&current) {
+                break None;
+            }
+            match links.get(&current).and_then(|node| node.parent.clone()) {
+                Some(parent) => {
+                    in_chain.insert(current.clone());
+                    chain.push(current);
+                    current = parent;
+                },
+                None => break root_transform(&current)
+            }
+        };
+        match base {
+            Some(mut parent_transform) => {
+                for child in chain.into_iter().rev() {
+                    parent_transform = links[&child].local.to_world(&parent_transform);
+                    resolved.insert(child.clone(), parent_transform.clone());
+                    order.push((child, parent_transform.clone()));
+                }
+            },
+            None => unresolvable.extend(chain)
+        }
+    }
+    order
+}
+
+pub struct OrphanedChildren<K> {
+    pub detached:Vec<K>,
+    pub despawned:Vec<K>,
+}
+
+/// Applies the `OrphanPolicy` of the children (and grandchildren if they are despawned) of `removed`
+///
+/// Nothing is changed here, `#[derive(Entity)]` applies the result itself for `#[hierarchy]` components
+pub fn orphans_of<'a, K:Clone + Send + Sync + Hash + Eq + ToBytes + FromBytes + 'a, I:Iterator<Item = (K, &'a Hierarchy<K>)>>(removed:K, nodes:I) -> OrphanedChildren<K> {
+    let mut children_of:HashMap<K, Vec<(K, OrphanPolicy)>> = HashMap::new();
+    for (key, node) in nodes {
+        if let Some(parent) = &node.parent {
+            children_of.entry(parent.clone()).or_insert_with(Vec::new).push((key, node.on_parent_removed));
+        }
+    }
+    let mut orphans = OrphanedChildren { detached: Vec::new(), despawned: Vec::new() };
+    let mut seen = HashSet::new();
+    seen.insert(removed.clone());
+    let mut to_remove = VecDeque::new();
+    to_remove.push_back(removed);
+    while let Some(parent) = to_remove.pop_front() {
+        for (child, policy) in children_of.remove(&parent).unwrap_or_default() {
+            if !seen.insert(child.clone()) {
+                continue;
+            }
+            match policy {
+                OrphanPolicy::Detach => orphans.detached.push(child),
+                OrphanPolicy::Despawn => {
+                    orphans.despawned.push(child.clone());
+                    to_remove.push_back(child);
+                }
+            }
+        }
+    }
+    orphans
+}
@@ -0,0 +1,69 @@
+use std::{collections::{hash_map::DefaultHasher, HashMap}, hash::{Hash, Hasher}, mem::discriminant, ops::Add};
+
+/// Component events that carry a value merge policies can combine
+pub trait MergeableEvent:Sized {
+    type Value;
+    fn value(&self) -> &Self::Value;
+    fn with_value(self, value:Self::Value) -> Self;
+}
+
+/// `#[merge = "last_writer_wins"]`, only the event applied last in the tick is kept
+pub fn last_writer_wins<E>(_first:E, second:E) -> E {
+    second
+}
+
+/// `#[merge = "sum"]`, the values of all events of the tick are added together
+pub fn sum_merge<E:MergeableEvent>(first:E, second:E) -> E where E::Value:Add<Output = E::Value> + Clone {
+    let total = first.value().clone() + second.value().clone();
+    first.with_value(total)
+}
+
+/// `#[merge = "min"]`, the event with the smallest value is kept, the first one on ties
+pub fn min_merge<E:MergeableEvent>(first:E, second:E) -> E where E::Value:PartialOrd {
+    if second.value() < first.value() {
+        second
+    }
+    else {
+        first
+    }
+}
+
+/// `#[merge = "max"]`, the event with the biggest value is kept, the first one on ties
+pub fn max_merge<E:MergeableEvent>(first:E, second:E) -> E where E::Value:PartialOrd {
+    if second.value() > first.value() {
+        second
+    }
+    else {
+        first
+    }
+}
+
+/// Kind of a value for `ComponentEvent::merge_kind`, the same for all the values of a struct and one per variant of an enum
+pub fn variant_kind<T>(value:&T) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    discriminant(value).hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Reduces all the events of a tick with the same key into one, in the order they would have been applied
+///
+/// `#[merge]` keys events on their target and `ComponentEvent::merge_kind`, so that a reducer never sees two different variants of an update.
+/// Merged events are returned in the order their keys were first seen
+pub fn merge_events<E, K:Hash + Eq, T:Fn(&E) -> K, R:FnMut(E, E) -> E>(events:Vec<E>, key:T, mut reduce:R) -> Vec<E> {
+    let mut merged:Vec<Option<E>> = Vec::with_capacity(events.len());
+    let mut indices:HashMap<K, usize> = HashMap::with_capacity(events.len());
+    for event in events {
+        let event_key = key(&event);
+        match indices.get(&event_key) {
+            Some(index) => {
+                let previous = merged[*index].take().unwrap();
+                merged[*index] = Some(reduce(previous, event));
+            },
+            None => {
+                indices.insert(event_key, merged.len());
+                merged.push(Some(event));
+            }
+        }
+    }
+    merged.into_iter().flatten().collect()
+}
@@ -5,4 +5,17 @@ pub mod rendering_info;
 pub mod status;
 pub mod engine;
 pub mod multiplayer;
-pub mod static_type_id;
\ No newline at end of file
+pub mod static_type_id;
+pub mod prefab;
+pub mod hierarchy;
+pub mod change_tracking;
+pub mod snapshot;
+pub mod reflection;
+pub mod deferred_events;
+pub mod event_order;
+pub mod merge;
+pub mod time_control;
+pub mod rng;
+pub mod headless;
+pub mod physics;
+pub mod character;
\ No newline at end of file
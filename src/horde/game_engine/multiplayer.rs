@@ -864,4 +864,13 @@ impl MustSync {
             _ => false
         }
     }
+    /// Syncs wherever either of the two would, used when merging events
+    pub fn union(&self, other:&MustSync) -> MustSync {
+        match (self.is_server() || other.is_server(), self.is_client() || other.is_client()) {
+            (true, true) => MustSync::Both,
+            (true, false) => MustSync::Server,
+            (false, true) => MustSync::Client,
+            (false, false) => MustSync::No
+        }
+    }
 }
\ No newline at end of file
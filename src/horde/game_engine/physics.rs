@@ -0,0 +1,425 @@
+use to_from_bytes_derive::{FromBytes, ToBytes};
+
+use crate::horde::geometry::{collision::{broad_phase::SweepAndPrune, narrow_phase::Collider, shapes::{Aabb, Capsule, Obb}, swept::SweepHit, Contact}, rotation::{Orientation, Quaternion, Rotation}, shapes_3d::Sphere, vec3d::Vec3Df};
+
+use super::{entity::{AliveMask, Component, EntityID, SimpleComponentEvent, SimpleComponentUpdate, StaticComponent}, multiplayer::Identify, position::EntityPosition};
+
+/// Masses under this are clamped to it, a zero mass would give an infinite inverse mass
+pub const MIN_MASS:f32 = 0.001;
+
+/// Shape of a rigid body around its position, capsules are along the local Z axis
+#[derive(Clone, Debug, PartialEq, ToBytes, FromBytes)]
+pub enum BodyShape {
+    Sphere{radius:f32},
+    Box{half_extents:Vec3Df},
+    Capsule{half_height:f32, radius:f32},
+}
+
+impl BodyShape {
+    pub fn to_collider(&self, pos:Vec3Df, orient:Orientation) -> Collider {
+        match self {
+            BodyShape::Sphere { radius } => Collider::Sphere(Sphere::new(pos, *radius)),
+            BodyShape::Box { half_extents } => Collider::Obb(Obb::new(pos, *half_extents, &Rotation::from_orientation(orient))),
+            BodyShape::Capsule { half_height, radius } => {
+                let axis = Rotation::from_orientation(orient).rotate(Vec3Df::new(0.0, 0.0, *half_height));
+                Collider::Capsule(Capsule::new(pos - axis, pos + axis, *radius))
+            }
+        }
+    }
+    /// Radius of a sphere around the position containing the whole shape
+    pub fn bounding_radius(&self) -> f32 {
+        match self {
+            BodyShape::Sphere { radius } => *radius,
+            BodyShape::Box { half_extents } => half_extents.norme(),
+            BodyShape::Capsule { half_height, radius } => half_height + radius,
+        }
+    }
+    /// Average of the diagonal of the inertia tensor for a mass of 1
+    fn unit_inertia(&self) -> f32 {
+        match self {
+            BodyShape::Sphere { radius } => 0.4 * radius * radius,
+            BodyShape::Box { half_extents } => 4.0 * half_extents.norme_square() / 18.0,
+            BodyShape::Capsule { half_height, radius } => {
+                let length = 2.0 * (half_height + radius);
+                (radius * radius / 2.0 + (3.0 * radius * radius + length * length) / 6.0) / 3.0
+            }
+        }
+    }
+}
+
+/// Rigid body component, the position and orientation of the body are the ones of the `EntityPosition` component of its entity
+///
+/// Inertia is approximated by a single value instead of a full tensor
+#[derive(Clone, Debug, PartialEq, ToBytes, FromBytes)]
+pub struct RigidBody {
+    pub shape:BodyShape,
+    /// 0.0 for static bodies, which never move but still block others
+    pub inverse_mass:f32,
+    pub inverse_inertia:f32,
+    pub velocity:Vec3Df,
+    pub angular_velocity:Vec3Df,
+    /// Bounciness, the highest of the two bodies is used
+    pub restitution:f32,
+    /// Coulomb friction coefficient, the geometric mean of the two bodies is used
+    pub friction:f32,
+    pub linear_damping:f32,
+    pub angular_damping:f32,
+    pub gravity_scale:f32,
+    /// Disabled bodies neither move nor collide, for example once their entity is despawned
+    pub enabled:bool,
+    pub sleeping:bool,
+    /// Ticks spent under the sleep speeds
+    pub still_ticks:u32,
+}
+
+impl RigidBody {
+    /// `mass` is clamped to `MIN_MASS`, use `new_static` for bodies that never move
+    pub fn new_dynamic(shape:BodyShape, mass:f32) -> Self {
+        let inverse_mass = 1.0 / mass.max(MIN_MASS);
+        let inverse_inertia = inverse_mass / shape.unit_inertia().max(f32::EPSILON);
+        Self { shape, inverse_mass, inverse_inertia, velocity: Vec3Df::zero(), angular_velocity: Vec3Df::zero(), restitution: 0.2, friction: 0.5, linear_damping: 0.01, angular_damping: 0.05, gravity_scale: 1.0, enabled: true, sleeping: false, still_ticks: 0 }
+    }
+    pub fn new_static(shape:BodyShape) -> Self {
+        Self { inverse_mass: 0.0, inverse_inertia: 0.0, ..Self::new_dynamic(shape, 1.0) }
+    }
+    pub fn with_restitution(mut self, restitution:f32) -> Self {
+        self.restitution = restitution;
+        self
+    }
+    pub fn with_friction(mut self, friction:f32) -> Self {
+        self.friction = friction;
+        self
+    }
+    pub fn with_damping(mut self, linear_damping:f32, angular_damping:f32) -> Self {
+        self.linear_damping = linear_damping;
+        self.angular_damping = angular_damping;
+        self
+    }
+    pub fn with_gravity_scale(mut self, gravity_scale:f32) -> Self {
+        self.gravity_scale = gravity_scale;
+        self
+    }
+    pub fn with_velocity(mut self, velocity:Vec3Df) -> Self {
+        self.velocity = velocity;
+        self
+    }
+    pub fn is_dynamic(&self) -> bool {
+        self.inverse_mass > 0.0
+    }
+    fn wake(&mut self) {
+        self.sleeping = false;
+        self.still_ticks = 0;
+    }
+}
+
+impl StaticComponent for RigidBody {
+
+}
+
+impl<ID:Identify> Component<ID> for RigidBody {
+    type SC = Self;
+    type CE = SimpleComponentEvent<ID, RigidBodyUpdate>;
+    fn from_static(static_comp:&Self::SC) -> Self {
+        static_comp.clone()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, ToBytes, FromBytes)]
+pub enum RigidBodyUpdate {
+    /// Sent by `step_rigid_body`, velocities are changes so impulses sent by the game in the same tick aren't lost
+    Step{velocity_change:Vec3Df, angular_velocity_change:Vec3Df, sleeping:bool, still_ticks:u32},
+    SetVelocity(Vec3Df),
+    SetAngularVelocity(Vec3Df),
+    /// Instant change of momentum at the center of mass
+    ApplyImpulse(Vec3Df),
+    ApplyAngularImpulse(Vec3Df),
+    SetEnabled(bool),
+    Wake,
+    Replace(RigidBody),
+}
+
+impl<ID:Identify> SimpleComponentUpdate<RigidBody, ID> for RigidBodyUpdate {
+    fn apply_to_comp(self, component:&mut RigidBody) {
+        match self {
+            RigidBodyUpdate::Step { velocity_change, angular_velocity_change, sleeping, still_ticks } => {
+                component.velocity += velocity_change;
+                component.angular_velocity += angular_velocity_change;
+                component.sleeping = sleeping;
+                component.still_ticks = still_ticks;
+            },
+            RigidBodyUpdate::SetVelocity(velocity) => {
+                component.velocity = velocity;
+                component.wake();
+            },
+            RigidBodyUpdate::SetAngularVelocity(angular_velocity) => {
+                component.angular_velocity = angular_velocity;
+                component.wake();
+            },
+            RigidBodyUpdate::ApplyImpulse(impulse) => {
+                component.velocity += impulse * component.inverse_mass;
+                component.wake();
+            },
+            RigidBodyUpdate::ApplyAngularImpulse(impulse) => {
+                component.angular_velocity += impulse * component.inverse_inertia;
+                component.wake();
+            },
+            RigidBodyUpdate::SetEnabled(enabled) => {
+                component.enabled = enabled;
+                component.wake();
+            },
+            RigidBodyUpdate::Wake => component.wake(),
+            RigidBodyUpdate::Replace(body) => *component = body,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PhysicsSettings {
+    pub gravity:Vec3Df,
+    /// Duration of one tick in seconds
+    pub tick_duration:f32,
+    pub sleep_speed:f32,
+    pub sleep_angular_speed:f32,
+    /// Ticks a body must stay under both sleep speeds before sleeping
+    pub ticks_to_sleep:u32,
+    /// Bodies hitting each other slower than this don't bounce, which keeps resting contacts still
+    pub restitution_threshold:f32,
+    /// Penetration left uncorrected, so resting contacts stay touching
+    pub penetration_slop:f32,
+    /// Fraction of the remaining penetration removed each tick
+    pub position_correction:f32,
+}
+
+impl PhysicsSettings {
+    /// Earth gravity along -Z
+    pub fn new(tick_duration:f32) -> Self {
+        Self { gravity: Vec3Df::new(0.0, 0.0, -9.81), tick_duration, sleep_speed: 0.05, sleep_angular_speed: 0.05, ticks_to_sleep: 30, restitution_threshold: 0.5, penetration_slop: 0.005, position_correction: 0.4 }
+    }
+}
+
+struct StaticCollider {
+    collider:Collider,
+    aabb:Aabb,
+    restitution:f32,
+    friction:f32,
+}
+
+/// Level geometry that bodies collide with, like terrain meshes, it never moves
+pub struct PhysicsStatics {
+    colliders:Vec<StaticCollider>,
+}
+
+impl PhysicsStatics {
+    pub fn new() -> Self {
+        Self { colliders: Vec::new() }
+    }
+    pub fn add(&mut self, collider:Collider, restitution:f32, friction:f32) -> usize {
+        self.colliders.push(StaticCollider { aabb: collider.get_aabb(), collider, restitution, friction });
+        self.colliders.len() - 1
+    }
+    pub fn len(&self) -> usize {
+        self.colliders.len()
+    }
+    /// Earliest hit of `collider` moving by `motion` against the statics
+    pub fn sweep(&self, collider:&Collider, motion:&Vec3Df) -> Option<SweepHit> {
+        let bounds = collider.get_aabb().swept(motion);
+        self.colliders.iter()
+            .filter(|fixed| fixed.aabb.intersects(&bounds))
+            .filter_map(|fixed| collider.sweep(motion, &fixed.collider, &Vec3Df::zero()))
+            .min_by(|h1, h2| h1.time.total_cmp(&h2.time))
+    }
+    /// Deepest contact of `collider` with every static it overlaps
+    pub fn contacts(&self, collider:&Collider) -> Vec<Contact> {
+        let aabb = collider.get_aabb();
+        self.colliders.iter()
+            .filter(|fixed| fixed.aabb.intersects(&aabb))
+            .filter_map(|fixed| collider.collide(&fixed.collider))
+            .collect()
+    }
+}
+
+/// Sweep and prune over the bodies of one entity vec, giving each body the others whose box overlaps its own
+///
+/// `#[derive(Entity)]` keeps one of these up to date after events are applied when a component is marked `#[rigid_body]`
+pub struct PhysicsBroadPhase {
+    sweep:SweepAndPrune<EntityID>,
+    candidates:Vec<Vec<EntityID>>,
+}
+
+impl PhysicsBroadPhase {
+    pub fn new() -> Self {
+        Self { sweep: SweepAndPrune::new(), candidates: Vec::new() }
+    }
+    /// Moves the box of every enabled body of a living entity, removes the others, then recomputes the overlapping pairs
+    pub fn update<ID:Identify, P:EntityPosition<ID>>(&mut self, bodies:&[RigidBody], positions:&[P], alive:&AliveMask) {
+        for (id, body) in bodies.iter().enumerate() {
+            if body.enabled && alive.is_alive(id) {
+                self.sweep.set(id, body.shape.to_collider(positions[id].get_pos(), positions[id].get_orientation()).get_aabb());
+            }
+            else {
+                self.sweep.remove(&id);
+            }
+        }
+        self.candidates.iter_mut().for_each(|candidates| candidates.clear());
+        self.candidates.resize(bodies.len(), Vec::new());
+        for (first, second) in self.sweep.get_pairs() {
+            self.candidates[first].push(second);
+            self.candidates[second].push(first);
+        }
+        // Pairs come out in sweep order, sorting keeps the contacts of a body in the same order on every peer
+        self.candidates.iter_mut().for_each(|candidates| candidates.sort_unstable());
+    }
+    /// Bodies whose box overlapped the one of `id` at the last update
+    pub fn candidates(&self, id:EntityID) -> &[EntityID] {
+        self.candidates.get(id).map(|candidates| candidates.as_slice()).unwrap_or(&[])
+    }
+}
+
+/// What `step_rigid_body` computed for one body, turn it into events with `body_event` and `move_event`
+#[derive(Clone, Debug, PartialEq)]
+pub struct BodyStep {
+    pub update:RigidBodyUpdate,
+    pub pos:Vec3Df,
+    pub orient:Orientation,
+}
+
+impl BodyStep {
+    pub fn body_event<ID:Identify>(&self, id:EntityID, source:Option<ID>) -> SimpleComponentEvent<ID, RigidBodyUpdate> {
+        SimpleComponentEvent::new(id, source, self.update.clone())
+    }
+    /// None if the position component doesn't implement `EntityPosition::move_event`
+    pub fn move_event<ID:Identify, P:EntityPosition<ID>>(&self, id:EntityID, source:Option<ID>) -> Option<P::CE> {
+        P::move_event(id, source, self.pos, self.orient)
+    }
+}
+
+/// One contact of the body being stepped, with what it needs from the other side
+struct Touch {
+    contact:Contact,
+    other_pos:Vec3Df,
+    other_velocity:Vec3Df,
+    other_angular_velocity:Vec3Df,
+    other_inverse_mass:f32,
+    other_inverse_inertia:f32,
+    restitution:f32,
+    friction:f32,
+}
+
+fn predicted_velocity(body:&RigidBody, settings:&PhysicsSettings) -> (Vec3Df, Vec3Df) {
+    if body.sleeping || !body.is_dynamic() {
+        return (Vec3Df::zero(), Vec3Df::zero())
+    }
+    let dt = settings.tick_duration;
+    let velocity = (body.velocity + settings.gravity * (body.gravity_scale * dt)) * (1.0 / (1.0 + dt * body.linear_damping));
+    let angular_velocity = body.angular_velocity * (1.0 / (1.0 + dt * body.angular_damping));
+    (velocity, angular_velocity)
+}
+
+/// Semi-implicit Euler step of body `id`, with impulse based contact response against the `candidates` bodies of `bodies` and `statics`
+///
+/// Called for every entity by the `physics_stage` that `#[derive(Entity)]` generates for `#[rigid_body]` components, which sends the events of the returned step through the tunnels of the entity vec,
+/// so physics goes through ordinary component events and works with multiplayer sync and deterministic events
+///
+/// Every body is computed from the state of the previous tick only, so bodies can be stepped in any order, on any thread, with the same result.
+/// `candidates` are the bodies that may touch this one, usually from `PhysicsBroadPhase::candidates`
+///
+/// None when the body doesn't change : disabled, static, or sleeping with nothing awake touching it
+pub fn step_rigid_body<ID:Identify, P:EntityPosition<ID>>(id:EntityID, bodies:&[RigidBody], positions:&[P], candidates:&[EntityID], statics:&PhysicsStatics, settings:&PhysicsSettings) -> Option<BodyStep> {
+    let body = &bodies[id];
+    if !body.enabled || !body.is_dynamic() {
+        return None
+    }
+    let pos = positions[id].get_pos();
+    let orient = positions[id].get_orientation();
+    let collider = body.shape.to_collider(pos, orient);
+
+    let mut touches = Vec::new();
+    for other_id in candidates.iter().copied() {
+        let other = &bodies[other_id];
+        if other_id == id || !other.enabled || (body.sleeping && (other.sleeping || !other.is_dynamic())) {
+            continue
+        }
+        let other_pos = positions[other_id].get_pos();
+        if let Some(contact) = collider.collide(&other.shape.to_collider(other_pos, positions[other_id].get_orientation())) {
+            let (other_velocity, other_angular_velocity) = predicted_velocity(other, settings);
+            let (other_inverse_mass, other_inverse_inertia) = if other.sleeping {(0.0, 0.0)} else {(other.inverse_mass, other.inverse_inertia)};
+            touches.push(Touch { contact, other_pos, other_velocity, other_angular_velocity, other_inverse_mass, other_inverse_inertia, restitution: body.restitution.max(other.restitution), friction: (body.friction * other.friction).sqrt() });
+        }
+    }
+    if body.sleeping {
+        if touches.is_empty() {
+            return None
+        }
+    }
+    let aabb = collider.get_aabb();
+    for fixed in statics.colliders.iter() {
+        if !fixed.aabb.intersects(&aabb) {
+            continue
+        }
+        if let Some(contact) = collider.collide(&fixed.collider) {
+            touches.push(Touch { contact, other_pos: contact.point, other_velocity: Vec3Df::zero(), other_angular_velocity: Vec3Df::zero(), other_inverse_mass: 0.0, other_inverse_inertia: 0.0, restitution: body.restitution.max(fixed.restitution), friction: (body.friction * fixed.friction).sqrt() });
+        }
+    }
+
+    let mut woken = body.clone();
+    woken.wake();
+    let (velocity, angular_velocity) = predicted_velocity(&woken, settings);
+    let mut velocity_change = Vec3Df::zero();
+    let mut angular_velocity_change = Vec3Df::zero();
+    let mut correction = Vec3Df::zero();
+    for touch in touches.iter() {
+        let normal = touch.contact.normal;
+        let r_a = touch.contact.point - pos;
+        let r_b = touch.contact.point - touch.other_pos;
+        let relative = (touch.other_velocity + touch.other_angular_velocity.cross(&r_b)) - (velocity + angular_velocity.cross(&r_a));
+        let normal_speed = relative.dot(&normal);
+        let masses = body.inverse_mass + touch.other_inverse_mass;
+        if masses > 0.0 {
+            correction -= normal * ((touch.contact.depth - settings.penetration_slop).max(0.0) * settings.position_correction * body.inverse_mass / masses);
+        }
+        if normal_speed >= 0.0 {
+            continue
+        }
+        let restitution = if -normal_speed < settings.restitution_threshold {0.0} else {touch.restitution};
+        let k_normal = masses + body.inverse_inertia * r_a.cross(&normal).norme_square() + touch.other_inverse_inertia * r_b.cross(&normal).norme_square();
+        let normal_impulse = -(1.0 + restitution) * normal_speed / k_normal;
+        let mut impulse = normal * normal_impulse;
+        let tangent = relative - normal * normal_speed;
+        let tangent_speed = tangent.norme();
+        if tangent_speed > f32::EPSILON {
+            let tangent = tangent / tangent_speed;
+            let k_tangent = masses + body.inverse_inertia * r_a.cross(&tangent).norme_square() + touch.other_inverse_inertia * r_b.cross(&tangent).norme_square();
+            let max_friction = touch.friction * normal_impulse;
+            impulse += tangent * (-tangent_speed / k_tangent).clamp(-max_friction, max_friction);
+        }
+        // `impulse` is what the other body receives, this one gets the opposite
+        velocity_change -= impulse * body.inverse_mass;
+        angular_velocity_change -= r_a.cross(&impulse) * body.inverse_inertia;
+    }
+
+    let dt = settings.tick_duration;
+    let mut new_velocity = velocity + velocity_change;
+    let mut new_angular_velocity = angular_velocity + angular_velocity_change;
+    let new_pos = pos + correction + new_velocity * dt;
+    let angular_speed = new_angular_velocity.norme();
+    let new_orient = if angular_speed * dt > f32::EPSILON {
+        let turn = Quaternion::from_axis_angle(new_angular_velocity / angular_speed, angular_speed * dt);
+        turn.compose(&Quaternion::new_from_euler(orient.yaw, orient.pitch, orient.roll)).to_orientation()
+    }
+    else {
+        orient
+    };
+
+    let still = new_velocity.norme() < settings.sleep_speed && angular_speed < settings.sleep_angular_speed;
+    let still_ticks = if still {body.still_ticks + 1} else {0};
+    let sleeping = still_ticks >= settings.ticks_to_sleep;
+    if sleeping {
+        new_velocity = Vec3Df::zero();
+        new_angular_velocity = Vec3Df::zero();
+    }
+    Some(BodyStep {
+        update: RigidBodyUpdate::Step { velocity_change: new_velocity - body.velocity, angular_velocity_change: new_angular_velocity - body.angular_velocity, sleeping, still_ticks },
+        pos: new_pos,
+        orient: new_orient,
+    })
+}
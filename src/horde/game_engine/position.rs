@@ -1,6 +1,6 @@
 use crate::horde::geometry::{rotation::{Orientation, Rotation}, vec3d::Vec3Df};
 
-use super::{entity::Component, multiplayer::Identify};
+use super::{entity::{Component, EntityID}, multiplayer::Identify};
 
 
 
@@ -8,4 +8,10 @@ pub trait EntityPosition<ID:Identify>:Component<ID> {
     fn get_pos(&self) -> Vec3Df;
     fn get_orientation(&self) -> Orientation;
     fn get_rotation(&self) -> Option<&Rotation>;
+    /// Event moving entity `id` to `pos` with `orient`, used by systems that move entities through events like physics
+    ///
+    /// None (the default) if the component can't be moved that way
+    fn move_event(_id:EntityID, _source:Option<ID>, _pos:Vec3Df, _orient:Orientation) -> Option<Self::CE> {
+        None
+    }
 }
\ No newline at end of file
@@ -0,0 +1,329 @@
+use std::{collections::HashMap, fs, path::PathBuf, sync::{Arc, RwLock}};
+
+use to_from_bytes::{save_type, type_from_file, FromBytes, ToBytes};
+use to_from_bytes_derive::{FromBytes, ToBytes};
+
+use crate::horde::geometry::{rotation::Orientation, vec3d::{Number, Vec3D}};
+
+use super::{entity::Entity, multiplayer::Identify};
+
+/// Implemented by `#[derive(Entity)]` on structs marked with `#[prefab]`
+///
+/// it converts the static type and the `used_in_new` components of an entity to and from the text prefab format, field by field
+pub trait PrefabEntity<ID:Identify>:Entity<ID> {
+    type Defaults:Clone + Send + Sync + ToBytes + FromBytes;
+    fn static_from_text(fields:&PrefabTextFields) -> Result<Self::SE, PrefabError>;
+    fn defaults_from_text(fields:&PrefabTextFields) -> Result<Self::Defaults, PrefabError>;
+    fn write_static_text(static_type:&Self::SE, text:&mut String);
+    fn write_defaults_text(defaults:&Self::Defaults, text:&mut String);
+}
+
+/// A prefab describes a kind of entity without any Rust code : its static type, the names of the meshes it uses and the default values of its `used_in_new` components
+///
+/// Prefabs can be stored in binary form using `to_from_bytes`, or in a human-editable text form that looks like this :
+/// ```text
+/// prefab = goblin
+/// mesh = goblin_body
+/// static.health = 100
+/// default.pos = 0.0 0.0 0.0
+/// ```
+/// where `static.` and `default.` lines name the fields of the entity struct
+#[derive(Clone, ToBytes, FromBytes)]
+pub struct EntityPrefab<SE:Clone + ToBytes + FromBytes, D:Clone + ToBytes + FromBytes> {
+    pub name:String,
+    pub meshes:Vec<String>,
+    pub static_type:SE,
+    pub defaults:D,
+}
+
+impl<SE:Clone + ToBytes + FromBytes, D:Clone + ToBytes + FromBytes> EntityPrefab<SE, D> {
+    pub fn new(name:String, meshes:Vec<String>, static_type:SE, defaults:D) -> Self {
+        Self { name, meshes, static_type, defaults }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum PrefabError {
+    CouldntRead(PathBuf),
+    CouldntDecode(PathBuf),
+    CouldntWrite(PathBuf),
+    BadLine{line:usize, text:String},
+    NoPrefabStarted{line:usize},
+    MissingField{prefab:String, field:String},
+    BadValue{prefab:String, field:String, value:String},
+    DuplicateName(String),
+}
+
+/// The raw text of one prefab, split into its fields but not decoded yet
+#[derive(Clone, Debug)]
+pub struct PrefabTextFields {
+    pub name:String,
+    pub meshes:Vec<String>,
+    pub static_fields:HashMap<String, String>,
+    pub default_fields:HashMap<String, String>,
+}
+
+impl PrefabTextFields {
+    pub fn new(name:String) -> Self {
+        Self { name, meshes: Vec::new(), static_fields: HashMap::new(), default_fields: HashMap::new() }
+    }
+    pub fn get_static<T:PrefabText>(&self, field:&str) -> Result<T, PrefabError> {
+        self.decode_field(&self.static_fields, field)
+    }
+    pub fn get_default<T:PrefabText>(&self, field:&str) -> Result<T, PrefabError> {
+        self.decode_field(&self.default_fields, field)
+    }
+    fn decode_field<T:PrefabText>(&self, fields:&HashMap<String, String>, field:&str) -> Result<T, PrefabError> {
+        match fields.get(field) {
+            Some(value) => T::read_text(value).ok_or_else(|| PrefabError::BadValue { prefab: self.name.clone(), field: field.to_string(), value: value.clone() }),
+            None => Err(PrefabError::MissingField { prefab: self.name.clone(), field: field.to_string() })
+        }
+    }
+}
+
+/// Splits a prefab text file into the fields of each prefab it contains, in order
+///
+/// Only the spaces around `=` and at the ends of lines are dropped, a `String` value that starts or ends with spaces writes them escaped
+pub fn parse_prefab_text(text:&str) -> Result<Vec<PrefabTextFields>, PrefabError> {
+    let mut prefabs:Vec<PrefabTextFields> = Vec::new();
+    for (i, raw_line) in text.lines().enumerate() {
+        let line = raw_line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (key, value) = match line.split_once('=') {
+            Some((key, value)) => (key.trim(), value.trim()),
+            None => return Err(PrefabError::BadLine { line: i + 1, text: raw_line.to_string() })
+        };
+        if key == "prefab" {
+            prefabs.push(PrefabTextFields::new(value.to_string()));
+            continue;
+        }
+        let current = match prefabs.last_mut() {
+            Some(current) => current,
+            None => return Err(PrefabError::NoPrefabStarted { line: i + 1 })
+        };
+        if key == "mesh" {
+            current.meshes.push(value.to_string());
+        }
+        else if let Some(field) = key.strip_prefix("static.") {
+            current.static_fields.insert(field.trim().to_string(), value.to_string());
+        }
+        else if let Some(field) = key.strip_prefix("default.") {
+            current.default_fields.insert(field.trim().to_string(), value.to_string());
+        }
+        else {
+            return Err(PrefabError::BadLine { line: i + 1, text: raw_line.to_string() })
+        }
+    }
+    Ok(prefabs)
+}
+
+pub fn prefabs_from_text<ID:Identify, E:PrefabEntity<ID>>(text:&str) -> Result<Vec<EntityPrefab<E::SE, E::Defaults>>, PrefabError> where E::SE:Clone + ToBytes + FromBytes {
+    let mut prefabs = Vec::new();
+    for fields in parse_prefab_text(text)? {
+        let static_type = E::static_from_text(&fields)?;
+        let defaults = E::defaults_from_text(&fields)?;
+        prefabs.push(EntityPrefab::new(fields.name, fields.meshes, static_type, defaults));
+    }
+    Ok(prefabs)
+}
+
+pub fn prefabs_to_text<ID:Identify, E:PrefabEntity<ID>>(prefabs:&[EntityPrefab<E::SE, E::Defaults>]) -> String where E::SE:Clone + ToBytes + FromBytes {
+    let mut text = String::new();
+    for prefab in prefabs {
+        text.push_str(&format!("prefab = {}\n", prefab.name));
+        for mesh in &prefab.meshes {
+            text.push_str(&format!("mesh = {}\n", mesh));
+        }
+        E::write_static_text(&prefab.static_type, &mut text);
+        E::write_defaults_text(&prefab.defaults, &mut text);
+        text.push('\n');
+    }
+    text
+}
+
+pub fn load_text_prefabs<ID:Identify, E:PrefabEntity<ID>>(path:PathBuf) -> Result<Vec<EntityPrefab<E::SE, E::Defaults>>, PrefabError> where E::SE:Clone + ToBytes + FromBytes {
+    match fs::read_to_string(&path) {
+        Ok(text) => prefabs_from_text::<ID, E>(&text),
+        Err(_) => Err(PrefabError::CouldntRead(path))
+    }
+}
+
+pub fn save_text_prefabs<ID:Identify, E:PrefabEntity<ID>>(path:PathBuf, prefabs:&[EntityPrefab<E::SE, E::Defaults>]) -> Result<(), PrefabError> where E::SE:Clone + ToBytes + FromBytes {
+    match fs::write(&path, prefabs_to_text::<ID, E>(prefabs)) {
+        Ok(()) => Ok(()),
+        Err(_) => Err(PrefabError::CouldntWrite(path))
+    }
+}
+
+pub fn load_binary_prefabs<SE:Clone + ToBytes + FromBytes, D:Clone + ToBytes + FromBytes>(path:PathBuf) -> Result<Vec<EntityPrefab<SE, D>>, PrefabError> {
+    if !path.exists() {
+        return Err(PrefabError::CouldntRead(path))
+    }
+    type_from_file(path.clone()).map_err(|_| PrefabError::CouldntDecode(path))
+}
+
+pub fn save_binary_prefabs<SE:Clone + ToBytes + FromBytes, D:Clone + ToBytes + FromBytes>(path:PathBuf, prefabs:Vec<EntityPrefab<SE, D>>) -> Result<(), PrefabError> {
+    if save_type(path.clone(), prefabs) {
+        Ok(())
+    }
+    else {
+        Err(PrefabError::CouldntWrite(path))
+    }
+}
+
+#[derive(Clone)]
+pub struct RegisteredPrefab<D:Clone> {
+    pub static_type:usize,
+    pub meshes:Vec<String>,
+    pub defaults:D,
+}
+
+/// Prefabs registered in an entity vec, by name
+///
+/// Registering a prefab adds its static type to the entity vec, the registry then remembers which static type ID it got
+#[derive(Clone)]
+pub struct PrefabRegistry<D:Clone> {
+    prefabs:Arc<RwLock<HashMap<String, RegisteredPrefab<D>>>>,
+}
+
+impl<D:Clone> PrefabRegistry<D> {
+    pub fn new() -> Self {
+        Self { prefabs: Arc::new(RwLock::new(HashMap::new())) }
+    }
+    /// Refuses to replace a prefab that was already registered under that name
+    pub fn register(&self, name:String, prefab:RegisteredPrefab<D>) -> Result<(), PrefabError> {
+        let mut prefabs = self.prefabs.write().unwrap();
+        if prefabs.contains_key(&name) {
+            return Err(PrefabError::DuplicateName(name))
+        }
+        prefabs.insert(name, prefab);
+        Ok(())
+    }
+    pub fn contains(&self, name:&str) -> bool {
+        self.prefabs.read().unwrap().contains_key(name)
+    }
+    pub fn get(&self, name:&str) -> Option<RegisteredPrefab<D>> {
+        self.prefabs.read().unwrap().get(name).cloned()
+    }
+    pub fn get_static_type_of(&self, name:&str) -> Option<usize> {
+        self.prefabs.read().unwrap().get(name).map(|prefab| prefab.static_type)
+    }
+    /// Names of the meshes of the prefab registered as this static type, the renderer resolves them, see `MeshesRead::named_instances`
+    pub fn get_meshes_of_static_type(&self, static_type:usize) -> Option<Vec<String>> {
+        let prefabs = self.prefabs.read().unwrap();
+        prefabs.values().find(|prefab| prefab.static_type == static_type).map(|prefab| prefab.meshes.clone())
+    }
+    pub fn names(&self) -> Vec<String> {
+        self.prefabs.read().unwrap().keys().cloned().collect()
+    }
+}
+
+/// A value that can be written in and read from a prefab text file, in a single line
+pub trait PrefabText:Sized {
+    fn write_text(&self, text:&mut String);
+    fn read_text(text:&str) -> Option<Self>;
+}
+
+macro_rules! prefab_text_from_str {
+    ($($t:ty),*) => {
+        $(
+            impl PrefabText for $t {
+                fn write_text(&self, text:&mut String) {
+                    text.push_str(&self.to_string());
+                }
+                fn read_text(text:&str) -> Option<Self> {
+                    text.trim().parse().ok()
+                }
+            }
+        )*
+    };
+}
+
+prefab_text_from_str!(f32, f64, u8, u16, u32, u64, usize, i8, i16, i32, i64, isize, bool);
+
+/// Backslashes, tabs and line breaks are escaped so the value stays on its line, and so are the spaces at its ends so they survive the trimming of the line
+impl PrefabText for String {
+    fn write_text(&self, text:&mut String) {
+        let start = self.len() - self.trim_start_matches(' ').len();
+        let end = self.trim_end_matches(' ').len();
+        for (i, c) in self.char_indices() {
+            match c {
+                '\\' => text.push_str("\\\\"),
+                '\n' => text.push_str("\\n"),
+                '\r' => text.push_str("\\r"),
+                '\t' => text.push_str("\\t"),
+                ' ' if i < start || i >= end => text.push_str("\\s"),
+                c => text.push(c)
+            }
+        }
+    }
+    fn read_text(text:&str) -> Option<Self> {
+        let mut value = String::with_capacity(text.len());
+        let mut chars = text.chars();
+        while let Some(c) = chars.next() {
+            if c == '\\' {
+                match chars.next()? {
+                    '\\' => value.push('\\'),
+                    'n' => value.push('\n'),
+                    'r' => value.push('\r'),
+                    't' => value.push('\t'),
+                    's' => value.push(' '),
+                    _ => return None
+                }
+            }
+            else {
+                value.push(c);
+            }
+        }
+        Some(value)
+    }
+}
+
+impl<T:PrefabText> PrefabText for Option<T> {
+    fn write_text(&self, text:&mut String) {
+        match self {
+            Some(value) => value.write_text(text),
+            None => text.push_str("none")
+        }
+    }
+    fn read_text(text:&str) -> Option<Self> {
+        if text.trim() == "none" {
+            Some(None)
+        }
+        else {
+            T::read_text(text).map(Some)
+        }
+    }
+}
+
+impl<N:Number + PrefabText> PrefabText for Vec3D<N> {
+    fn write_text(&self, text:&mut String) {
+        self.x.write_text(text);
+        text.push(' ');
+        self.y.write_text(text);
+        text.push(' ');
+        self.z.write_text(text);
+    }
+    fn read_text(text:&str) -> Option<Self> {
+        let mut coords = text.split_whitespace();
+        let x = N::read_text(coords.next()?)?;
+        let y = N::read_text(coords.next()?)?;
+        let z = N::read_text(coords.next()?)?;
+        match coords.next() {
+            Some(_) => None,
+            None => Some(Vec3D::new(x, y, z))
+        }
+    }
+}
+
+impl PrefabText for Orientation {
+    fn write_text(&self, text:&mut String) {
+        text.push_str(&format!("{} {} {}", self.yaw, self.pitch, self.roll));
+    }
+    fn read_text(text:&str) -> Option<Self> {
+        let angles = Vec3D::<f32>::read_text(text)?;
+        Some(Orientation::new(angles.x, angles.y, angles.z))
+    }
+}
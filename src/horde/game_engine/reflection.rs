@@ -0,0 +1,78 @@
+use crate::horde::geometry::vec3d::Vec3Df;
+
+use super::{entity::{Component, EntityID}, multiplayer::Identify, prefab::PrefabText};
+
+/// What `#[derive(Entity)]` knows about one component of an entity
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ComponentMetadata {
+    pub name:&'static str,
+    pub type_name:&'static str,
+    pub used_in_new:bool,
+    pub used_in_render:bool,
+    pub must_sync:bool,
+    pub position:bool,
+    pub static_id:bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EntityMetadata {
+    pub name:&'static str,
+    /// In field declaration order, which is also the order used by `component` indices elsewhere
+    pub components:&'static [ComponentMetadata],
+}
+
+impl EntityMetadata {
+    pub fn component_index(&self, name:&str) -> Option<usize> {
+        self.components.iter().position(|component| component.name == name)
+    }
+    pub fn component(&self, name:&str) -> Option<&'static ComponentMetadata> {
+        self.components.iter().find(|component| component.name == name)
+    }
+}
+
+/// Implemented for every `#[derive(Entity)]` struct
+pub trait EntityReflection {
+    const METADATA:EntityMetadata;
+}
+
+/// Needed on every component of entities marked with `#[reflect_debug]`, so that debug tools can replace its value through a component event
+///
+/// Values are read with `PrefabText`, in the same format as prefab files
+pub trait EditableComponent<ID:Identify>:Component<ID> + PrefabText {
+    /// Event replacing the component of entity `id` with `value`, None (the default) if it can't be edited
+    fn set_event(_id:EntityID, _value:Self) -> Option<Self::CE> {
+        None
+    }
+}
+
+/// Implemented on the `VecRead` of entities marked with `#[reflect_debug]`, every component of which must implement `Debug`
+pub trait EntityValuesText {
+    fn metadata(&self) -> &'static EntityMetadata;
+    /// Number of entity IDs, including despawned ones
+    fn entity_count(&self) -> usize;
+    fn is_alive(&self, id:EntityID) -> bool;
+    fn component_text(&self, id:EntityID, component:usize) -> Option<String>;
+    fn position_of(&self, id:EntityID) -> Option<Vec3Df>;
+    fn all_components_text(&self, id:EntityID) -> Vec<(&'static str, String)> {
+        let metadata = self.metadata();
+        let mut texts = Vec::with_capacity(metadata.components.len());
+        for (i, component) in metadata.components.iter().enumerate() {
+            match self.component_text(id, i) {
+                Some(text) => texts.push((component.name, text)),
+                None => ()
+            }
+        }
+        texts
+    }
+    fn first_alive(&self) -> Option<EntityID> {
+        (0..self.entity_count()).find(|id| self.is_alive(*id))
+    }
+    /// Closest living entity after `id`
+    fn next_alive(&self, id:EntityID) -> Option<EntityID> {
+        (id.saturating_add(1)..self.entity_count()).find(|id| self.is_alive(*id))
+    }
+    /// Closest living entity before `id`
+    fn previous_alive(&self, id:EntityID) -> Option<EntityID> {
+        (0..id.min(self.entity_count())).rev().find(|id| self.is_alive(*id))
+    }
+}
@@ -0,0 +1,94 @@
+use std::{cell::Cell, sync::{atomic::{AtomicBool, AtomicU64, Ordering}, Arc}};
+
+use fastrand::Rng;
+use to_from_bytes_derive::{FromBytes, ToBytes};
+
+use super::entity::EntityID;
+
+thread_local! {
+    static RNG_STAGE:Cell<usize> = Cell::new(0);
+}
+
+/// Called by engines with `#[do_rng]` at the start of every tick stage, so `EngineRng::entity_stream` knows which stage it is in
+pub fn set_rng_stage(stage:usize) {
+    RNG_STAGE.with(|current| current.set(stage));
+}
+
+/// Everything needed to reproduce the streams of an engine, saved in snapshots and replicated to clients
+#[derive(Clone, Copy, PartialEq, Debug, ToBytes, FromBytes)]
+pub struct RngState {
+    pub seed:u64,
+    pub tick:u64,
+}
+
+/// SplitMix64 finaliser, spreads close inputs far apart so neighbouring streams don't correlate
+fn mix(mut value:u64) -> u64 {
+    value = value.wrapping_add(0x9E3779B97F4A7C15);
+    value = (value ^ (value >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    value = (value ^ (value >> 27)).wrapping_mul(0x94D049BB133111EB);
+    value ^ (value >> 31)
+}
+
+/// Shared handle deriving random streams from a master seed, it can be cloned into extra data to be used from tick stages
+///
+/// Every stream only depends on the seed, the tick, the stage and what it is asked for, never on which thread asks or when, so ticks stay reproducible
+#[derive(Clone)]
+pub struct EngineRng {
+    seed:Arc<AtomicU64>,
+    tick:Arc<AtomicU64>,
+    changed:Arc<AtomicBool>,
+}
+
+impl EngineRng {
+    pub fn new(seed:u64) -> Self {
+        Self { seed: Arc::new(AtomicU64::new(seed)), tick: Arc::new(AtomicU64::new(0)), changed: Arc::new(AtomicBool::new(false)) }
+    }
+    pub fn get_seed(&self) -> u64 {
+        self.seed.load(Ordering::Relaxed)
+    }
+    /// Restarts every stream from tick 0, only call between ticks
+    pub fn set_seed(&self, seed:u64) {
+        self.seed.store(seed, Ordering::Relaxed);
+        self.tick.store(0, Ordering::Relaxed);
+        self.changed.store(true, Ordering::Relaxed);
+    }
+    pub fn current_tick(&self) -> u64 {
+        self.tick.load(Ordering::Relaxed)
+    }
+    /// Called once per tick by the engine's `BEGIN_TICK_TASK`
+    pub fn advance(&self) {
+        self.tick.fetch_add(1, Ordering::Relaxed);
+    }
+    /// `salt` separates unrelated uses of the same entity and stage, like entity types or systems
+    pub fn stream(&self, salt:u64, entity:EntityID, stage:usize) -> Rng {
+        let mut state = mix(self.get_seed());
+        for part in [self.current_tick(), stage as u64, salt, entity as u64] {
+            state = mix(state ^ part);
+        }
+        Rng::with_seed(state)
+    }
+    /// Stream of an entity in the stage currently running on this thread
+    pub fn entity_stream(&self, salt:u64, entity:EntityID) -> Rng {
+        self.stream(salt, entity, RNG_STAGE.with(|current| current.get()))
+    }
+    /// Stream shared by a whole tick, for sequential code outside of stages
+    pub fn tick_stream(&self, salt:u64) -> Rng {
+        self.stream(salt, EntityID::MAX, usize::MAX)
+    }
+    pub fn get_state(&self) -> RngState {
+        RngState { seed: self.get_seed(), tick: self.current_tick() }
+    }
+    pub fn load_state(&self, state:RngState) {
+        self.seed.store(state.seed, Ordering::Relaxed);
+        self.tick.store(state.tick, Ordering::Relaxed);
+    }
+    /// Returns the state to replicate if the seed changed since the last call
+    pub fn take_change(&self) -> Option<RngState> {
+        if self.changed.swap(false, Ordering::Relaxed) {
+            Some(self.get_state())
+        }
+        else {
+            None
+        }
+    }
+}
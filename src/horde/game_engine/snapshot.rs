@@ -0,0 +1,75 @@
+use std::{fs, path::PathBuf};
+
+use to_from_bytes::{ByteDecoder, FromBytes, ToBytes};
+use to_from_bytes_derive::{FromBytes, ToBytes};
+
+/// Bumped every time the layout of generated snapshots changes
+pub const SNAPSHOT_FORMAT_VERSION:u32 = 1;
+
+/// Written at the start of every snapshot file, it can be decoded without decoding the rest of the snapshot
+#[derive(Clone, Debug, PartialEq, ToBytes, FromBytes)]
+pub struct SnapshotHeader {
+    pub format_version:u32,
+    pub tick:usize,
+}
+
+impl SnapshotHeader {
+    pub fn new(tick:usize) -> Self {
+        Self { format_version: SNAPSHOT_FORMAT_VERSION, tick }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum SnapshotError {
+    CouldntWrite(PathBuf),
+    CouldntRead(PathBuf),
+    CouldntDecode(PathBuf),
+    WrongVersion{expected:u32, found:u32},
+}
+
+/// Implemented by the `#[extra_data]` of engines with `#[do_snapshots]`, to choose what part of it ends up in snapshots
+pub trait SnapshotExtraData {
+    type Saved:ToBytes + FromBytes;
+    fn save_snapshot_part(&self) -> Self::Saved;
+    fn load_snapshot_part(&mut self, saved:Self::Saved);
+}
+
+pub fn write_snapshot<B:ToBytes>(path:PathBuf, header:SnapshotHeader, body:B) -> Result<(), SnapshotError> {
+    let mut bytes = Vec::with_capacity(header.get_bytes_size() + body.get_bytes_size());
+    header.add_bytes(&mut bytes);
+    body.add_bytes(&mut bytes);
+    match fs::write(&path, bytes) {
+        Ok(()) => Ok(()),
+        Err(_) => Err(SnapshotError::CouldntWrite(path))
+    }
+}
+
+pub fn read_snapshot_header(path:PathBuf) -> Result<SnapshotHeader, SnapshotError> {
+    let file_bytes = match fs::read(&path) {
+        Ok(file_bytes) => file_bytes,
+        Err(_) => return Err(SnapshotError::CouldntRead(path))
+    };
+    match SnapshotHeader::get_decoder().decode_slice_borrow(&mut Vec::new(), &file_bytes) {
+        Some((header, _)) => Ok(header),
+        None => Err(SnapshotError::CouldntDecode(path))
+    }
+}
+
+/// Checks the format version before decoding the body of the snapshot
+pub fn read_snapshot<B:FromBytes>(path:PathBuf) -> Result<(SnapshotHeader, B), SnapshotError> {
+    let file_bytes = match fs::read(&path) {
+        Ok(file_bytes) => file_bytes,
+        Err(_) => return Err(SnapshotError::CouldntRead(path))
+    };
+    let (header, header_len):(SnapshotHeader, usize) = match SnapshotHeader::get_decoder().decode_slice_borrow(&mut Vec::new(), &file_bytes) {
+        Some(decoded) => decoded,
+        None => return Err(SnapshotError::CouldntDecode(path))
+    };
+    if header.format_version != SNAPSHOT_FORMAT_VERSION {
+        return Err(SnapshotError::WrongVersion { expected: SNAPSHOT_FORMAT_VERSION, found: header.format_version })
+    }
+    match B::get_decoder().decode_slice_borrow(&mut Vec::with_capacity(file_bytes.len() - header_len), &file_bytes[header_len..]) {
+        Some((body, _)) => Ok((header, body)),
+        None => Err(SnapshotError::CouldntDecode(path))
+    }
+}
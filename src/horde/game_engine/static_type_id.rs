@@ -1,3 +1,8 @@
 pub trait HasStaticTypeID {
     fn get_id(&self) -> usize;
-}
\ No newline at end of file
+}
+
+/// Needed on the `#[static_id]` component of `#[prefab]` entities, so that spawning from a prefab can point the new entity to the static type the prefab was registered as
+pub trait SetStaticTypeID:HasStaticTypeID {
+    fn set_id(&mut self, id:usize);
+}
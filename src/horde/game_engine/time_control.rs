@@ -0,0 +1,167 @@
+use std::sync::{atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering}, Arc};
+
+use to_from_bytes_derive::{FromBytes, ToBytes};
+
+use crate::horde::frontend::interact::{Button, ButtonReport};
+
+pub const MIN_TIME_SCALE:f32 = 0.25;
+pub const MAX_TIME_SCALE:f32 = 8.0;
+
+/// What gets replicated from the server to clients, `steps` is the number of single steps asked since the last replication
+#[derive(Clone, Copy, PartialEq, Debug, ToBytes, FromBytes)]
+pub struct TimeControlState {
+    pub paused:bool,
+    pub scale:f32,
+    pub steps:u32,
+}
+
+/// Shared handle controlling simulation time, it can be cloned into extra data or the UI
+///
+/// Engines with `#[do_time_control]` call `begin_tick` in their `BEGIN_TICK_TASK`, and skip their stages on paused ticks, and `FixedStepRunner::with_time_control` makes the tick rate follow the scale
+///
+/// Only handles with authority (the server, or any single player engine) can change anything, the others get the changes through `apply_state`
+#[derive(Clone)]
+pub struct TimeControl {
+    paused:Arc<AtomicBool>,
+    pending_steps:Arc<AtomicUsize>,
+    unreplicated_steps:Arc<AtomicUsize>,
+    scale:Arc<AtomicU32>,
+    running:Arc<AtomicBool>,
+    changed:Arc<AtomicBool>,
+    authority:bool,
+}
+
+impl TimeControl {
+    pub fn new(authority:bool) -> Self {
+        Self {
+            paused: Arc::new(AtomicBool::new(false)),
+            pending_steps: Arc::new(AtomicUsize::new(0)),
+            unreplicated_steps: Arc::new(AtomicUsize::new(0)),
+            scale: Arc::new(AtomicU32::new(1.0_f32.to_bits())),
+            running: Arc::new(AtomicBool::new(true)),
+            changed: Arc::new(AtomicBool::new(false)),
+            authority
+        }
+    }
+    pub fn has_authority(&self) -> bool {
+        self.authority
+    }
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::Relaxed)
+    }
+    pub fn get_scale(&self) -> f32 {
+        f32::from_bits(self.scale.load(Ordering::Relaxed))
+    }
+    pub fn pause(&self) -> Result<(), ()> {
+        self.set_paused(true)
+    }
+    pub fn resume(&self) -> Result<(), ()> {
+        self.set_paused(false)
+    }
+    pub fn toggle_pause(&self) -> Result<(), ()> {
+        self.set_paused(!self.is_paused())
+    }
+    fn set_paused(&self, paused:bool) -> Result<(), ()> {
+        if !self.authority {
+            return Err(())
+        }
+        self.paused.store(paused, Ordering::Relaxed);
+        if !paused {
+            self.pending_steps.store(0, Ordering::Relaxed);
+        }
+        self.changed.store(true, Ordering::Relaxed);
+        Ok(())
+    }
+    /// Pauses if needed, then lets exactly one more tick run
+    pub fn step(&self) -> Result<(), ()> {
+        if !self.authority {
+            return Err(())
+        }
+        self.paused.store(true, Ordering::Relaxed);
+        self.pending_steps.fetch_add(1, Ordering::Relaxed);
+        self.unreplicated_steps.fetch_add(1, Ordering::Relaxed);
+        self.changed.store(true, Ordering::Relaxed);
+        Ok(())
+    }
+    /// Clamped between `MIN_TIME_SCALE` and `MAX_TIME_SCALE`
+    pub fn set_scale(&self, scale:f32) -> Result<(), ()> {
+        if !self.authority {
+            return Err(())
+        }
+        self.scale.store(scale.clamp(MIN_TIME_SCALE, MAX_TIME_SCALE).to_bits(), Ordering::Relaxed);
+        self.changed.store(true, Ordering::Relaxed);
+        Ok(())
+    }
+    pub fn double_scale(&self) -> Result<(), ()> {
+        self.set_scale(self.get_scale() * 2.0)
+    }
+    pub fn halve_scale(&self) -> Result<(), ()> {
+        self.set_scale(self.get_scale() * 0.5)
+    }
+    /// Decides once at the start of a tick whether its tick stages run, consuming a pending step if paused
+    pub fn begin_tick(&self) -> bool {
+        let running = !self.is_paused() || self.pending_steps.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |steps| steps.checked_sub(1)).is_ok();
+        self.running.store(running, Ordering::Relaxed);
+        running
+    }
+    /// Whether the current tick was allowed to run by the last `begin_tick`
+    pub fn is_tick_running(&self) -> bool {
+        self.running.load(Ordering::Relaxed)
+    }
+    pub fn get_state(&self) -> TimeControlState {
+        TimeControlState { paused: self.is_paused(), scale: self.get_scale(), steps: self.pending_steps.load(Ordering::Relaxed) as u32 }
+    }
+    /// Returns the state to replicate if anything changed since the last call
+    pub fn take_change(&self) -> Option<TimeControlState> {
+        if self.changed.swap(false, Ordering::Relaxed) {
+            let steps = self.unreplicated_steps.swap(0, Ordering::Relaxed) as u32;
+            Some(TimeControlState { paused: self.is_paused(), scale: self.get_scale(), steps })
+        }
+        else {
+            None
+        }
+    }
+    /// Used by clients to follow the server, ignored by handles with authority
+    pub fn apply_state(&self, state:TimeControlState) {
+        if !self.authority {
+            self.paused.store(state.paused, Ordering::Relaxed);
+            self.scale.store(state.scale.clamp(MIN_TIME_SCALE, MAX_TIME_SCALE).to_bits(), Ordering::Relaxed);
+            if state.paused {
+                self.pending_steps.fetch_add(state.steps as usize, Ordering::Relaxed);
+            }
+            else {
+                self.pending_steps.store(0, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+/// Debug keybinding for a `TimeControl`, by default P pauses, N steps one tick and Plus/Minus double/halve the scale
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct TimeControlKeys {
+    pub toggle_pause:Button,
+    pub step:Button,
+    pub faster:Button,
+    pub slower:Button,
+}
+
+impl TimeControlKeys {
+    pub fn new_simple() -> Self {
+        Self { toggle_pause: Button::P, step: Button::N, faster: Button::Plus, slower: Button::Minus }
+    }
+    /// Call once per frame after the report was updated, presses on handles without authority are ignored
+    pub fn handle_buttons(&self, report:&ButtonReport, control:&TimeControl) {
+        if report.is_newly_pressed(self.toggle_pause) {
+            let _ = control.toggle_pause();
+        }
+        if report.is_newly_pressed(self.step) {
+            let _ = control.step();
+        }
+        if report.is_newly_pressed(self.faster) {
+            let _ = control.double_scale();
+        }
+        if report.is_newly_pressed(self.slower) {
+            let _ = control.halve_scale();
+        }
+    }
+}
@@ -4,7 +4,7 @@ use crossbeam::channel::{unbounded, Receiver, Sender};
 
 use crate::horde::{game_engine::multiplayer::MustSync, rendering::RenderingBackend};
 
-use super::multiplayer::Identify;
+use super::{event_order::{sort_events, SequencedEvent}, multiplayer::Identify};
 
 pub trait World<ID:Identify>: Sized + Sync + Send + Clone {
     type WE:WorldEvent<Self, ID>;
@@ -23,6 +23,7 @@ pub struct WorldHandler<W: World<ID>, ID:Identify> {
     pub world: Arc<RwLock<W>>,
     pub tunnels_in: WorldTunnelsIn<W, ID>,
     pub tunnels_out: WorldTunnelsOut<W, ID>,
+    deterministic:bool,
     marker:PhantomData<ID>,
 }
 
@@ -35,18 +36,36 @@ impl<W: World<ID>, ID:Identify> WorldHandler<W, ID> {
             world: map_lock,
             tunnels_in: tunnel_pair.0,
             tunnels_out: tunnel_pair.1,
+            deterministic:false,
             marker:PhantomData{},
         }
     }
+    /// When set, events are applied sorted by source, then by the turn and sequence number of the send, instead of in the order they arrived
+    pub fn set_deterministic(&mut self, deterministic:bool) {
+        self.deterministic = deterministic;
+    }
+    pub fn is_deterministic(&self) -> bool {
+        self.deterministic
+    }
+    fn receive_all_events(&self) -> Vec<W::WE> {
+        let mut events = Vec::new();
+        while let Ok(event) = self
+            .tunnels_in
+            .map_events
+            .recv_timeout(Duration::from_nanos(10))
+        {
+            events.push(event);
+        }
+        if self.deterministic {
+            sort_events(&mut events, |event| (0, event.get_source()));
+        }
+        events.into_iter().map(|event| event.event).collect()
+    }
     pub fn apply_all_events<'a>(&self, write_handler: &mut WorldWriteHandler<'a, W, ID>, multiplayer:Option<(RwLockWriteGuard<'a, Vec<W::WE>>, RwLockWriteGuard<'a, Vec<W::WE>>, bool)>) {
         match multiplayer {
             Some((mut writer,mut all_writer, is_server)) => {
                 if is_server {
-                    while let Ok(event) = self
-                        .tunnels_in
-                        .map_events
-                        .recv_timeout(Duration::from_nanos(10))
-                    {
+                    for event in self.receive_all_events() {
                         if event.should_sync().is_server() {
                             writer.push(event.clone());
                         }
@@ -55,11 +74,7 @@ impl<W: World<ID>, ID:Identify> WorldHandler<W, ID> {
                     }
                 }
                 else {
-                    while let Ok(event) = self
-                        .tunnels_in
-                        .map_events
-                        .recv_timeout(Duration::from_nanos(10))
-                    {
+                    for event in self.receive_all_events() {
                         if event.should_sync().is_client()  {
                             writer.push(event.clone());
                         }
@@ -69,11 +84,7 @@ impl<W: World<ID>, ID:Identify> WorldHandler<W, ID> {
                 
             }
             None => {
-                while let Ok(event) = self
-                    .tunnels_in
-                    .map_events
-                    .recv_timeout(Duration::from_nanos(10))
-                {
+                for event in self.receive_all_events() {
                     event.apply_event(&mut write_handler.world);
                 }
             }
@@ -90,7 +101,7 @@ impl<W: World<ID>, ID:Identify> WorldHandler<W, ID> {
 }
 #[derive(Clone)]
 pub struct WorldTunnelsIn<W: World<ID>, ID:Identify> {
-    map_events: Receiver<W::WE>,
+    map_events: Receiver<SequencedEvent<W::WE>>,
     stop: WorldStop,
     stop_tunnel: Receiver<bool>,
     marker:PhantomData<ID>,
@@ -135,7 +146,7 @@ impl<W:World<ID>, ID:Identify> WorldOutHandler<W, ID> {
 }
 #[derive(Clone)]
 pub struct WorldTunnelsOut<W: World<ID>, ID:Identify> {
-    map_events: Sender<W::WE>,
+    map_events: Sender<SequencedEvent<W::WE>>,
     stop_tunnel: Sender<bool>,
     thread_number:usize,
     number_of_threads:usize,
@@ -147,7 +158,7 @@ impl<W: World<ID>, ID:Identify> WorldTunnelsOut<W, ID> {
         self.stop_tunnel.send(true);
     }
     pub fn send_event(&self, event: W::WE) {
-        self.map_events.send(event);
+        self.map_events.send(SequencedEvent::new(event));
     }
     pub fn update_number_of_threads(&mut self, number_of_threads:usize, thread_number:usize) {
         self.number_of_threads = number_of_threads;
@@ -0,0 +1,121 @@
+use std::{collections::HashMap, hash::Hash};
+
+use crate::horde::geometry::vec3d::Vec3Df;
+
+use super::shapes::Aabb;
+
+fn axis_min(aabb:&Aabb, axis:usize) -> f32 {
+    aabb.min.coords_to_array()[axis]
+}
+
+fn axis_max(aabb:&Aabb, axis:usize) -> f32 {
+    aabb.max.coords_to_array()[axis]
+}
+
+/// The axis along which the centers are the most spread out, so the sweep skips the most pairs
+fn best_axis<'a, I:Iterator<Item = &'a Aabb>>(boxes:I) -> usize {
+    let mut count = 0.0;
+    let mut sum = Vec3Df::zero();
+    let mut sum_squared = Vec3Df::zero();
+    for aabb in boxes {
+        let center = aabb.get_center();
+        sum += center;
+        sum_squared += center.component_product(&center);
+        count += 1.0;
+    }
+    if count == 0.0 {
+        return 0
+    }
+    let variance = sum_squared / count - (sum / count).component_product(&(sum / count));
+    if variance.x >= variance.y && variance.x >= variance.z {
+        0
+    }
+    else if variance.y >= variance.z {
+        1
+    }
+    else {
+        2
+    }
+}
+
+/// Every pair of overlapping boxes, by index, in one sweep
+pub fn overlapping_pairs(boxes:&[Aabb]) -> Vec<(usize, usize)> {
+    let axis = best_axis(boxes.iter());
+    let mut order:Vec<usize> = (0..boxes.len()).collect();
+    order.sort_unstable_by(|a, b| axis_min(&boxes[*a], axis).total_cmp(&axis_min(&boxes[*b], axis)));
+    let mut pairs = Vec::new();
+    for (i, first) in order.iter().enumerate() {
+        let end = axis_max(&boxes[*first], axis);
+        for second in order[i + 1..].iter() {
+            if axis_min(&boxes[*second], axis) > end {
+                break
+            }
+            if boxes[*first].intersects(&boxes[*second]) {
+                pairs.push(((*first).min(*second), (*first).max(*second)));
+            }
+        }
+    }
+    pairs
+}
+
+/// Sweep and prune broad phase that keeps its sorted order between calls
+///
+/// Objects barely move from one tick to the next, so re-sorting the previous order is close to linear
+pub struct SweepAndPrune<ID:Clone + Eq + Hash> {
+    boxes:HashMap<ID, Aabb>,
+    order:Vec<ID>,
+}
+
+impl<ID:Clone + Eq + Hash> SweepAndPrune<ID> {
+    pub fn new() -> Self {
+        Self { boxes: HashMap::new(), order: Vec::new() }
+    }
+    pub fn len(&self) -> usize {
+        self.order.len()
+    }
+    /// Adds the object or updates its box
+    pub fn set(&mut self, id:ID, aabb:Aabb) {
+        if self.boxes.insert(id.clone(), aabb).is_none() {
+            self.order.push(id);
+        }
+    }
+    pub fn remove(&mut self, id:&ID) -> Option<Aabb> {
+        let aabb = self.boxes.remove(id)?;
+        self.order.retain(|other| other != id);
+        Some(aabb)
+    }
+    pub fn get(&self, id:&ID) -> Option<&Aabb> {
+        self.boxes.get(id)
+    }
+    /// Every pair of objects whose boxes overlap, to pass to the narrow phase
+    pub fn get_pairs(&mut self) -> Vec<(ID, ID)> {
+        let axis = best_axis(self.boxes.values());
+        // Insertion sort, fast on nearly sorted orders
+        for i in 1..self.order.len() {
+            let mut j = i;
+            while j > 0 && axis_min(&self.boxes[&self.order[j - 1]], axis) > axis_min(&self.boxes[&self.order[j]], axis) {
+                self.order.swap(j - 1, j);
+                j -= 1;
+            }
+        }
+        let mut pairs = Vec::new();
+        for (i, first) in self.order.iter().enumerate() {
+            let first_box = &self.boxes[first];
+            let end = axis_max(first_box, axis);
+            for second in self.order[i + 1..].iter() {
+                let second_box = &self.boxes[second];
+                if axis_min(second_box, axis) > end {
+                    break
+                }
+                if first_box.intersects(second_box) {
+                    pairs.push((first.clone(), second.clone()));
+                }
+            }
+        }
+        pairs
+    }
+    /// Objects whose box overlaps `aabb`
+    pub fn query(&self, aabb:&Aabb) -> Vec<ID> {
+        self.order.iter().filter(|id| self.boxes[*id].intersects(aabb)).cloned().collect()
+    }
+}
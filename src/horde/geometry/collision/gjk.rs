@@ -0,0 +1,295 @@
+use crate::horde::geometry::vec3d::Vec3Df;
+
+use super::ConvexShape;
+
+const MAX_ITERATIONS:usize = 64;
+const EPA_TOLERANCE:f32 = 0.0001;
+
+/// Point of the Minkowski difference `a - b`, along with the points of both shapes it comes from
+#[derive(Clone, Copy, Debug)]
+pub struct SupportPoint {
+    pub a:Vec3Df,
+    pub b:Vec3Df,
+    pub p:Vec3Df,
+}
+
+fn support<A:ConvexShape + ?Sized, B:ConvexShape + ?Sized>(a:&A, b:&B, direction:&Vec3Df) -> SupportPoint {
+    let on_a = a.support(direction);
+    let on_b = b.support(&-direction);
+    SupportPoint { a: on_a, b: on_b, p: on_a - on_b }
+}
+
+/// Result of GJK on the cores of two shapes, margins are not taken into account
+pub enum GjkResult {
+    Separated{distance:f32, closest_a:Vec3Df, closest_b:Vec3Df},
+    /// The last simplex, it contains the origin (or touches it)
+    Intersecting(Vec<SupportPoint>),
+}
+
+/// Returns the closest point of segment `a`-`b` to `p`, and how far along the segment it is
+pub fn closest_point_on_segment(p:&Vec3Df, a:&Vec3Df, b:&Vec3Df) -> (Vec3Df, f32) {
+    let ab = b - a;
+    let length_squared = ab.norme_square();
+    if length_squared <= f32::EPSILON {
+        return (*a, 0.0)
+    }
+    let t = ((p - a).dot(&ab) / length_squared).clamp(0.0, 1.0);
+    (*a + ab * t, t)
+}
+
+/// Returns the closest point of triangle `a`, `b`, `c` to `p`, and its barycentric coordinates
+pub fn closest_point_on_triangle(p:&Vec3Df, a:&Vec3Df, b:&Vec3Df, c:&Vec3Df) -> (Vec3Df, [f32 ; 3]) {
+    let ab = b - a;
+    let ac = c - a;
+    let ap = p - a;
+    let d1 = ab.dot(&ap);
+    let d2 = ac.dot(&ap);
+    if d1 <= 0.0 && d2 <= 0.0 {
+        return (*a, [1.0, 0.0, 0.0])
+    }
+    let bp = p - b;
+    let d3 = ab.dot(&bp);
+    let d4 = ac.dot(&bp);
+    if d3 >= 0.0 && d4 <= d3 {
+        return (*b, [0.0, 1.0, 0.0])
+    }
+    let vc = d1 * d4 - d3 * d2;
+    if vc <= 0.0 && d1 >= 0.0 && d3 <= 0.0 {
+        let v = d1 / (d1 - d3);
+        return (*a + ab * v, [1.0 - v, v, 0.0])
+    }
+    let cp = p - c;
+    let d5 = ab.dot(&cp);
+    let d6 = ac.dot(&cp);
+    if d6 >= 0.0 && d5 <= d6 {
+        return (*c, [0.0, 0.0, 1.0])
+    }
+    let vb = d5 * d2 - d1 * d6;
+    if vb <= 0.0 && d2 >= 0.0 && d6 <= 0.0 {
+        let w = d2 / (d2 - d6);
+        return (*a + ac * w, [1.0 - w, 0.0, w])
+    }
+    let va = d3 * d6 - d5 * d4;
+    if va <= 0.0 && (d4 - d3) >= 0.0 && (d5 - d6) >= 0.0 {
+        let w = (d4 - d3) / ((d4 - d3) + (d5 - d6));
+        return (*b + (c - b) * w, [0.0, 1.0 - w, w])
+    }
+    let denom = 1.0 / (va + vb + vc);
+    let v = vb * denom;
+    let w = vc * denom;
+    (*a + ab * v + ac * w, [1.0 - v - w, v, w])
+}
+
+/// Closest point of the simplex to the origin, with the vertices that are needed to express it and their weights
+///
+/// None if the simplex is a tetrahedron containing the origin
+fn closest_on_simplex(points:&[SupportPoint]) -> Option<(Vec3Df, Vec<(SupportPoint, f32)>)> {
+    let origin = Vec3Df::zero();
+    let weighted:Vec<(SupportPoint, f32)> = match points.len() {
+        1 => vec![(points[0], 1.0)],
+        2 => {
+            let (_, t) = closest_point_on_segment(&origin, &points[0].p, &points[1].p);
+            vec![(points[0], 1.0 - t), (points[1], t)]
+        },
+        3 => {
+            let (_, weights) = closest_point_on_triangle(&origin, &points[0].p, &points[1].p, &points[2].p);
+            vec![(points[0], weights[0]), (points[1], weights[1]), (points[2], weights[2])]
+        },
+        _ => {
+            let faces = [[0, 1, 2, 3], [0, 2, 3, 1], [0, 3, 1, 2], [1, 3, 2, 0]];
+            let mut best:Option<(f32, Vec<(SupportPoint, f32)>)> = None;
+            let mut outside_any = false;
+            for [i, j, k, opposite] in faces {
+                let normal = (points[j].p - points[i].p).cross(&(points[k].p - points[i].p));
+                let side_origin = normal.dot(&(origin - points[i].p));
+                let side_opposite = normal.dot(&(points[opposite].p - points[i].p));
+                if side_opposite.abs() > f32::EPSILON && side_origin * side_opposite >= 0.0 {
+                    continue
+                }
+                outside_any = true;
+                let (closest, weights) = closest_point_on_triangle(&origin, &points[i].p, &points[j].p, &points[k].p);
+                let distance = closest.norme_square();
+                if best.as_ref().is_none_or(|(best_distance, _)| distance < *best_distance) {
+                    best = Some((distance, vec![(points[i], weights[0]), (points[j], weights[1]), (points[k], weights[2])]));
+                }
+            }
+            if !outside_any {
+                return None
+            }
+            best.unwrap().1
+        }
+    };
+    let mut closest = Vec3Df::zero();
+    for (point, weight) in weighted.iter() {
+        closest += point.p * *weight;
+    }
+    Some((closest, weighted.into_iter().filter(|(_, weight)| *weight > 0.0).collect()))
+}
+
+fn weighted_points(simplex:&Vec<(SupportPoint, f32)>) -> (Vec3Df, Vec3Df) {
+    let mut on_a = Vec3Df::zero();
+    let mut on_b = Vec3Df::zero();
+    for (point, weight) in simplex.iter() {
+        on_a += point.a * *weight;
+        on_b += point.b * *weight;
+    }
+    (on_a, on_b)
+}
+
+/// Distance between the cores of two convex shapes, or the simplex proving they overlap
+pub fn gjk<A:ConvexShape + ?Sized, B:ConvexShape + ?Sized>(a:&A, b:&B) -> GjkResult {
+    let mut direction = a.center() - b.center();
+    if direction.norme_square() <= f32::EPSILON {
+        direction = Vec3Df::new(1.0, 0.0, 0.0);
+    }
+    let first = support(a, b, &direction);
+    let mut simplex = vec![(first, 1.0)];
+    let mut v = first.p;
+    for i in 0..MAX_ITERATIONS {
+        let v_squared = v.norme_square();
+        if v_squared <= 1e-10 {
+            return GjkResult::Intersecting(simplex.into_iter().map(|(point, _)| point).collect())
+        }
+        let w = support(a, b, &-v);
+        if v_squared - v.dot(&w.p) <= 1e-6 * v_squared || simplex.iter().any(|(point, _)| point.p.dist_squared(&w.p) <= 1e-12) {
+            break
+        }
+        let mut points:Vec<SupportPoint> = simplex.iter().map(|(point, _)| *point).collect();
+        points.push(w);
+        match closest_on_simplex(&points) {
+            None => return GjkResult::Intersecting(points),
+            Some((closest, reduced)) => {
+                if closest.norme_square() >= v_squared {
+                    break
+                }
+                v = closest;
+                simplex = reduced;
+            }
+        }
+    }
+    let (closest_a, closest_b) = weighted_points(&simplex);
+    GjkResult::Separated { distance: v.norme(), closest_a, closest_b }
+}
+
+/// How deep the cores of two shapes overlap, `normal` points from `a` to `b`
+pub struct Penetration {
+    pub normal:Vec3Df,
+    pub depth:f32,
+    pub point_a:Vec3Df,
+    pub point_b:Vec3Df,
+}
+
+struct EpaFace {
+    indices:[usize ; 3],
+    normal:Vec3Df,
+    distance:f32,
+}
+
+fn epa_face(points:&Vec<SupportPoint>, indices:[usize ; 3], inside:&Vec3Df) -> Option<EpaFace> {
+    let [i, j, k] = indices;
+    let normal = (points[j].p - points[i].p).cross(&(points[k].p - points[i].p));
+    let length = normal.norme();
+    if length <= f32::EPSILON {
+        return None
+    }
+    let normal = normal / length;
+    if normal.dot(&(inside - points[i].p)) > 0.0 {
+        Some(EpaFace { indices: [i, k, j], normal: -normal, distance: -normal.dot(&points[i].p) })
+    }
+    else {
+        Some(EpaFace { indices, normal, distance: normal.dot(&points[i].p) })
+    }
+}
+
+/// Grows a GJK simplex into a tetrahedron, fails if the Minkowski difference is flat
+fn blow_up<A:ConvexShape + ?Sized, B:ConvexShape + ?Sized>(a:&A, b:&B, points:&mut Vec<SupportPoint>) -> bool {
+    let axis = [Vec3Df::new(1.0, 0.0, 0.0), Vec3Df::new(0.0, 1.0, 0.0), Vec3Df::new(0.0, 0.0, 1.0)];
+    if points.len() == 1 {
+        for direction in axis.iter().flat_map(|axis| [*axis, -axis]) {
+            let w = support(a, b, &direction);
+            if w.p.dist_squared(&points[0].p) > 1e-10 {
+                points.push(w);
+                break
+            }
+        }
+    }
+    if points.len() == 2 {
+        let line = points[1].p - points[0].p;
+        'search : for axis in axis.iter() {
+            let side = line.cross(axis);
+            if side.norme_square() <= 1e-10 {
+                continue
+            }
+            for direction in [side, -side] {
+                let w = support(a, b, &direction);
+                if (w.p - points[0].p).cross(&line).norme_square() > 1e-10 {
+                    points.push(w);
+                    break 'search
+                }
+            }
+        }
+    }
+    if points.len() == 3 {
+        let normal = (points[1].p - points[0].p).cross(&(points[2].p - points[0].p));
+        for direction in [normal, -normal] {
+            let w = support(a, b, &direction);
+            if normal.dot(&(w.p - points[0].p)).abs() > 1e-8 {
+                points.push(w);
+                break
+            }
+        }
+    }
+    points.len() == 4
+}
+
+/// Expanding polytope algorithm, run on the simplex of an intersecting `gjk` to find the penetration of the cores
+///
+/// None if the overlap is degenerate (flat Minkowski difference, the shapes only touch)
+pub fn epa<A:ConvexShape + ?Sized, B:ConvexShape + ?Sized>(a:&A, b:&B, simplex:Vec<SupportPoint>) -> Option<Penetration> {
+    let mut points = simplex;
+    points.truncate(4);
+    if !blow_up(a, b, &mut points) {
+        return None
+    }
+    let inside = (points[0].p + points[1].p + points[2].p + points[3].p) / 4.0;
+    let mut faces = Vec::with_capacity(32);
+    for indices in [[0, 1, 2], [0, 3, 1], [0, 2, 3], [1, 3, 2]] {
+        faces.push(epa_face(&points, indices, &inside)?);
+    }
+    for i in 0..MAX_ITERATIONS {
+        let closest = faces.iter().enumerate().min_by(|(_, f1), (_, f2)| f1.distance.total_cmp(&f2.distance)).map(|(i, _)| i)?;
+        let normal = faces[closest].normal;
+        let w = support(a, b, &normal);
+        if w.p.dot(&normal) - faces[closest].distance <= EPA_TOLERANCE || i == MAX_ITERATIONS - 1 {
+            break
+        }
+        let new_index = points.len();
+        points.push(w);
+        let mut horizon:Vec<(usize, usize)> = Vec::new();
+        faces.retain(|face| {
+            if face.normal.dot(&(w.p - points[face.indices[0]].p)) > 0.0 {
+                for edge in [(face.indices[0], face.indices[1]), (face.indices[1], face.indices[2]), (face.indices[2], face.indices[0])] {
+                    match horizon.iter().position(|other| *other == (edge.1, edge.0)) {
+                        Some(position) => {horizon.swap_remove(position);},
+                        None => horizon.push(edge)
+                    }
+                }
+                false
+            }
+            else {
+                true
+            }
+        });
+        for (start, end) in horizon {
+            if let Some(face) = epa_face(&points, [start, end, new_index], &inside) {
+                faces.push(face);
+            }
+        }
+    }
+    let face = faces.iter().min_by(|f1, f2| f1.distance.total_cmp(&f2.distance))?;
+    let [i, j, k] = face.indices;
+    let (_, weights) = closest_point_on_triangle(&(face.normal * face.distance), &points[i].p, &points[j].p, &points[k].p);
+    let point_a = points[i].a * weights[0] + points[j].a * weights[1] + points[k].a * weights[2];
+    let point_b = points[i].b * weights[0] + points[j].b * weights[1] + points[k].b * weights[2];
+    Some(Penetration { normal: face.normal, depth: face.distance.max(0.0), point_a, point_b })
+}
@@ -0,0 +1,102 @@
+use super::{shapes_3d::{FixedConvexFace, Sphere}, vec3d::Vec3Df};
+
+use shapes::Aabb;
+
+pub mod shapes;
+pub mod gjk;
+pub mod narrow_phase;
+pub mod broad_phase;
+pub mod swept;
+
+/// Where two shapes touch
+///
+/// `normal` is normalised and points from the first shape to the second one, moving the first shape by `-normal * depth` separates them
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Contact {
+    pub point:Vec3Df,
+    pub normal:Vec3Df,
+    pub depth:f32,
+}
+
+impl Contact {
+    /// The same contact seen from the second shape
+    pub fn flipped(&self) -> Self {
+        Self { point: self.point, normal: -self.normal, depth: self.depth }
+    }
+}
+
+/// Shapes usable by GJK, EPA and the swept tests
+///
+/// Rounded shapes are a core (a point for spheres, a segment for capsules) with a margin around it, which keeps the algorithms exact on them
+pub trait ConvexShape {
+    /// Farthest point of the core in `direction`, `direction` doesn't have to be normalised
+    fn support(&self, direction:&Vec3Df) -> Vec3Df;
+    fn margin(&self) -> f32 {
+        0.0
+    }
+    /// Any point inside the shape, used as a starting guess
+    fn center(&self) -> Vec3Df;
+    fn get_aabb(&self) -> Aabb {
+        let margin = Vec3Df::all_ones() * self.margin();
+        let min = Vec3Df::new(
+            self.support(&Vec3Df::new(-1.0, 0.0, 0.0)).x,
+            self.support(&Vec3Df::new(0.0, -1.0, 0.0)).y,
+            self.support(&Vec3Df::new(0.0, 0.0, -1.0)).z,
+        );
+        let max = Vec3Df::new(
+            self.support(&Vec3Df::new(1.0, 0.0, 0.0)).x,
+            self.support(&Vec3Df::new(0.0, 1.0, 0.0)).y,
+            self.support(&Vec3Df::new(0.0, 0.0, 1.0)).z,
+        );
+        Aabb::new(min - margin, max + margin)
+    }
+}
+
+impl ConvexShape for Sphere {
+    fn support(&self, direction:&Vec3Df) -> Vec3Df {
+        self.get_origin()
+    }
+    fn margin(&self) -> f32 {
+        self.get_radius()
+    }
+    fn center(&self) -> Vec3Df {
+        self.get_origin()
+    }
+}
+
+impl<const N:usize> ConvexShape for FixedConvexFace<N> {
+    fn support(&self, direction:&Vec3Df) -> Vec3Df {
+        let mut best = self.get_point(0);
+        let mut best_dot = best.dot(direction);
+        for i in 1..N {
+            let point = self.get_point(i);
+            let dot = point.dot(direction);
+            if dot > best_dot {
+                best = point;
+                best_dot = dot;
+            }
+        }
+        best
+    }
+    fn center(&self) -> Vec3Df {
+        self.barycenter()
+    }
+}
+
+/// A shape moved by `offset` without copying it, used by the swept tests
+pub struct Translated<'a, S:ConvexShape + ?Sized> {
+    pub shape:&'a S,
+    pub offset:Vec3Df,
+}
+
+impl<'a, S:ConvexShape + ?Sized> ConvexShape for Translated<'a, S> {
+    fn support(&self, direction:&Vec3Df) -> Vec3Df {
+        self.shape.support(direction) + self.offset
+    }
+    fn margin(&self) -> f32 {
+        self.shape.margin()
+    }
+    fn center(&self) -> Vec3Df {
+        self.shape.center() + self.offset
+    }
+}
@@ -0,0 +1,262 @@
+use crate::horde::geometry::{shapes_3d::{Sphere, Triangle}, vec3d::Vec3Df, Intersection};
+
+use super::{gjk::{closest_point_on_segment, closest_point_on_triangle, epa, gjk, GjkResult}, shapes::{Aabb, Capsule, ConvexHull, Obb, TriangleMesh}, Contact, ConvexShape};
+
+/// Used when the shapes don't give a contact normal, Z is up
+const FALLBACK_NORMAL:Vec3Df = Vec3Df::new(0.0, 0.0, 1.0);
+
+fn normal_or_fallback(direction:Vec3Df) -> Vec3Df {
+    let length = direction.norme();
+    if length > f32::EPSILON {
+        direction / length
+    }
+    else {
+        FALLBACK_NORMAL
+    }
+}
+
+/// Contact between two spheres given as center and radius, shared by every rounded pair
+fn point_point(a:&Vec3Df, radius_a:f32, b:&Vec3Df, radius_b:f32) -> Option<Contact> {
+    let distance_squared = a.dist_squared(b);
+    let radiuses = radius_a + radius_b;
+    if distance_squared > radiuses * radiuses {
+        return None
+    }
+    let distance = distance_squared.sqrt();
+    let normal = normal_or_fallback(b - a);
+    let depth = radiuses - distance;
+    Some(Contact { point: *a + normal * (radius_a - depth / 2.0), normal, depth })
+}
+
+pub fn sphere_sphere(a:&Sphere, b:&Sphere) -> Option<Contact> {
+    point_point(&a.get_origin(), a.get_radius(), &b.get_origin(), b.get_radius())
+}
+
+pub fn sphere_aabb(a:&Sphere, b:&Aabb) -> Option<Contact> {
+    let center = a.get_origin();
+    let radius = a.get_radius();
+    if !b.contains_point(&center) {
+        let closest = b.closest_point(&center);
+        let distance_squared = closest.dist_squared(&center);
+        if distance_squared > radius * radius {
+            return None
+        }
+        let distance = distance_squared.sqrt();
+        return Some(Contact { point: closest, normal: normal_or_fallback(closest - center), depth: radius - distance })
+    }
+    // Center inside the box, push it out through the nearest face
+    let faces = [
+        (center.x - b.min.x, Vec3Df::new(-1.0, 0.0, 0.0)),
+        (b.max.x - center.x, Vec3Df::new(1.0, 0.0, 0.0)),
+        (center.y - b.min.y, Vec3Df::new(0.0, -1.0, 0.0)),
+        (b.max.y - center.y, Vec3Df::new(0.0, 1.0, 0.0)),
+        (center.z - b.min.z, Vec3Df::new(0.0, 0.0, -1.0)),
+        (b.max.z - center.z, Vec3Df::new(0.0, 0.0, 1.0)),
+    ];
+    let (distance, face_normal) = faces.into_iter().min_by(|(d1, _), (d2, _)| d1.total_cmp(d2)).unwrap();
+    Some(Contact { point: center + face_normal * distance, normal: -face_normal, depth: radius + distance })
+}
+
+pub fn aabb_aabb(a:&Aabb, b:&Aabb) -> Option<Contact> {
+    if !a.intersects(b) {
+        return None
+    }
+    let overlap_min = Vec3Df::new(a.min.x.max(b.min.x), a.min.y.max(b.min.y), a.min.z.max(b.min.z));
+    let overlap_max = Vec3Df::new(a.max.x.min(b.max.x), a.max.y.min(b.max.y), a.max.z.min(b.max.z));
+    // Shortest way out on each axis, in both directions, so boxes contained in others on an axis get the right depth
+    let (depth, normal) = [
+        (a.max.x - b.min.x, Vec3Df::new(1.0, 0.0, 0.0)),
+        (b.max.x - a.min.x, Vec3Df::new(-1.0, 0.0, 0.0)),
+        (a.max.y - b.min.y, Vec3Df::new(0.0, 1.0, 0.0)),
+        (b.max.y - a.min.y, Vec3Df::new(0.0, -1.0, 0.0)),
+        (a.max.z - b.min.z, Vec3Df::new(0.0, 0.0, 1.0)),
+        (b.max.z - a.min.z, Vec3Df::new(0.0, 0.0, -1.0)),
+    ].into_iter().min_by(|(d1, _), (d2, _)| d1.total_cmp(d2)).unwrap();
+    Some(Contact { point: (overlap_min + overlap_max) / 2.0, normal, depth })
+}
+
+pub fn sphere_capsule(a:&Sphere, b:&Capsule) -> Option<Contact> {
+    let (closest, _) = closest_point_on_segment(&a.get_origin(), &b.start, &b.end);
+    point_point(&a.get_origin(), a.get_radius(), &closest, b.radius)
+}
+
+/// Closest points between segments `p1`-`q1` and `p2`-`q2`
+pub fn closest_points_segments(p1:&Vec3Df, q1:&Vec3Df, p2:&Vec3Df, q2:&Vec3Df) -> (Vec3Df, Vec3Df) {
+    let d1 = q1 - p1;
+    let d2 = q2 - p2;
+    let r = p1 - p2;
+    let a = d1.norme_square();
+    let e = d2.norme_square();
+    let f = d2.dot(&r);
+    if a <= f32::EPSILON && e <= f32::EPSILON {
+        return (*p1, *p2)
+    }
+    let (s, t) = if a <= f32::EPSILON {
+        (0.0, (f / e).clamp(0.0, 1.0))
+    }
+    else {
+        let c = d1.dot(&r);
+        if e <= f32::EPSILON {
+            ((-c / a).clamp(0.0, 1.0), 0.0)
+        }
+        else {
+            let b = d1.dot(&d2);
+            let denom = a * e - b * b;
+            let mut s = if denom > f32::EPSILON {((b * f - c * e) / denom).clamp(0.0, 1.0)} else {0.0};
+            let mut t = (b * s + f) / e;
+            if t < 0.0 {
+                t = 0.0;
+                s = (-c / a).clamp(0.0, 1.0);
+            }
+            else if t > 1.0 {
+                t = 1.0;
+                s = ((b - c) / a).clamp(0.0, 1.0);
+            }
+            (s, t)
+        }
+    };
+    (*p1 + d1 * s, *p2 + d2 * t)
+}
+
+pub fn capsule_capsule(a:&Capsule, b:&Capsule) -> Option<Contact> {
+    let (on_a, on_b) = closest_points_segments(&a.start, &a.end, &b.start, &b.end);
+    point_point(&on_a, a.radius, &on_b, b.radius)
+}
+
+/// Sphere against a two sided triangle, a sphere centered on the triangle is pushed along its normal
+pub fn sphere_triangle(a:&Sphere, b:&Triangle) -> Option<Contact> {
+    let points = b.get_points();
+    let center = a.get_origin();
+    let (closest, _) = closest_point_on_triangle(&center, &points[0], &points[1], &points[2]);
+    let distance_squared = closest.dist_squared(&center);
+    if distance_squared > a.get_radius() * a.get_radius() {
+        return None
+    }
+    let distance = distance_squared.sqrt();
+    let normal = if distance > f32::EPSILON {(closest - center) / distance} else {-normal_or_fallback((points[1] - points[0]).cross(&(points[2] - points[0])))};
+    Some(Contact { point: closest, normal, depth: a.get_radius() - distance })
+}
+
+/// Contact between any two convex shapes, through GJK then EPA when the cores overlap
+pub fn collide_convex<A:ConvexShape + ?Sized, B:ConvexShape + ?Sized>(a:&A, b:&B) -> Option<Contact> {
+    let margins = a.margin() + b.margin();
+    match gjk(a, b) {
+        GjkResult::Separated { distance, closest_a, closest_b } => {
+            if distance > margins {
+                return None
+            }
+            let normal = normal_or_fallback(closest_b - closest_a);
+            let point_a = closest_a + normal * a.margin();
+            let point_b = closest_b - normal * b.margin();
+            Some(Contact { point: (point_a + point_b) / 2.0, normal, depth: margins - distance })
+        },
+        GjkResult::Intersecting(simplex) => match epa(a, b, simplex) {
+            Some(penetration) => {
+                let point_a = penetration.point_a + penetration.normal * a.margin();
+                let point_b = penetration.point_b - penetration.normal * b.margin();
+                Some(Contact { point: (point_a + point_b) / 2.0, normal: penetration.normal, depth: penetration.depth + margins })
+            },
+            None => Some(Contact { point: (a.center() + b.center()) / 2.0, normal: normal_or_fallback(b.center() - a.center()), depth: margins })
+        }
+    }
+}
+
+impl TriangleMesh {
+    /// One contact per triangle touching `shape`, normals point from `shape` to the mesh
+    pub fn contacts_with<S:ConvexShape + ?Sized>(&self, shape:&S) -> Vec<Contact> {
+        let bounds = shape.get_aabb();
+        self.triangles_in(&bounds).filter_map(|(_, triangle)| collide_convex(shape, triangle)).collect()
+    }
+    pub fn contacts_with_sphere(&self, sphere:&Sphere) -> Vec<Contact> {
+        let bounds = sphere.get_aabb();
+        self.triangles_in(&bounds).filter_map(|(_, triangle)| sphere_triangle(sphere, triangle)).collect()
+    }
+    /// One contact per pair of touching triangles, normals point from this mesh to `other`
+    pub fn contacts_with_mesh(&self, other:&TriangleMesh) -> Vec<Contact> {
+        let mut contacts = Vec::new();
+        if !self.get_aabb().intersects(&other.get_aabb()) {
+            return contacts
+        }
+        for (_, triangle) in self.triangles_in(&other.get_aabb()) {
+            let bounds = Aabb::from_points(&triangle.get_points());
+            for (_, other_triangle) in other.triangles_in(&bounds) {
+                if let Some(contact) = collide_convex(triangle, other_triangle) {
+                    contacts.push(contact);
+                }
+            }
+        }
+        contacts
+    }
+}
+
+/// Any shape the narrow phase knows about
+#[derive(Clone, Debug, PartialEq)]
+pub enum Collider {
+    Sphere(Sphere),
+    Aabb(Aabb),
+    Obb(Obb),
+    Capsule(Capsule),
+    ConvexHull(ConvexHull),
+    TriangleMesh(TriangleMesh),
+}
+
+impl Collider {
+    pub fn get_aabb(&self) -> Aabb {
+        match self {
+            Collider::TriangleMesh(mesh) => mesh.get_aabb(),
+            convex => convex.as_convex().unwrap().get_aabb()
+        }
+    }
+    /// Every collider but triangle meshes
+    pub fn as_convex(&self) -> Option<&dyn ConvexShape> {
+        match self {
+            Collider::Sphere(sphere) => Some(sphere),
+            Collider::Aabb(aabb) => Some(aabb),
+            Collider::Obb(obb) => Some(obb),
+            Collider::Capsule(capsule) => Some(capsule),
+            Collider::ConvexHull(hull) => Some(hull),
+            Collider::TriangleMesh(_) => None,
+        }
+    }
+    pub fn translated(&self, offset:&Vec3Df) -> Self {
+        match self {
+            Collider::Sphere(sphere) => Collider::Sphere(Sphere::new(sphere.get_origin() + offset, sphere.get_radius())),
+            Collider::Aabb(aabb) => Collider::Aabb(aabb.translated(offset)),
+            Collider::Obb(obb) => Collider::Obb(Obb { center: obb.center + offset, half_extents: obb.half_extents, axis: obb.axis }),
+            Collider::Capsule(capsule) => Collider::Capsule(Capsule::new(capsule.start + offset, capsule.end + offset, capsule.radius)),
+            Collider::ConvexHull(hull) => Collider::ConvexHull(hull.translated(offset)),
+            Collider::TriangleMesh(mesh) => Collider::TriangleMesh(mesh.translated(offset)),
+        }
+    }
+    /// Every contact between the two colliders, normals point from `self` to `other`
+    ///
+    /// Convex pairs give at most one contact, triangle meshes give one per touching triangle
+    pub fn contacts(&self, other:&Collider) -> Vec<Contact> {
+        match (self, other) {
+            (Collider::Sphere(a), Collider::Sphere(b)) => sphere_sphere(a, b).into_iter().collect(),
+            (Collider::Sphere(a), Collider::Aabb(b)) => sphere_aabb(a, b).into_iter().collect(),
+            (Collider::Aabb(a), Collider::Sphere(b)) => sphere_aabb(b, a).map(|contact| contact.flipped()).into_iter().collect(),
+            (Collider::Aabb(a), Collider::Aabb(b)) => aabb_aabb(a, b).into_iter().collect(),
+            (Collider::Sphere(a), Collider::Capsule(b)) => sphere_capsule(a, b).into_iter().collect(),
+            (Collider::Capsule(a), Collider::Sphere(b)) => sphere_capsule(b, a).map(|contact| contact.flipped()).into_iter().collect(),
+            (Collider::Capsule(a), Collider::Capsule(b)) => capsule_capsule(a, b).into_iter().collect(),
+            (Collider::TriangleMesh(a), Collider::TriangleMesh(b)) => a.contacts_with_mesh(b),
+            (Collider::Sphere(a), Collider::TriangleMesh(b)) => b.contacts_with_sphere(a),
+            (Collider::TriangleMesh(a), Collider::Sphere(b)) => a.contacts_with_sphere(b).into_iter().map(|contact| contact.flipped()).collect(),
+            (convex, Collider::TriangleMesh(mesh)) => mesh.contacts_with(convex.as_convex().unwrap()),
+            (Collider::TriangleMesh(mesh), convex) => mesh.contacts_with(convex.as_convex().unwrap()).into_iter().map(|contact| contact.flipped()).collect(),
+            (a, b) => collide_convex(a.as_convex().unwrap(), b.as_convex().unwrap()).into_iter().collect(),
+        }
+    }
+    /// The deepest contact between the two colliders
+    pub fn collide(&self, other:&Collider) -> Option<Contact> {
+        self.contacts(other).into_iter().max_by(|c1, c2| c1.depth.total_cmp(&c2.depth))
+    }
+}
+
+impl Intersection<Collider> for Collider {
+    type IntersectionType = Option<Contact>;
+    fn intersect_with(&self, target:&Collider) -> Self::IntersectionType {
+        self.collide(target)
+    }
+}
@@ -0,0 +1,259 @@
+use crate::horde::geometry::{rotation::Rotation, shapes_3d::Triangle, vec3d::Vec3Df};
+
+use super::ConvexShape;
+
+/// Axis aligned bounding box
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Aabb {
+    pub min:Vec3Df,
+    pub max:Vec3Df,
+}
+
+impl Aabb {
+    pub fn new(min:Vec3Df, max:Vec3Df) -> Self {
+        Self { min, max }
+    }
+    pub fn from_center(center:Vec3Df, half_extents:Vec3Df) -> Self {
+        Self { min: center - half_extents, max: center + half_extents }
+    }
+    /// Panics on an empty slice
+    pub fn from_points(points:&[Vec3Df]) -> Self {
+        let mut aabb = Self::new(points[0], points[0]);
+        for point in points.iter().skip(1) {
+            aabb.add_point(point);
+        }
+        aabb
+    }
+    pub fn add_point(&mut self, point:&Vec3Df) {
+        self.min = Vec3Df::new(self.min.x.min(point.x), self.min.y.min(point.y), self.min.z.min(point.z));
+        self.max = Vec3Df::new(self.max.x.max(point.x), self.max.y.max(point.y), self.max.z.max(point.z));
+    }
+    pub fn merge(&self, other:&Self) -> Self {
+        let mut merged = *self;
+        merged.add_point(&other.min);
+        merged.add_point(&other.max);
+        merged
+    }
+    pub fn expanded(&self, margin:f32) -> Self {
+        let margin = Vec3Df::all_ones() * margin;
+        Self { min: self.min - margin, max: self.max + margin }
+    }
+    /// Box covering every position of this one along `motion`
+    pub fn swept(&self, motion:&Vec3Df) -> Self {
+        self.merge(&self.translated(motion))
+    }
+    pub fn translated(&self, offset:&Vec3Df) -> Self {
+        Self { min: self.min + offset, max: self.max + offset }
+    }
+    pub fn get_center(&self) -> Vec3Df {
+        (self.min + self.max) / 2.0
+    }
+    pub fn get_half_extents(&self) -> Vec3Df {
+        (self.max - self.min) / 2.0
+    }
+    pub fn intersects(&self, other:&Self) -> bool {
+        self.min.x <= other.max.x && self.max.x >= other.min.x &&
+        self.min.y <= other.max.y && self.max.y >= other.min.y &&
+        self.min.z <= other.max.z && self.max.z >= other.min.z
+    }
+    pub fn contains_point(&self, point:&Vec3Df) -> bool {
+        point.x >= self.min.x && point.x <= self.max.x &&
+        point.y >= self.min.y && point.y <= self.max.y &&
+        point.z >= self.min.z && point.z <= self.max.z
+    }
+    pub fn closest_point(&self, point:&Vec3Df) -> Vec3Df {
+        point.clamp(self.min.x, self.min.y, self.min.z, self.max.x, self.max.y, self.max.z)
+    }
+    /// Fraction of `motion` (in 0.0..=1.0) after which this box first touches `other`, 0.0 if they already overlap
+    pub fn sweep(&self, motion:&Vec3Df, other:&Self) -> Option<f32> {
+        let mut enter:f32 = 0.0;
+        let mut exit:f32 = 1.0;
+        for ((min, max), (other_min, other_max), speed) in [
+            ((self.min.x, self.max.x), (other.min.x, other.max.x), motion.x),
+            ((self.min.y, self.max.y), (other.min.y, other.max.y), motion.y),
+            ((self.min.z, self.max.z), (other.min.z, other.max.z), motion.z),
+        ] {
+            if speed == 0.0 {
+                if max < other_min || min > other_max {
+                    return None
+                }
+            }
+            else {
+                let t1 = (other_min - max) / speed;
+                let t2 = (other_max - min) / speed;
+                enter = enter.max(t1.min(t2));
+                exit = exit.min(t1.max(t2));
+                if enter > exit {
+                    return None
+                }
+            }
+        }
+        Some(enter)
+    }
+}
+
+impl ConvexShape for Aabb {
+    fn support(&self, direction:&Vec3Df) -> Vec3Df {
+        Vec3Df::new(
+            if direction.x >= 0.0 {self.max.x} else {self.min.x},
+            if direction.y >= 0.0 {self.max.y} else {self.min.y},
+            if direction.z >= 0.0 {self.max.z} else {self.min.z},
+        )
+    }
+    fn center(&self) -> Vec3Df {
+        self.get_center()
+    }
+    fn get_aabb(&self) -> Aabb {
+        *self
+    }
+}
+
+/// Oriented bounding box
+#[derive(Clone, Debug, PartialEq)]
+pub struct Obb {
+    pub center:Vec3Df,
+    pub half_extents:Vec3Df,
+    /// Normalised local X, Y and Z axis of the box
+    pub axis:[Vec3Df ; 3],
+}
+
+impl Obb {
+    pub fn new(center:Vec3Df, half_extents:Vec3Df, rotation:&Rotation) -> Self {
+        Self { center, half_extents, axis: [
+            rotation.rotate(Vec3Df::new(1.0, 0.0, 0.0)),
+            rotation.rotate(Vec3Df::new(0.0, 1.0, 0.0)),
+            rotation.rotate(Vec3Df::new(0.0, 0.0, 1.0)),
+        ] }
+    }
+    pub fn get_corners(&self) -> [Vec3Df ; 8] {
+        let [x, y, z] = [self.axis[0] * self.half_extents.x, self.axis[1] * self.half_extents.y, self.axis[2] * self.half_extents.z];
+        let c = self.center;
+        [c - x - y - z, c + x - y - z, c - x + y - z, c + x + y - z, c - x - y + z, c + x - y + z, c - x + y + z, c + x + y + z]
+    }
+}
+
+impl ConvexShape for Obb {
+    fn support(&self, direction:&Vec3Df) -> Vec3Df {
+        let mut point = self.center;
+        for (axis, half) in self.axis.iter().zip(self.half_extents.coords_to_array()) {
+            if axis.dot(direction) >= 0.0 {
+                point += *axis * half;
+            }
+            else {
+                point -= *axis * half;
+            }
+        }
+        point
+    }
+    fn center(&self) -> Vec3Df {
+        self.center
+    }
+}
+
+/// Segment from `start` to `end` with a radius around it
+#[derive(Clone, Debug, PartialEq)]
+pub struct Capsule {
+    pub start:Vec3Df,
+    pub end:Vec3Df,
+    pub radius:f32,
+}
+
+impl Capsule {
+    pub fn new(start:Vec3Df, end:Vec3Df, radius:f32) -> Self {
+        Self { start, end, radius }
+    }
+}
+
+impl ConvexShape for Capsule {
+    fn support(&self, direction:&Vec3Df) -> Vec3Df {
+        if self.start.dot(direction) >= self.end.dot(direction) {
+            self.start
+        }
+        else {
+            self.end
+        }
+    }
+    fn margin(&self) -> f32 {
+        self.radius
+    }
+    fn center(&self) -> Vec3Df {
+        (self.start + self.end) / 2.0
+    }
+}
+
+/// Convex hull of a point cloud, the points don't need to all be on the hull
+#[derive(Clone, Debug, PartialEq)]
+pub struct ConvexHull {
+    points:Vec<Vec3Df>,
+    center:Vec3Df,
+}
+
+impl ConvexHull {
+    /// Panics on an empty point cloud
+    pub fn new(points:Vec<Vec3Df>) -> Self {
+        let mut center = Vec3Df::zero();
+        for point in points.iter() {
+            center += point;
+        }
+        center /= points.len() as f32;
+        Self { points, center }
+    }
+    pub fn get_points(&self) -> &Vec<Vec3Df> {
+        &self.points
+    }
+    pub fn translated(&self, offset:&Vec3Df) -> Self {
+        Self { points: self.points.iter().map(|point| *point + offset).collect(), center: self.center + offset }
+    }
+}
+
+impl ConvexShape for ConvexHull {
+    fn support(&self, direction:&Vec3Df) -> Vec3Df {
+        let mut best = self.points[0];
+        let mut best_dot = best.dot(direction);
+        for point in self.points.iter().skip(1) {
+            let dot = point.dot(direction);
+            if dot > best_dot {
+                best = *point;
+                best_dot = dot;
+            }
+        }
+        best
+    }
+    fn center(&self) -> Vec3Df {
+        self.center
+    }
+}
+
+/// Static triangle soup, tested triangle by triangle against other shapes
+#[derive(Clone, Debug, PartialEq)]
+pub struct TriangleMesh {
+    triangles:Vec<Triangle>,
+    bounds:Vec<Aabb>,
+    aabb:Aabb,
+}
+
+impl TriangleMesh {
+    /// Panics on an empty mesh
+    pub fn new(triangles:Vec<Triangle>) -> Self {
+        let bounds:Vec<Aabb> = triangles.iter().map(|triangle| Aabb::from_points(&triangle.get_points())).collect();
+        let mut aabb = bounds[0];
+        for bound in bounds.iter().skip(1) {
+            aabb = aabb.merge(bound);
+        }
+        Self { triangles, bounds, aabb }
+    }
+    pub fn get_triangles(&self) -> &Vec<Triangle> {
+        &self.triangles
+    }
+    pub fn get_aabb(&self) -> Aabb {
+        self.aabb
+    }
+    /// Triangles whose bounding box touches `aabb`, with their index
+    pub fn triangles_in(&self, aabb:&Aabb) -> impl Iterator<Item = (usize, &Triangle)> {
+        let aabb = *aabb;
+        self.bounds.iter().enumerate().filter(move |(_, bound)| bound.intersects(&aabb)).map(|(i, _)| (i, &self.triangles[i]))
+    }
+    pub fn translated(&self, offset:&Vec3Df) -> Self {
+        Self { triangles: self.triangles.iter().map(|triangle| triangle.clone().add_pos(*offset)).collect(), bounds: self.bounds.iter().map(|bound| bound.translated(offset)).collect(), aabb: self.aabb.translated(offset) }
+    }
+}
@@ -0,0 +1,80 @@
+use crate::horde::geometry::vec3d::Vec3Df;
+
+use super::{gjk::{gjk, GjkResult}, narrow_phase::{collide_convex, Collider}, Contact, ConvexShape, Translated};
+
+/// How close the shapes must get for the sweep to count them as touching
+const SWEEP_TOLERANCE:f32 = 0.001;
+const MAX_SWEEP_STEPS:usize = 32;
+
+/// First time of impact of a sweep
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SweepHit {
+    /// Fraction of the motion (in 0.0..=1.0) done before the hit
+    pub time:f32,
+    /// Contact at the time of the hit, with a depth of 0.0 unless the shapes already overlapped at the start
+    pub contact:Contact,
+}
+
+/// Conservative advancement of `a` moving by `motion_a` and `b` moving by `motion_b` over the same step, so fast movers can't pass through each other
+pub fn sweep_convex<A:ConvexShape + ?Sized, B:ConvexShape + ?Sized>(a:&A, motion_a:&Vec3Df, b:&B, motion_b:&Vec3Df) -> Option<SweepHit> {
+    let relative = motion_a - motion_b;
+    let margins = a.margin() + b.margin();
+    let mut time = 0.0;
+    for i in 0..MAX_SWEEP_STEPS {
+        let moved_a = Translated { shape: a, offset: *motion_a * time };
+        let moved_b = Translated { shape: b, offset: *motion_b * time };
+        match gjk(&moved_a, &moved_b) {
+            GjkResult::Intersecting(_) => return collide_convex(&moved_a, &moved_b).map(|contact| SweepHit { time, contact }),
+            GjkResult::Separated { distance, closest_a, closest_b } => {
+                let gap = distance - margins;
+                if gap <= 0.0 {
+                    return collide_convex(&moved_a, &moved_b).map(|contact| SweepHit { time, contact })
+                }
+                let normal = (closest_b - closest_a) / distance;
+                if gap <= SWEEP_TOLERANCE {
+                    let point_a = closest_a + normal * a.margin();
+                    let point_b = closest_b - normal * b.margin();
+                    return Some(SweepHit { time, contact: Contact { point: (point_a + point_b) / 2.0, normal, depth: 0.0 } })
+                }
+                let closing_speed = relative.dot(&normal);
+                if closing_speed <= f32::EPSILON {
+                    return None
+                }
+                time += gap / closing_speed;
+                if time > 1.0 {
+                    return None
+                }
+            }
+        }
+    }
+    None
+}
+
+impl Collider {
+    /// Earliest hit between this collider moving by `motion` and `other` moving by `other_motion`, normals point from `self` to `other`
+    pub fn sweep(&self, motion:&Vec3Df, other:&Collider, other_motion:&Vec3Df) -> Option<SweepHit> {
+        if !self.get_aabb().swept(motion).intersects(&other.get_aabb().swept(other_motion)) {
+            return None
+        }
+        let earliest = |hits:Vec<SweepHit>| hits.into_iter().min_by(|h1, h2| h1.time.total_cmp(&h2.time));
+        match (self, other) {
+            (Collider::TriangleMesh(a), Collider::TriangleMesh(b)) => {
+                let bounds = b.get_aabb().swept(&(other_motion - motion));
+                earliest(a.triangles_in(&bounds).flat_map(|(_, triangle)| {
+                    b.triangles_in(&triangle.get_aabb().swept(&(motion - other_motion))).filter_map(|(_, other_triangle)| sweep_convex(triangle, motion, other_triangle, other_motion)).collect::<Vec<SweepHit>>()
+                }).collect())
+            },
+            (convex, Collider::TriangleMesh(mesh)) => {
+                let shape = convex.as_convex().unwrap();
+                let bounds = shape.get_aabb().swept(&(motion - other_motion));
+                earliest(mesh.triangles_in(&bounds).filter_map(|(_, triangle)| sweep_convex(shape, motion, triangle, other_motion)).collect())
+            },
+            (Collider::TriangleMesh(mesh), convex) => {
+                let shape = convex.as_convex().unwrap();
+                let bounds = shape.get_aabb().swept(&(other_motion - motion));
+                earliest(mesh.triangles_in(&bounds).filter_map(|(_, triangle)| sweep_convex(triangle, motion, shape, other_motion)).collect())
+            },
+            (a, b) => sweep_convex(a.as_convex().unwrap(), motion, b.as_convex().unwrap(), other_motion)
+        }
+    }
+}
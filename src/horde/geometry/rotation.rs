@@ -42,6 +42,11 @@ impl Quaternion {
 
         q
     }
+    /// Rotation of `angle` radians around `axis`, which must be normalised
+    pub fn from_axis_angle(axis:Vec3Df, angle:f32) -> Quaternion {
+        let (sin, cos) = (angle * 0.5).sin_cos();
+        Quaternion { w: cos, x: axis.x * sin, y: axis.y * sin, z: axis.z * sin }
+    }
     #[inline(always)]
     pub fn rotate(&self, cible: Vec3Df) -> Vec3Df {
         Vec3Df::new(
@@ -52,6 +57,21 @@ impl Quaternion {
         //let t = Vec3D::new(self.x, self.y, self.z).cross(&cible) * 2.0;
         //(cible + (t * self.w)) + Vec3D::new(self.x, self.y, self.z).cross(&t)
     }
+    /// Quaternion for the rotation `other` followed by `self`
+    pub fn compose(&self, other:&Self) -> Self {
+        Self::new(
+            self.w * other.w - self.x * other.x - self.y * other.y - self.z * other.z,
+            self.w * other.x + self.x * other.w + self.y * other.z - self.z * other.y,
+            self.w * other.y - self.x * other.z + self.y * other.w + self.z * other.x,
+            self.w * other.z + self.x * other.y - self.y * other.x + self.z * other.w,
+        )
+    }
+    pub fn to_orientation(&self) -> Orientation {
+        let roll = (2.0 * (self.w * self.x + self.y * self.z)).atan2(1.0 - 2.0 * (self.x.powi(2) + self.y.powi(2)));
+        let pitch = (2.0 * (self.w * self.y - self.z * self.x)).clamp(-1.0, 1.0).asin();
+        let yaw = (2.0 * (self.w * self.z + self.x * self.y)).atan2(1.0 - 2.0 * (self.y.powi(2) + self.z.powi(2)));
+        Orientation::new(yaw, pitch, roll)
+    }
     pub fn invert(&self) -> Self {
         let len = self.w.powi(2) + self.x.powi(2) + self.y.powi(2) + self.z.powi(2);
         Self { w: self.w/len, x: -self.x/len, y: -self.y/len, z: -self.z/len }
@@ -481,6 +481,7 @@ impl Triangle {
     }
 }
 
+#[derive(Clone, Debug, PartialEq)]
 pub struct Sphere {
     radius:f32,
     origin:Vec3Df,
@@ -490,6 +491,12 @@ impl Sphere {
     pub fn new(origin:Vec3Df, radius:f32) -> Self {
         Self { radius, origin }
     }
+    pub fn get_origin(&self) -> Vec3Df {
+        self.origin
+    }
+    pub fn get_radius(&self) -> f32 {
+        self.radius
+    }
     pub fn add_triangles<const PRECISION:usize>(&self,reverse:bool,tris:&mut Vec<Triangle>) {
         let angle = PI/(PRECISION as f32);
         let mut current_face = Vec::new();
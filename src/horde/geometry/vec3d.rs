@@ -440,7 +440,7 @@ impl Vec3Df {
         ((autre.x - self.x).powi(2) + (autre.z - self.z).powi(2)).sqrt()
     }
     pub fn dist_squared(&self, autre: &Self) -> f32 {
-        (autre.x - self.x).powi(2) + (autre.x - self.x).powi(2) + (autre.x - self.x).powi(2)
+        (autre.x - self.x).powi(2) + (autre.y - self.y).powi(2) + (autre.z - self.z).powi(2)
     }
     pub fn roughly_under(&self, threshold:f32) -> bool {
         self.x.abs() < threshold && self.y.abs() < threshold && self.z.abs() < threshold
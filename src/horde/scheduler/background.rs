@@ -0,0 +1,175 @@
+use std::{any::Any, future::Future, panic::{catch_unwind, AssertUnwindSafe}, pin::Pin, sync::{atomic::{AtomicBool, AtomicU32, Ordering}, Arc, Mutex}, task::{Context, Poll, Waker}};
+
+use crossbeam::channel::Sender;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum BackgroundError {
+    Cancelled,
+    Panicked(String),
+}
+
+pub(crate) fn panic_message(payload:Box<dyn Any + Send>) -> String {
+    match payload.downcast::<String>() {
+        Ok(message) => *message,
+        Err(payload) => match payload.downcast::<&'static str>() {
+            Ok(message) => message.to_string(),
+            Err(_) => "unknown panic".to_string()
+        }
+    }
+}
+
+/// Given to background work so it can report progress and notice cancellation
+#[derive(Clone)]
+pub struct BackgroundContext {
+    cancelled:Arc<AtomicBool>,
+    progress:Arc<AtomicU32>,
+}
+
+impl BackgroundContext {
+    /// Cancellation is cooperative, long work should check this regularly and return early
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+    /// Clamped to 0.0..1.0
+    pub fn set_progress(&self, progress:f32) {
+        self.progress.store(progress.clamp(0.0, 1.0).to_bits(), Ordering::Relaxed);
+    }
+}
+
+/// Status of a background job without access to its result, returned when the result goes to a callback
+#[derive(Clone)]
+pub struct BackgroundStatus {
+    context:BackgroundContext,
+    finished:Arc<AtomicBool>,
+}
+
+impl BackgroundStatus {
+    fn new() -> Self {
+        Self { context: BackgroundContext { cancelled: Arc::new(AtomicBool::new(false)), progress: Arc::new(AtomicU32::new(0.0_f32.to_bits())) }, finished: Arc::new(AtomicBool::new(false)) }
+    }
+    /// The job ends with `BackgroundError::Cancelled` if it hasn't started yet, or once it returns if it has
+    pub fn cancel(&self) {
+        self.context.cancelled.store(true, Ordering::Relaxed);
+    }
+    pub fn is_cancelled(&self) -> bool {
+        self.context.is_cancelled()
+    }
+    pub fn get_progress(&self) -> f32 {
+        f32::from_bits(self.context.progress.load(Ordering::Relaxed))
+    }
+    pub fn is_finished(&self) -> bool {
+        self.finished.load(Ordering::Acquire)
+    }
+}
+
+struct BackgroundResult<T> {
+    result:Option<Result<T, BackgroundError>>,
+    waker:Option<Waker>,
+}
+
+/// Pollable handle to the typed output of a background job, it is also a `Future`
+pub struct BackgroundHandle<T> {
+    status:BackgroundStatus,
+    shared:Arc<Mutex<BackgroundResult<T>>>,
+}
+
+impl<T> BackgroundHandle<T> {
+    pub fn get_status(&self) -> BackgroundStatus {
+        self.status.clone()
+    }
+    pub fn cancel(&self) {
+        self.status.cancel();
+    }
+    pub fn get_progress(&self) -> f32 {
+        self.status.get_progress()
+    }
+    pub fn is_finished(&self) -> bool {
+        self.status.is_finished()
+    }
+    /// Returns the result once, as soon as the job is done
+    pub fn try_take(&self) -> Option<Result<T, BackgroundError>> {
+        self.shared.lock().unwrap().result.take()
+    }
+}
+
+impl<T> Future for BackgroundHandle<T> {
+    type Output = Result<T, BackgroundError>;
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut shared = self.shared.lock().unwrap();
+        match shared.result.take() {
+            Some(result) => Poll::Ready(result),
+            None => {
+                shared.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+/// Type erased job run by background threads next to the `HordeBackgroundTask`s of the scheduler
+#[derive(Clone)]
+pub struct BackgroundJob {
+    name:String,
+    job:Arc<Mutex<Option<Box<dyn FnOnce() + Send>>>>,
+}
+
+impl BackgroundJob {
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+    pub fn perform(self) {
+        let job = self.job.lock().unwrap().take();
+        if let Some(job) = job {
+            job();
+        }
+    }
+}
+
+/// Callbacks sent back to the scheduler, they run at the start of its next tick
+pub type BackgroundCallback = Box<dyn FnOnce() + Send>;
+
+fn run_work<T, F:FnOnce(&BackgroundContext) -> T>(status:&BackgroundStatus, work:F) -> Result<T, BackgroundError> {
+    if status.is_cancelled() {
+        return Err(BackgroundError::Cancelled)
+    }
+    match catch_unwind(AssertUnwindSafe(|| work(&status.context))) {
+        Ok(output) => if status.is_cancelled() {
+            Err(BackgroundError::Cancelled)
+        }
+        else {
+            status.context.progress.store(1.0_f32.to_bits(), Ordering::Relaxed);
+            Ok(output)
+        },
+        Err(payload) => Err(BackgroundError::Panicked(panic_message(payload)))
+    }
+}
+
+pub(crate) fn new_job<T:Send + 'static, F:FnOnce(&BackgroundContext) -> T + Send + 'static>(name:&str, work:F) -> (BackgroundJob, BackgroundHandle<T>) {
+    let status = BackgroundStatus::new();
+    let shared = Arc::new(Mutex::new(BackgroundResult { result: None, waker: None }));
+    let handle = BackgroundHandle { status: status.clone(), shared: shared.clone() };
+    let job:Box<dyn FnOnce() + Send> = Box::new(move || {
+        let result = run_work(&status, work);
+        let waker = {
+            let mut shared = shared.lock().unwrap();
+            shared.result = Some(result);
+            shared.waker.take()
+        };
+        status.finished.store(true, Ordering::Release);
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    });
+    (BackgroundJob { name: name.to_string(), job: Arc::new(Mutex::new(Some(job))) }, handle)
+}
+
+pub(crate) fn new_job_with_callback<T:Send + 'static, F:FnOnce(&BackgroundContext) -> T + Send + 'static, C:FnOnce(Result<T, BackgroundError>) + Send + 'static>(name:&str, work:F, callback:C, callbacks:Sender<BackgroundCallback>) -> (BackgroundJob, BackgroundStatus) {
+    let status = BackgroundStatus::new();
+    let job_status = status.clone();
+    let job:Box<dyn FnOnce() + Send> = Box::new(move || {
+        let result = run_work(&job_status, work);
+        job_status.finished.store(true, Ordering::Release);
+        let _ = callbacks.send(Box::new(move || callback(result)));
+    });
+    (BackgroundJob { name: name.to_string(), job: Arc::new(Mutex::new(Some(job))) }, status)
+}
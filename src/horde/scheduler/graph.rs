@@ -0,0 +1,220 @@
+use std::collections::HashMap;
+
+use super::{HordeTask, HordeTaskQueue, HordeTaskSequence, SequencedTask};
+
+/// One task of a `TaskGraphBuilder`, along with what it must wait for and the data it touches
+///
+/// Data is named by strings, two tasks touching the same name must be ordered by dependencies unless they both only read it
+#[derive(Clone, Debug)]
+pub struct TaskNode<HT:HordeTask> {
+    task:HT,
+    depends_on:Vec<HT>,
+    reads:Vec<String>,
+    writes:Vec<String>,
+}
+
+impl<HT:HordeTask> TaskNode<HT> {
+    pub fn new(task:HT) -> Self {
+        Self { task, depends_on: Vec::new(), reads: Vec::new(), writes: Vec::new() }
+    }
+    pub fn after(mut self, task:HT) -> Self {
+        self.depends_on.push(task);
+        self
+    }
+    pub fn reads(mut self, data:&str) -> Self {
+        self.reads.push(data.to_string());
+        self
+    }
+    pub fn writes(mut self, data:&str) -> Self {
+        self.writes.push(data.to_string());
+        self
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum TaskGraphError<HT:HordeTask> {
+    /// Tasks are identified by value in the scheduler, so each can only appear once per graph
+    DuplicateTask(HT),
+    /// A task with a `max_threads` of 0 would never run, so nothing depending on it would either
+    NoThreads(HT),
+    UnknownDependency{task:HT, dependency:HT},
+    /// The tasks of the cycle, in dependency order
+    Cycle(Vec<HT>),
+    ConflictingWriters{data:String, first:HT, second:HT},
+    UnorderedReadWrite{data:String, writer:HT, reader:HT},
+}
+
+#[derive(Clone)]
+pub struct TaskGraphBuilder<HT:HordeTask> {
+    nodes:Vec<TaskNode<HT>>,
+}
+
+impl<HT:HordeTask> TaskGraphBuilder<HT> {
+    pub fn new() -> Self {
+        Self { nodes: Vec::new() }
+    }
+    pub fn with(mut self, node:TaskNode<HT>) -> Self {
+        self.nodes.push(node);
+        self
+    }
+    pub fn add(&mut self, node:TaskNode<HT>) {
+        self.nodes.push(node);
+    }
+    /// Checks the graph and computes its execution order
+    pub fn build(self) -> Result<TaskGraph<HT>, TaskGraphError<HT>> {
+        let mut indices = HashMap::with_capacity(self.nodes.len());
+        for (i, node) in self.nodes.iter().enumerate() {
+            if indices.insert(node.task.clone(), i).is_some() {
+                return Err(TaskGraphError::DuplicateTask(node.task.clone()))
+            }
+            if node.task.max_threads() == 0 {
+                return Err(TaskGraphError::NoThreads(node.task.clone()))
+            }
+        }
+        let mut dependencies = vec![Vec::new() ; self.nodes.len()];
+        let mut dependents = vec![Vec::new() ; self.nodes.len()];
+        for (i, node) in self.nodes.iter().enumerate() {
+            for dependency in node.depends_on.iter() {
+                match indices.get(dependency) {
+                    Some(dep_index) => if !dependencies[i].contains(dep_index) {
+                        dependencies[i].push(*dep_index);
+                        dependents[*dep_index].push(i);
+                    },
+                    None => return Err(TaskGraphError::UnknownDependency { task: node.task.clone(), dependency: dependency.clone() })
+                }
+            }
+        }
+
+        // Kahn's algorithm, level by level
+        let mut remaining:Vec<usize> = dependencies.iter().map(|deps| deps.len()).collect();
+        let mut levels = Vec::new();
+        let mut current:Vec<usize> = (0..self.nodes.len()).filter(|i| remaining[*i] == 0).collect();
+        let mut placed = 0;
+        while !current.is_empty() {
+            placed += current.len();
+            let mut next = Vec::new();
+            for i in current.iter() {
+                for dependent in dependents[*i].iter() {
+                    remaining[*dependent] -= 1;
+                    if remaining[*dependent] == 0 {
+                        next.push(*dependent);
+                    }
+                }
+            }
+            levels.push(current);
+            next.sort();
+            current = next;
+        }
+        if placed < self.nodes.len() {
+            return Err(TaskGraphError::Cycle(find_cycle(&dependencies, &remaining).into_iter().map(|i| self.nodes[i].task.clone()).collect()))
+        }
+
+        // ancestors[i][j] is true if j always finishes before i starts
+        let order:Vec<usize> = levels.iter().flatten().copied().collect();
+        let mut ancestors = vec![vec![false ; self.nodes.len()] ; self.nodes.len()];
+        for i in order.iter() {
+            for dependency in dependencies[*i].clone() {
+                ancestors[*i][dependency] = true;
+                for j in 0..self.nodes.len() {
+                    if ancestors[dependency][j] {
+                        ancestors[*i][j] = true;
+                    }
+                }
+            }
+        }
+        let ordered = |a:usize, b:usize| ancestors[a][b] || ancestors[b][a];
+        for (a, node_a) in self.nodes.iter().enumerate() {
+            for (b, node_b) in self.nodes.iter().enumerate().skip(a + 1) {
+                if ordered(a, b) {
+                    continue
+                }
+                for data in node_a.writes.iter() {
+                    if node_b.writes.contains(data) {
+                        return Err(TaskGraphError::ConflictingWriters { data: data.clone(), first: node_a.task.clone(), second: node_b.task.clone() })
+                    }
+                    if node_b.reads.contains(data) {
+                        return Err(TaskGraphError::UnorderedReadWrite { data: data.clone(), writer: node_a.task.clone(), reader: node_b.task.clone() })
+                    }
+                }
+                for data in node_b.writes.iter() {
+                    if node_a.reads.contains(data) {
+                        return Err(TaskGraphError::UnorderedReadWrite { data: data.clone(), writer: node_b.task.clone(), reader: node_a.task.clone() })
+                    }
+                }
+            }
+        }
+
+        Ok(TaskGraph { tasks: self.nodes.into_iter().map(|node| node.task).collect(), dependencies, dependents, indices, levels })
+    }
+}
+
+/// Every node left by Kahn's algorithm has a dependency that was left too, so following them always ends up looping
+fn find_cycle(dependencies:&Vec<Vec<usize>>, remaining:&Vec<usize>) -> Vec<usize> {
+    let start = (0..remaining.len()).find(|i| remaining[*i] > 0).unwrap();
+    let mut path = vec![start];
+    let mut position_in_path = HashMap::new();
+    position_in_path.insert(start, 0);
+    let mut current = start;
+    loop {
+        let next = *dependencies[current].iter().find(|dep| remaining[**dep] > 0).unwrap();
+        match position_in_path.get(&next) {
+            Some(position) => {
+                let mut cycle = path[*position..].to_vec();
+                cycle.reverse();
+                return cycle
+            },
+            None => {
+                position_in_path.insert(next, path.len());
+                path.push(next);
+                current = next;
+            }
+        }
+    }
+}
+
+/// A checked task graph, run it with `HordeScheduler::tick_graph` or turn it into a queue
+#[derive(Clone, Debug)]
+pub struct TaskGraph<HT:HordeTask> {
+    tasks:Vec<HT>,
+    dependencies:Vec<Vec<usize>>,
+    dependents:Vec<Vec<usize>>,
+    indices:HashMap<HT, usize>,
+    levels:Vec<Vec<usize>>,
+}
+
+impl<HT:HordeTask> TaskGraph<HT> {
+    pub fn len(&self) -> usize {
+        self.tasks.len()
+    }
+    /// Groups of tasks that can all run at the same time, each group only depends on the ones before it
+    ///
+    /// Within a group, tasks are in the order their nodes were added to the builder
+    pub fn get_levels(&self) -> Vec<Vec<HT>> {
+        self.levels.iter().map(|level| level.iter().map(|i| self.tasks[*i].clone()).collect()).collect()
+    }
+    pub fn get_task(&self, index:usize) -> &HT {
+        &self.tasks[index]
+    }
+    pub fn index_of(&self, task:&HT) -> Option<usize> {
+        self.indices.get(task).copied()
+    }
+    pub fn dependency_counts(&self) -> Vec<usize> {
+        self.dependencies.iter().map(|deps| deps.len()).collect()
+    }
+    pub fn dependents_of(&self, index:usize) -> &Vec<usize> {
+        &self.dependents[index]
+    }
+    /// Queue for the sequence scheduler, the first sequence starts one sequence per task, which waits for the dependencies of its task then starts it
+    ///
+    /// Each task starts as soon as its own dependencies are done, like with `HordeScheduler::tick_graph`, and the tick waits for all of them
+    pub fn to_queue(&self) -> HordeTaskQueue<HT> {
+        let mut sequences = Vec::with_capacity(self.tasks.len() + 1);
+        sequences.push(HordeTaskSequence::new((0..self.tasks.len()).map(|i| SequencedTask::StartSequence(i + 1)).collect()));
+        for (i, task) in self.tasks.iter().enumerate() {
+            let mut sequence:Vec<SequencedTask<HT>> = self.dependencies[i].iter().map(|dependency| SequencedTask::WaitFor(self.tasks[*dependency].clone())).collect();
+            sequence.push(SequencedTask::StartTask(task.clone()));
+            sequences.push(HordeTaskSequence::new(sequence));
+        }
+        HordeTaskQueue::new(sequences, self.tasks.clone())
+    }
+}
@@ -1,6 +1,22 @@
-use std::{collections::HashMap, fmt::{Debug, Display}, hash::Hash, thread};
+use std::{collections::{HashMap, VecDeque}, fmt::Debug, hash::Hash, panic::{catch_unwind, AssertUnwindSafe}, sync::{atomic::{AtomicU64, Ordering}, Arc}, thread, time::{Duration, Instant}};
 
-use crossbeam::channel::{Receiver, Sender, unbounded};
+use crossbeam::channel::{Receiver, RecvTimeoutError, Sender, unbounded};
+use background::{panic_message, new_job, new_job_with_callback, BackgroundCallback, BackgroundContext, BackgroundError, BackgroundHandle, BackgroundJob, BackgroundStatus};
+use graph::TaskGraph;
+use periodic::{BudgetedTask, BudgetedTaskID, BudgetedTasks, PeriodicTask};
+use profiler::{set_thread_profiler, HordeProfiler, ProfileKind};
+use watchdog::{SchedulerDump, SchedulerError, TaskPanic, TaskStop, WorkerState, WorkerStates};
+use work_stealing::AdaptiveThreads;
+
+use super::game_engine::time_control::TimeControl;
+
+pub mod runner;
+pub mod profiler;
+pub mod graph;
+pub mod work_stealing;
+pub mod background;
+pub mod watchdog;
+pub mod periodic;
 
 /// This trait is meant to be implemented automatically on a type generated by the `task_derive` crate found in the Hord3 repository
 pub trait HordeTaskHandler:Clone {
@@ -19,6 +35,10 @@ pub trait HordeTask: Hash + Eq + PartialEq  + Clone + Send + Sync + Debug {
 
 pub trait HordeBackgroundTask:Clone + Send + Sync {
     fn perform(self);
+    /// Name given to this task in profiles
+    fn profile_name(&self) -> String {
+        "background task".to_string()
+    }
 }
  
 /// HordeTaskData is meant to be automatically implemented on a type generated by the `task_derive` crate found in the Hord3 repository
@@ -80,7 +100,7 @@ impl<HT:HordeTask> HordeTaskSequence<HT> {
 
 /// The SequencedTask is the building block of a HordeTaskSequence, 
 /// - StartTask(task) lets you try to start a given task on its maximum number of threads
-/// - WaitFor(task) will block that sequence until the specified task is complete (all threads working on it have finished it)
+/// - WaitFor(task) will block that sequence until the specified task is started in the current tick and complete (all threads working on it have finished it)
 /// - StartSequence(sequence_index) will start another sequence in the HordeTaskQueue that the HordeTaskSequence this SequencedTask is in is a part of
 /// 
 /// No tasks are automatically waited at the end of a tick, all tasks that must end should have a corresponding SequencedTask::WaitFor in an executed sequence in each tick
@@ -97,14 +117,29 @@ pub struct HordeScheduler<HT:HordeTask, HBT:HordeBackgroundTask> {
     current_tasks:HordeTaskQueue<HT>,
     task_counter:HashMap<HT, usize>,
     send:Sender<SchedulerTask<HT>>,
-    rcv:Receiver<HT>,
+    rcv:Receiver<TaskStop<HT>>,
     number_of_threads:usize,
     idle_threads:usize,
     tasks_in_flight:usize,
     background_task_sender:Sender<SchedulerBackgroundTask<HBT>>,
     background_task_stop:Receiver<usize>,
     number_of_background_threads:usize,
-    background_tasks_in_flight:usize,
+    profiler:HordeProfiler,
+    task_rcv:Receiver<SchedulerTask<HT>>,
+    stop_send:Sender<TaskStop<HT>>,
+    busy_nanos:Arc<AtomicU64>,
+    next_worker:usize,
+    callbacks_send:Sender<BackgroundCallback>,
+    callbacks_rcv:Receiver<BackgroundCallback>,
+    watchdog_timeout:Option<Duration>,
+    worker_states:WorkerStates,
+    panics:Vec<TaskPanic<HT>>,
+    tick_count:u64,
+    periodic:Vec<PeriodicTask<HT>>,
+    budgeted:BudgetedTasks,
+    tick_budget:Option<Duration>,
+    time_control:Option<TimeControl>,
+    timed_out:Option<SchedulerDump<HT>>,
 }
 
 pub enum SchedulerTask<HT:HordeTask> {
@@ -114,57 +149,380 @@ pub enum SchedulerTask<HT:HordeTask> {
 
 pub enum SchedulerBackgroundTask<HBT:HordeBackgroundTask> {
     Task{tsk:HBT, thread_number:usize, number_of_threads_on_task:usize},
+    /// Sent by `HordeScheduler::spawn_background`
+    Job(BackgroundJob),
     Stop,
 }
 
 
 impl<HT:HordeTask + 'static, HBT:HordeBackgroundTask + 'static> HordeScheduler<HT, HBT> {
-    /// This creates a new scheduler, the number_of_threads here is the total thread budget given to it, the scheduler will only create and handle as many threads as that budget, and it currently cannot be changed post-creation
+    /// This creates a new scheduler, the number_of_threads here is the total thread budget given to it, the scheduler will only create and handle as many threads as that budget, it can be changed between ticks with `set_number_of_threads`
     pub fn new(initial_queue:HordeTaskQueue<HT>, handler:HT::HTH, number_of_threads:usize, number_of_background_threads:usize) -> Self {
         let task_counter = HashMap::new();
         let (send_task, receive_task) = unbounded();
         let (send_stop, recveive_stop) = unbounded();
         let (send_bg, recv_bg) = unbounded();
         let (send_bg_stop, recv_bg_stop) = unbounded();
-        let mut out = Self {handler, task_counter, current_tasks:initial_queue, send:send_task, rcv:recveive_stop, number_of_threads, idle_threads:number_of_threads, tasks_in_flight:0,background_task_sender:send_bg, background_task_stop:recv_bg_stop, number_of_background_threads, background_tasks_in_flight:0};
+        let (callbacks_send, callbacks_rcv) = unbounded();
+        let profiler = HordeProfiler::new(240);
+        profiler.name_worker(0, "scheduler".to_string());
+        let mut out = Self {handler, task_counter, current_tasks:initial_queue, send:send_task, rcv:recveive_stop, number_of_threads:0, idle_threads:number_of_threads, tasks_in_flight:0,background_task_sender:send_bg, background_task_stop:recv_bg_stop, number_of_background_threads, profiler, task_rcv:receive_task, stop_send:send_stop, busy_nanos:Arc::new(AtomicU64::new(0)), next_worker:number_of_background_threads + 1, callbacks_send, callbacks_rcv, watchdog_timeout:None, worker_states:WorkerStates::new(), panics:Vec::new(), tick_count:0, periodic:Vec::new(), budgeted:BudgetedTasks::new(), tick_budget:None, time_control:None, timed_out:None};
         for i in 0..number_of_threads {
-            let send_clone = send_stop.clone();
-            let rcv_clone = receive_task.clone();
-            let data = HT::data_from_handler(&out.handler);
-            thread::spawn(move || {
-                task_thread(rcv_clone, send_clone, data);
-            });
+            out.spawn_task_thread();
         }
         for i in 0..number_of_background_threads {
             let send_clone = send_bg_stop.clone();
             let rcv_clone = recv_bg.clone();
+            let profiler = out.profiler.clone();
+            let worker = i + 1;
+            profiler.name_worker(worker, format!("background thread {i}"));
             thread::spawn(move || {
-                background_task_thread(rcv_clone, send_clone);
+                set_thread_profiler(profiler.clone(), worker);
+                background_task_thread(rcv_clone, send_clone, profiler, worker);
             });
         }
         out
     }
 
+    fn spawn_task_thread(&mut self) {
+        let send_clone = self.stop_send.clone();
+        let rcv_clone = self.task_rcv.clone();
+        let data = HT::data_from_handler(&self.handler);
+        let profiler = self.profiler.clone();
+        let busy_nanos = self.busy_nanos.clone();
+        let worker_states = self.worker_states.clone();
+        let worker = self.next_worker;
+        profiler.name_worker(worker, format!("task thread {}", worker - self.number_of_background_threads - 1));
+        thread::spawn(move || {
+            set_thread_profiler(profiler.clone(), worker);
+            task_thread(rcv_clone, send_clone, data, profiler, worker, busy_nanos, worker_states);
+        });
+        self.next_worker += 1;
+        self.number_of_threads += 1;
+    }
+
+    pub fn get_number_of_threads(&self) -> usize {
+        self.number_of_threads
+    }
+
+    /// Only call between ticks, extra threads are stopped the next time they are idle
+    ///
+    /// Tasks still ask for `max_threads` threads, with fewer workers their parts just run one after the other, which costs little for tasks using a `ChunkSource`
+    pub fn set_number_of_threads(&mut self, number_of_threads:usize) {
+        let number_of_threads = number_of_threads.max(1);
+        while self.number_of_threads < number_of_threads {
+            self.spawn_task_thread();
+        }
+        while self.number_of_threads > number_of_threads {
+            self.send.send(SchedulerTask::Stop).expect("couldn't send task for some reason");
+            self.number_of_threads -= 1;
+        }
+        self.idle_threads = self.number_of_threads.saturating_sub(self.tasks_in_flight);
+    }
+
+    /// Time worker threads spent in tasks since the last call
+    pub fn take_busy_time(&self) -> Duration {
+        Duration::from_nanos(self.busy_nanos.swap(0, Ordering::Relaxed))
+    }
+
+    /// Call after each tick with its duration, resizes the worker pool following `policy` and returns the new number of threads
+    pub fn adapt_threads(&mut self, policy:&AdaptiveThreads, tick_time:Duration) -> usize {
+        let busy = self.take_busy_time();
+        let available = tick_time.as_secs_f64() * self.number_of_threads as f64;
+        let utilisation = if available > 0.0 {(busy.as_secs_f64() / available).min(1.0)} else {1.0};
+        let wanted = policy.wanted_threads(self.number_of_threads, tick_time, utilisation);
+        if wanted != self.number_of_threads {
+            self.set_number_of_threads(wanted);
+        }
+        self.number_of_threads
+    }
+
     /// this associated function sets a new queue up to be used in the next tick, it MUST be called before scheduler.tick() for anything to happen
     pub fn initialise(&mut self, new_queue:HordeTaskQueue<HT>) {
+        self.task_counter.retain(|_, counter| *counter > 0);
         self.current_tasks = new_queue;
         self.current_tasks.tasks[0].start_sequence();
     }
 
-    /// Tick executes the queue that the scheduler currently has, and may block indefinitely if the queue is impossible to finish
-    pub fn tick(&mut self) {
+    /// Ticks taking longer than `timeout` end with `SchedulerError::Timeout` instead of blocking forever, `None` (the default) disables the watchdog
+    pub fn set_watchdog(&mut self, timeout:Option<Duration>) {
+        self.watchdog_timeout = timeout;
+        self.worker_states.set_enabled(timeout.is_some());
+    }
+
+    /// Once a tick timed out, every following tick returns the same `SchedulerError::Timeout` right away, until `clear_timeout` succeeds
+    pub fn has_timed_out(&self) -> bool {
+        self.timed_out.is_some()
+    }
+
+    /// Clears the timeout once every task part still running when it fired has finished, returns false (and keeps it) while some haven't
+    ///
+    /// The tick that timed out is abandoned along with its panics, call `initialise` with a new queue before ticking again
+    pub fn clear_timeout(&mut self) -> bool {
+        if self.timed_out.is_none() {
+            return true
+        }
+        while let Ok(stop) = self.rcv.try_recv() {
+            self.count_task_stop(stop);
+        }
+        if self.tasks_in_flight > 0 {
+            return false
+        }
+        self.timed_out = None;
+        self.panics.clear();
+        self.task_counter.clear();
+        true
+    }
+
+    fn check_timed_out(&self) -> Result<(), SchedulerError<HT>> {
+        match &self.timed_out {
+            Some(dump) => Err(SchedulerError::Timeout(dump.clone())),
+            None => Ok(())
+        }
+    }
+
+    /// Tick executes the queue that the scheduler currently has, it may block indefinitely if the queue is impossible to finish and no watchdog is set
+    ///
+    /// Panics in tasks are caught and returned once the tick is over
+    pub fn tick(&mut self) -> Result<(), SchedulerError<HT>> {
+        self.check_timed_out()?;
+        let start = Instant::now();
+        self.run_background_callbacks();
+        self.run_queue(start)?;
+        self.end_tick(start)?;
+        self.check_sequences_finished()
+    }
+
+    /// Executes the queue like `tick` for a render frame, without the end of tick bookkeeping : the tick count doesn't move, periodic and budgeted tasks don't run and it's profiled as a frame
+    pub fn frame(&mut self) -> Result<(), SchedulerError<HT>> {
+        self.check_timed_out()?;
+        let start = Instant::now();
+        self.run_queue(start)?;
+        self.profiler.record("frame".to_string(), ProfileKind::Frame, 0, start, Instant::now());
+        self.take_panics()?;
+        self.check_sequences_finished()
+    }
+
+    /// A sequence still waiting once the queue ran out waits for a task no sequence started this tick
+    fn check_sequences_finished(&self) -> Result<(), SchedulerError<HT>> {
+        let mut waiting_for = Vec::new();
+        for (i, seq) in self.current_tasks.tasks.iter().enumerate() {
+            if seq.get_state() == SequenceState::Started {
+                if let SequencedTask::WaitFor(task) = seq.current_task() {
+                    waiting_for.push((i, task));
+                }
+            }
+        }
+        if waiting_for.is_empty() {
+            Ok(())
+        }
+        else {
+            Err(SchedulerError::NeverStarted(waiting_for))
+        }
+    }
+
+    fn run_queue(&mut self, start:Instant) -> Result<(), SchedulerError<HT>> {
         let mut finished = false;
         while !finished {
-            if !self.advance_all_sequences() && !self.receive_stop() {
+            if !self.advance_all_sequences() && !self.receive_stop(start)? {
                 finished = true;
             }
         }
+        Ok(())
+    }
+
+    /// Runs the periodic tasks due this tick, then the budgeted tasks, none of that happens on ticks paused by the time control
+    fn end_tick(&mut self, start:Instant) -> Result<(), SchedulerError<HT>> {
+        if self.time_control.as_ref().is_some_and(|time_control| !time_control.is_tick_running()) {
+            self.profiler.record("tick".to_string(), ProfileKind::Tick, 0, start, Instant::now());
+            return self.take_panics()
+        }
+        let due:Vec<HT> = self.periodic.iter().filter(|periodic| periodic.is_due(self.tick_count)).map(|periodic| periodic.task.clone()).collect();
+        for task in due.iter() {
+            self.start_task(task.clone());
+        }
+        while due.iter().any(|task| self.task_counter.get(task).is_some_and(|counter| *counter > 0)) {
+            self.receive_task_stop(start)?;
+        }
+        let profiler = self.profiler.clone();
+        self.budgeted.run(self.tick_budget.map(|budget| (start, budget)), |name, task_start| {
+            profiler.record(name, ProfileKind::Task, 0, task_start, Instant::now());
+        });
+        self.tick_count += 1;
+        self.profiler.record("tick".to_string(), ProfileKind::Tick, 0, start, Instant::now());
+        self.take_panics()
+    }
+
+    /// Ticks paused by `time_control` don't count and don't run periodic or budgeted tasks, the queue has to call its `begin_tick`, like the `BEGIN_TICK_TASK` of engines with `#[do_time_control]`
+    pub fn set_time_control(&mut self, time_control:Option<TimeControl>) {
+        self.time_control = time_control;
+    }
+
+    /// Number of simulation ticks done since the scheduler was created, used to know which periodic tasks are due
+    pub fn get_tick_count(&self) -> u64 {
+        self.tick_count
+    }
+
+    /// Starts `task` after the queue of every tick where `tick % interval == phase % interval`, the tick waits for it to finish
+    ///
+    /// The task shouldn't also be in the queue
+    pub fn add_periodic(&mut self, task:HT, interval:u64, phase:u64) {
+        self.periodic.push(PeriodicTask::new(task, interval, phase));
+    }
+
+    pub fn remove_periodic(&mut self, task:&HT) {
+        self.periodic.retain(|periodic| periodic.task != *task);
+    }
+
+    /// `task` runs for about `slice` at the end of every tick until it returns `BudgetedStep::Done`, higher `priority` tasks run first and get the time left under the tick budget first
+    pub fn add_budgeted<T:BudgetedTask + 'static>(&mut self, task:T, slice:Duration, priority:i32) -> BudgetedTaskID {
+        self.budgeted.add(Box::new(task), slice, priority)
+    }
+
+    /// Returns false if the task was already done or removed
+    pub fn remove_budgeted(&mut self, id:BudgetedTaskID) -> bool {
+        self.budgeted.remove(id)
+    }
+
+    pub fn get_number_of_budgeted(&self) -> usize {
+        self.budgeted.len()
+    }
+
+    /// Time a whole tick should take, budgeted tasks that yielded share what is left of it after their own slices, `None` (the default) gives them no leftover time
+    pub fn set_tick_budget(&mut self, budget:Option<Duration>) {
+        self.tick_budget = budget;
+    }
+
+    fn take_panics(&mut self) -> Result<(), SchedulerError<HT>> {
+        if self.panics.is_empty() {
+            Ok(())
+        }
+        else {
+            Err(SchedulerError::TaskPanicked(std::mem::take(&mut self.panics)))
+        }
+    }
+
+    /// Waits for one part of a task to finish, returns it once its counter is updated
+    fn receive_task_stop(&mut self, tick_start:Instant) -> Result<HT, SchedulerError<HT>> {
+        let stop = match self.watchdog_timeout {
+            Some(timeout) => match self.rcv.recv_timeout((tick_start + timeout).saturating_duration_since(Instant::now())) {
+                Ok(stop) => stop,
+                Err(RecvTimeoutError::Timeout) => {
+                    let dump = self.get_dump(tick_start);
+                    self.timed_out = Some(dump.clone());
+                    return Err(SchedulerError::Timeout(dump))
+                },
+                Err(error) => panic!("{}", error)
+            },
+            None => match self.rcv.recv() {
+                Ok(stop) => stop,
+                Err(error) => panic!("{}", error)
+            }
+        };
+        Ok(self.count_task_stop(stop))
+    }
+
+    fn count_task_stop(&mut self, stop:TaskStop<HT>) -> HT {
+        let task = match stop {
+            TaskStop::Finished(task) => task,
+            TaskStop::Panicked(panic) => {
+                let task = panic.task.clone();
+                self.panics.push(panic);
+                task
+            }
+        };
+        match self.task_counter.get_mut(&task) {
+            Some(counter) => *counter -= 1,
+            None => panic!("task end received before start... OH MY GOD !")
+        }
+        self.tasks_in_flight -= 1;
+        self.idle_threads = self.number_of_threads.saturating_sub(self.tasks_in_flight);
+        task
+    }
+
+    /// What every thread, task and sequence is doing right now
+    pub fn get_dump(&self, tick_start:Instant) -> SchedulerDump<HT> {
+        let mut unfinished = Vec::new();
+        let mut finished = Vec::new();
+        for (task, counter) in self.task_counter.iter() {
+            if *counter > 0 {
+                unfinished.push((task.clone(), *counter));
+            }
+            else {
+                finished.push(task.clone());
+            }
+        }
+        let mut waiting_for = Vec::new();
+        for (i, seq) in self.current_tasks.tasks.iter().enumerate() {
+            if seq.get_state() == SequenceState::Started {
+                if let SequencedTask::WaitFor(task) = seq.current_task() {
+                    waiting_for.push((i, task));
+                }
+            }
+        }
+        SchedulerDump { elapsed: tick_start.elapsed(), workers: self.worker_states.get_all(), unfinished, finished, waiting_for }
+    }
+
+    /// Runs every task of the graph once, starting each one as soon as all of its dependencies are done, doesn't need `initialise`
+    pub fn tick_graph(&mut self, graph:&TaskGraph<HT>) -> Result<(), SchedulerError<HT>> {
+        self.check_timed_out()?;
+        let start = Instant::now();
+        self.run_background_callbacks();
+        let mut remaining = graph.dependency_counts();
+        let mut ready:VecDeque<usize> = (0..graph.len()).filter(|i| remaining[*i] == 0).collect();
+        let mut finished = 0;
+        while finished < graph.len() {
+            while let Some(index) = ready.pop_front() {
+                self.start_task(graph.get_task(index).clone());
+            }
+            let stop = self.receive_task_stop(start)?;
+            if self.task_counter.get(&stop) == Some(&0) {
+                finished += 1;
+                let index = graph.index_of(&stop).expect("Task that isn't in the graph finished");
+                for dependent in graph.dependents_of(index) {
+                    remaining[*dependent] -= 1;
+                    if remaining[*dependent] == 0 {
+                        ready.push_back(*dependent);
+                    }
+                }
+            }
+            while let Ok(stop) = self.background_task_stop.try_recv() {
+                ()
+            }
+        }
+        self.end_tick(start)
+    }
+
+    /// The profiler is disabled by default, enable it or start a trace through this handle
+    pub fn get_profiler(&self) -> HordeProfiler {
+        self.profiler.clone()
+    }
+
+    /// Runs `work` on a background thread, its output can be polled or awaited through the handle
+    pub fn spawn_background<T:Send + 'static, F:FnOnce(&BackgroundContext) -> T + Send + 'static>(&self, name:&str, work:F) -> BackgroundHandle<T> {
+        let (job, handle) = new_job(name, work);
+        self.background_task_sender.send(SchedulerBackgroundTask::Job(job)).expect("couldn't send background job for some reason");
+        handle
+    }
+
+    /// Runs `work` on a background thread, then `callback` on the thread calling `tick` at the start of the tick following its completion
+    pub fn spawn_background_with_callback<T:Send + 'static, F:FnOnce(&BackgroundContext) -> T + Send + 'static, C:FnOnce(Result<T, BackgroundError>) + Send + 'static>(&self, name:&str, work:F, callback:C) -> BackgroundStatus {
+        let (job, status) = new_job_with_callback(name, work, callback, self.callbacks_send.clone());
+        self.background_task_sender.send(SchedulerBackgroundTask::Job(job)).expect("couldn't send background job for some reason");
+        status
+    }
+
+    /// Called at the start of every tick, can also be called between ticks
+    pub fn run_background_callbacks(&mut self) {
+        while let Ok(callback) = self.callbacks_rcv.try_recv() {
+            callback();
+        }
     }
 
     pub fn get_background_send(&self) -> Sender<SchedulerBackgroundTask<HBT>> {
         self.background_task_sender.clone()
     }
-    fn receive_stop(&mut self) -> bool {
+    fn receive_stop(&mut self, tick_start:Instant) -> Result<bool, SchedulerError<HT>> {
         let mut anything_to_receive = false;
         for task in &self.current_tasks.must_be_finished_before_next {
             match self.task_counter.get(task) {
@@ -173,25 +531,16 @@ impl<HT:HordeTask + 'static, HBT:HordeBackgroundTask + 'static> HordeScheduler<H
             }
         }
         if anything_to_receive {
-            match self.rcv.recv() {
-                Ok(stop) => match self.task_counter.get_mut(&stop) {
-                    Some(counter) => {
-                        *counter -= 1;
-                        if self.current_tasks.must_be_finished_before_next.contains(&stop) && *counter == 0 {
-                            self.current_tasks.must_be_finished_before_next.retain(|tsk| {*tsk != stop});
-                            //dbg!(stop);
-                        }
-                        self.tasks_in_flight -= 1;
-                    },
-                    None => panic!("task end received before start... OH MY GOD !")
-                },
-                Err(error) => panic!("{}", error)
+            let stop = self.receive_task_stop(tick_start)?;
+            if self.current_tasks.must_be_finished_before_next.contains(&stop) && self.task_counter.get(&stop) == Some(&0) {
+                self.current_tasks.must_be_finished_before_next.retain(|tsk| {*tsk != stop});
+                //dbg!(stop);
             }
         }
         while let Ok(stop) = self.background_task_stop.try_recv() {
             ()
         }
-        anything_to_receive
+        Ok(anything_to_receive)
     }
     fn start_task(&mut self, task:HT) {
         let threads = task.max_threads();
@@ -224,7 +573,7 @@ impl<HT:HordeTask + 'static, HBT:HordeBackgroundTask + 'static> HordeScheduler<H
                         }
                         match self.task_counter.get(&tsk) {
                             Some(counter) => if *counter == 0 {seq.advance_sequence(); advanced_a_sequence = true;}
-                            None => panic!("waiting for a task end before it started... THE HORROR !")
+                            None => () // Another sequence hasn't started it yet, if none does the tick ends with SchedulerError::NeverStarted
                         }
                     }
                     
@@ -254,24 +603,63 @@ impl<HT:HordeTask + 'static, HBT:HordeBackgroundTask + 'static> HordeScheduler<H
     }
 }
 
-fn task_thread<HT:HordeTask>(task_rcv:Receiver<SchedulerTask<HT>>, stop_send:Sender<HT>, mut data:HT::HTD) {
+fn task_thread<HT:HordeTask>(task_rcv:Receiver<SchedulerTask<HT>>, stop_send:Sender<TaskStop<HT>>, mut data:HT::HTD, profiler:HordeProfiler, worker:usize, busy_nanos:Arc<AtomicU64>, worker_states:WorkerStates) {
+    worker_states.set(worker, WorkerState::Idle);
     loop {
         match task_rcv.recv() {
             Ok(sc_task) => match sc_task {
-                SchedulerTask::Stop => break,
-                SchedulerTask::Task{tsk, thread_number, number_of_threads_on_task} => {data.do_task(tsk.clone(), thread_number, number_of_threads_on_task); stop_send.send(tsk).expect("couldn't send stop womp womp"); }
+                SchedulerTask::Stop => {
+                    worker_states.remove(worker);
+                    break
+                },
+                SchedulerTask::Task{tsk, thread_number, number_of_threads_on_task} => {
+                    let start = Instant::now();
+                    let watched = worker_states.is_enabled();
+                    if watched {
+                        worker_states.set(worker, WorkerState::Running { task: format!("{:?}", tsk), thread_number, since: start });
+                    }
+                    let result = catch_unwind(AssertUnwindSafe(|| data.do_task(tsk.clone(), thread_number, number_of_threads_on_task)));
+                    if watched {
+                        worker_states.set(worker, WorkerState::Idle);
+                    }
+                    let end = Instant::now();
+                    busy_nanos.fetch_add((end - start).as_nanos() as u64, Ordering::Relaxed);
+                    if profiler.is_enabled() {
+                        profiler.record(format!("{:?}", tsk), ProfileKind::Task, worker, start, end);
+                    }
+                    let stop = match result {
+                        Ok(()) => TaskStop::Finished(tsk),
+                        Err(payload) => TaskStop::Panicked(TaskPanic { task: tsk, worker, thread_number, message: panic_message(payload) })
+                    };
+                    stop_send.send(stop).expect("couldn't send stop womp womp");
+                }
             }
             Err(error) => panic!("grosse erreur dans thread de travail {}", error)
         }
     }
 }
 
-fn background_task_thread<HBT:HordeBackgroundTask>(recv_bg:Receiver<SchedulerBackgroundTask<HBT>>, stop_send:Sender<usize>) {
+fn background_task_thread<HBT:HordeBackgroundTask>(recv_bg:Receiver<SchedulerBackgroundTask<HBT>>, stop_send:Sender<usize>, profiler:HordeProfiler, worker:usize) {
     loop {
         match recv_bg.recv() {
             Ok(sc_task) => match sc_task {
                 SchedulerBackgroundTask::Stop => break,
-                SchedulerBackgroundTask::Task{tsk, thread_number, number_of_threads_on_task} => {tsk.perform(); stop_send.send(1).expect("couldn't send stop womp womp"); }
+                SchedulerBackgroundTask::Task{tsk, thread_number, number_of_threads_on_task} => {
+                    let name = if profiler.is_enabled() {Some(tsk.profile_name())} else {None};
+                    let start = Instant::now();
+                    tsk.perform();
+                    if let Some(name) = name {
+                        profiler.record(name, ProfileKind::Background, worker, start, Instant::now());
+                    }
+                    stop_send.send(1).expect("couldn't send stop womp womp");
+                },
+                SchedulerBackgroundTask::Job(job) => {
+                    let name = job.get_name().to_string();
+                    let start = Instant::now();
+                    job.perform();
+                    profiler.record(name, ProfileKind::Background, worker, start, Instant::now());
+                    stop_send.send(1).expect("couldn't send stop womp womp");
+                }
             }
             Err(error) => panic!("grosse erreur dans thread de travail {}", error)
         }
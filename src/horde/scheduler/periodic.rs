@@ -0,0 +1,149 @@
+use std::time::{Duration, Instant};
+
+use super::HordeTask;
+
+/// A task started by the scheduler on every tick where `tick % interval == phase % interval`, after the queue of that tick is done
+///
+/// Spread tasks with the same interval over different phases so they don't all land on the same tick
+#[derive(Clone, Debug, PartialEq)]
+pub struct PeriodicTask<HT:HordeTask> {
+    pub task:HT,
+    pub interval:u64,
+    pub phase:u64,
+}
+
+impl<HT:HordeTask> PeriodicTask<HT> {
+    pub fn new(task:HT, interval:u64, phase:u64) -> Self {
+        let interval = interval.max(1);
+        Self { task, interval, phase: phase % interval }
+    }
+    pub fn is_due(&self, tick:u64) -> bool {
+        tick % self.interval == self.phase
+    }
+}
+
+/// Time given to one run of a `BudgetedTask`
+#[derive(Clone, Copy, Debug)]
+pub struct TaskBudget {
+    start:Instant,
+    slice:Duration,
+}
+
+impl TaskBudget {
+    pub fn new(slice:Duration) -> Self {
+        Self { start: Instant::now(), slice }
+    }
+    pub fn get_slice(&self) -> Duration {
+        self.slice
+    }
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+    pub fn remaining(&self) -> Duration {
+        self.slice.saturating_sub(self.start.elapsed())
+    }
+    /// Check this regularly and yield once it is true
+    pub fn is_exhausted(&self) -> bool {
+        self.start.elapsed() >= self.slice
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BudgetedStep {
+    /// Not done, run again with the next budget
+    Yield,
+    /// Removed from the scheduler
+    Done,
+}
+
+/// Work split over as many ticks as it needs, it keeps its own progress between runs
+///
+/// Runs on the scheduler thread once the tick's tasks are done, so it can't block task threads
+pub trait BudgetedTask:Send {
+    fn run(&mut self, budget:&TaskBudget) -> BudgetedStep;
+    /// Name given to this task in profiles
+    fn profile_name(&self) -> String {
+        "budgeted task".to_string()
+    }
+}
+
+impl<F:FnMut(&TaskBudget) -> BudgetedStep + Send> BudgetedTask for F {
+    fn run(&mut self, budget:&TaskBudget) -> BudgetedStep {
+        self(budget)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct BudgetedTaskID {
+    pub id:usize,
+}
+
+pub(crate) struct BudgetedEntry {
+    pub id:BudgetedTaskID,
+    pub task:Box<dyn BudgetedTask>,
+    pub slice:Duration,
+    pub priority:i32,
+}
+
+/// Budgeted tasks sorted by priority, highest first, in order of registration for equal priorities
+pub(crate) struct BudgetedTasks {
+    entries:Vec<BudgetedEntry>,
+    next_id:usize,
+}
+
+impl BudgetedTasks {
+    pub fn new() -> Self {
+        Self { entries: Vec::new(), next_id: 0 }
+    }
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+    pub fn add(&mut self, task:Box<dyn BudgetedTask>, slice:Duration, priority:i32) -> BudgetedTaskID {
+        let id = BudgetedTaskID { id: self.next_id };
+        self.next_id += 1;
+        let position = self.entries.iter().position(|entry| entry.priority < priority).unwrap_or(self.entries.len());
+        self.entries.insert(position, BudgetedEntry { id, task, slice, priority });
+        id
+    }
+    pub fn remove(&mut self, id:BudgetedTaskID) -> bool {
+        let len = self.entries.len();
+        self.entries.retain(|entry| entry.id != id);
+        len != self.entries.len()
+    }
+    /// Every task gets its own slice, then the ones that yielded share what is left of `leftover` by priority
+    ///
+    /// `on_run` is called with the name and start of every run, for profiling
+    pub fn run<F:FnMut(String, Instant)>(&mut self, leftover:Option<(Instant, Duration)>, mut on_run:F) {
+        let mut yielded = vec![false ; self.entries.len()];
+        let mut done = vec![false ; self.entries.len()];
+        for (i, entry) in self.entries.iter_mut().enumerate() {
+            let start = Instant::now();
+            match entry.task.run(&TaskBudget::new(entry.slice)) {
+                BudgetedStep::Done => done[i] = true,
+                BudgetedStep::Yield => yielded[i] = true,
+            }
+            on_run(entry.task.profile_name(), start);
+        }
+        if let Some((tick_start, tick_budget)) = leftover {
+            for (i, entry) in self.entries.iter_mut().enumerate() {
+                if !yielded[i] {
+                    continue
+                }
+                let left = tick_budget.saturating_sub(tick_start.elapsed());
+                if left.is_zero() {
+                    break
+                }
+                let start = Instant::now();
+                if entry.task.run(&TaskBudget::new(left)) == BudgetedStep::Done {
+                    done[i] = true;
+                }
+                on_run(entry.task.profile_name(), start);
+            }
+        }
+        let mut i = 0;
+        self.entries.retain(|_| {
+            i += 1;
+            !done[i - 1]
+        });
+    }
+}
@@ -0,0 +1,236 @@
+use std::{cell::RefCell, collections::{HashMap, VecDeque}, fs::File, io::{BufWriter, Write}, path::PathBuf, sync::{atomic::{AtomicBool, Ordering}, Arc, RwLock}, time::{Duration, Instant}};
+
+thread_local! {
+    static CURRENT_PROFILER:RefCell<Option<(HordeProfiler, usize)>> = RefCell::new(None);
+}
+
+/// Called by scheduler threads when they start, so `profile_scope` knows where to record
+pub fn set_thread_profiler(profiler:HordeProfiler, worker:usize) {
+    CURRENT_PROFILER.with(|current| *current.borrow_mut() = Some((profiler, worker)));
+}
+
+/// Records a span until the returned guard is dropped, does nothing outside of scheduler threads or when the profiler is disabled
+pub fn profile_scope(name:&str, kind:ProfileKind) -> Option<ProfileGuard> {
+    CURRENT_PROFILER.with(|current| match &*current.borrow() {
+        Some((profiler, worker)) => profiler.scope(name, kind, *worker),
+        None => None
+    })
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum ProfileKind {
+    Tick,
+    /// Render frames run through `HordeScheduler::frame`
+    Frame,
+    Task,
+    Stage,
+    Background,
+}
+
+impl ProfileKind {
+    fn category(&self) -> &'static str {
+        match self {
+            ProfileKind::Tick => "tick",
+            ProfileKind::Frame => "frame",
+            ProfileKind::Task => "task",
+            ProfileKind::Stage => "stage",
+            ProfileKind::Background => "background",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ProfileSpan {
+    pub name:String,
+    pub kind:ProfileKind,
+    /// 0 is the thread calling `HordeScheduler::tick`, then come task threads and background threads
+    pub worker:usize,
+    /// Since the creation of the profiler
+    pub start:Duration,
+    pub end:Duration,
+}
+
+impl ProfileSpan {
+    pub fn duration(&self) -> Duration {
+        self.end.saturating_sub(self.start)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct SpanStats {
+    pub samples:usize,
+    pub average:Duration,
+    pub p50:Duration,
+    pub p95:Duration,
+    pub p99:Duration,
+    pub max:Duration,
+}
+
+impl SpanStats {
+    fn from_window(window:&VecDeque<Duration>) -> Option<Self> {
+        if window.is_empty() {
+            return None
+        }
+        let mut sorted:Vec<Duration> = window.iter().copied().collect();
+        sorted.sort();
+        let percentile = |p:f64| sorted[((sorted.len() - 1) as f64 * p).round() as usize];
+        Some(Self {
+            samples: sorted.len(),
+            average: sorted.iter().sum::<Duration>() / sorted.len() as u32,
+            p50: percentile(0.50),
+            p95: percentile(0.95),
+            p99: percentile(0.99),
+            max: *sorted.last().unwrap()
+        })
+    }
+}
+
+struct ProfilerData {
+    windows:HashMap<(String, ProfileKind), VecDeque<Duration>>,
+    trace:Vec<ProfileSpan>,
+    worker_names:HashMap<usize, String>,
+}
+
+/// Shared handle recording how long scheduler tasks, engine tick stages and background tasks take
+///
+/// Durations are kept in rolling windows for statistics, spans are only kept for traces between `start_trace` and `write_chrome_trace`
+#[derive(Clone)]
+pub struct HordeProfiler {
+    enabled:Arc<AtomicBool>,
+    tracing:Arc<AtomicBool>,
+    epoch:Instant,
+    window_len:usize,
+    data:Arc<RwLock<ProfilerData>>,
+}
+
+/// Records its span when dropped
+pub struct ProfileGuard {
+    profiler:HordeProfiler,
+    name:String,
+    kind:ProfileKind,
+    worker:usize,
+    start:Instant,
+}
+
+impl Drop for ProfileGuard {
+    fn drop(&mut self) {
+        self.profiler.record(std::mem::take(&mut self.name), self.kind, self.worker, self.start, Instant::now());
+    }
+}
+
+impl HordeProfiler {
+    /// `window_len` is the number of samples per span name used for statistics
+    pub fn new(window_len:usize) -> Self {
+        Self {
+            enabled: Arc::new(AtomicBool::new(false)),
+            tracing: Arc::new(AtomicBool::new(false)),
+            epoch: Instant::now(),
+            window_len: window_len.max(1),
+            data: Arc::new(RwLock::new(ProfilerData { windows: HashMap::new(), trace: Vec::new(), worker_names: HashMap::new() }))
+        }
+    }
+    pub fn enable(&self) {
+        self.enabled.store(true, Ordering::Relaxed);
+    }
+    pub fn disable(&self) {
+        self.enabled.store(false, Ordering::Relaxed);
+        self.tracing.store(false, Ordering::Relaxed);
+    }
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+    /// Enables the profiler and starts keeping every span, previous trace spans are thrown away
+    pub fn start_trace(&self) {
+        self.data.write().unwrap().trace.clear();
+        self.enable();
+        self.tracing.store(true, Ordering::Relaxed);
+    }
+    pub fn is_tracing(&self) -> bool {
+        self.tracing.load(Ordering::Relaxed)
+    }
+    pub fn name_worker(&self, worker:usize, name:String) {
+        self.data.write().unwrap().worker_names.insert(worker, name);
+    }
+    pub fn scope(&self, name:&str, kind:ProfileKind, worker:usize) -> Option<ProfileGuard> {
+        if self.is_enabled() {
+            Some(ProfileGuard { profiler: self.clone(), name: name.to_string(), kind, worker, start: Instant::now() })
+        }
+        else {
+            None
+        }
+    }
+    pub fn record(&self, name:String, kind:ProfileKind, worker:usize, start:Instant, end:Instant) {
+        if !self.is_enabled() {
+            return
+        }
+        let span = ProfileSpan { name, kind, worker, start: start.saturating_duration_since(self.epoch), end: end.saturating_duration_since(self.epoch) };
+        let mut data = self.data.write().unwrap();
+        let window = data.windows.entry((span.name.clone(), kind)).or_insert_with(|| VecDeque::with_capacity(self.window_len));
+        if window.len() >= self.window_len {
+            window.pop_front();
+        }
+        window.push_back(span.duration());
+        if self.is_tracing() {
+            data.trace.push(span);
+        }
+    }
+    pub fn get_stats(&self, name:&str, kind:ProfileKind) -> Option<SpanStats> {
+        self.data.read().unwrap().windows.get(&(name.to_string(), kind)).and_then(SpanStats::from_window)
+    }
+    /// Sorted by average duration, longest first
+    pub fn get_all_stats(&self) -> Vec<(String, ProfileKind, SpanStats)> {
+        let data = self.data.read().unwrap();
+        let mut all_stats:Vec<(String, ProfileKind, SpanStats)> = data.windows.iter().filter_map(|((name, kind), window)| SpanStats::from_window(window).map(|stats| (name.clone(), *kind, stats))).collect();
+        all_stats.sort_by(|a, b| b.2.average.cmp(&a.2.average));
+        all_stats
+    }
+    pub fn reset_stats(&self) {
+        self.data.write().unwrap().windows.clear();
+    }
+    /// Stops tracing and writes every span since `start_trace` as a Chrome `about:tracing` / Perfetto JSON file
+    pub fn write_chrome_trace(&self, path:PathBuf) -> std::io::Result<()> {
+        self.tracing.store(false, Ordering::Relaxed);
+        let data = self.data.read().unwrap();
+        let mut writer = BufWriter::new(File::create(path)?);
+        write!(writer, "{{\"traceEvents\":[")?;
+        let mut first = true;
+        for (worker, name) in data.worker_names.iter() {
+            if !first {
+                write!(writer, ",")?;
+            }
+            first = false;
+            write!(writer, "{{\"name\":\"thread_name\",\"ph\":\"M\",\"pid\":0,\"tid\":{},\"args\":{{\"name\":\"{}\"}}}}", worker, escape_json(name))?;
+        }
+        for span in data.trace.iter() {
+            if !first {
+                write!(writer, ",")?;
+            }
+            first = false;
+            write!(
+                writer,
+                "{{\"name\":\"{}\",\"cat\":\"{}\",\"ph\":\"X\",\"ts\":{:.3},\"dur\":{:.3},\"pid\":0,\"tid\":{}}}",
+                escape_json(&span.name),
+                span.kind.category(),
+                span.start.as_secs_f64() * 1_000_000.0,
+                span.duration().as_secs_f64() * 1_000_000.0,
+                span.worker
+            )?;
+        }
+        write!(writer, "],\"displayTimeUnit\":\"ms\"}}")?;
+        writer.flush()
+    }
+}
+
+fn escape_json(text:&str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for character in text.chars() {
+        match character {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            character if character.is_control() => escaped.push_str(&format!("\\u{:04x}", character as u32)),
+            character => escaped.push(character),
+        }
+    }
+    escaped
+}
@@ -0,0 +1,198 @@
+use std::{sync::{atomic::{AtomicU32, Ordering}, Arc}, thread, time::{Duration, Instant}};
+
+use to_from_bytes::{FromBytes, ToBytes};
+
+use crate::horde::{game_engine::{deferred_events::DeferredEvents, time_control::TimeControl}, geometry::{vec3d::Vec3Df, HordeFloat}};
+
+use super::{watchdog::SchedulerError, HordeBackgroundTask, HordeScheduler, HordeTask, HordeTaskQueue};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RenderRate {
+    /// Renders as fast as possible, which is also what to use when presenting the framebuffer already waits for vsync
+    Uncapped,
+    /// Sleeps after each frame to render at most that many frames per second
+    Limited(f64),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FixedStepConfig {
+    pub ticks_per_second:f64,
+    /// Ticks done in a single frame when the simulation is late, the rest of the delay is dropped
+    pub max_catch_up_ticks:usize,
+    pub render_rate:RenderRate,
+}
+
+impl FixedStepConfig {
+    pub fn new(ticks_per_second:f64, render_rate:RenderRate) -> Self {
+        Self { ticks_per_second, max_catch_up_ticks: 5, render_rate }
+    }
+    pub fn tick_duration(&self) -> Duration {
+        Duration::from_secs_f64(1.0 / self.ticks_per_second)
+    }
+}
+
+/// How far between the last tick and the next one the current frame is, in 0.0..1.0
+///
+/// It can be cloned into the rendering engine or the extra data of a game engine so that rendering tasks can interpolate positions
+#[derive(Clone)]
+pub struct InterpolationAlpha {
+    bits:Arc<AtomicU32>,
+}
+
+impl InterpolationAlpha {
+    pub fn new() -> Self {
+        Self { bits: Arc::new(AtomicU32::new(0.0_f32.to_bits())) }
+    }
+    pub fn get(&self) -> f32 {
+        f32::from_bits(self.bits.load(Ordering::Relaxed))
+    }
+    pub fn set(&self, alpha:f32) {
+        self.bits.store(alpha.to_bits(), Ordering::Relaxed);
+    }
+    pub fn interpolate(&self, previous:&Vec3Df, current:&Vec3Df) -> Vec3Df {
+        interpolate(previous, current, self.get())
+    }
+}
+
+pub fn interpolate(previous:&Vec3Df, current:&Vec3Df, alpha:f32) -> Vec3Df {
+    previous.clone() + (current.clone() - previous.clone()) * (alpha as HordeFloat)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FrameReport {
+    pub ticks_done:usize,
+    pub ticks_dropped:usize,
+    pub alpha:f32,
+    pub frame_time:Duration,
+}
+
+/// Owns the game loop : ticks the simulation queue at a fixed rate, then runs the render queue once per frame with `HordeScheduler::frame`
+pub struct FixedStepRunner<HT:HordeTask, HBT:HordeBackgroundTask> {
+    scheduler:HordeScheduler<HT, HBT>,
+    simulation_queue:HordeTaskQueue<HT>,
+    render_queue:HordeTaskQueue<HT>,
+    config:FixedStepConfig,
+    alpha:InterpolationAlpha,
+    accumulator:Duration,
+    last_frame:Option<Instant>,
+    total_ticks:usize,
+    time_control:Option<TimeControl>,
+    tick_rate_listeners:Vec<Box<dyn Fn(f64) + Send>>,
+}
+
+impl<HT:HordeTask + 'static, HBT:HordeBackgroundTask + 'static> FixedStepRunner<HT, HBT> {
+    pub fn new(scheduler:HordeScheduler<HT, HBT>, simulation_queue:HordeTaskQueue<HT>, render_queue:HordeTaskQueue<HT>, config:FixedStepConfig) -> Self {
+        Self { scheduler, simulation_queue, render_queue, config, alpha: InterpolationAlpha::new(), accumulator: Duration::ZERO, last_frame: None, total_ticks: 0, time_control: None, tick_rate_listeners: Vec::new() }
+    }
+    /// Makes the tick rate follow the time scale, and keeps the alpha at 1.0 on paused ticks so rendering shows the latest state
+    ///
+    /// Paused ticks aren't counted in `total_ticks` and the scheduler skips its periodic and budgeted tasks on them
+    pub fn with_time_control(mut self, time_control:TimeControl) -> Self {
+        self.scheduler.set_time_control(Some(time_control.clone()));
+        self.time_control = Some(time_control);
+        self
+    }
+    /// Makes `ScheduleDelay::Seconds` of `deferred` use the tick rate of the config, now and whenever it changes
+    pub fn with_deferred_events<E:Clone + ToBytes + FromBytes + Send + Sync + 'static>(mut self, deferred:DeferredEvents<E>) -> Self {
+        deferred.set_ticks_per_second(self.config.ticks_per_second as f32);
+        self.tick_rate_listeners.push(Box::new(move |ticks_per_second| deferred.set_ticks_per_second(ticks_per_second as f32)));
+        self
+    }
+    pub fn get_alpha(&self) -> InterpolationAlpha {
+        self.alpha.clone()
+    }
+    pub fn config(&self) -> &FixedStepConfig {
+        &self.config
+    }
+    pub fn set_config(&mut self, config:FixedStepConfig) {
+        if config.ticks_per_second != self.config.ticks_per_second {
+            for listener in self.tick_rate_listeners.iter() {
+                listener(config.ticks_per_second);
+            }
+        }
+        self.config = config;
+    }
+    /// Ticks where the simulation ran, paused ones excluded
+    pub fn total_ticks(&self) -> usize {
+        self.total_ticks
+    }
+    pub fn scheduler(&mut self) -> &mut HordeScheduler<HT, HBT> {
+        &mut self.scheduler
+    }
+    /// Adds `elapsed` real time, scaled by the time control, to the time the simulation is behind
+    ///
+    /// Returns how many ticks to do now, at most `max_catch_up_ticks`, and how many were dropped, both are taken out of the accumulator
+    pub fn accumulate(&mut self, elapsed:Duration) -> (usize, usize) {
+        self.accumulator += match &self.time_control {
+            Some(time_control) => elapsed.mul_f32(time_control.get_scale()),
+            None => elapsed
+        };
+        let tick_duration = self.config.tick_duration();
+        let mut ticks_due = 0;
+        let mut ticks_dropped = 0;
+        while self.accumulator >= tick_duration {
+            self.accumulator -= tick_duration;
+            if ticks_due < self.config.max_catch_up_ticks {
+                ticks_due += 1;
+            }
+            else {
+                ticks_dropped += 1;
+            }
+        }
+        (ticks_due, ticks_dropped)
+    }
+    /// Does every tick that is due, then renders one frame
+    pub fn run_frame(&mut self) -> Result<FrameReport, SchedulerError<HT>> {
+        let frame_start = Instant::now();
+        let elapsed = match self.last_frame {
+            Some(last_frame) => frame_start - last_frame,
+            None => self.config.tick_duration(),
+        };
+        self.last_frame = Some(frame_start);
+        let (ticks_due, ticks_dropped) = self.accumulate(elapsed);
+
+        let tick_duration = self.config.tick_duration();
+        let mut ticks_done = 0;
+        while ticks_done < ticks_due {
+            self.scheduler.initialise(self.simulation_queue.clone());
+            self.scheduler.tick()?;
+            ticks_done += 1;
+            if self.time_control.as_ref().is_none_or(|time_control| time_control.is_tick_running()) {
+                self.total_ticks += 1;
+            }
+        }
+
+        let alpha = match &self.time_control {
+            Some(time_control) if time_control.is_paused() => 1.0,
+            _ => (self.accumulator.as_secs_f64() / tick_duration.as_secs_f64()) as f32
+        };
+        self.alpha.set(alpha);
+        self.scheduler.initialise(self.render_queue.clone());
+        self.scheduler.frame()?;
+
+        match self.config.render_rate {
+            RenderRate::Uncapped => (),
+            RenderRate::Limited(frames_per_second) => {
+                let frame_duration = Duration::from_secs_f64(1.0 / frames_per_second);
+                let spent = frame_start.elapsed();
+                if spent < frame_duration {
+                    thread::sleep(frame_duration - spent);
+                }
+            }
+        }
+        Ok(FrameReport { ticks_done, ticks_dropped, alpha, frame_time: frame_start.elapsed() })
+    }
+    /// Runs frames until `keep_going` returns false or the scheduler returns an error
+    pub fn run<F:FnMut(&FrameReport) -> bool>(&mut self, mut keep_going:F) -> Result<(), SchedulerError<HT>> {
+        loop {
+            let report = self.run_frame()?;
+            if !keep_going(&report) {
+                break
+            }
+        }
+        Ok(())
+    }
+    pub fn end_threads(self) {
+        self.scheduler.end_threads();
+    }
+}
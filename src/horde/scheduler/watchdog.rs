@@ -0,0 +1,127 @@
+use std::{collections::HashMap, fmt::{Debug, Display}, sync::{atomic::{AtomicBool, Ordering}, Arc, RwLock, RwLockReadGuard, RwLockWriteGuard}, time::{Duration, Instant}};
+
+use super::HordeTask;
+
+/// What a task thread sends back to the scheduler when it is done with its part of a task
+pub enum TaskStop<HT:HordeTask> {
+    Finished(HT),
+    Panicked(TaskPanic<HT>),
+}
+
+#[derive(Clone, Debug)]
+pub struct TaskPanic<HT:HordeTask> {
+    pub task:HT,
+    pub worker:usize,
+    pub thread_number:usize,
+    pub message:String,
+}
+
+#[derive(Clone, Debug)]
+pub enum WorkerState {
+    Idle,
+    Running{task:String, thread_number:usize, since:Instant},
+}
+
+/// What every worker thread is doing, each one keeps its own entry up to date while the watchdog is enabled
+///
+/// A poisoned lock is recovered from : entries are replaced whole, so a panic while holding it can't leave a half written one
+#[derive(Clone)]
+pub struct WorkerStates {
+    states:Arc<RwLock<HashMap<usize, WorkerState>>>,
+    enabled:Arc<AtomicBool>,
+}
+
+impl WorkerStates {
+    pub fn new() -> Self {
+        Self { states: Arc::new(RwLock::new(HashMap::new())), enabled: Arc::new(AtomicBool::new(false)) }
+    }
+    /// Workers only report which task they run while this is set, so ticks without a watchdog don't pay for it
+    pub fn set_enabled(&self, enabled:bool) {
+        self.enabled.store(enabled, Ordering::Relaxed);
+    }
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+    fn read(&self) -> RwLockReadGuard<'_, HashMap<usize, WorkerState>> {
+        self.states.read().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+    fn write(&self) -> RwLockWriteGuard<'_, HashMap<usize, WorkerState>> {
+        self.states.write().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+    pub fn set(&self, worker:usize, state:WorkerState) {
+        self.write().insert(worker, state);
+    }
+    pub fn remove(&self, worker:usize) {
+        self.write().remove(&worker);
+    }
+    /// Sorted by worker
+    pub fn get_all(&self) -> Vec<(usize, WorkerState)> {
+        let mut states:Vec<(usize, WorkerState)> = self.read().iter().map(|(worker, state)| (*worker, state.clone())).collect();
+        states.sort_by_key(|(worker, _)| *worker);
+        states
+    }
+}
+
+/// State of the scheduler when the watchdog fired
+#[derive(Clone, Debug)]
+pub struct SchedulerDump<HT:HordeTask> {
+    pub elapsed:Duration,
+    pub workers:Vec<(usize, WorkerState)>,
+    /// Started tasks with the number of their parts that haven't finished yet
+    pub unfinished:Vec<(HT, usize)>,
+    pub finished:Vec<HT>,
+    /// Tasks that sequences (by index) are blocked on
+    pub waiting_for:Vec<(usize, HT)>,
+}
+
+impl<HT:HordeTask> Display for SchedulerDump<HT> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Tick stuck for {:?}", self.elapsed)?;
+        for (worker, state) in self.workers.iter() {
+            match state {
+                WorkerState::Idle => writeln!(f, "  worker {} : idle", worker)?,
+                WorkerState::Running { task, thread_number, since } => writeln!(f, "  worker {} : running {} (part {}) for {:?}", worker, task, thread_number, since.elapsed())?,
+            }
+        }
+        for (task, parts) in self.unfinished.iter() {
+            writeln!(f, "  unfinished : {:?} ({} parts left)", task, parts)?;
+        }
+        for task in self.finished.iter() {
+            writeln!(f, "  finished : {:?}", task)?;
+        }
+        for (sequence, task) in self.waiting_for.iter() {
+            writeln!(f, "  sequence {} waiting for {:?}", sequence, task)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum SchedulerError<HT:HordeTask> {
+    /// The tick ran longer than the watchdog timeout, the stuck tasks may still be running so the scheduler refuses to tick again and keeps returning this dump until `HordeScheduler::clear_timeout`
+    Timeout(SchedulerDump<HT>),
+    /// The tick still finished, every part of the task that panicked counts as done
+    TaskPanicked(Vec<TaskPanic<HT>>),
+    /// The queue ran out while sequences (by index) were still waiting for tasks that no sequence started this tick, the rest of those sequences didn't run
+    NeverStarted(Vec<(usize, HT)>),
+}
+
+impl<HT:HordeTask> Display for SchedulerError<HT> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SchedulerError::Timeout(dump) => write!(f, "{}", dump),
+            SchedulerError::TaskPanicked(panics) => {
+                for panic in panics.iter() {
+                    writeln!(f, "Task {:?} panicked on worker {} (part {}) : {}", panic.task, panic.worker, panic.thread_number, panic.message)?;
+                }
+                Ok(())
+            },
+            SchedulerError::NeverStarted(waiting_for) => {
+                for (sequence, task) in waiting_for.iter() {
+                    writeln!(f, "Sequence {} waited for {:?}, which was never started", sequence, task)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
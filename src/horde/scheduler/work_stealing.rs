@@ -0,0 +1,121 @@
+use std::{ops::Range, sync::{atomic::{AtomicUsize, Ordering}, Arc}, time::Duration};
+
+/// Shared source of index ranges for all the threads working on one task, so fast threads keep taking work instead of idling on their static slice
+///
+/// Chunks start big and shrink as the work runs out, so threads finish close to each other even when costs are uneven
+///
+/// It resets itself once every thread of the task is done with it, so the same source can be kept in task data and used every tick
+#[derive(Clone)]
+pub struct ChunkSource {
+    next:Arc<AtomicUsize>,
+    done_threads:Arc<AtomicUsize>,
+    min_chunk:usize,
+}
+
+impl ChunkSource {
+    pub fn new(min_chunk:usize) -> Self {
+        Self { next: Arc::new(AtomicUsize::new(0)), done_threads: Arc::new(AtomicUsize::new(0)), min_chunk: min_chunk.max(1) }
+    }
+    /// Every thread of the task must call this with the same `len` and `number_of_threads` and drain the returned iterator (or drop it)
+    pub fn chunks(&self, len:usize, number_of_threads:usize) -> Chunks {
+        Chunks { source: self.clone(), len, number_of_threads: number_of_threads.max(1), finished: false }
+    }
+    /// Calls `work` on every index this thread manages to take
+    pub fn for_each<F:FnMut(usize)>(&self, len:usize, number_of_threads:usize, mut work:F) {
+        for chunk in self.chunks(len, number_of_threads) {
+            for i in chunk {
+                work(i);
+            }
+        }
+    }
+    fn take(&self, len:usize, number_of_threads:usize) -> Option<Range<usize>> {
+        let mut start = self.next.load(Ordering::Relaxed);
+        loop {
+            if start >= len {
+                return None
+            }
+            let size = ((len - start) / (number_of_threads * 2)).max(self.min_chunk);
+            let end = (start + size).min(len);
+            match self.next.compare_exchange_weak(start, end, Ordering::AcqRel, Ordering::Relaxed) {
+                Ok(_) => return Some(start..end),
+                Err(actual) => start = actual
+            }
+        }
+    }
+    fn thread_done(&self, number_of_threads:usize) {
+        if self.done_threads.fetch_add(1, Ordering::AcqRel) + 1 >= number_of_threads {
+            self.next.store(0, Ordering::Release);
+            self.done_threads.store(0, Ordering::Release);
+        }
+    }
+}
+
+pub struct Chunks {
+    source:ChunkSource,
+    len:usize,
+    number_of_threads:usize,
+    finished:bool,
+}
+
+impl Iterator for Chunks {
+    type Item = Range<usize>;
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None
+        }
+        match self.source.take(self.len, self.number_of_threads) {
+            Some(chunk) => Some(chunk),
+            None => {
+                self.finished = true;
+                self.source.thread_done(self.number_of_threads);
+                None
+            }
+        }
+    }
+}
+
+impl Drop for Chunks {
+    /// Dropping the iterator early gives up on the rest of the work for every thread, except while panicking where the other threads keep taking it and the panic goes on to the scheduler
+    fn drop(&mut self) {
+        if !self.finished {
+            if !std::thread::panicking() {
+                while self.source.take(self.len, self.number_of_threads).is_some() {
+
+                }
+            }
+            self.finished = true;
+            self.source.thread_done(self.number_of_threads);
+        }
+    }
+}
+
+/// Bounds and budget used by `HordeScheduler::adapt_threads` to resize the worker pool between ticks
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AdaptiveThreads {
+    pub min_threads:usize,
+    pub max_threads:usize,
+    pub tick_budget:Duration,
+}
+
+impl AdaptiveThreads {
+    /// `max_threads` is capped to the parallelism of the machine
+    pub fn new(min_threads:usize, max_threads:usize, tick_budget:Duration) -> Self {
+        let available = std::thread::available_parallelism().map(|parallelism| parallelism.get()).unwrap_or(max_threads);
+        let max_threads = max_threads.min(available).max(1);
+        Self { min_threads: min_threads.clamp(1, max_threads), max_threads, tick_budget }
+    }
+    /// `utilisation` is the time workers spent in tasks over the time they could have, in 0.0..1.0
+    ///
+    /// Grows when over budget with busy workers, shrinks when workers mostly wait, which also happens when other processes take the cores
+    pub fn wanted_threads(&self, current:usize, tick_time:Duration, utilisation:f64) -> usize {
+        if tick_time > self.tick_budget && utilisation > 0.75 {
+            (current + 1).min(self.max_threads)
+        }
+        else if utilisation < 0.4 {
+            current.saturating_sub(1).max(self.min_threads)
+        }
+        else {
+            current.clamp(self.min_threads, self.max_threads)
+        }
+    }
+}
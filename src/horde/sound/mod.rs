@@ -57,6 +57,10 @@ pub struct WavesHandler<GE:GameEngine> {
 }
 
 impl<GE:GameEngine> WavesHandler<GE> {
+    /// Handler that drops every request without opening a sound device, for headless engines and tests
+    pub fn null() -> Self {
+        WavesHandler { request_send: unbounded().0, compute_send:unbounded().0, camera_time_send:unbounded().0 }
+    }
     pub fn request_sound(&self, rqst:WaveRequest<GE>) {
         self.request_send.send(rqst);
     }
@@ -150,7 +154,7 @@ impl<GE:GameEngine + 'static> Waves<GE> {
         stream)
     }
     pub fn new_just_handler() -> WavesHandler<GE> {
-        WavesHandler { request_send: unbounded().0, compute_send:unbounded().0, camera_time_send:unbounded().0 }
+        WavesHandler::null()
     }
     pub fn wait_for_gec(&mut self) {
         self.compute_handler = Some(self.gec_rcv.recv().unwrap());
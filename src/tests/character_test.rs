@@ -0,0 +1,124 @@
+use crate::horde::{game_engine::{character::{CharacterController, CharacterInput, CharacterTunables}, physics::PhysicsStatics}, geometry::{collision::{narrow_phase::Collider, shapes::{Aabb, ConvexHull}}, vec3d::Vec3Df}};
+
+use super::{fixtures::{MovablePos, NewWalker, StaticWalker, WalkerVec}, headless_test::HeadlessEngineTID};
+
+const TICK:f32 = 1.0 / 60.0;
+/// Height of the position of a character standing on z = 0, half its height plus the skin width
+const STANDING:f32 = 0.92;
+const SETTLE_TICKS:usize = 30;
+
+fn controller() -> CharacterController {
+    CharacterController::new(CharacterTunables::new(0.3, 0.6))
+}
+
+/// Floor at z = 0, a 0.2 m step for x > 2 and y < -5, and a wall along x = 2 for y > 0
+fn floor_step_and_wall() -> PhysicsStatics {
+    let mut statics = PhysicsStatics::new();
+    statics.add(Collider::Aabb(Aabb::new(Vec3Df::new(-20.0, -20.0, -1.0), Vec3Df::new(20.0, 20.0, 0.0))), 0.0, 0.5);
+    statics.add(Collider::Aabb(Aabb::new(Vec3Df::new(2.0, -20.0, -1.0), Vec3Df::new(20.0, -5.0, 0.2))), 0.0, 0.5);
+    statics.add(Collider::Aabb(Aabb::new(Vec3Df::new(2.0, 0.0, -1.0), Vec3Df::new(3.0, 20.0, 3.0))), 0.0, 0.5);
+    statics
+}
+
+/// Ramp going up along X from x = `start`, 2 m long
+fn ramp(start:f32, min_y:f32, max_y:f32, degrees:f32) -> Collider {
+    let (end, height) = (start + 2.0, 2.0 * degrees.to_radians().tan());
+    Collider::ConvexHull(ConvexHull::new(vec![
+        Vec3Df::new(start, min_y, 0.0), Vec3Df::new(start, max_y, 0.0),
+        Vec3Df::new(end, min_y, 0.0), Vec3Df::new(end, max_y, 0.0),
+        Vec3Df::new(end, min_y, height), Vec3Df::new(end, max_y, height),
+    ]))
+}
+
+/// Floor at z = 0 with a 60° ramp for y < 0 and a 30° one for y > 0, both starting at x = 2
+fn floor_and_ramps() -> PhysicsStatics {
+    let mut statics = PhysicsStatics::new();
+    statics.add(Collider::Aabb(Aabb::new(Vec3Df::new(-20.0, -20.0, -1.0), Vec3Df::new(20.0, 20.0, 0.0))), 0.0, 0.5);
+    statics.add(ramp(2.0, -20.0, 0.0, 60.0), 0.0, 0.5);
+    statics.add(ramp(2.0, 0.0, 20.0, 30.0), 0.0, 0.5);
+    statics
+}
+
+/// Drops a character at `start`, lets it settle, then walks it with `wish_velocity`, returning where it was and whether it was grounded after each walking tick
+fn walk(statics:&PhysicsStatics, start:Vec3Df, wish_velocity:Vec3Df, ticks:usize) -> Vec<(Vec3Df, bool)> {
+    let vec:WalkerVec<HeadlessEngineTID> = WalkerVec::new(4);
+    vec.get_write().new_sct(StaticWalker { pos: MovablePos::new(Vec3Df::zero()), controller: controller() });
+    let id = vec.get_write().new_ent(NewWalker::new(MovablePos::new(start), controller()));
+    let mut trace = Vec::with_capacity(ticks);
+    for tick in 0..(SETTLE_TICKS + ticks) {
+        let input = CharacterInput { wish_velocity: if tick < SETTLE_TICKS {Vec3Df::zero()} else {wish_velocity}, jump: false };
+        vec.get_read().character_stage(id, &input, statics, TICK);
+        vec.apply_all_events(false);
+        if tick >= SETTLE_TICKS {
+            let reader = vec.get_read();
+            trace.push((reader.pos[id].pos, reader.controller[id].grounded));
+        }
+    }
+    trace
+}
+
+#[test]
+fn character_settles_and_steps_up_small_ledges() {
+    let statics = floor_step_and_wall();
+    let trace = walk(&statics, Vec3Df::new(0.0, -10.0, 1.5), Vec3Df::zero(), 1);
+    let (pos, grounded) = trace[0];
+    assert!(grounded);
+    assert!((pos.z - STANDING).abs() < 0.01, "standing at {:?}", pos);
+
+    // Walks onto the 0.2 m step at 3 m/s without ever leaving the ground
+    let trace = walk(&statics, Vec3Df::new(0.0, -10.0, STANDING), Vec3Df::new(3.0, 0.0, 0.0), 120);
+    assert!(trace.iter().all(|(_, grounded)| *grounded));
+    let (pos, _) = trace[119];
+    assert!(pos.x > 5.5, "stopped at {:?}", pos);
+    assert!((pos.z - (STANDING + 0.2)).abs() < 0.03, "standing at {:?}", pos);
+    assert!((pos.y + 10.0).abs() < 0.001);
+}
+
+#[test]
+fn character_snaps_down_steps_and_ramps() {
+    // Walking off the step and down the 30° ramp, the character follows the ground instead of falling
+    let trace = walk(&floor_step_and_wall(), Vec3Df::new(4.0, -10.0, STANDING + 0.2), Vec3Df::new(-3.0, 0.0, 0.0), 90);
+    assert!(trace.iter().all(|(_, grounded)| *grounded));
+    let (pos, _) = trace[89];
+    assert!(pos.x < 0.5);
+    assert!((pos.z - STANDING).abs() < 0.01, "standing at {:?}", pos);
+
+    let trace = walk(&floor_and_ramps(), Vec3Df::new(3.8, 10.0, 2.2), Vec3Df::new(-3.0, 0.0, 0.0), 60);
+    assert!(trace.iter().all(|(_, grounded)| *grounded));
+    assert!(trace.windows(2).all(|ticks| ticks[1].0.z <= ticks[0].0.z + 0.001));
+    assert!((trace[59].0.z - STANDING).abs() < 0.01, "standing at {:?}", trace[59].0);
+}
+
+#[test]
+fn character_climbs_gentle_slopes_only() {
+    let statics = floor_and_ramps();
+
+    // 30° is under the default 45° limit
+    let trace = walk(&statics, Vec3Df::new(0.0, 10.0, STANDING), Vec3Df::new(3.0, 0.0, 0.0), 60);
+    assert!(trace.iter().all(|(_, grounded)| *grounded));
+    assert!(trace[59].0.z > STANDING + 0.3, "stopped at {:?}", trace[59].0);
+
+    // 60° blocks like a wall, the character stays on the floor at its foot
+    let trace = walk(&statics, Vec3Df::new(0.0, -10.0, STANDING), Vec3Df::new(3.0, 0.0, 0.0), 120);
+    let (pos, grounded) = trace[119];
+    assert!(grounded);
+    assert!(pos.x < 2.0, "went up to {:?}", pos);
+    assert!((pos.z - STANDING).abs() < 0.01, "standing at {:?}", pos);
+
+    // Dropped on it, the character slides down to the floor instead of standing there
+    let trace = walk(&statics, Vec3Df::new(3.0, -10.0, 2.6), Vec3Df::zero(), 60);
+    let (pos, grounded) = trace[59];
+    assert!(grounded);
+    assert!(pos.x < 2.0, "stuck at {:?}", pos);
+    assert!((pos.z - STANDING).abs() < 0.01, "standing at {:?}", pos);
+}
+
+#[test]
+fn character_slides_along_walls() {
+    // Walking diagonally into the wall at x = 2, only the part along the wall is kept
+    let trace = walk(&floor_step_and_wall(), Vec3Df::new(0.0, 2.0, STANDING), Vec3Df::new(2.0, 2.0, 0.0), 90);
+    assert!(trace.iter().all(|(_, grounded)| *grounded));
+    let (pos, _) = trace[89];
+    assert!(pos.x < 2.0 - 0.29 && pos.x > 1.6, "stopped at {:?}", pos);
+    assert!((pos.y - 5.0).abs() < 0.05, "stopped at {:?}", pos);
+}
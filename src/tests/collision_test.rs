@@ -0,0 +1,171 @@
+use crate::horde::geometry::{collision::{broad_phase::{overlapping_pairs, SweepAndPrune}, narrow_phase::{aabb_aabb, collide_convex, sphere_sphere, Collider}, shapes::{Aabb, Capsule, TriangleMesh}, swept::sweep_convex}, shapes_3d::{Sphere, Triangle}, vec3d::Vec3Df};
+
+const EPSILON:f32 = 0.001;
+
+fn assert_close(found:Vec3Df, expected:Vec3Df) {
+    assert!(found.dist(&expected) < EPSILON, "expected {:?}, found {:?}", expected, found);
+}
+
+/// Large triangle lying on z = 0, facing up
+fn floor_triangle() -> Triangle {
+    Triangle::new([Vec3Df::new(-5.0, -5.0, 0.0), Vec3Df::new(5.0, -5.0, 0.0), Vec3Df::new(0.0, 5.0, 0.0)])
+}
+
+#[test]
+fn dist_squared_counts_every_axis() {
+    let a = Vec3Df::new(1.0, 2.0, 3.0);
+    assert_eq!(a.dist_squared(&Vec3Df::new(1.0, 5.0, 3.0)), 9.0);
+    assert_eq!(a.dist_squared(&Vec3Df::new(1.0, 2.0, -1.0)), 16.0);
+    assert_eq!(a.dist_squared(&Vec3Df::new(2.0, 4.0, 5.0)), 9.0);
+    assert!((a.dist_squared(&Vec3Df::new(-3.0, 0.5, 7.5)) - a.dist(&Vec3Df::new(-3.0, 0.5, 7.5)).powi(2)).abs() < EPSILON);
+
+    // Spheres only apart along Y or Z don't touch
+    let sphere = Sphere::new(Vec3Df::zero(), 1.0);
+    assert_eq!(sphere_sphere(&sphere, &Sphere::new(Vec3Df::new(0.0, 2.1, 0.0), 1.0)), None);
+    assert_eq!(sphere_sphere(&sphere, &Sphere::new(Vec3Df::new(0.0, 0.0, 2.1), 1.0)), None);
+}
+
+#[test]
+fn sphere_sphere_contacts() {
+    let a = Sphere::new(Vec3Df::zero(), 1.0);
+    let b = Sphere::new(Vec3Df::new(1.5, 0.0, 0.0), 1.0);
+    let contact = sphere_sphere(&a, &b).unwrap();
+    assert_close(contact.normal, Vec3Df::new(1.0, 0.0, 0.0));
+    assert_close(contact.point, Vec3Df::new(0.75, 0.0, 0.0));
+    assert!((contact.depth - 0.5).abs() < EPSILON);
+
+    // Seen from the other sphere, only the normal flips
+    let reverse = Collider::Sphere(b.clone()).collide(&Collider::Sphere(a.clone())).unwrap();
+    assert_close(reverse.normal, Vec3Df::new(-1.0, 0.0, 0.0));
+    assert!((reverse.depth - 0.5).abs() < EPSILON);
+
+    assert_eq!(sphere_sphere(&a, &Sphere::new(Vec3Df::new(2.1, 0.0, 0.0), 1.0)), None);
+
+    // Concentric spheres have no direction to separate along, they fall back to Z
+    let concentric = sphere_sphere(&a, &Sphere::new(Vec3Df::zero(), 0.5)).unwrap();
+    assert_close(concentric.normal, Vec3Df::new(0.0, 0.0, 1.0));
+    assert!((concentric.depth - 1.5).abs() < EPSILON);
+}
+
+#[test]
+fn box_box_contacts() {
+    let a = Aabb::new(Vec3Df::zero(), Vec3Df::new(2.0, 2.0, 2.0));
+    let b = Aabb::new(Vec3Df::new(1.5, 0.5, 0.0), Vec3Df::new(3.0, 1.5, 2.0));
+    let contact = aabb_aabb(&a, &b).unwrap();
+    assert_close(contact.normal, Vec3Df::new(1.0, 0.0, 0.0));
+    assert_close(contact.point, Vec3Df::new(1.75, 1.0, 1.0));
+    assert!((contact.depth - 0.5).abs() < EPSILON);
+
+    // Through the collider the pair gives a single contact
+    let contacts = Collider::Aabb(b.clone()).contacts(&Collider::Aabb(a.clone()));
+    assert_eq!(contacts.len(), 1);
+    assert_close(contacts[0].normal, Vec3Df::new(-1.0, 0.0, 0.0));
+
+    // A thin box sitting inside the other one on X is pushed out on the shallowest axis
+    let lid = Aabb::new(Vec3Df::new(0.5, 0.5, 1.8), Vec3Df::new(1.5, 1.5, 3.0));
+    let contact = aabb_aabb(&a, &lid).unwrap();
+    assert_close(contact.normal, Vec3Df::new(0.0, 0.0, 1.0));
+    assert!((contact.depth - 0.2).abs() < EPSILON);
+
+    assert_eq!(aabb_aabb(&a, &Aabb::new(Vec3Df::new(2.1, 0.0, 0.0), Vec3Df::new(3.0, 2.0, 2.0))), None);
+}
+
+#[test]
+fn capsule_triangle_contacts() {
+    let floor = floor_triangle();
+
+    // Standing capsule whose bottom sphere dips 0.1 into the floor
+    let standing = Capsule::new(Vec3Df::new(0.0, 0.0, 0.5), Vec3Df::new(0.0, 0.0, 2.0), 0.6);
+    let contact = collide_convex(&standing, &floor).unwrap();
+    assert_close(contact.normal, Vec3Df::new(0.0, 0.0, -1.0));
+    assert!((contact.depth - 0.1).abs() < EPSILON);
+    assert!(contact.point.z.abs() < 0.1);
+
+    // Lying capsule, its whole core is at the same height
+    let lying = Capsule::new(Vec3Df::new(-1.0, 0.0, 0.3), Vec3Df::new(1.0, 0.0, 0.3), 0.5);
+    let contact = collide_convex(&lying, &floor).unwrap();
+    assert_close(contact.normal, Vec3Df::new(0.0, 0.0, -1.0));
+    assert!((contact.depth - 0.2).abs() < EPSILON);
+
+    // Core going through the triangle, EPA pushes it out along the triangle normal
+    let crossing = Capsule::new(Vec3Df::new(0.0, 0.0, -0.2), Vec3Df::new(0.0, 0.0, 1.0), 0.1);
+    let contact = collide_convex(&crossing, &floor).unwrap();
+    assert!(contact.normal.z.abs() > 1.0 - EPSILON);
+    assert!(contact.depth > 0.1);
+
+    // Against a mesh, only the touching triangles give a contact, from the capsule to the mesh
+    let far = Triangle::new([Vec3Df::new(20.0, 0.0, 0.0), Vec3Df::new(21.0, 0.0, 0.0), Vec3Df::new(20.0, 1.0, 0.0)]);
+    let mesh = Collider::TriangleMesh(TriangleMesh::new(vec![floor.clone(), far]));
+    let contacts = Collider::Capsule(standing.clone()).contacts(&mesh);
+    assert_eq!(contacts.len(), 1);
+    assert_close(contacts[0].normal, Vec3Df::new(0.0, 0.0, -1.0));
+    let flipped = mesh.collide(&Collider::Capsule(standing)).unwrap();
+    assert_close(flipped.normal, Vec3Df::new(0.0, 0.0, 1.0));
+
+    let above = Capsule::new(Vec3Df::new(0.0, 0.0, 1.0), Vec3Df::new(0.0, 0.0, 2.0), 0.6);
+    assert_eq!(collide_convex(&above, &floor), None);
+}
+
+#[test]
+fn swept_fast_sphere_hits_thin_triangle() {
+    let floor = floor_triangle();
+    let bullet = Sphere::new(Vec3Df::new(0.0, 0.0, 1.0), 0.1);
+    let motion = Vec3Df::new(0.0, 0.0, -10.0);
+
+    // Both the start and the end of the step are clear of the triangle, a discrete test misses it
+    assert_eq!(collide_convex(&bullet, &floor), None);
+    assert_eq!(collide_convex(&Sphere::new(bullet.get_origin() + motion, 0.1), &floor), None);
+
+    // The sphere touches after 0.9 of the 10 units
+    let hit = sweep_convex(&bullet, &motion, &floor, &Vec3Df::zero()).unwrap();
+    assert!((hit.time - 0.09).abs() < EPSILON);
+    assert_close(hit.contact.normal, Vec3Df::new(0.0, 0.0, -1.0));
+    assert!(hit.contact.point.z.abs() < 0.01);
+
+    // Same hit when the triangle is the one moving up
+    let hit = sweep_convex(&bullet, &Vec3Df::zero(), &floor, &-motion).unwrap();
+    assert!((hit.time - 0.09).abs() < EPSILON);
+
+    // And through the colliders, against a mesh
+    let mesh = Collider::TriangleMesh(TriangleMesh::new(vec![floor.clone()]));
+    let hit = Collider::Sphere(bullet.clone()).sweep(&motion, &mesh, &Vec3Df::zero()).unwrap();
+    assert!((hit.time - 0.09).abs() < EPSILON);
+
+    // Moving away or passing beside the triangle never hits
+    assert_eq!(sweep_convex(&bullet, &-motion, &floor, &Vec3Df::zero()), None);
+    assert_eq!(sweep_convex(&Sphere::new(Vec3Df::new(10.0, 0.0, 1.0), 0.1), &motion, &floor, &Vec3Df::zero()), None);
+    assert_eq!(Collider::Sphere(bullet).sweep(&Vec3Df::new(0.0, 0.0, -0.5), &mesh, &Vec3Df::zero()), None);
+}
+
+#[test]
+fn sweep_and_prune_pairs() {
+    let unit = |x:f32, y:f32| Aabb::from_center(Vec3Df::new(x, y, 0.0), Vec3Df::new(0.5, 0.5, 0.5));
+    let boxes = vec![unit(0.0, 0.0), unit(0.8, 0.0), unit(5.0, 0.0), unit(0.4, 0.9), unit(5.0, 3.0)];
+    let mut sap = SweepAndPrune::new();
+    for (i, aabb) in boxes.iter().enumerate() {
+        sap.set(i, aabb.clone());
+    }
+    assert_eq!(sap.len(), 5);
+
+    let sorted = |pairs:Vec<(usize, usize)>| {
+        let mut pairs:Vec<(usize, usize)> = pairs.into_iter().map(|(a, b)| (a.min(b), a.max(b))).collect();
+        pairs.sort();
+        pairs
+    };
+    let expected = vec![(0, 1), (0, 3), (1, 3)];
+    assert_eq!(sorted(sap.get_pairs()), expected);
+    assert_eq!(sorted(overlapping_pairs(&boxes)), expected);
+
+    // Moving a box updates its pairs, without re-adding it
+    sap.set(2, unit(5.0, 2.5));
+    assert_eq!(sap.len(), 5);
+    assert_eq!(sorted(sap.get_pairs()), vec![(0, 1), (0, 3), (1, 3), (2, 4)]);
+
+    assert_eq!(sap.remove(&0), Some(unit(0.0, 0.0)));
+    assert_eq!(sap.remove(&0), None);
+    assert_eq!(sorted(sap.get_pairs()), vec![(1, 3), (2, 4)]);
+
+    let mut found = sap.query(&Aabb::new(Vec3Df::new(4.0, 2.0, 0.0), Vec3Df::new(6.0, 2.6, 0.0)));
+    found.sort();
+    assert_eq!(found, vec![2, 4]);
+}
@@ -1,11 +1,11 @@
-use std::{collections::HashMap, sync::{atomic::{AtomicBool, AtomicUsize, Ordering}, Arc, RwLock, RwLockReadGuard, RwLockWriteGuard}};
+use std::{collections::HashMap, net::Ipv4Addr, path::PathBuf, sync::{atomic::{AtomicBool, AtomicUsize, Ordering}, Arc, RwLock, RwLockReadGuard, RwLockWriteGuard}};
 
 use engine_derive::GameEngine;
 use to_from_bytes::{FromBytes, ToBytes};
 
-use crate::{defaults::default_rendering::vectorinator::{Vectorinator, VectorinatorWrite}, horde::{game_engine::{engine::{GameEngine, MovingObjectID}, entity::{Entity, EntityID, EntityVec, MultiplayerEntity, Renderable}, multiplayer::{GlobalComponent, GlobalEvent, HordeEventReport, HordeMultiModeChoice, HordeMultiplayer, Identify, MultiplayerEngine, MustSync}, world::{World, WorldComputeHandler, WorldEvent, WorldHandler, WorldOutHandler, WorldWriteHandler}}, geometry::vec3d::Vec3Df, rendering::RenderingBackend, scheduler::IndividualTask}, tests::entity_derive_test::CoolEntityVecWrite};
+use crate::{defaults::default_rendering::vectorinator::{Vectorinator, VectorinatorWrite}, horde::{frontend::{HordeWindowDimensions, SyncUnsafeHordeFramebuffer}, game_engine::{deferred_events::{DeferredEvents, DeferredEventsState, ScheduleDelay, ScheduledEventHandle}, engine::{GameEngine, MovingObjectID, BEGIN_TICK_TASK}, headless::HeadlessHarness, event_order::{begin_event_task, set_event_turn, SequencedEvent}, entity::{DespawnEvent, Entity, EntityID, EntityVec, MultiplayerEntity, Renderable}, multiplayer::{GlobalComponent, GlobalEvent, HordeEventReport, HordeMultiModeChoice, HordeMultiplayer, Identify, MultiplayerEngine, MustSync}, snapshot::{read_snapshot, read_snapshot_header, write_snapshot, SnapshotError, SnapshotExtraData, SnapshotHeader, SNAPSHOT_FORMAT_VERSION}, time_control::{TimeControl, TimeControlState}, rng::{set_rng_stage, EngineRng, RngState}, world::{World, WorldComputeHandler, WorldEvent, WorldHandler, WorldOutHandler, WorldWriteHandler}}, geometry::vec3d::Vec3Df, rendering::{framebuffer::HordeColorFormat, RenderingBackend}, scheduler::{profiler::{profile_scope, ProfileKind}, IndividualTask}}, tests::entity_derive_test::CoolEntityVecWrite};
 
-use super::entity_derive_test::{CoolEntity, CoolEntityVecRead};
+use super::entity_derive_test::{CoolComponent, CoolEntity, CoolEntityDeferredEvent, CoolEntityEvent, CoolEntityLifecycle, CoolEntityVec, CoolEntityVecRead, CoolEntityVecSnapshot, NewCoolEntity, RenderCounter, StaticCoolEntity};
 use to_from_bytes_derive::{FromBytes, ToBytes};
 
 #[derive(Clone, ToBytes, FromBytes, PartialEq)]
@@ -52,7 +52,50 @@ impl RenderingBackend for TestRB {
     type RenderingStatusUpdate = usize;
 }
 
-pub fn stage_1<'a>(turn:EntityTurn, id:EntityID, reader: &CoolEntityVecRead<'a, TestEngineTID>, world_read: &WorldComputeHandler<'a, TestWorld, TestEngineTID>, extra_data:&usize) {
+impl<'a> CoolEntityLifecycle<VectorinatorWrite<'a>, TestWorld, TestEngineTID> for CoolEntity {
+
+}
+
+/// Records which entities the lifecycle hooks were called on
+#[derive(Default)]
+pub struct HookLog {
+    pub spawned:Vec<EntityID>,
+    pub despawned:Vec<EntityID>,
+}
+
+impl CoolEntityLifecycle<HookLog, TestWorld, TestEngineTID> for CoolEntity {
+    fn on_spawn(id:EntityID, ents:&mut CoolEntityVecWrite<'_, TestEngineTID>, rendering_data:&mut HookLog, world:&WorldComputeHandler<'_, TestWorld, TestEngineTID>) {
+        rendering_data.spawned.push(id);
+    }
+    fn on_despawn(id:EntityID, ents:&mut CoolEntityVecWrite<'_, TestEngineTID>, rendering_data:&mut HookLog, world:&WorldComputeHandler<'_, TestWorld, TestEngineTID>) {
+        rendering_data.despawned.push(id);
+    }
+}
+
+#[derive(Clone)]
+pub struct TestExtraData {
+    pub value:usize,
+    /// When set, every entity spawns one more in stage 0, further along Y by a random amount drawn from this
+    pub spawner_rng:Option<EngineRng>,
+}
+
+impl TestExtraData {
+    pub fn new(value:usize) -> Self {
+        Self { value, spawner_rng: None }
+    }
+}
+
+impl SnapshotExtraData for TestExtraData {
+    type Saved = usize;
+    fn save_snapshot_part(&self) -> usize {
+        self.value
+    }
+    fn load_snapshot_part(&mut self, saved:usize) {
+        self.value = saved;
+    }
+}
+
+pub fn stage_1<'a>(turn:EntityTurn, id:EntityID, reader: &CoolEntityVecRead<'a, TestEngineTID>, world_read: &WorldComputeHandler<'a, TestWorld, TestEngineTID>, extra_data:&TestExtraData) {
     println!("TEST AFTER MAIN");
     match turn {
         EntityTurn::ent1 => {
@@ -61,16 +104,297 @@ pub fn stage_1<'a>(turn:EntityTurn, id:EntityID, reader: &CoolEntityVecRead<'a,
     }
 }
 
-pub fn stage_0<'a>(turn:EntityTurn, id:EntityID, reader: &CoolEntityVecRead<'a, TestEngineTID>, world_read: &WorldComputeHandler<'a, TestWorld, TestEngineTID>, extra_data:&usize) {
-    println!("TEST COMPUTE");
+pub fn stage_0<'a>(turn:EntityTurn, id:EntityID, reader: &CoolEntityVecRead<'a, TestEngineTID>, world_read: &WorldComputeHandler<'a, TestWorld, TestEngineTID>, extra_data:&TestExtraData) {
+    if let Some(rng) = &extra_data.spawner_rng {
+        let pos = reader.pos[id].pos + Vec3Df::new(0.0, rng.entity_stream(0, id).f32(), 0.0);
+        let _ = reader.tunnels.new_ents.send(SequencedEvent::new(NewCoolEntity::new(CoolComponent::new(pos), MustSync::No, None)));
+    }
 }
 
 #[derive(GameEngine)]
 #[rendering_engine = "Vectorinator"]
 #[do_multiplayer]
+#[do_snapshots]
+#[do_deferred_events]
+#[do_lifecycle_hooks]
+#[deterministic_events]
+#[do_time_control]
+#[profile_stages]
+#[do_rng]
 pub struct TestEngine {
     ent1:CoolEntity,
     world:TestWorld,
     #[extra_data]
-    extra_data:usize
-}
\ No newline at end of file
+    extra_data:TestExtraData
+}
+
+/// Server engine on a free port with no entity yet, `ScheduleDelay::Seconds` is converted with `ticks_per_second`
+pub fn test_engine(ticks_per_second:f32, rng_seed:u64) -> TestEngineBase {
+    let entity_vec = CoolEntityVec::new(64);
+    entity_vec.get_write().new_sct(StaticCoolEntity { pos: CoolComponent::new(Vec3Df::zero()), instance_id: None });
+    let vectorinator = Vectorinator::new(Arc::new(RwLock::new(SyncUnsafeHordeFramebuffer::new(HordeWindowDimensions::new(8, 8), HordeColorFormat::ARGB8888))));
+    let multi_choice = HordeMultiModeChoice::Server { adress: (Ipv4Addr::LOCALHOST, 0), max_players: 4, tick_tolerance: 10, tickrate: ticks_per_second as usize };
+    TestEngineBase::new(entity_vec, WorldHandler::new(TestWorld { test: 1 }), Arc::new(vectorinator), multi_choice, TestExtraData::new(1), ticks_per_second, rng_seed)
+}
+
+fn deferred_spawn(x:f32) -> TestEngineDeferred {
+    TestEngineDeferred::ent1(CoolEntityDeferredEvent::NewEnt(NewCoolEntity::new(CoolComponent::new(Vec3Df::new(x, 0.0, 0.0)), MustSync::No, None)))
+}
+
+fn spawned_xs(harness:&HeadlessHarness<TestEngineBase>) -> Vec<f32> {
+    harness.query(|engine| engine.ent1.get_read().pos.iter().map(|comp| comp.pos.x).collect())
+}
+
+#[test]
+fn deferred_events_fire_after_their_delay_unless_cancelled() {
+    let mut harness = HeadlessHarness::new(test_engine(20.0, 0), 2);
+    let (in_ticks, in_seconds, cancelled) = {
+        let engine = harness.engine();
+        (engine.schedule_event(ScheduleDelay::Ticks(3), deferred_spawn(3.0)), engine.schedule_event(ScheduleDelay::Seconds(0.1), deferred_spawn(2.0)), engine.schedule_event(ScheduleDelay::Ticks(2), deferred_spawn(-1.0)))
+    };
+    assert!(harness.engine().cancel_event(cancelled));
+    assert!(!harness.engine().cancel_event(cancelled));
+
+    // At 20 ticks per second, 0.1 s is 2 ticks, events fire at the start of their tick and are applied with its first stage
+    harness.run_ticks(1);
+    assert!(spawned_xs(&harness).is_empty());
+    harness.run_ticks(1);
+    assert_eq!(spawned_xs(&harness), vec![2.0]);
+    assert!(harness.query(|engine| engine.deferred.is_pending(in_ticks)));
+    harness.run_ticks(1);
+    assert_eq!(spawned_xs(&harness), vec![2.0, 3.0]);
+    assert!(!harness.query(|engine| engine.deferred.is_pending(in_seconds)));
+    assert!(!harness.engine().cancel_event(in_ticks));
+    harness.run_ticks(5);
+    assert_eq!(spawned_xs(&harness), vec![2.0, 3.0]);
+
+    // The same delay in seconds is twice as many ticks at twice the tick rate
+    let fast = test_engine(40.0, 0);
+    let handle = fast.schedule_event(ScheduleDelay::Seconds(0.1), deferred_spawn(0.0));
+    let mut harness = HeadlessHarness::new(fast, 2);
+    harness.run_ticks(3);
+    assert!(harness.query(|engine| engine.deferred.is_pending(handle)));
+    harness.run_ticks(1);
+    assert_eq!(spawned_xs(&harness), vec![0.0]);
+}
+
+fn snapshot_path(name:&str) -> PathBuf {
+    std::env::temp_dir().join(format!("hord3_{}_{}.snapshot", name, std::process::id()))
+}
+
+#[test]
+fn snapshot_round_trip_restores_the_engine() {
+    let path = snapshot_path("round_trip");
+    let mut harness = HeadlessHarness::new(test_engine(20.0, 7), 2);
+    {
+        let engine = harness.engine_mut();
+        for x in 0..4 {
+            engine.ent1.get_write().new_ent(NewCoolEntity::new(CoolComponent::new(Vec3Df::new(x as f32, 0.0, 0.0)), MustSync::No, None));
+        }
+        assert!(engine.ent1.get_write().despawn(1));
+        engine.world.world.write().unwrap().test = 5;
+        engine.extra_data.value = 3;
+        engine.schedule_event(ScheduleDelay::Ticks(4), deferred_spawn(10.0));
+    }
+    harness.run_ticks(2);
+    harness.engine().tick.store(12, Ordering::Relaxed);
+    harness.engine().save_snapshot(path.clone()).unwrap();
+    let saved_positions = harness.query(|engine| engine.ent1.get_read().pos.clone());
+    let saved_rng = harness.query(|engine| engine.rng.get_state());
+
+    // Everything saved changes before loading
+    {
+        let engine = harness.engine_mut();
+        engine.ent1.get_write().new_ent(NewCoolEntity::new(CoolComponent::new(Vec3Df::new(-5.0, 0.0, 0.0)), MustSync::No, None));
+        engine.ent1.get_write().despawn(0);
+        engine.world.world.write().unwrap().test = 9;
+        engine.extra_data.value = 8;
+        engine.rng.set_seed(1);
+        engine.deferred.load_state(DeferredEventsState { current_tick: 0, next_handle: 0, pending: Vec::new() });
+    }
+    let header = harness.engine_mut().load_snapshot(path.clone()).unwrap();
+    assert_eq!(header, SnapshotHeader::new(12));
+    assert_eq!(harness.engine().get_tick(), 12);
+    harness.assert_query_eq(saved_positions.clone(), |engine| engine.ent1.get_read().pos.clone());
+    harness.assert_query_eq(vec![true, false, true, true], |engine| (0..4).map(|id| engine.ent1.get_read().alive.is_alive(id)).collect::<Vec<bool>>());
+    harness.assert_query_eq(5, |engine| engine.world.world.read().unwrap().test);
+    harness.assert_query_eq(3, |engine| engine.extra_data.value);
+    harness.assert_query_eq(saved_rng, |engine| engine.rng.get_state());
+
+    // Rendering instances are rebuilt for living entities only
+    harness.assert_query_eq(vec![true, false, true, true], |engine| engine.ent1.get_read().instance_id.iter().map(|instance| instance.is_some()).collect::<Vec<bool>>());
+
+    // The restored wheel still fires the event 2 ticks later, in the slot freed by the despawn
+    harness.run_ticks(1);
+    harness.assert_query_eq(4, |engine| engine.ent1.get_read().pos.len());
+    harness.run_ticks(1);
+    harness.assert_query_eq(CoolComponent::new(Vec3Df::new(10.0, 0.0, 0.0)), |engine| engine.ent1.get_read().pos[1].clone());
+    let _ = std::fs::remove_file(path);
+}
+
+#[test]
+fn snapshot_errors() {
+    let mut engine = test_engine(20.0, 0);
+    let missing = snapshot_path("missing");
+    assert_eq!(engine.load_snapshot(missing.clone()).err(), Some(SnapshotError::CouldntRead(missing)));
+
+    // A snapshot from another format version is rejected before its body is decoded
+    let path = snapshot_path("wrong_version");
+    write_snapshot(path.clone(), SnapshotHeader { format_version: SNAPSHOT_FORMAT_VERSION + 1, tick: 3 }, 0_u8).unwrap();
+    assert_eq!(read_snapshot_header(path.clone()), Ok(SnapshotHeader { format_version: SNAPSHOT_FORMAT_VERSION + 1, tick: 3 }));
+    assert_eq!(engine.load_snapshot(path.clone()).err(), Some(SnapshotError::WrongVersion { expected: SNAPSHOT_FORMAT_VERSION, found: SNAPSHOT_FORMAT_VERSION + 1 }));
+
+    // The right version with a body that isn't a snapshot of this engine
+    write_snapshot(path.clone(), SnapshotHeader::new(3), 0_u8).unwrap();
+    assert_eq!(engine.load_snapshot(path.clone()).err(), Some(SnapshotError::CouldntDecode(path.clone())));
+    assert_eq!(engine.get_tick(), 0);
+    let _ = std::fs::remove_file(path);
+}
+
+#[test]
+fn deterministic_events_dont_depend_on_thread_scheduling() {
+    let mut harness = HeadlessHarness::new(test_engine(20.0, 0), 2);
+    let new_ents = harness.engine().ent1.tunnels_out.new_ents.clone();
+    let spawn = |x:f32, y:f32| SequencedEvent::new(NewCoolEntity::new(CoolComponent::new(Vec3Df::new(x, y, 0.0)), MustSync::No, None));
+
+    // Each thread plays the turn of one entity and sends two spawns, the later the turn the earlier it sends
+    let turns = [5, 2, 7, 0];
+    std::thread::scope(|scope| {
+        for (i, entity) in turns.iter().enumerate() {
+            let new_ents = new_ents.clone();
+            scope.spawn(move || {
+                std::thread::sleep(std::time::Duration::from_millis(5 * (turns.len() - i) as u64));
+                set_event_turn(0, 0, *entity);
+                let _ = new_ents.send(spawn(*entity as f32, 0.0));
+                let _ = new_ents.send(spawn(*entity as f32, 1.0));
+            });
+        }
+    });
+    // Sent last but outside of any turn, so applied first
+    begin_event_task();
+    let _ = new_ents.send(spawn(-1.0, 0.0));
+
+    harness.engine_mut().do_task(0, 0, 1);
+    let applied:Vec<(f32, f32)> = harness.query(|engine| engine.ent1.get_read().pos.iter().map(|comp| (comp.pos.x, comp.pos.y)).collect());
+    assert_eq!(applied, vec![(-1.0, 0.0), (0.0, 0.0), (0.0, 1.0), (2.0, 0.0), (2.0, 1.0), (5.0, 0.0), (5.0, 1.0), (7.0, 0.0), (7.0, 1.0)]);
+}
+
+/// Spawns 4 entities then runs `ticks` ticks, each stage split between `threads` clones of the engine running at the same time
+fn spawner_run(rng_seed:u64, threads:usize, ticks:usize) -> Vec<Vec3Df> {
+    let mut engine = test_engine(20.0, rng_seed);
+    for x in 0..4 {
+        engine.ent1.get_write().new_ent(NewCoolEntity::new(CoolComponent::new(Vec3Df::new(x as f32, 0.0, 0.0)), MustSync::No, None));
+    }
+    engine.extra_data.spawner_rng = Some(engine.rng.clone());
+    for _ in 0..ticks {
+        engine.do_task(BEGIN_TICK_TASK, 0, 1);
+        for stage in [100, 101] {
+            let mut clones = vec![engine.clone() ; threads];
+            std::thread::scope(|scope| {
+                for (thread_number, clone) in clones.iter_mut().enumerate() {
+                    scope.spawn(move || clone.do_task(stage, thread_number, threads));
+                }
+            });
+            engine.do_task(0, 0, 1);
+        }
+    }
+    let reader = engine.ent1.get_read();
+    reader.pos.iter().map(|comp| comp.pos).collect()
+}
+
+#[test]
+fn seeded_rng_reproduces_runs_across_thread_counts() {
+    // Every entity spawned one each tick, so 4 become 32 in 3 ticks
+    let reference = spawner_run(42, 1, 3);
+    assert_eq!(reference.len(), 32);
+    assert!(reference.iter().skip(4).all(|pos| pos.y > 0.0));
+    assert_eq!(spawner_run(42, 1, 3), reference);
+    assert_eq!(spawner_run(42, 3, 3), reference);
+    assert_eq!(spawner_run(42, 8, 3), reference);
+    assert_ne!(spawner_run(43, 1, 3), reference);
+
+    // Streams only depend on the seed, tick, stage, salt and entity, not on the thread drawing from them
+    let rng = EngineRng::new(42);
+    rng.advance();
+    let drawn:Vec<u64> = std::thread::scope(|scope| {
+        let handles:Vec<_> = (0..4).map(|entity| {
+            let rng = rng.clone();
+            scope.spawn(move || {
+                set_rng_stage(1);
+                rng.entity_stream(7, entity).u64(..)
+            })
+        }).collect();
+        handles.into_iter().map(|handle| handle.join().unwrap()).collect()
+    });
+    let expected:Vec<u64> = (0..4).map(|entity| rng.stream(7, entity, 1).u64(..)).collect();
+    assert_eq!(drawn, expected);
+    assert_ne!(rng.stream(7, 0, 1).u64(..), rng.stream(8, 0, 1).u64(..));
+    assert_ne!(rng.stream(7, 0, 1).u64(..), rng.stream(7, 0, 0).u64(..));
+
+    // Seeds are restarted from tick 0 and their change is reported once, to be replicated to clients
+    let other = EngineRng::new(5);
+    other.load_state(rng.get_state());
+    assert_eq!(other.stream(7, 3, 1).u64(..), expected[3]);
+    assert_eq!(other.take_change(), None);
+    other.set_seed(42);
+    assert_eq!(other.take_change(), Some(RngState { seed: 42, tick: 0 }));
+    assert_eq!(other.take_change(), None);
+}
+
+#[test]
+fn stages_and_renders_skip_despawned_slots() {
+    let mut engine = test_engine(20.0, 0);
+    for x in 0..3 {
+        engine.ent1.get_write().new_ent(NewCoolEntity::new(CoolComponent::new(Vec3Df::new(x as f32, 0.0, 0.0)), MustSync::No, None));
+    }
+    assert!(engine.ent1.get_write().despawn(1));
+    engine.extra_data.spawner_rng = Some(engine.rng.clone());
+
+    // Only entities 0 and 2 run stage 0, so each spawns one more at its own X
+    let mut harness = HeadlessHarness::new(engine, 2);
+    harness.tick();
+    let mut alive_xs:Vec<f32> = harness.query(|engine| {
+        let reader = engine.ent1.get_read();
+        reader.pos.iter().enumerate().filter(|(id, _)| reader.alive.is_alive(*id)).map(|(_, comp)| comp.pos.x).collect()
+    });
+    alive_xs.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    assert_eq!(alive_xs, vec![0.0, 0.0, 2.0, 2.0]);
+
+    let mut vec:CoolEntityVec<TestEngineTID> = CoolEntityVec::new(16);
+    vec.get_write().new_sct(StaticCoolEntity { pos: CoolComponent::new(Vec3Df::zero()), instance_id: None });
+    for x in 0..4 {
+        vec.get_write().new_ent(NewCoolEntity::new(CoolComponent::new(Vec3Df::new(x as f32, 0.0, 0.0)), MustSync::No, None));
+    }
+    assert!(vec.get_write().despawn(2));
+    let mut counter = RenderCounter::default();
+    vec.do_all_renders(&mut counter);
+    assert_eq!(counter.single, 3);
+    assert_eq!(vec.get_read().instance_id[2], None);
+}
+
+#[test]
+fn lifecycle_hooks_fire_once_per_spawn_and_despawn() {
+    let vec:CoolEntityVec<TestEngineTID> = CoolEntityVec::new(16);
+    vec.get_write().new_sct(StaticCoolEntity { pos: CoolComponent::new(Vec3Df::zero()), instance_id: None });
+    let world = WorldHandler::new(TestWorld { test: 1 });
+    let world_handler = WorldComputeHandler::from_world_handler(&world);
+    let mut log = HookLog::default();
+
+    begin_event_task();
+    for x in 0..3 {
+        let _ = vec.tunnels_out.new_ents.send(SequencedEvent::new(NewCoolEntity::new(CoolComponent::new(Vec3Df::new(x as f32, 0.0, 0.0)), MustSync::No, None)));
+    }
+    vec.apply_all_events(true);
+    vec.run_lifecycle_hooks(&mut log, &world_handler);
+    assert_eq!(log.spawned, vec![0, 1, 2]);
+    assert!(log.despawned.is_empty());
+
+    // Despawning twice in a tick only despawns once, and hooks that already ran don't run again
+    let _ = vec.tunnels_out.despawns.send(SequencedEvent::new(CoolEntityEvent::new(MustSync::No, DespawnEvent::new(1, None))));
+    let _ = vec.tunnels_out.despawns.send(SequencedEvent::new(CoolEntityEvent::new(MustSync::No, DespawnEvent::new(1, None))));
+    vec.apply_all_events(true);
+    vec.run_lifecycle_hooks(&mut log, &world_handler);
+    vec.run_lifecycle_hooks(&mut log, &world_handler);
+    assert_eq!(log.spawned, vec![0, 1, 2]);
+    assert_eq!(log.despawned, vec![1]);
+}
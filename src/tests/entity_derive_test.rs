@@ -3,18 +3,27 @@ use std::{collections::VecDeque, sync::{Arc, RwLock, RwLockReadGuard, RwLockWrit
 use entity_derive::{Entity};
 use to_from_bytes_derive::{FromBytes, ToBytes};
 
-use crate::{defaults::default_rendering::vectorinator::{VectorinatorWrite, meshes::{MeshID, MeshInstance}}, horde::{game_engine::{entity::{Component, ComponentEvent, EVecStopsIn, EVecStopsOut, Entity, EntityID, EntityVec, MultiplayerEntity, NewEntity, Renderable, StaticComponent, StaticEntity}, multiplayer::{Identify, MustSync}, position::EntityPosition, static_type_id::HasStaticTypeID}, geometry::{rotation::{Orientation, Rotation}, vec3d::Vec3Df}, utils::ARW}};
+use crate::{defaults::{default_rendering::vectorinator::{VectorinatorWrite, meshes::{Mesh, MeshID, MeshInstance, MeshLODS, Meshes, MeshesRead}}, default_ui::simple_ui::inspector::ComponentEditor}, horde::{game_engine::{change_tracking::ComponentChanges, deferred_events::DeferredEvents, event_order::{sort_events, SequencedEvent}, merge::{last_writer_wins, merge_events}, entity::{AliveMask, Component, ComponentEvent, DespawnEvent, EVecStopsIn, EVecStopsOut, Entity, EntityID, EntityVec, MultiplayerEntity, NewEntity, Renderable, StaticComponent, StaticEntity}, multiplayer::{Identify, MustSync}, position::EntityPosition, reflection::{ComponentMetadata, EditableComponent, EntityMetadata, EntityReflection, EntityValuesText}, prefab::{parse_prefab_text, prefabs_from_text, prefabs_to_text, EntityPrefab, PrefabEntity, PrefabError, PrefabRegistry, PrefabText, PrefabTextFields, RegisteredPrefab}, static_type_id::{HasStaticTypeID, SetStaticTypeID}, world::{World, WorldComputeHandler}}, geometry::{rotation::{Orientation, Rotation}, vec3d::Vec3Df}, utils::ARW}};
 
-#[derive(Clone, PartialEq, Eq, ToBytes, FromBytes)]
+use super::headless_test::HeadlessEngineTID;
+
+#[derive(Clone, PartialEq, Eq, Debug, ToBytes, FromBytes)]
 pub struct CoolComponent {
     pub pos:Vec3Df,
+    pub static_type:usize,
+}
+
+impl CoolComponent {
+    pub fn new(pos:Vec3Df) -> Self {
+        Self { pos, static_type: 0 }
+    }
 }
 
 impl<ID:Identify> Component<ID> for CoolComponent {
     type SC = Self;
     type CE = Self;
     fn from_static(static_comp:&Self::SC) -> Self {
-        Self { pos: static_comp.pos.clone() }
+        static_comp.clone()
     }
 }
 
@@ -55,7 +64,22 @@ impl<ID:Identify> ComponentEvent<Option<usize>, ID> for Option<usize> {
 
 impl HasStaticTypeID for CoolComponent {
     fn get_id(&self) -> usize {
-        0
+        self.static_type
+    }
+}
+
+impl SetStaticTypeID for CoolComponent {
+    fn set_id(&mut self, id:usize) {
+        self.static_type = id;
+    }
+}
+
+impl PrefabText for CoolComponent {
+    fn write_text(&self, text:&mut String) {
+        self.pos.write_text(text);
+    }
+    fn read_text(text:&str) -> Option<Self> {
+        Some(Self::new(Vec3Df::read_text(text)?))
     }
 }
 
@@ -63,6 +87,16 @@ impl StaticComponent for CoolComponent {
     
 }
 
+impl<ID:Identify> EditableComponent<ID> for CoolComponent {
+    fn set_event(_id:EntityID, value:Self) -> Option<Self> {
+        Some(value)
+    }
+}
+
+impl<ID:Identify> EditableComponent<ID> for Option<usize> {
+
+}
+
 impl<ID:Identify> ComponentEvent<CoolComponent, ID> for CoolComponent {
     type ComponentUpdate = Self;
     fn get_id(&self) -> crate::horde::game_engine::entity::EntityID {
@@ -77,7 +111,12 @@ impl<ID:Identify> ComponentEvent<CoolComponent, ID> for CoolComponent {
 }
 
 #[derive(Entity, Clone)]
-
+#[prefab]
+#[snapshot]
+#[reflect_debug]
+#[deferred_events]
+#[lifecycle_hooks]
+#[deterministic]
 pub struct CoolEntity {
     #[used_in_new]
     #[used_in_render]
@@ -86,6 +125,8 @@ pub struct CoolEntity {
     #[static_id]
     pub pos:CoolComponent,
     #[used_in_render]
+    #[not_saved]
+    #[merge = "last_writer_wins"]
     pub instance_id:Option<usize>,
 }
 
@@ -109,9 +150,183 @@ impl<'a, ID:Identify> RenderCoolEntity<VectorinatorWrite<'a>, ID> for CoolEntity
             }
         }
     }
+    fn remove_render(rendering_data:&mut VectorinatorWrite<'a>, pos: &mut CoolComponent, instance_id:&mut Option<usize>, static_type:&StaticCoolEntity<ID>) {
+        if let Some(id) = instance_id.take() {
+            rendering_data.meshes.remove_instance(id, 2);
+        }
+    }
+}
+
+/// Counts what the render hooks of `CoolEntity` are called with
+#[derive(Default)]
+pub struct RenderCounter {
+    pub single:usize,
+    pub batches:Vec<usize>,
+    pub removed:usize,
+    next_instance:usize,
+}
+
+impl<ID:Identify> RenderCoolEntity<RenderCounter, ID> for CoolEntity {
+    fn do_render_changes(rendering_data:&mut RenderCounter, pos: &mut CoolComponent, instance_id:&mut Option<usize>, static_type:&StaticCoolEntity<ID>) {
+        rendering_data.single += 1;
+        if instance_id.is_none() {
+            *instance_id = Some(rendering_data.next_instance);
+            rendering_data.next_instance += 1;
+        }
+    }
+    fn remove_render(rendering_data:&mut RenderCounter, pos: &mut CoolComponent, instance_id:&mut Option<usize>, static_type:&StaticCoolEntity<ID>) {
+        if instance_id.take().is_some() {
+            rendering_data.removed += 1;
+        }
+    }
+    fn do_batch_render_changes(rendering_data:&mut RenderCounter, pos:&mut [CoolComponent], instance_id:&mut [Option<usize>], static_types:&[StaticCoolEntity<ID>]) {
+        rendering_data.batches.push(pos.len());
+        for instance in instance_id.iter_mut() {
+            *instance = Some(rendering_data.next_instance);
+            rendering_data.next_instance += 1;
+        }
+    }
 }
 
 #[test]
 fn test_stuff() {
     
-}
\ No newline at end of file
+}
+
+#[test]
+fn spawn_batch_count_and_renders() {
+    let mut vec:CoolEntityVec<HeadlessEngineTID> = CoolEntityVec::new(16);
+    vec.get_write().new_sct(StaticCoolEntity { pos: CoolComponent::new(Vec3Df::zero()), instance_id: None });
+    vec.get_write().new_ent(NewCoolEntity::new(CoolComponent::new(Vec3Df::zero()), MustSync::No, None));
+    let range = vec.spawn_batch((0..100).map(|i| NewCoolEntity::new(CoolComponent::new(Vec3Df::new(i as f32, 0.0, 0.0)), MustSync::No, None)));
+    assert_eq!(range, 1..101);
+    {
+        let reader = vec.get_read();
+        assert_eq!(reader.get_expected_len(), 101);
+        assert_eq!(reader.pos[50].pos, Vec3Df::new(49.0, 0.0, 0.0));
+        assert_eq!(reader.alive.alive_count(), 101);
+    }
+
+    let mut counter = RenderCounter::default();
+    vec.do_range_renders(&mut counter, range.clone());
+    assert_eq!(counter.batches, vec![100]);
+    assert_eq!(counter.single, 0);
+    assert!(vec.get_read().instance_id[range.clone()].iter().all(|instance| instance.is_some()));
+    assert_eq!(vec.get_read().instance_id[0], None);
+
+    // Entity 0 was never rendered, everything else was in the batch
+    vec.do_all_renders(&mut counter);
+    assert_eq!(counter.single, 101);
+
+    // Despawned entities have their instance removed and aren't rendered anymore
+    assert!(vec.get_write().despawn(7));
+    assert!(!vec.get_write().despawn(7));
+    vec.do_all_renders(&mut counter);
+    assert_eq!(counter.removed, 1);
+    assert_eq!(counter.single, 201);
+
+    // A second batch goes after the last entity instead of reusing the free ID
+    let second = vec.spawn_batch((0..3).map(|i| NewCoolEntity::new(CoolComponent::new(Vec3Df::zero()), MustSync::No, None)));
+    assert_eq!(second, 101..104);
+    assert_eq!(vec.get_read().get_expected_len(), 104);
+}
+
+#[test]
+fn prefab_text_round_trip_and_registration() {
+    let text = "# Goblins\nprefab = goblin\nmesh = goblin_body\nstatic.pos = 0 0 0\nstatic.instance_id = none\ndefault.pos = 1 2 3\n";
+    let prefabs = prefabs_from_text::<HeadlessEngineTID, CoolEntity>(text).unwrap();
+    assert_eq!(prefabs.len(), 1);
+    assert_eq!(prefabs[0].name, "goblin");
+    assert_eq!(prefabs[0].meshes, vec!["goblin_body".to_string()]);
+    assert_eq!(prefabs[0].defaults.pos.pos, Vec3Df::new(1.0, 2.0, 3.0));
+    let written = prefabs_to_text::<HeadlessEngineTID, CoolEntity>(&prefabs);
+    let reread = prefabs_from_text::<HeadlessEngineTID, CoolEntity>(&written).unwrap();
+    assert_eq!(prefabs_to_text::<HeadlessEngineTID, CoolEntity>(&reread), written);
+    assert!(matches!(prefabs_from_text::<HeadlessEngineTID, CoolEntity>("mesh = goblin_body"), Err(PrefabError::NoPrefabStarted { line: 1 })));
+    assert!(matches!(prefabs_from_text::<HeadlessEngineTID, CoolEntity>("prefab = goblin\ndefault.pos = 1 2"), Err(PrefabError::MissingField { .. }) | Err(PrefabError::BadValue { .. })));
+
+    let mut escaped = String::new();
+    "two\nlines \\ here".to_string().write_text(&mut escaped);
+    assert!(!escaped.contains('\n'));
+    assert_eq!(String::read_text(&escaped), Some("two\nlines \\ here".to_string()));
+
+    // Spaces at the ends of a value survive the trimming of its line, the ones around `=` don't
+    let padded = "  padded\tname ".to_string();
+    let mut line = "prefab = goblin\nstatic.name =   ".to_string();
+    padded.write_text(&mut line);
+    line.push_str("   \n");
+    let fields = parse_prefab_text(&line).unwrap();
+    assert_eq!(fields[0].get_static::<String>("name"), Ok(padded));
+    assert_eq!(parse_prefab_text("prefab = goblin\ndefault.pos =  1 2 3 ").unwrap()[0].get_default::<Vec3Df>("pos"), Ok(Vec3Df::new(1.0, 2.0, 3.0)));
+
+    // Static type 0 is taken, so the prefab gets 1 and its entities point to it
+    let vec:CoolEntityVec<HeadlessEngineTID> = CoolEntityVec::new(16);
+    vec.get_write().new_sct(StaticCoolEntity { pos: CoolComponent::new(Vec3Df::zero()), instance_id: None });
+    assert_eq!(vec.get_write().register_prefab(prefabs[0].clone()), Ok(1));
+    assert_eq!(vec.get_write().register_prefab(prefabs[0].clone()), Err(PrefabError::DuplicateName("goblin".to_string())));
+    assert_eq!(vec.get_read().static_types.len(), 2);
+    let id = vec.get_write().new_ent_from_prefab("goblin", MustSync::No, None).unwrap();
+    assert_eq!(vec.get_read().pos[id], CoolComponent { pos: Vec3Df::new(1.0, 2.0, 3.0), static_type: 1 });
+    assert!(vec.get_write().new_ent_from_prefab("orc", MustSync::No, None).is_none());
+
+    // Instances only exist once the prefab's meshes are loaded
+    let meshes = Meshes::new(4, 4);
+    assert!(vec.get_read().prefab_mesh_instances(id, &meshes.get_read()).is_none());
+    meshes.get_write().add_mesh(Mesh::new(MeshLODS::new(Vec::new()), "goblin_body".to_string(), 1.0));
+    let instances = vec.get_read().prefab_mesh_instances(id, &meshes.get_read()).unwrap();
+    assert_eq!(instances.len(), 1);
+    assert_eq!(*instances[0].get_pos(), Vec3Df::new(1.0, 2.0, 3.0));
+    assert!(matches!(instances[0].get_mesh_id(), MeshID::Referenced(0)));
+}
+
+#[test]
+fn metadata_lists_components_in_declaration_order() {
+    let metadata = <CoolEntity as EntityReflection>::METADATA;
+    assert_eq!(metadata.name, "CoolEntity");
+    assert_eq!(metadata.components, &[
+        ComponentMetadata { name: "pos", type_name: "CoolComponent", used_in_new: true, used_in_render: true, must_sync: true, position: true, static_id: true },
+        ComponentMetadata { name: "instance_id", type_name: "Option < usize >", used_in_new: false, used_in_render: true, must_sync: false, position: false, static_id: false },
+    ]);
+    assert_eq!(metadata.component_index("instance_id"), Some(1));
+    assert_eq!(metadata.component("pos").map(|component| component.type_name), Some("CoolComponent"));
+    assert_eq!(metadata.component("health"), None);
+
+    let vec:CoolEntityVec<HeadlessEngineTID> = CoolEntityVec::new(16);
+    vec.get_write().new_sct(StaticCoolEntity { pos: CoolComponent::new(Vec3Df::zero()), instance_id: None });
+    let id = vec.get_write().new_ent(NewCoolEntity::new(CoolComponent::new(Vec3Df::new(1.0, 2.0, 3.0)), MustSync::No, None));
+    let reader = vec.get_read();
+    assert_eq!(*reader.metadata(), metadata);
+    let names:Vec<&str> = reader.all_components_text(id).into_iter().map(|(name, _)| name).collect();
+    assert_eq!(names, vec!["pos", "instance_id"]);
+}
+
+#[test]
+fn edits_become_component_events() {
+    let vec:CoolEntityVec<HeadlessEngineTID> = CoolEntityVec::new(16);
+    vec.get_write().new_sct(StaticCoolEntity { pos: CoolComponent::new(Vec3Df::zero()), instance_id: None });
+    let first = vec.get_write().new_ent(NewCoolEntity::new(CoolComponent::new(Vec3Df::zero()), MustSync::No, None));
+    let second = vec.get_write().new_ent(NewCoolEntity::new(CoolComponent::new(Vec3Df::zero()), MustSync::No, None));
+    {
+        let reader = vec.get_read();
+        assert!(reader.send_edit(first, 0, "1 2 3"));
+        assert!(!reader.send_edit(first, 0, "1 2"));
+        // Option<usize> keeps the default `set_event`, so it can't be edited
+        assert!(!reader.send_edit(first, 1, "4"));
+        assert!(!reader.send_edit(first, 2, "4"));
+    }
+    let sent = vec.tunnels_in.pos_in.try_recv().unwrap();
+    assert_eq!(sent.event.event.pos, Vec3Df::new(1.0, 2.0, 3.0));
+    assert_eq!(sent.event.must_be_synced, MustSync::Both);
+    assert!(vec.tunnels_in.pos_in.try_recv().is_err());
+
+    // Despawned entities can't be edited and are skipped when moving through them
+    let third = vec.get_write().new_ent(NewCoolEntity::new(CoolComponent::new(Vec3Df::zero()), MustSync::No, None));
+    assert!(vec.get_write().despawn(second));
+    let reader = vec.get_read();
+    assert!(!reader.send_edit(second, 0, "1 2 3"));
+    assert_eq!(reader.first_alive(), Some(first));
+    assert_eq!(reader.next_alive(first), Some(third));
+    assert_eq!(reader.previous_alive(third), Some(first));
+    assert_eq!(reader.next_alive(third), None);
+    assert_eq!(reader.previous_alive(first), None);
+}
@@ -0,0 +1,118 @@
+use entity_derive::Entity;
+use to_from_bytes_derive::{FromBytes, ToBytes};
+
+use crate::horde::{game_engine::{character::{step_character, CharacterController, CharacterInput}, change_tracking::ComponentChanges, entity::{AliveMask, Component, ComponentEvent, DespawnEvent, EVecStopsIn, EVecStopsOut, Entity, EntityID, EntityVec, NewEntity, SimpleComponentEvent, SimpleComponentUpdate, StaticComponent, StaticEntity}, hierarchy::{orphans_of, resolve_world_transforms, Hierarchy, InstanceTransforms, WorldTransform}, multiplayer::Identify, physics::{step_rigid_body, PhysicsBroadPhase, PhysicsSettings, PhysicsStatics, RigidBody}, position::EntityPosition, reflection::{ComponentMetadata, EntityMetadata, EntityReflection}, static_type_id::HasStaticTypeID}, geometry::{rotation::{Orientation, Rotation}, vec3d::Vec3Df}};
+
+/// Position component that can be moved through events, shared by the entities of the hierarchy, physics and character tests
+#[derive(Clone, Debug, PartialEq, ToBytes, FromBytes)]
+pub struct MovablePos {
+    pub pos:Vec3Df,
+    pub orient:Orientation,
+}
+
+impl MovablePos {
+    pub fn new(pos:Vec3Df) -> Self {
+        Self { pos, orient: Orientation::zero() }
+    }
+}
+
+#[derive(Clone, PartialEq, ToBytes, FromBytes)]
+pub struct MoveTo {
+    pub pos:Vec3Df,
+    pub orient:Orientation,
+}
+
+impl<ID:Identify> SimpleComponentUpdate<MovablePos, ID> for MoveTo {
+    fn apply_to_comp(self, component:&mut MovablePos) {
+        component.pos = self.pos;
+        component.orient = self.orient;
+    }
+}
+
+impl<ID:Identify> Component<ID> for MovablePos {
+    type SC = Self;
+    type CE = SimpleComponentEvent<ID, MoveTo>;
+    fn from_static(static_comp:&Self::SC) -> Self {
+        static_comp.clone()
+    }
+}
+
+impl StaticComponent for MovablePos {
+
+}
+
+impl<ID:Identify> EntityPosition<ID> for MovablePos {
+    fn get_pos(&self) -> Vec3Df {
+        self.pos
+    }
+    fn get_orientation(&self) -> Orientation {
+        self.orient
+    }
+    fn get_rotation(&self) -> Option<&Rotation> {
+        None
+    }
+    fn move_event(id:EntityID, source:Option<ID>, pos:Vec3Df, orient:Orientation) -> Option<Self::CE> {
+        Some(SimpleComponentEvent::new(id, source, MoveTo { pos, orient }))
+    }
+}
+
+impl HasStaticTypeID for MovablePos {
+    fn get_id(&self) -> usize {
+        0
+    }
+}
+
+#[derive(Entity, Clone)]
+pub struct Part {
+    #[used_in_new]
+    #[used_in_render]
+    #[position]
+    #[static_id]
+    pub pos:MovablePos,
+    #[used_in_new]
+    #[hierarchy]
+    pub hierarchy:Hierarchy<EntityID>,
+    #[used_in_render]
+    #[mesh_instance = "3"]
+    pub instance:Option<usize>,
+}
+
+impl<ID:Identify> NewEntity<Part, ID> for NewPart {
+    fn get_ent(self, static_type:&StaticPart<ID>) -> Part {
+        Part { pos: self.pos, hierarchy: self.hierarchy, instance: None }
+    }
+}
+
+#[derive(Entity, Clone)]
+pub struct Ball {
+    #[used_in_new]
+    #[position]
+    #[static_id]
+    pub pos:MovablePos,
+    #[used_in_new]
+    #[rigid_body]
+    pub body:RigidBody,
+}
+
+impl<ID:Identify> NewEntity<Ball, ID> for NewBall {
+    fn get_ent(self, static_type:&StaticBall<ID>) -> Ball {
+        Ball { pos: self.pos, body: self.body }
+    }
+}
+
+#[derive(Entity, Clone)]
+pub struct Walker {
+    #[used_in_new]
+    #[position]
+    #[static_id]
+    pub pos:MovablePos,
+    #[used_in_new]
+    #[character]
+    pub controller:CharacterController,
+}
+
+impl<ID:Identify> NewEntity<Walker, ID> for NewWalker {
+    fn get_ent(self, static_type:&StaticWalker<ID>) -> Walker {
+        Walker { pos: self.pos, controller: self.controller }
+    }
+}
@@ -0,0 +1,66 @@
+use std::sync::{atomic::{AtomicUsize, Ordering}, Arc};
+
+use engine_derive::GameEngine;
+
+use crate::{defaults::default_rendering::null_rendering::{NullRendering, NullRenderingWrite}, horde::{game_engine::{event_order::SequencedEvent, engine::{GameEngine, MovingObjectID, BEGIN_TICK_TASK}, entity::{Entity, EntityID, EntityVec, Renderable}, headless::HeadlessHarness, multiplayer::{Identify, MustSync}, world::{WorldComputeHandler, WorldHandler, WorldOutHandler, WorldWriteHandler}}, geometry::vec3d::Vec3Df, scheduler::IndividualTask}};
+
+use super::{engine_derive_test::TestWorld, entity_derive_test::{CoolComponent, CoolEntity, CoolEntityVec, CoolEntityVecRead, CoolEntityVecWrite, NewCoolEntity, RenderCoolEntity, StaticCoolEntity}};
+
+impl<'a, ID:Identify> RenderCoolEntity<NullRenderingWrite<'a>, ID> for CoolEntity {
+    fn do_render_changes(rendering_data:&mut NullRenderingWrite<'a>, pos: &mut CoolComponent, instance_id:&mut Option<usize>, static_type:&StaticCoolEntity<ID>) {
+        
+    }
+}
+
+impl<'a> Renderable<NullRenderingWrite<'a>> for TestWorld {
+    fn do_render_changes(&mut self, render_data:&mut NullRenderingWrite<'a>) {
+        
+    }
+}
+
+/// The first entity spawns a new one each tick, one step further along x
+pub fn stage_0<'a>(turn:EntityTurn, id:EntityID, reader: &CoolEntityVecRead<'a, HeadlessEngineTID>, world_read: &WorldComputeHandler<'a, TestWorld, HeadlessEngineTID>) {
+    match turn {
+        EntityTurn::ent1 => {
+            if id == 0 {
+                let next_x = reader.pos.len() as f32;
+                let _ = reader.tunnels.new_ents.send(SequencedEvent::new(NewCoolEntity::new(CoolComponent::new(Vec3Df::new(next_x, 0.0, 0.0)), MustSync::No, None)));
+            }
+        },
+    }
+}
+
+#[derive(GameEngine, Clone)]
+#[rendering_engine = "NullRendering"]
+#[tick_stages = 1]
+pub struct HeadlessEngine {
+    ent1:CoolEntity,
+    world:TestWorld,
+}
+
+pub fn headless_test() {
+    let entity_vec = CoolEntityVec::new(1000);
+    {
+        entity_vec.get_write().new_sct(StaticCoolEntity { pos: CoolComponent::new(Vec3Df::zero()), instance_id: None });
+        entity_vec.get_write().new_ent(NewCoolEntity::new(CoolComponent::new(Vec3Df::zero()), MustSync::No, None));
+    }
+    let engine = HeadlessEngineBase::new(entity_vec, WorldHandler::new(TestWorld { test: 1 }), Arc::new(NullRendering::new()));
+    let mut harness = HeadlessHarness::new(engine, 1);
+    harness.run_ticks(10);
+    assert_eq!(harness.ticks_done(), 10);
+    harness.assert_query_eq(11, |engine| engine.ent1.get_read().pos.len());
+    harness.assert_query_eq(11, |engine| engine.ent1.get_read().alive.alive_count());
+    harness.assert_query_eq(11, |engine| engine.ent1.get_read().changes.tick());
+    harness.assert_that("first entity didn't move", |engine| engine.ent1.get_read().pos[0].pos == Vec3Df::zero());
+    harness.assert_that("spawned entities are out of order", |engine| {
+        engine.ent1.get_read().pos.iter().enumerate().all(|(i, comp)| comp.pos == Vec3Df::new(i as f32, 0.0, 0.0))
+    });
+    harness.assert_query_eq(1, |engine| engine.world.world.read().unwrap().test);
+    let spawned_at = harness.run_until(10, |engine| engine.ent1.get_read().pos.len() >= 15).unwrap();
+    assert_eq!(spawned_at, 4);
+}
+
+#[test]
+fn test_headless() {
+    headless_test();
+}
@@ -0,0 +1,85 @@
+use crate::horde::{game_engine::{entity::DespawnEvent, hierarchy::{orphans_of, resolve_world_transforms, Hierarchy, InstanceTransforms, LocalTransform, OrphanPolicy, WorldTransform}, multiplayer::Identify}, geometry::{rotation::Orientation, vec3d::Vec3Df}};
+
+use super::{fixtures::{MovablePos, NewPart, Part, PartVec, RenderPart, StaticPart}, headless_test::HeadlessEngineTID};
+
+/// Hands out instance indices and records where the hierarchy moved them
+#[derive(Default)]
+pub struct InstanceLog {
+    next_instance:usize,
+    pub moved:Vec<(usize, usize, Vec3Df)>,
+}
+
+impl InstanceTransforms for InstanceLog {
+    fn set_instance_transform(&mut self, instance:usize, for_vec:usize, transform:&WorldTransform) {
+        self.moved.push((instance, for_vec, transform.pos));
+    }
+}
+
+impl<ID:Identify> RenderPart<InstanceLog, ID> for Part {
+    fn do_render_changes(rendering_data:&mut InstanceLog, pos:&mut MovablePos, instance:&mut Option<usize>, static_type:&StaticPart<ID>) {
+        if instance.is_none() {
+            *instance = Some(rendering_data.next_instance);
+            rendering_data.next_instance += 1;
+        }
+    }
+}
+
+#[test]
+fn hierarchy_resolves_renders_and_orphans() {
+    let mut vec:PartVec<HeadlessEngineTID> = PartVec::new(16);
+    vec.get_write().new_sct(StaticPart { pos: MovablePos::new(Vec3Df::zero()), hierarchy: Hierarchy::root(), instance: None });
+    let (root, child, grandchild, doomed) = {
+        let mut writer = vec.get_write();
+        let root = writer.new_ent(NewPart::new(MovablePos::new(Vec3Df::new(1.0, 0.0, 0.0)), Hierarchy::root()));
+        let child = writer.new_ent(NewPart::new(MovablePos::new(Vec3Df::zero()), Hierarchy::child_of(root, LocalTransform::new(Vec3Df::new(0.0, 1.0, 0.0), Orientation::zero()), OrphanPolicy::Detach)));
+        let grandchild = writer.new_ent(NewPart::new(MovablePos::new(Vec3Df::zero()), Hierarchy::child_of(child, LocalTransform::new(Vec3Df::new(0.0, 0.0, 1.0), Orientation::zero()), OrphanPolicy::Despawn)));
+        let doomed = writer.new_ent(NewPart::new(MovablePos::new(Vec3Df::zero()), Hierarchy::child_of(root, LocalTransform::zero(), OrphanPolicy::Despawn)));
+        (root, child, grandchild, doomed)
+    };
+
+    // Resolved right after events are applied, parents before children
+    vec.apply_all_events(false);
+    {
+        let reader = vec.get_read();
+        assert_eq!(reader.hierarchy[root].world, None);
+        assert_eq!(reader.hierarchy[child].world.as_ref().map(|world| world.pos), Some(Vec3Df::new(1.0, 1.0, 0.0)));
+        assert_eq!(reader.hierarchy[grandchild].world.as_ref().map(|world| world.pos), Some(Vec3Df::new(1.0, 1.0, 1.0)));
+        assert_eq!(reader.hierarchy[doomed].world.as_ref().map(|world| world.pos), Some(Vec3Df::new(1.0, 0.0, 0.0)));
+    }
+
+    // Children get their instance moved once it exists, roots are left to their own render
+    let mut log = InstanceLog::default();
+    vec.do_all_renders(&mut log);
+    log.moved.sort_by_key(|(instance, _, _)| *instance);
+    assert_eq!(log.moved, vec![(1, 3, Vec3Df::new(1.0, 1.0, 0.0)), (2, 3, Vec3Df::new(1.0, 1.0, 1.0)), (3, 3, Vec3Df::new(1.0, 0.0, 0.0))]);
+
+    // The detached child stays where it was and keeps its own despawn-policy child, the other one goes with the root
+    let _ = vec.get_read().tunnels.despawns.send(DespawnEvent::new(root, None));
+    vec.apply_all_events(false);
+    {
+        let reader = vec.get_read();
+        assert!(!reader.alive.is_alive(root));
+        assert!(!reader.alive.is_alive(doomed));
+        assert!(reader.alive.is_alive(child));
+        assert!(reader.alive.is_alive(grandchild));
+        assert_eq!(reader.hierarchy[child].parent, None);
+        assert_eq!(reader.hierarchy[child].world, None);
+        assert_eq!(reader.pos[child].pos, Vec3Df::new(1.0, 1.0, 0.0));
+        assert_eq!(reader.hierarchy[grandchild].world.as_ref().map(|world| world.pos), Some(Vec3Df::new(1.0, 1.0, 1.0)));
+    }
+}
+
+#[test]
+fn hierarchy_leaves_out_loops_and_cascades_despawns() {
+    let a = Hierarchy::child_of(1_usize, LocalTransform::zero(), OrphanPolicy::Despawn);
+    let b = Hierarchy::child_of(0_usize, LocalTransform::zero(), OrphanPolicy::Despawn);
+    let c = Hierarchy::child_of(3_usize, LocalTransform::new(Vec3Df::new(2.0, 0.0, 0.0), Orientation::zero()), OrphanPolicy::Despawn);
+    let root = Hierarchy::root();
+    let nodes = vec![(0, &a), (1, &b), (2, &c), (3, &root)];
+    let transforms = resolve_world_transforms(nodes.clone().into_iter(), |id| if *id == 3 {Some(WorldTransform::new(Vec3Df::new(0.0, 5.0, 0.0), Orientation::zero()))} else {None});
+    assert_eq!(transforms, vec![(2, WorldTransform::new(Vec3Df::new(2.0, 5.0, 0.0), Orientation::zero()))]);
+
+    let orphans = orphans_of(3, nodes.into_iter());
+    assert_eq!(orphans.despawned, vec![2]);
+    assert!(orphans.detached.is_empty());
+}
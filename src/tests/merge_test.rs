@@ -0,0 +1,99 @@
+use crate::horde::game_engine::{entity::{ComponentEvent, EntityID, SimpleComponentEvent}, hierarchy::{Hierarchy, HierarchyUpdate, OrphanPolicy}, merge::{last_writer_wins, max_merge, merge_events, min_merge, sum_merge, variant_kind, MergeableEvent}};
+
+use super::headless_test::HeadlessEngineTID;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Change {
+    Damage(i32),
+    Heal(i32),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct ChangeEvent {
+    target:EntityID,
+    change:Change,
+    value:i32,
+}
+
+impl ChangeEvent {
+    fn damage(target:EntityID, value:i32) -> Self {
+        Self { target, change: Change::Damage(value), value }
+    }
+    fn heal(target:EntityID, value:i32) -> Self {
+        Self { target, change: Change::Heal(value), value }
+    }
+}
+
+impl MergeableEvent for ChangeEvent {
+    type Value = i32;
+    fn value(&self) -> &i32 {
+        &self.value
+    }
+    fn with_value(self, value:i32) -> Self {
+        let change = match self.change {
+            Change::Damage(_) => Change::Damage(value),
+            Change::Heal(_) => Change::Heal(value),
+        };
+        Self { change, value, ..self }
+    }
+}
+
+fn merge_by_target<R:FnMut(ChangeEvent, ChangeEvent) -> ChangeEvent>(events:Vec<ChangeEvent>, reduce:R) -> Vec<ChangeEvent> {
+    merge_events(events, |event| (event.target, variant_kind(&event.change)), reduce)
+}
+
+fn damages(values:&[(EntityID, i32)]) -> Vec<ChangeEvent> {
+    values.iter().map(|(target, value)| ChangeEvent::damage(*target, *value)).collect()
+}
+
+#[test]
+fn last_writer_wins_keeps_the_last_event_of_each_target() {
+    let merged = merge_by_target(damages(&[(1, 5), (2, 7), (1, 3), (1, 9)]), last_writer_wins);
+    assert_eq!(merged, damages(&[(1, 9), (2, 7)]));
+}
+
+#[test]
+fn sum_adds_the_values_of_each_target() {
+    let merged = merge_by_target(damages(&[(1, 5), (2, 7), (1, 3), (1, 9)]), sum_merge);
+    assert_eq!(merged, damages(&[(1, 17), (2, 7)]));
+}
+
+#[test]
+fn min_and_max_keep_the_first_extreme_of_each_target() {
+    let events = vec![ChangeEvent::damage(1, 5), ChangeEvent::damage(2, 7), ChangeEvent::damage(1, 3), ChangeEvent::damage(1, 9)];
+    assert_eq!(merge_by_target(events.clone(), min_merge), damages(&[(1, 3), (2, 7)]));
+    assert_eq!(merge_by_target(events, max_merge), damages(&[(1, 9), (2, 7)]));
+
+    // Ties keep the event seen first
+    let tied = vec![ChangeEvent { target: 1, change: Change::Damage(0), value: 4 }, ChangeEvent::damage(1, 4)];
+    assert_eq!(merge_by_target(tied.clone(), min_merge), vec![tied[0]]);
+    assert_eq!(merge_by_target(tied.clone(), max_merge), vec![tied[0]]);
+}
+
+#[test]
+fn custom_reducers_see_events_in_order() {
+    let mut seen = Vec::new();
+    let merged = merge_by_target(damages(&[(1, 1), (1, 2), (1, 3)]), |first, second| {
+        seen.push((first.value, second.value));
+        second
+    });
+    assert_eq!(merged, damages(&[(1, 3)]));
+    assert_eq!(seen, vec![(1, 2), (2, 3)]);
+}
+
+#[test]
+fn different_variants_are_never_merged() {
+    let events = vec![ChangeEvent::damage(1, 5), ChangeEvent::heal(1, 2), ChangeEvent::damage(1, 3), ChangeEvent::heal(2, 4)];
+    let merged = merge_by_target(events, sum_merge);
+    assert_eq!(merged, vec![ChangeEvent::damage(1, 8), ChangeEvent::heal(1, 2), ChangeEvent::heal(2, 4)]);
+
+    // Simple component events take the kind of their update, other events their own variant
+    let parent = SimpleComponentEvent::<HeadlessEngineTID, HierarchyUpdate<usize>>::new(0, None, HierarchyUpdate::SetParent(Some(1)));
+    let other_parent = SimpleComponentEvent::<HeadlessEngineTID, HierarchyUpdate<usize>>::new(0, None, HierarchyUpdate::SetParent(None));
+    let policy = SimpleComponentEvent::<HeadlessEngineTID, HierarchyUpdate<usize>>::new(0, None, HierarchyUpdate::SetPolicy(OrphanPolicy::Detach));
+    let kind = |event:&SimpleComponentEvent<HeadlessEngineTID, HierarchyUpdate<usize>>| <SimpleComponentEvent<HeadlessEngineTID, HierarchyUpdate<usize>> as ComponentEvent<Hierarchy<usize>, HeadlessEngineTID>>::merge_kind(event);
+    assert_eq!(kind(&parent), kind(&other_parent));
+    assert_ne!(kind(&parent), kind(&policy));
+    assert_eq!(<Option<usize> as ComponentEvent<Option<usize>, HeadlessEngineTID>>::merge_kind(&Some(1)), <Option<usize> as ComponentEvent<Option<usize>, HeadlessEngineTID>>::merge_kind(&Some(2)));
+    assert_ne!(<Option<usize> as ComponentEvent<Option<usize>, HeadlessEngineTID>>::merge_kind(&Some(1)), <Option<usize> as ComponentEvent<Option<usize>, HeadlessEngineTID>>::merge_kind(&None));
+}
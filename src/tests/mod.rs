@@ -5,4 +5,21 @@ pub mod task_derive_test;
 #[cfg(test)]
 pub mod simd_tests;
 pub mod crazy_test;
-pub mod single_player_engine_test;
\ No newline at end of file
+pub mod single_player_engine_test;
+pub mod headless_test;
+#[cfg(test)]
+pub mod scheduler_test;
+#[cfg(test)]
+pub mod fixtures;
+#[cfg(test)]
+pub mod hierarchy_test;
+#[cfg(test)]
+pub mod collision_test;
+#[cfg(test)]
+pub mod physics_test;
+#[cfg(test)]
+pub mod picking_test;
+#[cfg(test)]
+pub mod character_test;
+#[cfg(test)]
+pub mod merge_test;
\ No newline at end of file
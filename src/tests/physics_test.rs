@@ -0,0 +1,92 @@
+use crate::horde::{game_engine::{entity::DespawnEvent, physics::{BodyShape, PhysicsSettings, PhysicsStatics, RigidBody, MIN_MASS}}, geometry::{collision::{narrow_phase::Collider, shapes::Aabb}, vec3d::Vec3Df}};
+
+use super::{fixtures::{BallVec, MovablePos, NewBall, StaticBall}, headless_test::HeadlessEngineTID};
+
+fn ball_vec() -> BallVec<HeadlessEngineTID> {
+    let vec = BallVec::new(8);
+    vec.get_write().new_sct(StaticBall { pos: MovablePos::new(Vec3Df::zero()), body: RigidBody::new_static(BodyShape::Sphere { radius: 0.5 }) });
+    vec
+}
+
+fn ball() -> RigidBody {
+    RigidBody::new_dynamic(BodyShape::Sphere { radius: 0.5 }, 1.0)
+}
+
+/// Applies the events of the last tick, which also updates the broad phase, then runs the physics stage of every ball
+fn tick(vec:&BallVec<HeadlessEngineTID>, statics:&PhysicsStatics, settings:&PhysicsSettings) {
+    vec.apply_all_events(false);
+    let reader = vec.get_read();
+    for id in 0..reader.get_expected_len() {
+        reader.physics_stage(id, statics, settings);
+    }
+}
+
+#[test]
+fn physics_stage_drops_balls_to_rest_and_bounces_them_apart() {
+    let settings = PhysicsSettings::new(1.0 / 60.0);
+    let mut statics = PhysicsStatics::new();
+    statics.add(Collider::Aabb(Aabb::new(Vec3Df::new(-10.0, -10.0, -1.0), Vec3Df::new(10.0, 10.0, 0.0))), 0.0, 0.5);
+    let vec = ball_vec();
+    let (falling, left, right) = {
+        let mut writer = vec.get_write();
+        let falling = writer.new_ent(NewBall::new(MovablePos::new(Vec3Df::new(0.0, 0.0, 2.0)), ball()));
+        let left = writer.new_ent(NewBall::new(MovablePos::new(Vec3Df::new(-1.0, 5.0, 5.0)), ball().with_gravity_scale(0.0).with_velocity(Vec3Df::new(2.0, 0.0, 0.0))));
+        let right = writer.new_ent(NewBall::new(MovablePos::new(Vec3Df::new(1.0, 5.0, 5.0)), ball().with_gravity_scale(0.0).with_velocity(Vec3Df::new(-2.0, 0.0, 0.0))));
+        (falling, left, right)
+    };
+    for _ in 0..180 {
+        tick(&vec, &statics, &settings);
+    }
+    vec.apply_all_events(false);
+
+    let reader = vec.get_read();
+    // Resting on the floor, a bit inside it because of the penetration slop, and asleep
+    assert!((reader.pos[falling].pos.z - 0.5).abs() < 0.01, "ball at {:?}", reader.pos[falling].pos);
+    assert!(reader.body[falling].sleeping);
+    assert_eq!(reader.body[falling].velocity, Vec3Df::zero());
+
+    // Head-on hit with a restitution of 0.2, both bounce back at a fifth of their speed
+    assert!((reader.body[left].velocity.x + 0.4).abs() < 0.02, "left ball at {:?}", reader.body[left].velocity);
+    assert!((reader.body[right].velocity.x - 0.4).abs() < 0.02, "right ball at {:?}", reader.body[right].velocity);
+    assert!(reader.pos[left].pos.x < -1.0 && reader.pos[right].pos.x > 1.0);
+    assert_eq!(reader.pos[left].pos.y, 5.0);
+}
+
+#[test]
+fn broad_phase_pairs_only_overlapping_enabled_bodies() {
+    let vec = ball_vec();
+    let (a, b, far, disabled) = {
+        let mut writer = vec.get_write();
+        let a = writer.new_ent(NewBall::new(MovablePos::new(Vec3Df::zero()), ball()));
+        let b = writer.new_ent(NewBall::new(MovablePos::new(Vec3Df::new(0.8, 0.0, 0.0)), ball()));
+        let far = writer.new_ent(NewBall::new(MovablePos::new(Vec3Df::new(10.0, 0.0, 0.0)), ball()));
+        let mut body = ball();
+        body.enabled = false;
+        let disabled = writer.new_ent(NewBall::new(MovablePos::new(Vec3Df::new(-0.8, 0.0, 0.0)), body));
+        (a, b, far, disabled)
+    };
+    vec.apply_all_events(false);
+    {
+        let reader = vec.get_read();
+        assert_eq!(reader.broad_phase.candidates(a), &[b]);
+        assert_eq!(reader.broad_phase.candidates(b), &[a]);
+        assert!(reader.broad_phase.candidates(far).is_empty());
+        assert!(reader.broad_phase.candidates(disabled).is_empty());
+    }
+
+    let _ = vec.get_read().tunnels.despawns.send(DespawnEvent::new(b, None));
+    vec.apply_all_events(false);
+    let reader = vec.get_read();
+    assert!(reader.broad_phase.candidates(a).is_empty());
+    assert!(reader.broad_phase.candidates(b).is_empty());
+}
+
+#[test]
+fn zero_and_negative_masses_are_clamped() {
+    for mass in [0.0, -2.0] {
+        let body = RigidBody::new_dynamic(BodyShape::Sphere { radius: 0.5 }, mass);
+        assert_eq!(body.inverse_mass, 1.0 / MIN_MASS);
+        assert!(body.inverse_inertia.is_finite());
+        assert!(body.is_dynamic());
+    }
+}
@@ -0,0 +1,127 @@
+use std::sync::Arc;
+
+use crate::{defaults::default_rendering::vectorinator::{meshes::{Mesh, MeshID, MeshInstance, MeshLOD, MeshLODS, MeshLODType, MeshTriangles, Meshes, TrianglePoint}, rendering_spaces::{Vec3DfCam, Vec3DfRaster, ViewportData}}, horde::{geometry::{line::Line3D, plane::EquationPlane, rotation::{Orientation, Rotation}, vec3d::Vec3Df}, rendering::camera::Camera}};
+
+const EPSILON:f32 = 0.001;
+
+fn assert_close(found:Vec3Df, expected:Vec3Df) {
+    assert!(found.dist(&expected) < EPSILON, "expected {:?}, found {:?}", expected, found);
+}
+
+/// One triangle with the right angle at (-1, -1, 0) in mesh space, its UVs going from 0 to 1 along X and Y
+fn triangle_mesh(size:f32) -> Mesh {
+    let mut triangles = MeshTriangles::with_capacity(1);
+    triangles.add_triangle(TrianglePoint::new(0, 0.0, 0.0, 255, 255, 255), TrianglePoint::new(1, 1.0, 0.0, 255, 255, 255), TrianglePoint::new(2, 0.0, 1.0, 255, 255, 255), 7, 0);
+    let lod = MeshLOD::new(vec![-1.0, 1.0, -1.0], vec![-1.0, -1.0, 1.0], vec![0.0, 0.0, 0.0], triangles);
+    Mesh::new(MeshLODS::new(vec![MeshLODType::Mesh(Arc::new(lod))]), "triangle".to_string(), size)
+}
+
+/// Meshes with `mesh` placed once in instance vec 0 for every position
+fn placed(mesh:Mesh, instances:&[(Vec3Df, Orientation)]) -> Meshes {
+    let meshes = Meshes::new(1, 1);
+    {
+        let mut writer = meshes.get_write();
+        let mesh_id = writer.add_mesh(mesh);
+        for (pos, orient) in instances {
+            writer.add_instance(MeshInstance::new(*pos, *orient, MeshID::Referenced(mesh_id), true, false, false), 0);
+        }
+    }
+    meshes
+}
+
+#[test]
+fn ray_triangle_distance_uv_and_normal() {
+    let meshes = placed(triangle_mesh(2.0), &[(Vec3Df::new(0.0, 0.0, 5.0), Orientation::zero())]);
+    let reader = meshes.get_read();
+
+    // Distances are in lengths of the director, the hit point being a quarter of the way along both edges
+    let from_above = Line3D::new(Vec3Df::new(-0.5, -0.5, 10.0), Vec3Df::new(0.0, 0.0, -2.0));
+    let hit = reader.ray_cast_instance(&from_above, 100.0, 0, 0).unwrap();
+    assert!((hit.distance - 2.5).abs() < EPSILON);
+    assert_close(hit.point, Vec3Df::new(-0.5, -0.5, 5.0));
+    assert!((hit.u - 0.25).abs() < EPSILON && (hit.v - 0.25).abs() < EPSILON);
+    assert_eq!((hit.triangle, hit.texture), (0, 7));
+
+    // Both faces are hit, the normal always faces the ray origin
+    assert_close(hit.normal, Vec3Df::new(0.0, 0.0, 1.0));
+    let from_below = Line3D::new(Vec3Df::new(0.25, -0.5, 0.0), Vec3Df::new(0.0, 0.0, 1.0));
+    let hit = reader.ray_cast_instance(&from_below, 100.0, 0, 0).unwrap();
+    assert!((hit.distance - 5.0).abs() < EPSILON);
+    assert_close(hit.normal, Vec3Df::new(0.0, 0.0, -1.0));
+    assert!((hit.u - 0.625).abs() < EPSILON && (hit.v - 0.25).abs() < EPSILON);
+
+    // Outside the triangle, behind the origin, or past the max distance
+    assert_eq!(reader.ray_cast_instance(&Line3D::new(Vec3Df::new(0.5, 0.5, 10.0), Vec3Df::new(0.0, 0.0, -1.0)), 100.0, 0, 0), None);
+    assert_eq!(reader.ray_cast_instance(&Line3D::new(Vec3Df::new(-0.5, -0.5, 10.0), Vec3Df::new(0.0, 0.0, 1.0)), 100.0, 0, 0), None);
+    assert_eq!(reader.ray_cast_instance(&from_above, 2.0, 0, 0), None);
+}
+
+#[test]
+fn ray_triangle_on_rotated_instance() {
+    let orient = Orientation::new(0.7, 0.3, -0.2);
+    let pos = Vec3Df::new(1.0, 2.0, 3.0);
+    let rotation = Rotation::from_orientation(orient);
+    let meshes = placed(triangle_mesh(2.0), &[(pos, orient)]);
+
+    // The same ray as straight above the triangle, seen from the rotated instance
+    let ray = Line3D::new(pos + rotation.rotate(Vec3Df::new(-0.5, -0.5, 5.0)), rotation.rotate(Vec3Df::new(0.0, 0.0, -1.0)));
+    let hit = meshes.get_read().ray_cast_instance(&ray, 100.0, 0, 0).unwrap();
+    assert!((hit.distance - 5.0).abs() < EPSILON);
+    assert_close(hit.point, pos + rotation.rotate(Vec3Df::new(-0.5, -0.5, 0.0)));
+    assert_close(hit.normal, rotation.rotate(Vec3Df::new(0.0, 0.0, 1.0)));
+    assert!((hit.u - 0.25).abs() < EPSILON && (hit.v - 0.25).abs() < EPSILON);
+}
+
+#[test]
+fn bounding_sphere_skips_triangles_and_closest_instance_wins() {
+    // The ray goes through the triangle but never comes within the size of the mesh from its position, so the triangles aren't even tested
+    let ray = Line3D::new(Vec3Df::new(-0.5, -0.5, 10.0), Vec3Df::new(0.0, 0.0, -1.0));
+    let small = placed(triangle_mesh(0.5), &[(Vec3Df::zero(), Orientation::zero())]);
+    assert_eq!(small.get_read().ray_cast_instance(&ray, 100.0, 0, 0), None);
+    let fitting = placed(triangle_mesh(1.5), &[(Vec3Df::zero(), Orientation::zero())]);
+    assert!(fitting.get_read().ray_cast_instance(&ray, 100.0, 0, 0).is_some());
+
+    // The sphere must also be reached before the max distance
+    assert_eq!(fitting.get_read().ray_cast_instance(&ray, 5.0, 0, 0), None);
+
+    let stacked = placed(triangle_mesh(2.0), &[(Vec3Df::zero(), Orientation::zero()), (Vec3Df::new(0.0, 0.0, 3.0), Orientation::zero()), (Vec3Df::new(20.0, 0.0, 0.0), Orientation::zero())]);
+    let hit = stacked.get_read().ray_cast(&ray, 100.0).unwrap();
+    assert_eq!((hit.instance_vec, hit.instance), (0, 1));
+    assert!((hit.distance - 7.0).abs() < EPSILON);
+    assert_eq!(hit.find_entity(0, &[None, Some(2), Some(1)]), Some(2));
+    assert_eq!(hit.find_entity(1, &[Some(1)]), None);
+}
+
+fn viewport_data(camera:&Camera) -> ViewportData {
+    ViewportData {
+        near_clipping_plane: 1.0,
+        half_image_width: 400.0,
+        half_image_height: 300.0,
+        aspect_ratio: 800.0 / 600.0,
+        camera_plane: EquationPlane::new(Vec3Df::new(0.0, 0.0, 1.0), -1.0),
+        image_height: 600.0,
+        image_width: 800.0,
+        poscam: camera.pos,
+        rotat_cam: Rotation::new_from_inverted_orient(camera.orient)
+    }
+}
+
+#[test]
+fn screen_rays_go_through_their_pixel() {
+    let camera = Camera::new(Vec3Df::new(1.0, 2.0, 3.0), Orientation::new(0.7, 0.3, -0.2));
+    let viewport_data = viewport_data(&camera);
+    let forward = Rotation::from_orientation(camera.orient).rotate(Vec3Df::new(0.0, 0.0, 1.0));
+
+    // The center of the screen looks straight ahead, with a normalised director
+    let center = viewport_data.screen_ray(&camera, viewport_data.half_image_width, viewport_data.half_image_height * viewport_data.aspect_ratio);
+    assert_close(*center.get_origin(), camera.pos);
+    assert_close(*center.get_director(), forward);
+
+    // Any point projected on the screen is on the ray of its pixel
+    let point = camera.pos + Rotation::from_orientation(camera.orient).rotate(Vec3Df::new(0.8, -0.5, 4.0));
+    let raster = Vec3DfRaster::from_cameraspace(Vec3DfCam::from_realspace(point, &viewport_data.poscam, &viewport_data.rotat_cam), &viewport_data);
+    assert!(raster.is_point_on_screen(&viewport_data));
+    let ray = viewport_data.screen_ray(&camera, raster.x, raster.y);
+    assert!((ray.get_director().norme() - 1.0).abs() < EPSILON);
+    assert_close(*ray.get_director(), (point - camera.pos).normalise());
+}
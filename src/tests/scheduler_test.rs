@@ -0,0 +1,601 @@
+use std::{ops::Range, panic::{catch_unwind, AssertUnwindSafe}, sync::{atomic::{AtomicBool, AtomicUsize, Ordering}, Arc, Mutex}, time::{Duration, Instant}};
+
+use crossbeam::channel::{Receiver, Sender};
+
+use crate::horde::{game_engine::time_control::{TimeControl, TimeControlState, MAX_TIME_SCALE, MIN_TIME_SCALE}, scheduler::{background::BackgroundError, watchdog::{SchedulerError, WorkerState}, graph::{TaskGraph, TaskGraphBuilder, TaskGraphError, TaskNode}, periodic::{BudgetedStep, PeriodicTask, TaskBudget}, profiler::{HordeProfiler, ProfileKind}, runner::{FixedStepConfig, FixedStepRunner, RenderRate}, work_stealing::{AdaptiveThreads, ChunkSource}, EmptyBackgroundTask, HordeScheduler, HordeTask, HordeTaskData, HordeTaskHandler, HordeTaskQueue, HordeTaskSequence, SequencedTask}};
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub enum GraphTask {
+    Input,
+    Physics,
+    Ai,
+    Audio,
+    Render,
+    Idle,
+}
+
+/// Records the tasks in the order they ran
+#[derive(Clone)]
+pub struct GraphLog {
+    runs:Arc<Mutex<Vec<GraphTask>>>,
+    render_gate:Arc<Mutex<Option<Receiver<()>>>>,
+}
+
+impl GraphLog {
+    pub fn new() -> Self {
+        Self { runs: Arc::new(Mutex::new(Vec::new())), render_gate: Arc::new(Mutex::new(None)) }
+    }
+    /// Every following `Render` waits for something to be sent through the returned sender before finishing
+    fn hold_render(&self) -> Sender<()> {
+        let (send, recv) = crossbeam::channel::unbounded();
+        *self.render_gate.lock().unwrap() = Some(recv);
+        send
+    }
+    pub fn get_runs(&self) -> Vec<GraphTask> {
+        self.runs.lock().unwrap().clone()
+    }
+    fn position_of(&self, task:&GraphTask) -> usize {
+        self.get_runs().iter().position(|run| run == task).unwrap()
+    }
+}
+
+impl HordeTaskHandler for GraphLog {
+
+}
+
+impl HordeTaskData<GraphTask> for GraphLog {
+    fn do_task(&mut self, task:GraphTask, thread_number:usize, number_of_threads:usize) {
+        if task == GraphTask::Render {
+            let gate = self.render_gate.lock().unwrap().clone();
+            if let Some(gate) = gate {
+                let _ = gate.recv();
+            }
+        }
+        self.runs.lock().unwrap().push(task);
+    }
+}
+
+impl HordeTask for GraphTask {
+    type HTH = GraphLog;
+    type HTD = GraphLog;
+    fn max_threads(&self) -> usize {
+        match self {
+            GraphTask::Idle => 0,
+            _ => 1
+        }
+    }
+    fn data_from_handler(handler:&Self::HTH) -> Self::HTD {
+        handler.clone()
+    }
+}
+
+fn frame_graph() -> TaskGraphBuilder<GraphTask> {
+    TaskGraphBuilder::new()
+        .with(TaskNode::new(GraphTask::Input).writes("input"))
+        .with(TaskNode::new(GraphTask::Physics).after(GraphTask::Input).reads("input").writes("positions"))
+        .with(TaskNode::new(GraphTask::Ai).after(GraphTask::Input).reads("input").writes("plans"))
+        .with(TaskNode::new(GraphTask::Audio).reads("plans").after(GraphTask::Ai))
+        .with(TaskNode::new(GraphTask::Render).after(GraphTask::Physics).reads("positions"))
+}
+
+#[test]
+fn graph_levels() {
+    let graph = frame_graph().build().unwrap();
+    assert_eq!(graph.get_levels(), vec![vec![GraphTask::Input], vec![GraphTask::Physics, GraphTask::Ai], vec![GraphTask::Audio, GraphTask::Render]]);
+}
+
+#[test]
+fn graph_detects_cycle() {
+    let result = TaskGraphBuilder::new()
+        .with(TaskNode::new(GraphTask::Input))
+        .with(TaskNode::new(GraphTask::Physics).after(GraphTask::Render))
+        .with(TaskNode::new(GraphTask::Ai).after(GraphTask::Physics))
+        .with(TaskNode::new(GraphTask::Render).after(GraphTask::Ai).after(GraphTask::Input))
+        .build();
+    match result {
+        Err(TaskGraphError::Cycle(cycle)) => {
+            assert_eq!(cycle.len(), 3);
+            for task in [GraphTask::Physics, GraphTask::Ai, GraphTask::Render] {
+                assert!(cycle.contains(&task));
+            }
+        },
+        _ => panic!("cycle not detected")
+    }
+}
+
+#[test]
+fn graph_detects_conflicts() {
+    let writers = TaskGraphBuilder::new()
+        .with(TaskNode::new(GraphTask::Physics).writes("positions"))
+        .with(TaskNode::new(GraphTask::Ai).writes("positions"))
+        .build();
+    assert_eq!(writers.err(), Some(TaskGraphError::ConflictingWriters { data: "positions".to_string(), first: GraphTask::Physics, second: GraphTask::Ai }));
+
+    let read_write = TaskGraphBuilder::new()
+        .with(TaskNode::new(GraphTask::Render).reads("positions"))
+        .with(TaskNode::new(GraphTask::Physics).writes("positions"))
+        .build();
+    assert_eq!(read_write.err(), Some(TaskGraphError::UnorderedReadWrite { data: "positions".to_string(), writer: GraphTask::Physics, reader: GraphTask::Render }));
+
+    // Ordered through a dependency chain, or only reading, is fine
+    let ordered = TaskGraphBuilder::new()
+        .with(TaskNode::new(GraphTask::Physics).writes("positions"))
+        .with(TaskNode::new(GraphTask::Ai).after(GraphTask::Physics))
+        .with(TaskNode::new(GraphTask::Render).after(GraphTask::Ai).writes("positions"))
+        .with(TaskNode::new(GraphTask::Audio).after(GraphTask::Render).reads("positions"))
+        .with(TaskNode::new(GraphTask::Input).after(GraphTask::Render).reads("positions"))
+        .build();
+    assert!(ordered.is_ok());
+}
+
+#[test]
+fn graph_rejects_bad_tasks() {
+    let no_threads = TaskGraphBuilder::new().with(TaskNode::new(GraphTask::Idle)).build();
+    assert_eq!(no_threads.err(), Some(TaskGraphError::NoThreads(GraphTask::Idle)));
+    let duplicate = TaskGraphBuilder::new().with(TaskNode::new(GraphTask::Ai)).with(TaskNode::new(GraphTask::Ai)).build();
+    assert_eq!(duplicate.err(), Some(TaskGraphError::DuplicateTask(GraphTask::Ai)));
+    let unknown = TaskGraphBuilder::new().with(TaskNode::new(GraphTask::Ai).after(GraphTask::Input)).build();
+    assert_eq!(unknown.err(), Some(TaskGraphError::UnknownDependency { task: GraphTask::Ai, dependency: GraphTask::Input }));
+}
+
+fn assert_frame_order(log:&GraphLog) {
+    assert_eq!(log.get_runs().len(), 5);
+    assert!(log.position_of(&GraphTask::Input) < log.position_of(&GraphTask::Physics));
+    assert!(log.position_of(&GraphTask::Input) < log.position_of(&GraphTask::Ai));
+    assert!(log.position_of(&GraphTask::Ai) < log.position_of(&GraphTask::Audio));
+    assert!(log.position_of(&GraphTask::Physics) < log.position_of(&GraphTask::Render));
+}
+
+#[test]
+fn graph_queue_follows_dependencies() {
+    let graph = frame_graph().build().unwrap();
+    let log = GraphLog::new();
+    let mut scheduler:HordeScheduler<GraphTask, EmptyBackgroundTask> = HordeScheduler::new(graph.to_queue(), log.clone(), 3, 0);
+    for _ in 0..3 {
+        log.runs.lock().unwrap().clear();
+        scheduler.initialise(graph.to_queue());
+        scheduler.tick().unwrap();
+        assert_frame_order(&log);
+    }
+    log.runs.lock().unwrap().clear();
+    scheduler.tick_graph(&graph).unwrap();
+    assert_frame_order(&log);
+    scheduler.end_threads();
+}
+
+#[test]
+fn waiting_for_a_task_never_started_is_an_error() {
+    let log = GraphLog::new();
+    let queue = || HordeTaskQueue::new(vec![
+        HordeTaskSequence::new(vec![SequencedTask::StartTask(GraphTask::Input), SequencedTask::WaitFor(GraphTask::Input), SequencedTask::WaitFor(GraphTask::Physics), SequencedTask::StartTask(GraphTask::Render)]),
+    ], vec![]);
+    let mut scheduler:HordeScheduler<GraphTask, EmptyBackgroundTask> = HordeScheduler::new(queue(), log.clone(), 1, 0);
+    scheduler.initialise(queue());
+    match scheduler.tick() {
+        Err(SchedulerError::NeverStarted(waiting_for)) => assert_eq!(waiting_for, vec![(0, GraphTask::Physics)]),
+        _ => panic!("the stuck sequence wasn't reported")
+    }
+    assert_eq!(log.get_runs(), vec![GraphTask::Input]);
+
+    // Nothing is left running, so the next tick works
+    scheduler.initialise(HordeTaskQueue::new(vec![HordeTaskSequence::new(vec![SequencedTask::StartTask(GraphTask::Render), SequencedTask::WaitFor(GraphTask::Render)])], vec![]));
+    scheduler.tick().unwrap();
+    assert_eq!(log.get_runs(), vec![GraphTask::Input, GraphTask::Render]);
+    scheduler.end_threads();
+}
+
+#[test]
+fn time_control_pause_and_step() {
+    let control = TimeControl::new(true);
+    assert!(control.begin_tick());
+    control.pause().unwrap();
+    assert!(!control.begin_tick());
+    assert!(!control.is_tick_running());
+    control.step().unwrap();
+    control.step().unwrap();
+    assert!(control.begin_tick());
+    assert!(control.begin_tick());
+    assert!(!control.begin_tick());
+    control.resume().unwrap();
+    assert!(control.begin_tick());
+
+    control.set_scale(100.0).unwrap();
+    assert_eq!(control.get_scale(), MAX_TIME_SCALE);
+    control.set_scale(0.0).unwrap();
+    assert_eq!(control.get_scale(), MIN_TIME_SCALE);
+
+    // Only the authority changes anything, the others follow its state
+    let client = TimeControl::new(false);
+    assert!(client.pause().is_err());
+    assert!(client.set_scale(2.0).is_err());
+    client.apply_state(TimeControlState { paused: true, scale: 2.0, steps: 1 });
+    assert_eq!(client.get_scale(), 2.0);
+    assert!(client.begin_tick());
+    assert!(!client.begin_tick());
+}
+
+fn test_runner(time_control:&TimeControl) -> FixedStepRunner<GraphTask, EmptyBackgroundTask> {
+    let graph = frame_graph().build().unwrap();
+    let scheduler = HordeScheduler::new(graph.to_queue(), GraphLog::new(), 1, 0);
+    FixedStepRunner::new(scheduler, graph.to_queue(), graph.to_queue(), FixedStepConfig::new(10.0, RenderRate::Uncapped)).with_time_control(time_control.clone())
+}
+
+#[test]
+fn runner_accumulator_follows_scale() {
+    let control = TimeControl::new(true);
+    let mut runner = test_runner(&control);
+    let tick = runner.config().tick_duration();
+    assert_eq!(runner.accumulate(tick * 3 + tick / 2), (3, 0));
+
+    // Half a tick is left over every time
+    control.set_scale(2.0).unwrap();
+    assert_eq!(runner.accumulate(tick / 2), (1, 0));
+
+    control.set_scale(0.25).unwrap();
+    assert_eq!(runner.accumulate(tick), (0, 0));
+    assert_eq!(runner.accumulate(tick * 2), (1, 0));
+
+    // Past `max_catch_up_ticks` the delay is dropped
+    control.set_scale(8.0).unwrap();
+    assert_eq!(runner.accumulate(tick + tick / 16), (5, 3));
+
+    // Paused ticks still come due, the engine skips their stages
+    control.set_scale(1.0).unwrap();
+    control.pause().unwrap();
+    assert_eq!(runner.accumulate(tick * 2), (2, 0));
+    runner.end_threads();
+}
+
+#[test]
+fn profiler_span_counts() {
+    let graph = frame_graph().build().unwrap();
+    let mut scheduler:HordeScheduler<GraphTask, EmptyBackgroundTask> = HordeScheduler::new(graph.to_queue(), GraphLog::new(), 2, 0);
+    let profiler = scheduler.get_profiler();
+
+    // Nothing is recorded while disabled
+    scheduler.initialise(graph.to_queue());
+    scheduler.tick().unwrap();
+    assert!(profiler.get_stats("tick", ProfileKind::Tick).is_none());
+
+    profiler.enable();
+    for _ in 0..3 {
+        scheduler.initialise(graph.to_queue());
+        scheduler.tick().unwrap();
+    }
+    for _ in 0..2 {
+        scheduler.initialise(graph.to_queue());
+        scheduler.frame().unwrap();
+    }
+    assert_eq!(profiler.get_stats("tick", ProfileKind::Tick).unwrap().samples, 3);
+    assert_eq!(profiler.get_stats("frame", ProfileKind::Frame).unwrap().samples, 2);
+    for task in ["Input", "Physics", "Ai", "Audio", "Render"] {
+        assert_eq!(profiler.get_stats(task, ProfileKind::Task).unwrap().samples, 5);
+    }
+    assert_eq!(scheduler.get_tick_count(), 4);
+
+    profiler.reset_stats();
+    assert!(profiler.get_all_stats().is_empty());
+    scheduler.end_threads();
+}
+
+#[test]
+fn profiler_windows_and_trace() {
+    let profiler = HordeProfiler::new(4);
+    profiler.start_trace();
+    let start = Instant::now();
+    for i in 0..10 {
+        profiler.record("stage \"0\"".to_string(), ProfileKind::Stage, 1, start, start + Duration::from_millis(i));
+    }
+    let stats = profiler.get_stats("stage \"0\"", ProfileKind::Stage).unwrap();
+    assert_eq!(stats.samples, 4);
+    assert_eq!(stats.max, Duration::from_millis(9));
+    assert_eq!(stats.p50, Duration::from_millis(8));
+
+    let path = std::env::temp_dir().join(format!("horde_trace_test_{}.json", std::process::id()));
+    profiler.name_worker(1, "task thread 0".to_string());
+    profiler.write_chrome_trace(path.clone()).unwrap();
+    let trace = std::fs::read_to_string(&path).unwrap();
+    let _ = std::fs::remove_file(&path);
+    assert_eq!(trace.matches("\"ph\":\"X\"").count(), 10);
+    assert_eq!(trace.matches("\"ph\":\"M\"").count(), 1);
+    assert!(trace.contains("stage \\\"0\\\""));
+    assert!(!profiler.is_tracing());
+}
+
+fn wait_until<F:FnMut() -> bool>(mut condition:F) {
+    let start = Instant::now();
+    while !condition() {
+        assert!(start.elapsed() < Duration::from_secs(10), "condition never held");
+        std::thread::sleep(Duration::from_millis(1));
+    }
+}
+
+#[test]
+fn background_job_completes_and_reports() {
+    let graph = frame_graph().build().unwrap();
+    let mut scheduler:HordeScheduler<GraphTask, EmptyBackgroundTask> = HordeScheduler::new(graph.to_queue(), GraphLog::new(), 1, 1);
+
+    let handle = scheduler.spawn_background("terrain", |context| {
+        context.set_progress(0.5);
+        (0..10).sum::<usize>()
+    });
+    wait_until(|| handle.is_finished());
+    assert_eq!(handle.get_progress(), 1.0);
+    assert_eq!(handle.try_take(), Some(Ok(45)));
+    assert_eq!(handle.try_take(), None);
+
+    let failing = scheduler.spawn_background("mesh", |context| -> usize { panic!("missing mesh") });
+    wait_until(|| failing.is_finished());
+    assert_eq!(failing.try_take(), Some(Err(BackgroundError::Panicked("missing mesh".to_string()))));
+
+    // The only background thread is kept busy so the next job is cancelled before it starts
+    let (release_send, release_recv) = crossbeam::channel::unbounded::<()>();
+    let blocker = scheduler.spawn_background("blocker", move |context| release_recv.recv().is_ok());
+    let cancelled = scheduler.spawn_background("cancelled", |context| 1);
+    cancelled.cancel();
+    release_send.send(()).unwrap();
+    wait_until(|| cancelled.is_finished());
+    assert_eq!(blocker.try_take(), Some(Ok(true)));
+    assert_eq!(cancelled.try_take(), Some(Err(BackgroundError::Cancelled)));
+
+    // Callbacks wait for the next tick
+    let received = Arc::new(Mutex::new(None));
+    let callback_received = received.clone();
+    let status = scheduler.spawn_background_with_callback("save", |context| "saved".to_string(), move |result| *callback_received.lock().unwrap() = Some(result));
+    wait_until(|| status.is_finished());
+    std::thread::sleep(Duration::from_millis(10));
+    assert_eq!(*received.lock().unwrap(), None);
+    scheduler.initialise(graph.to_queue());
+    scheduler.tick().unwrap();
+    assert_eq!(*received.lock().unwrap(), Some(Ok("saved".to_string())));
+    scheduler.end_threads();
+}
+
+#[test]
+fn watchdog_times_out_dumps_and_recovers() {
+    let graph = frame_graph().build().unwrap();
+    let log = GraphLog::new();
+    let mut scheduler:HordeScheduler<GraphTask, EmptyBackgroundTask> = HordeScheduler::new(graph.to_queue(), log.clone(), 2, 0);
+    scheduler.set_watchdog(Some(Duration::from_millis(100)));
+    scheduler.initialise(graph.to_queue());
+    scheduler.tick().unwrap();
+
+    let release = log.hold_render();
+    scheduler.initialise(graph.to_queue());
+    let started = Instant::now();
+    let dump = match scheduler.tick() {
+        Err(SchedulerError::Timeout(dump)) => dump,
+        _ => panic!("the stuck tick didn't time out")
+    };
+    assert!(started.elapsed() >= Duration::from_millis(100));
+    assert_eq!(dump.unfinished, vec![(GraphTask::Render, 1)]);
+    assert!(dump.finished.contains(&GraphTask::Audio));
+    assert_eq!(dump.workers.iter().filter(|(_, state)| matches!(state, WorkerState::Running { task, .. } if task == "Render")).count(), 1);
+    assert!(format!("{}", SchedulerError::Timeout(dump.clone())).contains("unfinished : Render (1 parts left)"));
+
+    // Stuck until Render is done, then the scheduler can be used again
+    assert!(scheduler.has_timed_out());
+    assert!(matches!(scheduler.tick(), Err(SchedulerError::Timeout(_))));
+    assert!(!scheduler.clear_timeout());
+    *log.render_gate.lock().unwrap() = None;
+    release.send(()).unwrap();
+    wait_until(|| scheduler.clear_timeout());
+    assert!(!scheduler.has_timed_out());
+    log.runs.lock().unwrap().clear();
+    scheduler.initialise(graph.to_queue());
+    scheduler.tick().unwrap();
+    assert_frame_order(&log);
+    scheduler.end_threads();
+}
+
+/// Runs one task on `threads` threads pulling from `source`, the thread given in `panicking` takes the first chunk and panics with it
+fn take_all_chunks(source:&ChunkSource, len:usize, threads:usize, panicking:Option<usize>) -> (Vec<Range<usize>>, Vec<usize>) {
+    let chunks = Mutex::new(Vec::new());
+    let visits:Vec<AtomicUsize> = (0..len).map(|_| AtomicUsize::new(0)).collect();
+    let first_taken = AtomicBool::new(panicking.is_none());
+    std::thread::scope(|scope| {
+        for thread in 0..threads {
+            let (chunks, visits, first_taken) = (&chunks, &visits, &first_taken);
+            scope.spawn(move || {
+                let _ = catch_unwind(AssertUnwindSafe(|| {
+                    if panicking != Some(thread) {
+                        wait_until(|| first_taken.load(Ordering::Acquire));
+                    }
+                    for chunk in source.chunks(len, threads) {
+                        chunks.lock().unwrap().push(chunk.clone());
+                        first_taken.store(true, Ordering::Release);
+                        if panicking == Some(thread) {
+                            panic!("iterator panicked");
+                        }
+                        for i in chunk {
+                            visits[i].fetch_add(1, Ordering::Relaxed);
+                        }
+                    }
+                }));
+            });
+        }
+    });
+    let mut chunks = chunks.into_inner().unwrap();
+    chunks.sort_by_key(|chunk| chunk.start);
+    (chunks, visits.iter().map(|visits| visits.load(Ordering::Relaxed)).collect())
+}
+
+fn assert_chunks_cover(chunks:&[Range<usize>], len:usize) {
+    let mut next = 0;
+    for chunk in chunks {
+        assert_eq!(chunk.start, next, "chunks overlap or leave a gap");
+        assert!(chunk.end > chunk.start);
+        next = chunk.end;
+    }
+    assert_eq!(next, len);
+}
+
+#[test]
+fn work_stealing_hands_every_chunk_out_once() {
+    let source = ChunkSource::new(3);
+    // The source resets once every thread is done, so the next tick gets all the work again
+    for _ in 0..3 {
+        let (chunks, visits) = take_all_chunks(&source, 1000, 4, None);
+        assert_chunks_cover(&chunks, 1000);
+        assert!(visits.iter().all(|visits| *visits == 1));
+        assert!(chunks.iter().any(|chunk| chunk.len() > 3) && chunks.iter().any(|chunk| chunk.len() == 3));
+    }
+
+    // A panicking thread loses the rest of its chunk but the others still take everything left
+    let (chunks, visits) = take_all_chunks(&source, 1000, 4, Some(2));
+    assert_chunks_cover(&chunks, 1000);
+    let lost = chunks.iter().filter(|chunk| visits[chunk.start] == 0).count();
+    assert_eq!(lost, 1);
+    assert!(visits.iter().all(|visits| *visits <= 1));
+    let (chunks, visits) = take_all_chunks(&source, 1000, 4, None);
+    assert_chunks_cover(&chunks, 1000);
+    assert!(visits.iter().all(|visits| *visits == 1));
+
+    let mut seen = Vec::new();
+    source.for_each(10, 1, |i| seen.push(i));
+    assert_eq!(seen, (0..10).collect::<Vec<usize>>());
+}
+
+fn live_workers(scheduler:&HordeScheduler<GraphTask, EmptyBackgroundTask>) -> usize {
+    scheduler.get_dump(Instant::now()).workers.len()
+}
+
+#[test]
+fn thread_count_follows_set_and_adapt() {
+    let graph = frame_graph().build().unwrap();
+    let mut scheduler:HordeScheduler<GraphTask, EmptyBackgroundTask> = HordeScheduler::new(graph.to_queue(), GraphLog::new(), 2, 0);
+    wait_until(|| live_workers(&scheduler) == 2);
+    scheduler.set_number_of_threads(4);
+    assert_eq!(scheduler.get_number_of_threads(), 4);
+    wait_until(|| live_workers(&scheduler) == 4);
+    scheduler.set_number_of_threads(0);
+    assert_eq!(scheduler.get_number_of_threads(), 1);
+    wait_until(|| live_workers(&scheduler) == 1);
+    scheduler.initialise(graph.to_queue());
+    scheduler.tick().unwrap();
+
+    // Busy workers over budget grow the pool, idle ones shrink it, both within the bounds of the policy
+    let policy = AdaptiveThreads::new(1, 3, Duration::ZERO);
+    let grown = 2.min(policy.max_threads);
+    assert_eq!(scheduler.adapt_threads(&policy, Duration::from_nanos(1)), grown);
+    assert_eq!(scheduler.get_number_of_threads(), grown);
+    wait_until(|| live_workers(&scheduler) == grown);
+    assert_eq!(scheduler.adapt_threads(&policy, Duration::from_secs(10)), 1);
+    assert_eq!(scheduler.adapt_threads(&policy, Duration::from_secs(10)), 1);
+    wait_until(|| live_workers(&scheduler) == 1);
+    scheduler.initialise(graph.to_queue());
+    scheduler.tick().unwrap();
+    scheduler.end_threads();
+}
+
+/// Scheduler running only `Input` then `Physics` each tick, leaving the other tasks free to be periodic
+fn periodic_scheduler() -> (HordeScheduler<GraphTask, EmptyBackgroundTask>, TaskGraph<GraphTask>, GraphLog) {
+    let graph = TaskGraphBuilder::new()
+        .with(TaskNode::new(GraphTask::Input).writes("input"))
+        .with(TaskNode::new(GraphTask::Physics).after(GraphTask::Input).reads("input"))
+        .build().unwrap();
+    let log = GraphLog::new();
+    (HordeScheduler::new(graph.to_queue(), log.clone(), 2, 0), graph, log)
+}
+
+/// Ticks once and returns what ran in it
+fn tick_runs(scheduler:&mut HordeScheduler<GraphTask, EmptyBackgroundTask>, graph:&TaskGraph<GraphTask>, log:&GraphLog) -> Vec<GraphTask> {
+    log.runs.lock().unwrap().clear();
+    scheduler.initialise(graph.to_queue());
+    scheduler.tick().unwrap();
+    log.get_runs()
+}
+
+/// Budgeted task taking one step per run until `steps` are done
+fn stepper(name:&'static str, steps:usize, runs:Arc<Mutex<Vec<&'static str>>>) -> impl FnMut(&TaskBudget) -> BudgetedStep + Send {
+    let mut done = 0;
+    move |budget:&TaskBudget| {
+        assert!(!budget.get_slice().is_zero());
+        runs.lock().unwrap().push(name);
+        done += 1;
+        if done >= steps {BudgetedStep::Done} else {BudgetedStep::Yield}
+    }
+}
+
+#[test]
+fn periodic_tasks_run_on_their_ticks() {
+    let (mut scheduler, graph, log) = periodic_scheduler();
+    scheduler.add_periodic(GraphTask::Audio, 3, 4);
+    scheduler.add_periodic(GraphTask::Ai, 2, 0);
+    assert_eq!(PeriodicTask::new(GraphTask::Audio, 3, 4).phase, 1);
+    assert!(PeriodicTask::new(GraphTask::Audio, 0, 5).is_due(7));
+
+    let mut audio_ticks = Vec::new();
+    let mut ai_ticks = Vec::new();
+    for tick in 0..7 {
+        assert_eq!(scheduler.get_tick_count(), tick);
+        let runs = tick_runs(&mut scheduler, &graph, &log);
+        // Periodic tasks start once the queue is done, and the tick waits for them
+        assert_eq!(&runs[..2], &[GraphTask::Input, GraphTask::Physics]);
+        if runs.contains(&GraphTask::Audio) {
+            audio_ticks.push(tick);
+        }
+        if runs.contains(&GraphTask::Ai) {
+            ai_ticks.push(tick);
+        }
+    }
+    assert_eq!(audio_ticks, vec![1, 4]);
+    assert_eq!(ai_ticks, vec![0, 2, 4, 6]);
+
+    scheduler.remove_periodic(&GraphTask::Ai);
+    assert!(!tick_runs(&mut scheduler, &graph, &log).contains(&GraphTask::Ai));
+    scheduler.end_threads();
+}
+
+#[test]
+fn budgeted_tasks_resume_across_ticks() {
+    let (mut scheduler, graph, log) = periodic_scheduler();
+    let runs = Arc::new(Mutex::new(Vec::new()));
+    scheduler.add_budgeted(stepper("low", 3, runs.clone()), Duration::from_millis(1), 0);
+    let high = scheduler.add_budgeted(stepper("high", 10, runs.clone()), Duration::from_millis(1), 5);
+    assert_eq!(scheduler.get_number_of_budgeted(), 2);
+
+    // One run each per tick, highest priority first, until the task says it is done
+    for _ in 0..3 {
+        tick_runs(&mut scheduler, &graph, &log);
+    }
+    assert_eq!(*runs.lock().unwrap(), vec!["high", "low", "high", "low", "high", "low"]);
+    assert_eq!(scheduler.get_number_of_budgeted(), 1);
+    tick_runs(&mut scheduler, &graph, &log);
+    assert_eq!(runs.lock().unwrap().len(), 7);
+
+    // With a tick budget, tasks that yielded run again in the time left
+    runs.lock().unwrap().clear();
+    scheduler.set_tick_budget(Some(Duration::from_secs(10)));
+    tick_runs(&mut scheduler, &graph, &log);
+    assert_eq!(*runs.lock().unwrap(), vec!["high", "high"]);
+    assert!(scheduler.remove_budgeted(high));
+    assert!(!scheduler.remove_budgeted(high));
+    tick_runs(&mut scheduler, &graph, &log);
+    assert_eq!(runs.lock().unwrap().len(), 2);
+    scheduler.end_threads();
+}
+
+#[test]
+fn paused_ticks_skip_periodic_and_budgeted_tasks() {
+    let (mut scheduler, graph, log) = periodic_scheduler();
+    let control = TimeControl::new(true);
+    scheduler.set_time_control(Some(control.clone()));
+    scheduler.add_periodic(GraphTask::Audio, 1, 0);
+    let runs = Arc::new(Mutex::new(Vec::new()));
+    scheduler.add_budgeted(stepper("save", 5, runs.clone()), Duration::from_millis(1), 0);
+
+    control.pause().unwrap();
+    assert!(!control.begin_tick());
+    // The queue still runs, but the tick doesn't count
+    assert_eq!(tick_runs(&mut scheduler, &graph, &log), vec![GraphTask::Input, GraphTask::Physics]);
+    assert_eq!(scheduler.get_tick_count(), 0);
+    assert!(runs.lock().unwrap().is_empty());
+
+    control.step().unwrap();
+    assert!(control.begin_tick());
+    assert!(tick_runs(&mut scheduler, &graph, &log).contains(&GraphTask::Audio));
+    assert_eq!(scheduler.get_tick_count(), 1);
+    assert_eq!(*runs.lock().unwrap(), vec!["save"]);
+    scheduler.end_threads();
+}
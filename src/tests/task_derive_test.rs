@@ -5,15 +5,17 @@ use task_derive::HordeTask;
 
 use crate::{defaults::{default_frontends::minifb_frontend::MiniFBWindow, default_rendering::vectorinator::{Vectorinator, meshes::{Mesh, MeshID, MeshInstance, MeshLOD, MeshLODS, MeshLODType, MeshTriangles, TrianglePoint}, textures::rgb_to_argb}, default_ui::simple_ui::{SimpleUI, TextCentering, UIDimensions, UIElement, UIElementBackground, UIElementContent, UIElementID, UIEvent, UIUnit, UIUserAction, UIVector, UserEvent}}, horde::{frontend::{HordeWindowDimensions, SyncUnsafeHordeFramebuffer, WindowingHandler}, game_engine::{multiplayer::{HordeMultiModeChoice, MustSync}, world::WorldHandler}, geometry::{rotation::Orientation, vec3d::Vec3Df}, rendering::{camera::Camera, framebuffer::HordeColorFormat}, scheduler::{EmptyBackgroundTask, HordeScheduler, HordeTask, HordeTaskData, HordeTaskHandler, HordeTaskQueue, HordeTaskSequence, IndividualTask, SequencedTask}, sound::{ARWWaves, SoundRequest, WaveIdentification, WavePosition, WaveRequest, WaveSink, Waves, WavesHandler}}};
 
-use super::{engine_derive_test::{TestEngineBase, TestWorld}, entity_derive_test::{CoolComponent, CoolEntityVec, NewCoolEntity}, single_player_engine_test::{SinglePEngine, SinglePEngineBase, SinglePWorld}};
+use super::{engine_derive_test::{TestEngineBase, TestExtraData, TestWorld}, entity_derive_test::{CoolComponent, CoolEntityVec, NewCoolEntity}, single_player_engine_test::{SinglePEngine, SinglePEngineBase, SinglePWorld}};
 
 pub fn lance_serveur() {
     let world = TestWorld { test: 1};
     let entity_vec = CoolEntityVec::new(1000);
     let vectorinator = Vectorinator::new(Arc::new(RwLock::new(SyncUnsafeHordeFramebuffer::new(HordeWindowDimensions::new(100, 100), HordeColorFormat::ARGB8888))));
-    let engine = TestEngineBase::new(entity_vec, WorldHandler::new(world), Arc::new(vectorinator.clone()), HordeMultiModeChoice::Server { adress: (Ipv4Addr::new(127, 0, 0, 1), 5678), max_players: 100, tick_tolerance: 10,tickrate:30 }, 1);
+    let engine = TestEngineBase::new(entity_vec, WorldHandler::new(world), Arc::new(vectorinator.clone()), HordeMultiModeChoice::Server { adress: (Ipv4Addr::new(127, 0, 0, 1), 5678), max_players: 100, tick_tolerance: 10,tickrate:30 }, TestExtraData::new(1), 30.0, 42);
     let handler = TestServerTaskTaskHandler::new(engine);
     let queue = HordeTaskQueue::new(vec![HordeTaskSequence::new(vec![
+        SequencedTask::StartTask(TestServerTask::BeginTick),
+        SequencedTask::WaitFor(TestServerTask::BeginTick),
         SequencedTask::StartTask(TestServerTask::Main),
         SequencedTask::WaitFor(TestServerTask::Main),
         SequencedTask::StartTask(TestServerTask::ApplyEvents),
@@ -39,7 +41,7 @@ pub fn lance_serveur() {
     loop {
         let start = Instant::now();
         scheduler.initialise(queue.clone());
-        scheduler.tick();
+        scheduler.tick().unwrap();
         let duration = start.elapsed().as_secs_f64();
         if duration < 1.0/30.0 {
             thread::sleep(Duration::from_secs_f64(1.0/30.0 - duration));
@@ -58,7 +60,7 @@ pub fn singleplayer_test() {
     let world = SinglePWorld { test: 1};
     let entity_vec = CoolEntityVec::new(1000);
     {
-        entity_vec.get_write().new_ent(NewCoolEntity::new(CoolComponent {pos:Vec3Df::zero()}, MustSync::No, None));
+        entity_vec.get_write().new_ent(NewCoolEntity::new(CoolComponent::new(Vec3Df::zero()), MustSync::No, None));
     }
     
     let windowing = WindowingHandler::new::<MiniFBWindow>(HordeWindowDimensions::new(1280, 720), HordeColorFormat::ARGB8888);
@@ -124,6 +126,8 @@ pub fn singleplayer_test() {
         SequencedTask::WaitFor(TestSinglePlayerTask::PrepareRendering),
         SequencedTask::StartSequence(1),
         SequencedTask::StartTask(TestSinglePlayerTask::UpdateSoundPositions),
+        SequencedTask::StartTask(TestSinglePlayerTask::BeginTick),
+        SequencedTask::WaitFor(TestSinglePlayerTask::BeginTick),
         SequencedTask::StartTask(TestSinglePlayerTask::Main),
         SequencedTask::WaitFor(TestSinglePlayerTask::Main),
         SequencedTask::WaitFor(TestSinglePlayerTask::UpdateSoundPositions),
@@ -239,7 +243,7 @@ pub fn singleplayer_test() {
             Err(_) => ()
         }
         scheduler.initialise(queue.clone());
-        scheduler.tick();
+        scheduler.tick().unwrap();
         //println!("FPS : {}", 1.0/Instant::now().checked_duration_since(start).unwrap().as_secs_f64())
     }
     scheduler.end_threads();
@@ -254,11 +258,13 @@ pub fn client_test(name:String) {
     let framebuf = windowing.get_outside_framebuf();
     let vectorinator = Vectorinator::new(framebuf.clone());
     let (cs, cr) = channel();
-    let engine = TestEngineBase::new(entity_vec, WorldHandler::new(world), Arc::new(vectorinator.clone()), HordeMultiModeChoice::Client { adress: Some((Ipv4Addr::new(127, 0, 0, 1), 5678)), name:name.clone(), chat:cr }, 1);
+    let engine = TestEngineBase::new(entity_vec, WorldHandler::new(world), Arc::new(vectorinator.clone()), HordeMultiModeChoice::Client { adress: Some((Ipv4Addr::new(127, 0, 0, 1), 5678)), name:name.clone(), chat:cr }, TestExtraData::new(1), 30.0, 0);
     
     let handler = TestTaskTaskHandler::new(engine, windowing, vectorinator.clone());
     let queue = HordeTaskQueue::new(vec![HordeTaskSequence::new(vec![
         SequencedTask::StartSequence(1),
+        SequencedTask::StartTask(TestTask::BeginTick),
+        SequencedTask::WaitFor(TestTask::BeginTick),
         SequencedTask::StartTask(TestTask::Main),
         SequencedTask::WaitFor(TestTask::Main),
         SequencedTask::StartTask(TestTask::ApplyEvents),
@@ -360,7 +366,7 @@ pub fn client_test(name:String) {
             /*thread::sleep(Duration::from_millis(10));*/
         }
         scheduler.initialise(queue.clone());
-        scheduler.tick();
+        scheduler.tick().unwrap();
         //println!("FPS : {}", 1.0/Instant::now().checked_duration_since(start).unwrap().as_secs_f64())
     }
     scheduler.end_threads();
@@ -388,6 +394,11 @@ pub enum TestTask {
     #[type_task_id = 0]
     ApplyEvents,
 
+    #[uses_type = "TestEngineBase"]
+    #[max_threads = 1]
+    #[type_task_id = 40]
+    BeginTick,
+
     #[uses_type = "TestEngineBase"]
     #[max_threads = 3]
     #[type_task_id = 100]
@@ -461,6 +472,11 @@ pub enum TestServerTask {
     #[type_task_id = 0]
     ApplyEvents,
 
+    #[uses_type = "TestEngineBase"]
+    #[max_threads = 1]
+    #[type_task_id = 40]
+    BeginTick,
+
     #[uses_type = "TestEngineBase"]
     #[max_threads = 3]
     #[type_task_id = 100]
@@ -505,6 +521,11 @@ pub enum TestSinglePlayerTask {
     #[type_task_id = 0]
     ApplyEvents,
 
+    #[uses_type = "SinglePEngineBase"]
+    #[max_threads = 1]
+    #[type_task_id = 40]
+    BeginTick,
+
     #[uses_type = "SinglePEngineBase"]
     #[max_threads = 3]
     #[type_task_id = 100]